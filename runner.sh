@@ -12,11 +12,55 @@ function get_fifo_by_pid() {
 
 function setup_env() {
     dir_path="/tmp/tmp_crust_${1}"
-    mkdir "$dir_path"
+    mkdir -m 700 "$dir_path"
     mkfifo "${dir_path}/fifo"
+    mkfifo "${dir_path}/control"
+    mkfifo "${dir_path}/control_reply"
+    mkfifo -m 600 "${dir_path}/secret"
     echo "${dir_path}/fifo"
 }
 
+# Prefixes every line of secret with its label.
+function label_lines() {
+    printf '%s %s' "$1" "${2//$'\n'/$'\n'"$1" }"
+}
+
+# Secrets of command are read here - background process has neither
+# terminal nor environment of client. They are passed via private pipe
+# of background process as labelled lines (see `session::secret`):
+# password of `machine unlock` and keys of `--pkey-inline*` env:/fd:
+# sources.
+function collect_secrets() {
+    local prev="" arg source key password
+    if [[ "$1" == "machine" && "$2" == "unlock" ]]; then
+        read -r -s -p "Password for $3 (empty to use inventory credentials): " password </dev/tty
+        echo >&2
+        label_lines "password" "$password"
+        echo
+    fi
+    for arg in "$@"; do
+        source=""
+        if [[ "$arg" == --pkey-inline*=* ]]; then
+            source="${arg#*=}"
+        elif [[ "$prev" == --pkey-inline* ]]; then
+            source="$arg"
+        fi
+        prev="$arg"
+        case "$source" in
+            env:*)
+                key="${source#env:}"
+                key="${!key}"
+                ;;
+            fd:*)
+                key="$(cat <&"${source#fd:}")" || return 1
+                ;;
+            *) continue ;;
+        esac
+        label_lines "$source" "${key%$'\n'}"
+        echo
+    done
+}
+
 function get_bg_process_pid() {
     echo "$(ps aux | grep '[c]rust' | awk '{print $2}' | head -n 1)"
 }
@@ -39,6 +83,9 @@ function show_help() {
     echo "  -h        Show help."
     echo "  -b        Run in background."
     echo "  -e        Exit background process (if exists)."
+    echo "  -c        Send command to control pipe of background process"
+    echo "            (e.g. 'machine channels --close 3' while a command hangs"
+    echo "            or 'run cancel 2' to stop a multi-host run)."
     exit 0
 }
 
@@ -58,6 +105,18 @@ for i in "$@"; do
                 exit 0
             fi
             ;;
+        -c | --control)
+            pid=$(get_bg_process_pid)
+            if [[ -z "$pid" ]]; then
+                echo >&2 "No active crust process to control"
+                exit 1
+            fi
+            shift 1
+            [[ "$1" == "--" ]] && shift 1
+            echo "$*" >"/tmp/tmp_crust_${pid}/control"
+            cat "/tmp/tmp_crust_${pid}/control_reply"
+            exit 0
+            ;;
         -b | --background)
             BG_FLAG=true
             export CRUST_BG_MODE=true
@@ -92,7 +151,12 @@ else
         #  pass command to existing fifo
         fifo="$(get_fifo_by_pid $pid)"
     fi
+    secrets="$(collect_secrets "$@")" || exit 1
     echo $cmd >"$fifo"
+    if [[ -n "$secrets" ]]; then
+        printf '%s\n' "$secrets" >"/tmp/tmp_crust_${pid}/secret"
+        unset secrets
+    fi
 fi
 
 if [[ -z "$BG_FLAG" ]]; then
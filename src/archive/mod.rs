@@ -0,0 +1,390 @@
+use std::cell::RefCell;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::response::CrustResult;
+use crate::machine::utilities::{self, utilities};
+use crate::machine::Machine;
+use crate::scp::{scp, TransferOptions};
+use crate::utils::shell::{quote, quote_path};
+
+pub mod parser;
+
+/// Compression of tar archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+/// Format of archive, recognized by its name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Tar(Option<Compression>),
+    Zip,
+}
+
+/// Name suffixes of archives (the first matching one wins).
+const SUFFIXES: &[(&str, ArchiveFormat)] = &[
+    (".tar", ArchiveFormat::Tar(None)),
+    (".tar.gz", ArchiveFormat::Tar(Some(Compression::Gzip))),
+    (".tgz", ArchiveFormat::Tar(Some(Compression::Gzip))),
+    (".tar.bz2", ArchiveFormat::Tar(Some(Compression::Bzip2))),
+    (".tbz2", ArchiveFormat::Tar(Some(Compression::Bzip2))),
+    (".tar.xz", ArchiveFormat::Tar(Some(Compression::Xz))),
+    (".txz", ArchiveFormat::Tar(Some(Compression::Xz))),
+    (".tar.zst", ArchiveFormat::Tar(Some(Compression::Zstd))),
+    (".zip", ArchiveFormat::Zip),
+];
+
+impl ArchiveFormat {
+    /// Recognizes format of archive by its name.
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use crust::archive::{ArchiveFormat, Compression};
+    ///
+    /// assert_eq!(
+    ///     ArchiveFormat::detect(Path::new("/backups/db.tar.gz")).unwrap(),
+    ///     ArchiveFormat::Tar(Some(Compression::Gzip))
+    /// );
+    /// assert!(ArchiveFormat::detect(Path::new("/backups/db.rar")).is_err());
+    /// ```
+    pub fn detect(path: &Path) -> Result<Self, CrustError> {
+        let name = path.to_string_lossy().to_lowercase();
+        SUFFIXES
+            .iter()
+            .find(|(suffix, _)| name.ends_with(suffix))
+            .map(|(_, format)| *format)
+            .ok_or_else(|| CrustError {
+                code: ExitCode::Parser,
+                message: format!(
+                    "Unsupported archive '{}'. Use tar (.tar, .tar.gz, .tgz, .tar.bz2, .tar.xz, .tar.zst) or zip",
+                    path.display()
+                ),
+            })
+    }
+
+    /// Name of tool reading archive on machine.
+    fn tool(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar(_) => "tar",
+            ArchiveFormat::Zip => "unzip",
+        }
+    }
+
+    /// Option of tar selecting decompression (GNU tar, bsdtar and busybox
+    /// accept the same ones).
+    fn tar_flag(compression: Option<Compression>) -> &'static str {
+        match compression {
+            None => "",
+            Some(Compression::Gzip) => "-z ",
+            Some(Compression::Bzip2) => "-j ",
+            Some(Compression::Xz) => "-J ",
+            Some(Compression::Zstd) => "--zstd ",
+        }
+    }
+
+    /// Command printing names of members (one per line).
+    fn list_command(&self, archive: &Path) -> String {
+        match self {
+            ArchiveFormat::Tar(compression) => format!(
+                "tar {}-tf {}",
+                ArchiveFormat::tar_flag(*compression),
+                quote_path(archive)
+            ),
+            ArchiveFormat::Zip => format!("unzip -Z1 {}", quote_path(archive)),
+        }
+    }
+
+    /// Command extracting members into directory.
+    fn extract_command(&self, archive: &Path, dir: &Path, members: &[String]) -> String {
+        let members = members
+            .iter()
+            .map(|m| quote(m))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match self {
+            ArchiveFormat::Tar(compression) => format!(
+                "tar {}-xf {} -C {} -- {members}",
+                ArchiveFormat::tar_flag(*compression),
+                quote_path(archive),
+                quote_path(dir)
+            ),
+            ArchiveFormat::Zip => format!(
+                "unzip -q -o {} {members} -d {}",
+                quote_path(archive),
+                quote_path(dir)
+            ),
+        }
+    }
+}
+
+/// Checks whether member can be extracted (relative path which does
+/// not leave destination directory).
+pub fn validate_member(member: &str) -> Result<(), CrustError> {
+    let path = Path::new(member);
+    let escapes = path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if member.trim().is_empty() || escapes {
+        return Err(CrustError {
+            code: ExitCode::Parser,
+            message: format!("Invalid archive member '{member}'. Use relative path without '..'"),
+        });
+    }
+    Ok(())
+}
+
+/// Detects format of archive and checks whether machine has a tool to
+/// read it.
+fn prepare(machine: &dyn Machine, archive: &Path) -> Result<ArchiveFormat, CrustError> {
+    let format = ArchiveFormat::detect(archive)?;
+    if machine.host_os().is_windows() {
+        return Err(CrustError {
+            code: ExitCode::Remote,
+            message: format!("Archives can not be read on Windows machine {machine}"),
+        });
+    }
+    let tools = utilities(machine);
+    let available = match format {
+        ArchiveFormat::Tar(_) => tools.tar,
+        ArchiveFormat::Zip => tools.unzip,
+    };
+    if !available {
+        return Err(CrustError {
+            code: ExitCode::Remote,
+            message: format!(
+                "Archive can not be read on {machine} - '{}' is not available",
+                format.tool()
+            ),
+        });
+    }
+    Ok(format)
+}
+
+/// Runs command reading archive and fails with its stderr.
+fn run(machine: &dyn Machine, command: &str) -> Result<CrustResult, CrustError> {
+    let output = machine.exec(command)?;
+    match output.is_success() {
+        true => Ok(output),
+        false => Err(CrustError {
+            code: ExitCode::Remote,
+            message: format!(
+                "Can not read archive on {machine}: {}",
+                output.stderr().trim()
+            ),
+        }),
+    }
+}
+
+/// Lists members of archive - it is read on machine, nothing is
+/// downloaded.
+pub fn list(machine: &dyn Machine, archive: &Path) -> Result<Vec<String>, CrustError> {
+    let format = prepare(machine, archive)?;
+    let output = run(machine, &format.list_command(archive))?;
+    Ok(output
+        .stdout()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Extracts members of archive into a temporary directory on machine and
+/// downloads only them into `to` directory (paths inside archive are
+/// kept).
+pub fn extract(
+    source: &Rc<RefCell<Box<dyn Machine>>>,
+    local: &Rc<RefCell<Box<dyn Machine>>>,
+    archive: &Path,
+    members: &[String],
+    to: &Path,
+) -> Result<CrustResult, CrustError> {
+    let dir = {
+        let machine = source.borrow();
+        let format = prepare(machine.as_ref(), archive)?;
+        let dir = PathBuf::from(run(machine.as_ref(), "mktemp -d")?.stdout().trim());
+        if let Err(e) = run(
+            machine.as_ref(),
+            &format.extract_command(archive, &dir, members),
+        ) {
+            cleanup(machine.as_ref(), &dir);
+            return Err(e);
+        }
+        dir
+    };
+
+    let result = fetch(source, local, &dir, to);
+    cleanup(source.borrow().as_ref(), &dir);
+    result?;
+    Ok(CrustResult::new(
+        &format!(
+            "Extracted {} members of {} to {}",
+            members.len(),
+            archive.display(),
+            to.display()
+        ),
+        "",
+        0,
+    ))
+}
+
+/// Downloads extracted members - every file found in extraction
+/// directory, so directories of tar and patterns of zip (e.g. 'dir/*',
+/// '*.conf') are fetched with whatever they matched.
+fn fetch(
+    source: &Rc<RefCell<Box<dyn Machine>>>,
+    local: &Rc<RefCell<Box<dyn Machine>>>,
+    dir: &Path,
+    to: &Path,
+) -> Result<(), CrustError> {
+    let entries = source.borrow().list_tree(dir)?;
+    for entry in entries {
+        let target = entry.join_to(to);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        scp(
+            source,
+            local,
+            entry.join_to(dir),
+            target,
+            &TransferOptions::default(),
+        )?;
+    }
+    Ok(())
+}
+
+fn cleanup(machine: &dyn Machine, dir: &Path) {
+    if let Err(e) = utilities::remove_tree(machine, dir) {
+        log::warn!("Can not remove '{}' on {machine}: {e}", dir.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeTransport;
+    use crate::machine::local::LocalMachine;
+
+    #[test]
+    fn test_archive_commands() {
+        let format = ArchiveFormat::detect(Path::new("/b/x.TGZ")).unwrap();
+        assert_eq!(
+            format.list_command(Path::new("/b/x.TGZ")),
+            "tar -z -tf '/b/x.TGZ'"
+        );
+        assert_eq!(
+            format.extract_command(
+                Path::new("/b/x.tgz"),
+                Path::new("/tmp/d"),
+                &[String::from("etc/app.conf"), String::from("it's")]
+            ),
+            "tar -z -xf '/b/x.tgz' -C '/tmp/d' -- 'etc/app.conf' 'it'\\''s'"
+        );
+        assert_eq!(
+            ArchiveFormat::Zip.list_command(Path::new("/b/x.zip")),
+            "unzip -Z1 '/b/x.zip'"
+        );
+        assert!(validate_member("../etc/passwd").is_err());
+        assert!(validate_member("/etc/passwd").is_err());
+        assert!(validate_member("etc/./passwd").is_ok());
+    }
+
+    #[test]
+    fn test_list_and_extract_tar_members() {
+        let root = std::env::temp_dir().join(format!("crust-archive-{}", uuid::Uuid::new_v4()));
+        let data = root.join("data");
+        std::fs::create_dir_all(data.join("etc")).unwrap();
+        std::fs::write(data.join("etc/app.conf"), "port=1").unwrap();
+        std::fs::write(data.join("big.img"), "0".repeat(1024)).unwrap();
+        let archive = root.join("backup.tar.gz");
+        let status = std::process::Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&data)
+            .args(["etc", "big.img"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let fake = FakeTransport::new("user@host");
+        let mut members = list(&fake, &archive).unwrap();
+        members.sort();
+        assert_eq!(members, vec!["big.img", "etc/", "etc/app.conf"]);
+
+        let source: Rc<RefCell<Box<dyn Machine>>> = Rc::new(RefCell::new(Box::new(fake)));
+        let local: Rc<RefCell<Box<dyn Machine>>> =
+            Rc::new(RefCell::new(Box::new(LocalMachine::default())));
+        let out = root.join("out");
+        let result = extract(
+            &source,
+            &local,
+            &archive,
+            &[String::from("etc/app.conf")],
+            &out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.stdout(),
+            format!(
+                "Extracted 1 members of {} to {}",
+                archive.display(),
+                out.display()
+            )
+        );
+        assert_eq!(
+            std::fs::read_to_string(out.join("etc/app.conf")).unwrap(),
+            "port=1"
+        );
+        assert!(!out.join("big.img").exists());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_extract_zip_pattern() {
+        let root = std::env::temp_dir().join(format!("crust-archive-{}", uuid::Uuid::new_v4()));
+        let data = root.join("data");
+        std::fs::create_dir_all(data.join("etc")).unwrap();
+        std::fs::write(data.join("etc/app.conf"), "port=1").unwrap();
+        std::fs::write(data.join("etc/db.conf"), "port=2").unwrap();
+        std::fs::write(data.join("etc/notes.txt"), "-").unwrap();
+        let archive = root.join("backup.zip");
+        let status = std::process::Command::new("zip")
+            .arg("-qr")
+            .arg(&archive)
+            .arg("etc")
+            .current_dir(&data)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let source: Rc<RefCell<Box<dyn Machine>>> =
+            Rc::new(RefCell::new(Box::new(FakeTransport::new("user@host"))));
+        let local: Rc<RefCell<Box<dyn Machine>>> =
+            Rc::new(RefCell::new(Box::new(LocalMachine::default())));
+        let out = root.join("out");
+        extract(
+            &source,
+            &local,
+            &archive,
+            &[String::from("etc/*.conf")],
+            &out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(out.join("etc/db.conf")).unwrap(),
+            "port=2"
+        );
+        assert!(out.join("etc/app.conf").exists());
+        assert!(!out.join("etc/notes.txt").exists());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+
+use crate::archive::{validate_member, ArchiveFormat};
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+use crate::stage::parser::FileAuthArgs;
+use crate::stage::RemoteFile;
+
+#[derive(Debug, Clone, Args)]
+pub struct ArchiveArgs {
+    #[clap(subcommand)]
+    pub action: ArchiveAction,
+}
+
+impl Validation for ArchiveArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        match &mut self.action {
+            ArchiveAction::Ls(args) => args.validate(),
+            ArchiveAction::Extract(args) => args.validate(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ArchiveAction {
+    /// Lists members of remote archive (without downloading it)
+    Ls(ArchiveLsArgs),
+
+    /// Downloads only selected members of remote archive
+    Extract(ArchiveExtractArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ArchiveLsArgs {
+    /// Remote archive (<user>@<host>:<path> or <alias>:<path>) - tar
+    /// (optionally gz, bz2, xz, zst compressed) or zip
+    pub file: String,
+
+    #[clap(flatten)]
+    pub auth: FileAuthArgs,
+}
+
+impl Validation for ArchiveLsArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        let file = RemoteFile::parse(&self.file, &self.auth)?;
+        ArchiveFormat::detect(&file.path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ArchiveExtractArgs {
+    /// Remote archive (<user>@<host>:<path> or <alias>:<path>) - tar
+    /// (optionally gz, bz2, xz, zst compressed) or zip
+    pub file: String,
+
+    /// Path of member inside archive (can be used many times). Directory
+    /// of tar archive is extracted with its content, members of zip
+    /// archive are unzip patterns (e.g. 'dir/*')
+    #[clap(long, required = true)]
+    pub member: Vec<String>,
+
+    /// Local directory for extracted members
+    #[clap(long, default_value = ".")]
+    pub to: PathBuf,
+
+    #[clap(flatten)]
+    pub auth: FileAuthArgs,
+}
+
+impl Validation for ArchiveExtractArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        let file = RemoteFile::parse(&self.file, &self.auth)?;
+        ArchiveFormat::detect(&file.path)?;
+        for member in &self.member {
+            validate_member(member)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::{Condvar, Mutex};
+
+use crate::error::{CrustError, ExitCode};
+
+/// Slots of operations running in background process at once and the
+/// queue of operations waiting for them.
+static STATE: Mutex<State> = Mutex::new(State::new());
+static RELEASED: Condvar = Condvar::new();
+
+/// Class of operation. Interactive operations (execs, listings) are
+/// short and somebody waits for them, bulk ones (transfers) may take
+/// hours. Classes are ordered by priority - the first one wins a free
+/// slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OperationClass {
+    Interactive,
+    Bulk,
+}
+
+impl std::fmt::Display for OperationClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OperationClass::Interactive => write!(f, "interactive"),
+            OperationClass::Bulk => write!(f, "bulk"),
+        }
+    }
+}
+
+/// Concurrency budget of background process.
+/// - slots: operations running at once (of any class)
+/// - bulk: bulk operations running at once - the rest of slots is kept
+///   for interactive operations, so they never wait for transfers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    pub slots: usize,
+    pub bulk: usize,
+}
+
+/// Budget used until it is set by `set_budget`.
+const DEFAULT_BUDGET: Budget = Budget { slots: 4, bulk: 2 };
+
+impl Default for Budget {
+    fn default() -> Self {
+        DEFAULT_BUDGET
+    }
+}
+
+impl Budget {
+    /// Checks whether budget leaves a slot for every class.
+    /// # Example
+    /// ```
+    /// use crust::budget::Budget;
+    ///
+    /// assert!(Budget { slots: 4, bulk: 3 }.validate().is_ok());
+    /// assert!(Budget { slots: 2, bulk: 2 }.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), CrustError> {
+        if self.bulk == 0 || self.bulk >= self.slots {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!(
+                    "Bulk slots must be greater than 0 and lower than slots ({}) - one slot is kept for interactive operations",
+                    self.slots
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Maximal number of running operations of class.
+    fn limit(&self, class: OperationClass) -> usize {
+        match class {
+            OperationClass::Interactive => self.slots,
+            OperationClass::Bulk => self.bulk,
+        }
+    }
+}
+
+/// Running and waiting operations. Waiting ones are identified by
+/// tickets given in arrival order.
+#[derive(Debug)]
+struct State {
+    budget: Budget,
+    running: [usize; 2],
+    waiting: Vec<(OperationClass, u64)>,
+    next_ticket: u64,
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            budget: DEFAULT_BUDGET,
+            running: [0, 0],
+            waiting: Vec::new(),
+            next_ticket: 0,
+        }
+    }
+
+    fn has_room(&self, class: OperationClass) -> bool {
+        self.running.iter().sum::<usize>() < self.budget.slots
+            && self.running[class as usize] < self.budget.limit(class)
+    }
+
+    /// Operation may start when there is room for its class, it is the
+    /// oldest waiting one of its class and no operation of a more
+    /// important class could take the slot instead.
+    fn can_start(&self, class: OperationClass, ticket: u64) -> bool {
+        let oldest = self
+            .waiting
+            .iter()
+            .find(|(c, _)| *c == class)
+            .is_some_and(|(_, t)| *t == ticket);
+        let preferred = self
+            .waiting
+            .iter()
+            .any(|(c, _)| *c < class && self.has_room(*c));
+        oldest && !preferred && self.has_room(class)
+    }
+}
+
+/// Slot taken by running operation - it is given back on drop.
+#[derive(Debug)]
+pub struct Permit {
+    class: OperationClass,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        STATE.lock().unwrap().running[self.class as usize] -= 1;
+        RELEASED.notify_all();
+    }
+}
+
+/// Sets budget of the current process (it applies to operations which
+/// did not get their slot yet).
+pub fn set_budget(budget: Budget) {
+    STATE.lock().unwrap().budget = budget;
+    RELEASED.notify_all();
+}
+
+/// Waits for a free slot of class. Operations of the same class start
+/// in arrival order, interactive ones go before every waiting bulk one.
+pub fn acquire(class: OperationClass) -> Permit {
+    let mut state = STATE.lock().unwrap();
+    let ticket = state.next_ticket;
+    state.next_ticket += 1;
+    state.waiting.push((class, ticket));
+    if !state.can_start(class, ticket) {
+        log::debug!("Operation ({class}) waits for a free slot");
+    }
+    while !state.can_start(class, ticket) {
+        state = RELEASED.wait(state).unwrap();
+    }
+
+    state.waiting.retain(|(_, t)| *t != ticket);
+    state.running[class as usize] += 1;
+    // Another waiting operation may fit into the remaining slots
+    RELEASED.notify_all();
+    Permit { class }
+}
+
+/// Human readable usage of slots.
+pub fn render() -> String {
+    let state = STATE.lock().unwrap();
+    let waiting = |class: OperationClass| state.waiting.iter().filter(|(c, _)| *c == class).count();
+    format!(
+        "Slots: {}/{} busy (interactive {}, bulk {}/{}), waiting: interactive {}, bulk {}",
+        state.running.iter().sum::<usize>(),
+        state.budget.slots,
+        state.running[OperationClass::Interactive as usize],
+        state.running[OperationClass::Bulk as usize],
+        state.budget.bulk,
+        waiting(OperationClass::Interactive),
+        waiting(OperationClass::Bulk),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[serial]
+    #[test]
+    fn test_bulk_operations_keep_slot_for_interactive() {
+        set_budget(Budget { slots: 2, bulk: 1 });
+        let transfer = acquire(OperationClass::Bulk);
+
+        let (sender, receiver) = mpsc::channel();
+        let bulk_sender = sender.clone();
+        let waiting = std::thread::spawn(move || {
+            let _permit = acquire(OperationClass::Bulk);
+            bulk_sender.send(OperationClass::Bulk).unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(receiver.try_recv().is_err());
+
+        let exec = acquire(OperationClass::Interactive);
+        assert_eq!(
+            render(),
+            "Slots: 2/2 busy (interactive 1, bulk 1/1), waiting: interactive 0, bulk 1"
+        );
+        drop(exec);
+        drop(transfer);
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            OperationClass::Bulk
+        );
+        waiting.join().unwrap();
+        drop(sender);
+        set_budget(Budget::default());
+    }
+
+    #[test]
+    fn test_interactive_operations_win_free_slot() {
+        let state = State {
+            budget: Budget { slots: 2, bulk: 1 },
+            running: [1, 0],
+            waiting: vec![(OperationClass::Bulk, 1), (OperationClass::Interactive, 2)],
+            next_ticket: 3,
+        };
+
+        assert!(!state.can_start(OperationClass::Bulk, 1));
+        assert!(state.can_start(OperationClass::Interactive, 2));
+        assert!(!state.can_start(OperationClass::Interactive, 1));
+    }
+}
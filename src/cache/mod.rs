@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::CrustError;
+
+pub mod parser;
+
+/// Extension of files with keys of cached copies.
+const KEY_EXTENSION: &str = "key";
+
+/// Identifies a version of remote file. If any of the values changes
+/// (e.g. file was modified), cached copy is not used anymore.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheKey {
+    machine: String,
+    path: PathBuf,
+    mtime: u64,
+    size: u64,
+}
+
+impl CacheKey {
+    pub fn new(machine: &str, path: &Path, mtime: u64, size: u64) -> Self {
+        Self {
+            machine: machine.to_string(),
+            path: path.to_path_buf(),
+            mtime,
+            size,
+        }
+    }
+
+    /// Full description of key, stored next to cached content to detect
+    /// collisions of file names.
+    fn description(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.machine,
+            self.path.display(),
+            self.mtime,
+            self.size
+        )
+    }
+
+    /// Name of file which stores cached content (sha256 of description,
+    /// stable between releases of crust).
+    fn file_name(&self) -> String {
+        format!("{:x}", Sha256::digest(self.description().as_bytes()))
+    }
+}
+
+/// Local storage of downloaded remote files.
+pub struct DownloadCache {
+    dir: PathBuf,
+}
+
+impl DownloadCache {
+    /// Creates a cache stored in passed directory.
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Default location of cache: `$CRUST_CACHE_DIR`, `$XDG_CACHE_HOME/crust`
+    /// or `$HOME/.cache/crust` (in this order).
+    pub fn default_dir() -> PathBuf {
+        if let Ok(dir) = std::env::var("CRUST_CACHE_DIR") {
+            return PathBuf::from(dir);
+        }
+        if let Ok(dir) = std::env::var("XDG_CACHE_HOME") {
+            return PathBuf::from(dir).join("crust");
+        }
+        let home = std::env::var("HOME").unwrap_or_else(|_| String::from("/tmp"));
+        PathBuf::from(home).join(".cache").join("crust")
+    }
+
+    /// Path to file describing key of cached copy.
+    fn key_path(path: &Path) -> PathBuf {
+        path.with_extension(KEY_EXTENSION)
+    }
+
+    /// Gets a path to cached copy of file (if exists). Copy stored for
+    /// other key with the same file name is not used.
+    pub fn get(&self, key: &CacheKey) -> Option<PathBuf> {
+        let path = self.dir.join(key.file_name());
+        let stored = std::fs::read_to_string(Self::key_path(&path)).ok()?;
+        match path.is_file() && stored == key.description() {
+            true => Some(path),
+            false => None,
+        }
+    }
+
+    /// Saves copy of passed (already downloaded) file.
+    pub fn store(&self, key: &CacheKey, file: &Path) -> Result<PathBuf, CrustError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(key.file_name());
+        std::fs::copy(file, &path)?;
+        std::fs::write(Self::key_path(&path), key.description())?;
+        log::debug!(
+            "Stored '{}' in cache ({})",
+            key.path.display(),
+            path.display()
+        );
+        Ok(path)
+    }
+
+    /// Removes all cached files. Returns number of removed entries (files
+    /// with their keys).
+    pub fn clear(&self) -> Result<usize, CrustError> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                if path.extension().is_none_or(|ext| ext != KEY_EXTENSION) {
+                    removed += 1;
+                }
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+impl Default for DownloadCache {
+    fn default() -> Self {
+        Self::new(DownloadCache::default_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn tmp_cache() -> DownloadCache {
+        DownloadCache::new(PathBuf::from(format!(
+            "/tmp/tmp.{}",
+            Uuid::new_v4().as_u128()
+        )))
+    }
+
+    fn key(mtime: u64) -> CacheKey {
+        CacheKey::new("MachineID<1>", Path::new("/etc/hosts"), mtime, 10)
+    }
+
+    #[test]
+    fn test_cache_miss() {
+        let cache = tmp_cache();
+
+        assert_eq!(cache.get(&key(1)), None);
+    }
+
+    #[test]
+    fn test_store_and_get_from_cache() {
+        let cache = tmp_cache();
+        let file = PathBuf::from(format!("/tmp/tmp.{}", Uuid::new_v4().as_u128()));
+        std::fs::write(&file, "content").unwrap();
+
+        let stored = cache.store(&key(1), &file).unwrap();
+
+        assert_eq!(cache.get(&key(1)), Some(stored.clone()));
+        assert_eq!(std::fs::read_to_string(stored).unwrap(), "content");
+        assert_eq!(cache.get(&key(2)), None);
+
+        let _ = std::fs::remove_file(file);
+        let _ = std::fs::remove_dir_all(cache.dir);
+    }
+
+    #[test]
+    fn test_clear_cache() {
+        let cache = tmp_cache();
+        let file = PathBuf::from(format!("/tmp/tmp.{}", Uuid::new_v4().as_u128()));
+        std::fs::write(&file, "content").unwrap();
+        cache.store(&key(1), &file).unwrap();
+        cache.store(&key(2), &file).unwrap();
+
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert_eq!(cache.get(&key(1)), None);
+
+        let _ = std::fs::remove_file(file);
+        let _ = std::fs::remove_dir_all(cache.dir);
+    }
+
+    #[test]
+    fn test_clear_not_existing_cache() {
+        let cache = tmp_cache();
+
+        assert_eq!(cache.clear().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_colliding_file_name_is_not_used() {
+        let cache = tmp_cache();
+        let file = PathBuf::from(format!("/tmp/tmp.{}", Uuid::new_v4().as_u128()));
+        std::fs::write(&file, "content").unwrap();
+        let stored = cache.store(&key(1), &file).unwrap();
+
+        // Simulate other key stored under the same file name
+        std::fs::write(DownloadCache::key_path(&stored), key(2).description()).unwrap();
+
+        assert_eq!(cache.get(&key(1)), None);
+
+        let _ = std::fs::remove_file(file);
+        let _ = std::fs::remove_dir_all(cache.dir);
+    }
+
+    #[test]
+    fn test_cache_key_depends_on_file_version() {
+        assert_ne!(key(1).file_name(), key(2).file_name());
+        assert_eq!(key(1).file_name(), key(1).file_name());
+        assert_eq!(key(1).file_name().len(), 64);
+    }
+}
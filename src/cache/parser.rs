@@ -0,0 +1,22 @@
+use clap::{Args, Subcommand};
+
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+
+#[derive(Debug, Clone, Args)]
+pub struct CacheArgs {
+    #[clap(subcommand)]
+    pub action: CacheAction,
+}
+
+impl Validation for CacheArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum CacheAction {
+    /// Removes all files from local download cache
+    Clear,
+}
@@ -0,0 +1,337 @@
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use ssh2::Session;
+
+use crate::error::{CrustError, ExitCode};
+
+/// Interval (in ms) in which blocked reads wake up to check whether
+/// their channel was requested to be closed.
+pub const POLL_TIMEOUT_MS: u32 = 500;
+
+/// Every channel opened in the current process. In background mode it can
+/// be inspected (and stuck channels closed) via control pipe.
+static CHANNELS: Mutex<Vec<ChannelInfo>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Gives id of run which channels opened now belong to. It is set by
+/// owner of runs, so connection layer does not depend on it.
+static RUN_HOOK: OnceLock<fn() -> Option<u64>> = OnceLock::new();
+
+/// What channel is used for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelKind {
+    Exec,
+    Scp,
+    Sftp,
+}
+
+impl std::fmt::Display for ChannelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChannelKind::Exec => write!(f, "exec"),
+            ChannelKind::Scp => write!(f, "scp"),
+            ChannelKind::Sftp => write!(f, "sftp"),
+        }
+    }
+}
+
+/// Single open channel.
+/// - target: machine on which channel is opened
+/// - description: what is done (command, transferred path)
+/// - run: multi-host run during which channel was opened
+/// - close_requested: user asked to close channel (it is closed by its
+///   owner at the nearest occasion)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelInfo {
+    pub id: u64,
+    pub target: String,
+    pub kind: ChannelKind,
+    pub description: String,
+    pub opened: DateTime<Utc>,
+    pub run: Option<u64>,
+    pub close_requested: bool,
+}
+
+/// Registration of open channel - it is removed from registry on drop.
+pub struct ChannelGuard {
+    id: u64,
+}
+
+impl ChannelGuard {
+    /// Registers a new channel.
+    pub fn open(target: &str, kind: ChannelKind, description: &str) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        log::trace!("Opened {kind} channel {id} on {target}: {description}");
+        CHANNELS.lock().unwrap().push(ChannelInfo {
+            id,
+            target: target.to_string(),
+            kind,
+            description: description.to_string(),
+            opened: Utc::now(),
+            run: RUN_HOOK.get().and_then(|current| current()),
+            close_requested: false,
+        });
+        Self { id }
+    }
+
+    /// Getter for channel id.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns error if user requested to close the channel.
+    pub fn check(&self) -> Result<(), CrustError> {
+        let requested = CHANNELS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|c| c.id == self.id && c.close_requested);
+        match requested {
+            true => Err(CrustError {
+                code: ExitCode::Ssh,
+                message: format!("Channel {} was force-closed", self.id),
+            }),
+            false => Ok(()),
+        }
+    }
+
+    /// Reads from channel stream, retrying timed out reads (session must
+    /// have a timeout set) until data comes or channel is force-closed.
+    pub fn read(&self, stream: &mut impl Read, buf: &mut [u8]) -> Result<usize, CrustError> {
+        loop {
+            match stream.read(buf) {
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                    self.check()?
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// Writes whole buffer into channel stream, retrying timed out writes
+    /// (see `read`).
+    pub fn write_all(&self, stream: &mut impl Write, mut buf: &[u8]) -> Result<(), CrustError> {
+        while !buf.is_empty() {
+            match stream.write(buf) {
+                Ok(0) => return Err(std::io::Error::from(ErrorKind::WriteZero).into()),
+                Ok(len) => buf = &buf[len..],
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                    self.check()?
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads whole stream (see `read`).
+    pub fn read_to_string(&self, stream: &mut impl Read) -> Result<String, CrustError> {
+        let mut data = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            match self.read(stream, &mut buf)? {
+                0 => return Ok(String::from_utf8(data)?),
+                len => data.extend_from_slice(&buf[..len]),
+            }
+        }
+    }
+}
+
+impl Drop for ChannelGuard {
+    fn drop(&mut self) {
+        CHANNELS.lock().unwrap().retain(|c| c.id != self.id);
+    }
+}
+
+/// Runs body with poll timeout set on sessions, so their blocked reads
+/// and writes wake up to check whether channel was requested to be closed
+/// (see `ChannelGuard::read`). Previous timeouts are restored.
+pub fn polling<T>(sessions: &[Session], body: impl FnOnce() -> T) -> T {
+    let timeouts = sessions.iter().map(|s| s.timeout()).collect::<Vec<_>>();
+    sessions.iter().for_each(|s| s.set_timeout(POLL_TIMEOUT_MS));
+    let result = body();
+    sessions
+        .iter()
+        .zip(timeouts)
+        .for_each(|(s, timeout)| s.set_timeout(timeout));
+    result
+}
+
+/// Gets a copy of currently open channels.
+pub fn list() -> Vec<ChannelInfo> {
+    CHANNELS.lock().unwrap().clone()
+}
+
+/// Asks owner of channel to close it.
+pub fn request_close(id: u64) -> Result<(), CrustError> {
+    let mut channels = CHANNELS.lock().unwrap();
+    match channels.iter_mut().find(|c| c.id == id) {
+        Some(channel) => {
+            channel.close_requested = true;
+            Ok(())
+        }
+        None => Err(CrustError {
+            code: ExitCode::Parser,
+            message: format!("There is no open channel with id {id}"),
+        }),
+    }
+}
+
+/// Sets function giving id of run in progress - channels opened in the
+/// meantime are assigned to it (and closed when run is cancelled).
+pub fn set_run_hook(current: fn() -> Option<u64>) {
+    let _ = RUN_HOOK.set(current);
+}
+
+/// Asks owners of all channels opened during passed run to close them.
+/// Returns a number of affected channels.
+pub fn request_close_run(run: u64) -> usize {
+    let mut channels = CHANNELS.lock().unwrap();
+    channels
+        .iter_mut()
+        .filter(|c| c.run == Some(run))
+        .map(|c| c.close_requested = true)
+        .count()
+}
+
+/// Table of passed channels.
+pub fn render(channels: &[ChannelInfo]) -> String {
+    if channels.is_empty() {
+        return String::from("No open channels");
+    }
+
+    let now = Utc::now();
+    let mut lines = vec![format!("{} open channels", channels.len())];
+    for channel in channels {
+        let age = (now - channel.opened).num_seconds();
+        let state = match channel.close_requested {
+            true => " [closing]",
+            false => "",
+        };
+        lines.push(format!(
+            "  #{} {} {} ({age}s): {}{state}",
+            channel.id, channel.kind, channel.target, channel.description
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    /// Stream which times out `timeouts` times before giving data.
+    struct SlowStream {
+        timeouts: usize,
+        data: &'static [u8],
+    }
+
+    impl Read for SlowStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.timeouts > 0 {
+                self.timeouts -= 1;
+                return Err(ErrorKind::TimedOut.into());
+            }
+            let len = self.data.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    /// Sink which times out `timeouts` times and then takes at most
+    /// `chunk` bytes per write.
+    struct SlowSink {
+        timeouts: usize,
+        chunk: usize,
+        data: Vec<u8>,
+    }
+
+    impl Write for SlowSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.timeouts > 0 {
+                self.timeouts -= 1;
+                return Err(ErrorKind::TimedOut.into());
+            }
+            let len = self.chunk.min(buf.len());
+            self.data.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[serial]
+    #[test]
+    fn test_guard_registers_channel() {
+        let guard = ChannelGuard::open("user@host", ChannelKind::Exec, "sleep 100");
+
+        let channel = list().into_iter().find(|c| c.id == guard.id()).unwrap();
+        assert_eq!(channel.kind, ChannelKind::Exec);
+        assert_eq!(channel.description, "sleep 100");
+        assert!(render(&[channel]).contains("exec user@host (0s): sleep 100"));
+
+        let id = guard.id();
+        std::mem::drop(guard);
+        assert!(list().iter().all(|c| c.id != id));
+    }
+
+    #[serial]
+    #[test]
+    fn test_read_retries_timeouts() {
+        let guard = ChannelGuard::open("user@host", ChannelKind::Exec, "echo");
+        let mut stream = SlowStream {
+            timeouts: 3,
+            data: b"output",
+        };
+
+        assert_eq!(guard.read_to_string(&mut stream).unwrap(), "output");
+    }
+
+    #[serial]
+    #[test]
+    fn test_write_retries_timeouts() {
+        let guard = ChannelGuard::open("user@host", ChannelKind::Scp, "/a.txt");
+        let mut sink = SlowSink {
+            timeouts: 2,
+            chunk: 4,
+            data: Vec::new(),
+        };
+
+        guard.write_all(&mut sink, b"0123456789").unwrap();
+        assert_eq!(sink.data, b"0123456789");
+    }
+
+    #[serial]
+    #[test]
+    fn test_force_close_stops_read() {
+        let guard = ChannelGuard::open("user@host", ChannelKind::Scp, "/big.img");
+        request_close(guard.id()).unwrap();
+        let mut stream = SlowStream {
+            timeouts: usize::MAX,
+            data: b"",
+        };
+
+        let err = guard.read_to_string(&mut stream).err().unwrap();
+        assert_eq!(err.code, ExitCode::Ssh);
+        assert_eq!(
+            err.message,
+            format!("Channel {} was force-closed", guard.id())
+        );
+    }
+
+    #[test]
+    fn test_close_unknown_channel() {
+        let err = request_close(u64::MAX).err().unwrap();
+        assert_eq!(
+            err.message,
+            format!("There is no open channel with id {}", u64::MAX)
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use ssh2::{MethodType, Session};
+
+use crate::error::CrustError;
+
+/// Key exchange algorithms allowed in `modern-crypto` build.
+pub const APPROVED_KEX: &[&str] = &[
+    "ecdh-sha2-nistp256",
+    "ecdh-sha2-nistp384",
+    "ecdh-sha2-nistp521",
+    "diffie-hellman-group16-sha512",
+    "diffie-hellman-group14-sha256",
+];
+
+/// Host key algorithms allowed in `modern-crypto` build.
+pub const APPROVED_HOSTKEY: &[&str] = &[
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "rsa-sha2-512",
+    "rsa-sha2-256",
+];
+
+/// Ciphers allowed in `modern-crypto` build.
+pub const APPROVED_CIPHERS: &[&str] = &[
+    "aes256-gcm@openssh.com",
+    "aes128-gcm@openssh.com",
+    "aes256-ctr",
+    "aes192-ctr",
+    "aes128-ctr",
+];
+
+/// MAC algorithms allowed in `modern-crypto` build.
+pub const APPROVED_MACS: &[&str] = &[
+    "hmac-sha2-512-etm@openssh.com",
+    "hmac-sha2-256-etm@openssh.com",
+    "hmac-sha2-512",
+    "hmac-sha2-256",
+];
+
+/// Name of algorithms policy compiled into binary.
+pub fn policy() -> &'static str {
+    match cfg!(feature = "modern-crypto") {
+        true => "modern-crypto",
+        false => "default",
+    }
+}
+
+/// Restricts algorithms offered during handshake to approved ones.
+/// Must be called before `Session::handshake`. Without `modern-crypto`
+/// feature libssh2 defaults are left untouched.
+pub fn apply_preferences(session: &Session) -> Result<(), CrustError> {
+    if cfg!(feature = "modern-crypto") {
+        session.method_pref(MethodType::Kex, &APPROVED_KEX.join(","))?;
+        session.method_pref(MethodType::HostKey, &APPROVED_HOSTKEY.join(","))?;
+        session.method_pref(MethodType::CryptCs, &APPROVED_CIPHERS.join(","))?;
+        session.method_pref(MethodType::CryptSc, &APPROVED_CIPHERS.join(","))?;
+        session.method_pref(MethodType::MacCs, &APPROVED_MACS.join(","))?;
+        session.method_pref(MethodType::MacSc, &APPROVED_MACS.join(","))?;
+    }
+    Ok(())
+}
+
+/// Algorithms negotiated with remote host during handshake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegotiatedAlgorithms {
+    pub kex: String,
+    pub hostkey: String,
+    pub cipher_cs: String,
+    pub cipher_sc: String,
+    pub mac_cs: String,
+    pub mac_sc: String,
+}
+
+impl NegotiatedAlgorithms {
+    /// Reads active algorithms from already established session.
+    pub fn from_session(session: &Session) -> Self {
+        let method = |mtype| session.methods(mtype).unwrap_or("unknown").to_string();
+        Self {
+            kex: method(MethodType::Kex),
+            hostkey: method(MethodType::HostKey),
+            cipher_cs: method(MethodType::CryptCs),
+            cipher_sc: method(MethodType::CryptSc),
+            mac_cs: method(MethodType::MacCs),
+            mac_sc: method(MethodType::MacSc),
+        }
+    }
+
+    /// Pairs of (label, negotiated value, approved values).
+    fn entries(&self) -> [(&str, &str, &[&str]); 6] {
+        [
+            ("kex", &self.kex, APPROVED_KEX),
+            ("hostkey", &self.hostkey, APPROVED_HOSTKEY),
+            ("cipher (client->server)", &self.cipher_cs, APPROVED_CIPHERS),
+            ("cipher (server->client)", &self.cipher_sc, APPROVED_CIPHERS),
+            ("mac (client->server)", &self.mac_cs, APPROVED_MACS),
+            ("mac (server->client)", &self.mac_sc, APPROVED_MACS),
+        ]
+    }
+
+    /// Checks whether every negotiated algorithm is an approved one.
+    /// AEAD ciphers (gcm) have integrated MAC, so mac is not checked then.
+    pub fn is_approved(&self) -> bool {
+        self.entries().iter().all(|(label, value, approved)| {
+            approved.contains(value) || (label.starts_with("mac") && self.uses_aead())
+        })
+    }
+
+    fn uses_aead(&self) -> bool {
+        self.cipher_cs.contains("gcm") && self.cipher_sc.contains("gcm")
+    }
+
+    /// Human readable report with status of every algorithm.
+    pub fn report(&self, host: &str) -> String {
+        let mut lines = vec![format!("Crypto report for {host} (policy: {})", policy())];
+        for (label, value, approved) in self.entries() {
+            let status = match approved.contains(&value) {
+                true => "approved",
+                false if label.starts_with("mac") && self.uses_aead() => "approved (aead)",
+                false => "NOT APPROVED",
+            };
+            lines.push(format!("  {label:<24} {value:<32} [{status}]"));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn algorithms() -> NegotiatedAlgorithms {
+        NegotiatedAlgorithms {
+            kex: String::from("ecdh-sha2-nistp256"),
+            hostkey: String::from("rsa-sha2-512"),
+            cipher_cs: String::from("aes256-ctr"),
+            cipher_sc: String::from("aes128-ctr"),
+            mac_cs: String::from("hmac-sha2-256"),
+            mac_sc: String::from("hmac-sha2-512"),
+        }
+    }
+
+    #[test]
+    fn test_approved_algorithms() {
+        assert!(algorithms().is_approved());
+    }
+
+    #[test]
+    fn test_not_approved_algorithms() {
+        let mut algs = algorithms();
+        algs.kex = String::from("curve25519-sha256");
+
+        assert!(!algs.is_approved());
+        assert!(algs
+            .report("host")
+            .contains("curve25519-sha256                [NOT APPROVED]"));
+    }
+
+    #[test]
+    fn test_aead_cipher_does_not_require_mac() {
+        let mut algs = algorithms();
+        algs.cipher_cs = String::from("aes256-gcm@openssh.com");
+        algs.cipher_sc = String::from("aes256-gcm@openssh.com");
+        algs.mac_cs = String::from("none");
+        algs.mac_sc = String::from("none");
+
+        assert!(algs.is_approved());
+        assert!(algs.report("host").contains("[approved (aead)]"));
+    }
+
+    #[test]
+    fn test_report_header() {
+        let report = algorithms().report("RemoteMachine<user@host>");
+
+        assert!(report.starts_with(&format!(
+            "Crypto report for RemoteMachine<user@host> (policy: {})",
+            policy()
+        )));
+        assert_eq!(report.lines().count(), 7);
+    }
+
+    #[test]
+    fn test_apply_preferences_on_new_session() {
+        let session = Session::new().unwrap();
+
+        assert!(apply_preferences(&session).is_ok());
+    }
+}
@@ -0,0 +1,344 @@
+use std::fmt::Display;
+use std::net::Ipv6Addr;
+use std::path::PathBuf;
+
+use crate::error::{CrustError, ExitCode};
+
+/// Machine (and optionally path on it) pointed in command line - the one
+/// grammar shared by addresses of exec, scp, with and archive commands:
+///
+/// ```text
+/// endpoint = machine [":" path]
+/// machine  = user "@" host [":" port] | alias
+/// host     = name | ipv4 | ipv6 | "[" ipv6 "]"
+/// ```
+/// - bare ipv6 host (`user@fe80::1`) can not be followed by port or path,
+///   use brackets then (`user@[fe80::1]:2222:/srv`)
+/// - digits right after host are port (`user@host:2222`), path is the
+///   part after them (`user@host:2222:/srv`)
+///
+/// Either user and host or alias is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub alias: Option<String>,
+    pub path: Option<PathBuf>,
+}
+
+impl Endpoint {
+    /// Parses machine with optional path.
+    /// # Example
+    /// ```
+    /// use std::path::PathBuf;
+    /// use crust::connection::endpoint::Endpoint;
+    ///
+    /// let endpoint = Endpoint::parse("deploy@[fe80::1]:2222:/srv/app").unwrap();
+    /// assert_eq!(endpoint.user.as_deref(), Some("deploy"));
+    /// assert_eq!(endpoint.host.as_deref(), Some("fe80::1"));
+    /// assert_eq!(endpoint.port, Some(2222));
+    /// assert_eq!(endpoint.path, Some(PathBuf::from("/srv/app")));
+    /// assert!(endpoint.is_ipv6());
+    ///
+    /// let endpoint = Endpoint::parse("backend:logs/app.log").unwrap();
+    /// assert_eq!(endpoint.alias.as_deref(), Some("backend"));
+    /// assert_eq!(endpoint.path, Some(PathBuf::from("logs/app.log")));
+    /// ```
+    pub fn parse(spec: &str) -> Result<Self, CrustError> {
+        let invalid = || CrustError {
+            code: ExitCode::Parser,
+            message: format!(
+                "Invalid endpoint '{spec}'. Use <user>@<host>[:<port>][:<path>] or <alias>[:<path>]"
+            ),
+        };
+
+        let Some((user, rest)) = spec.split_once('@') else {
+            let (alias, path) = match spec.split_once(':') {
+                Some((alias, path)) => (alias, Some(path)),
+                None => (spec, None),
+            };
+            if !is_name(alias) {
+                return Err(invalid());
+            }
+            return Ok(Self {
+                alias: Some(alias.to_string()),
+                path: parse_path(path).ok_or_else(invalid)?,
+                ..Self::empty()
+            });
+        };
+
+        if !is_name(user) {
+            return Err(invalid());
+        }
+        let (host, rest) = split_host(rest).ok_or_else(invalid)?;
+        let (port, path) = match rest {
+            None => (None, None),
+            Some(rest) if rest.starts_with(':') => return Err(invalid()),
+            Some(rest) => match rest.split_once(':') {
+                Some((port, path)) if is_port(port) => (Some(port), Some(path)),
+                None if is_port(rest) => (Some(rest), None),
+                _ => (None, Some(rest)),
+            },
+        };
+        let port = match port {
+            Some(port) => Some(port.parse::<u16>().map_err(|_| invalid())?),
+            None => None,
+        };
+
+        Ok(Self {
+            user: Some(user.to_string()),
+            host: Some(host.to_string()),
+            port,
+            path: parse_path(path).ok_or_else(invalid)?,
+            ..Self::empty()
+        })
+    }
+
+    /// Parses address of machine (`<user>@<host>[:<port>]`) - alias and
+    /// path are not allowed.
+    /// # Example
+    /// ```
+    /// use crust::connection::endpoint::Endpoint;
+    ///
+    /// let endpoint = Endpoint::parse_address("root@::1").unwrap();
+    /// assert_eq!(endpoint.host.as_deref(), Some("::1"));
+    /// assert!(Endpoint::parse_address("root@host:/tmp").is_err());
+    /// assert!(Endpoint::parse_address("backend").is_err());
+    /// ```
+    pub fn parse_address(addr: &str) -> Result<Self, CrustError> {
+        match Endpoint::parse(addr) {
+            Ok(endpoint) if endpoint.alias.is_none() && endpoint.path.is_none() => Ok(endpoint),
+            _ => Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Invalid address pattern. Use <user>@<host>".to_string(),
+            }),
+        }
+    }
+
+    /// Checks whether host is an ipv6 address.
+    pub fn is_ipv6(&self) -> bool {
+        self.host
+            .as_ref()
+            .is_some_and(|host| host.parse::<Ipv6Addr>().is_ok())
+    }
+
+    /// Address of machine (`<user>@<host>`, ipv6 host in brackets) - None
+    /// for endpoint with alias.
+    pub fn address(&self) -> Option<String> {
+        match (&self.user, &self.host) {
+            (Some(user), Some(host)) if self.is_ipv6() => Some(format!("{user}@[{host}]")),
+            (Some(user), Some(host)) => Some(format!("{user}@{host}")),
+            _ => None,
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            user: None,
+            host: None,
+            port: None,
+            alias: None,
+            path: None,
+        }
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (&self.alias, self.address()) {
+            (Some(alias), _) => write!(f, "{alias}")?,
+            (None, Some(address)) => write!(f, "{address}")?,
+            (None, None) => {}
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, ":{}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits host from the rest of endpoint (after `:`).
+fn split_host(value: &str) -> Option<(&str, Option<&str>)> {
+    if let Some(bracketed) = value.strip_prefix('[') {
+        let (host, rest) = bracketed.split_once(']')?;
+        host.parse::<Ipv6Addr>().ok()?;
+        return match rest {
+            "" => Some((host, None)),
+            _ => Some((host, Some(rest.strip_prefix(':')?))),
+        };
+    }
+    if value.parse::<Ipv6Addr>().is_ok() {
+        return Some((value, None));
+    }
+
+    let (host, rest) = match value.split_once(':') {
+        Some((host, rest)) => (host, Some(rest)),
+        None => (value, None),
+    };
+    is_name(host).then_some((host, rest))
+}
+
+/// Path must not be empty when separator was passed.
+fn parse_path(path: Option<&str>) -> Option<Option<PathBuf>> {
+    match path {
+        Some("") => None,
+        path => Some(path.map(PathBuf::from)),
+    }
+}
+
+fn is_name(value: &str) -> bool {
+    !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '@' | ':' | '/' | '[' | ']'))
+}
+
+fn is_port(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(
+        user: Option<&str>,
+        host: Option<&str>,
+        port: Option<u16>,
+        alias: Option<&str>,
+        path: Option<&str>,
+    ) -> Endpoint {
+        Endpoint {
+            user: user.map(String::from),
+            host: host.map(String::from),
+            port,
+            alias: alias.map(String::from),
+            path: path.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn test_parse_addresses() {
+        let cases = [
+            (
+                "user@host",
+                endpoint(Some("user"), Some("host"), None, None, None),
+            ),
+            (
+                "user@10.0.0.1:2222",
+                endpoint(Some("user"), Some("10.0.0.1"), Some(2222), None, None),
+            ),
+            (
+                "user@host.example.com",
+                endpoint(Some("user"), Some("host.example.com"), None, None, None),
+            ),
+            (
+                "user@::1",
+                endpoint(Some("user"), Some("::1"), None, None, None),
+            ),
+            (
+                "user@fe80::1:2",
+                endpoint(Some("user"), Some("fe80::1:2"), None, None, None),
+            ),
+            (
+                "user@[::1]",
+                endpoint(Some("user"), Some("::1"), None, None, None),
+            ),
+            (
+                "user@[2001:db8::7]:2222",
+                endpoint(Some("user"), Some("2001:db8::7"), Some(2222), None, None),
+            ),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(Endpoint::parse(spec).unwrap(), expected, "{spec}");
+            assert_eq!(Endpoint::parse_address(spec).unwrap(), expected, "{spec}");
+        }
+    }
+
+    #[test]
+    fn test_parse_paths_and_aliases() {
+        let cases = [
+            (
+                "user@host:/var/log",
+                endpoint(Some("user"), Some("host"), None, None, Some("/var/log")),
+            ),
+            (
+                "user@host:logs/app.log",
+                endpoint(Some("user"), Some("host"), None, None, Some("logs/app.log")),
+            ),
+            (
+                "user@host:2222:/srv",
+                endpoint(Some("user"), Some("host"), Some(2222), None, Some("/srv")),
+            ),
+            (
+                "user@host:2222x",
+                endpoint(Some("user"), Some("host"), None, None, Some("2222x")),
+            ),
+            (
+                "user@[::1]:/srv",
+                endpoint(Some("user"), Some("::1"), None, None, Some("/srv")),
+            ),
+            (
+                "user@[::1]:22:C:/data",
+                endpoint(Some("user"), Some("::1"), Some(22), None, Some("C:/data")),
+            ),
+            ("backend", endpoint(None, None, None, Some("backend"), None)),
+            (
+                "db-1:/backups/x.tar",
+                endpoint(None, None, None, Some("db-1"), Some("/backups/x.tar")),
+            ),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(Endpoint::parse(spec).unwrap(), expected, "{spec}");
+        }
+        assert!(Endpoint::parse_address("user@host:/srv").is_err());
+        assert!(Endpoint::parse_address("backend").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid_endpoints() {
+        let specs = [
+            "",
+            "@host",
+            "user@",
+            "user@host@other",
+            "us er@host",
+            "user@host:",
+            "user@host:99999",
+            "user@[::1",
+            "user@[host]",
+            "user@[::1]x",
+            "user@fe80::1:/srv",
+            ":/path",
+            "alias:",
+            "/tmp/file",
+        ];
+        for spec in specs {
+            assert!(Endpoint::parse(spec).is_err(), "{spec}");
+        }
+        assert_eq!(
+            Endpoint::parse("user@").err().unwrap().message,
+            "Invalid endpoint 'user@'. Use <user>@<host>[:<port>][:<path>] or <alias>[:<path>]"
+        );
+    }
+
+    #[test]
+    fn test_display_endpoint() {
+        for spec in [
+            "user@host",
+            "user@[::1]:2222:/srv",
+            "user@host:/srv",
+            "backend:/srv",
+        ] {
+            assert_eq!(Endpoint::parse(spec).unwrap().to_string(), spec);
+        }
+        assert_eq!(
+            Endpoint::parse("user@::1").unwrap().address().unwrap(),
+            "user@[::1]"
+        );
+    }
+}
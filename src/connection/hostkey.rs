@@ -0,0 +1,299 @@
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use clap::ValueEnum;
+use ssh2::{CheckResult, HashType, HostKeyType, KnownHostFileKind, Session};
+
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::output;
+use crate::session::{self, EventKind, SessionEvent};
+
+/// How host keys of remote machines are verified (like `StrictHostKeyChecking`).
+/// - Yes: only hosts from known_hosts are accepted
+/// - AcceptNew: unknown hosts are added to known_hosts, changed keys are rejected
+/// - No: host keys are not verified
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum HostKeyPolicy {
+    Yes,
+    AcceptNew,
+    No,
+}
+
+static POLICY: Mutex<HostKeyPolicy> = Mutex::new(HostKeyPolicy::No);
+
+thread_local! {
+    /// Policy passed with operation run by this thread.
+    static OPERATION: Cell<Option<HostKeyPolicy>> = const { Cell::new(None) };
+}
+
+/// Sets default policy of process - used when operation does not pass
+/// its own one and by connections opened outside of operations (e.g.
+/// refreshed standby sessions).
+pub fn set_policy(policy: HostKeyPolicy) {
+    *POLICY.lock().unwrap() = policy;
+}
+
+/// Gets policy of current operation (or default one).
+pub fn policy() -> HostKeyPolicy {
+    OPERATION
+        .with(Cell::get)
+        .unwrap_or_else(|| *POLICY.lock().unwrap())
+}
+
+/// Gets policy passed with current operation.
+pub fn current() -> Option<HostKeyPolicy> {
+    OPERATION.with(Cell::get)
+}
+
+/// Runs body with policy passed with operation (default one is used
+/// without it).
+pub fn within<T>(policy: Option<HostKeyPolicy>, body: impl FnOnce() -> T) -> T {
+    let previous = OPERATION.with(|current| current.replace(policy));
+    let result = body();
+    OPERATION.with(|current| current.set(previous));
+    result
+}
+
+/// Location of known_hosts: `$CRUST_KNOWN_HOSTS` or `$HOME/.ssh/known_hosts`.
+pub fn known_hosts_path() -> PathBuf {
+    if let Ok(path) = std::env::var("CRUST_KNOWN_HOSTS") {
+        return PathBuf::from(path);
+    }
+    let home = std::env::var("HOME").unwrap_or_else(|_| String::from("/tmp"));
+    PathBuf::from(home).join(".ssh").join("known_hosts")
+}
+
+/// Name of host in known_hosts (port is added only if it is not a default one).
+fn host_entry(host: &str, port: u16) -> String {
+    match port {
+        22 => host.to_string(),
+        _ => format!("[{host}]:{port}"),
+    }
+}
+
+/// Checks host key of session (after handshake) with current policy.
+pub fn verify(session: &Session, host: &str, port: u16) -> Result<(), CrustError> {
+    let policy = policy();
+    if policy == HostKeyPolicy::No {
+        return Ok(());
+    }
+
+    let (key, key_type) = session.host_key().ok_or_else(|| CrustError {
+        code: ExitCode::Ssh,
+        message: format!("Host {host} did not provide a host key"),
+    })?;
+    let digest = session.host_key_hash(HashType::Sha256).unwrap_or_default();
+    let fingerprint = format!("SHA256:{}", base64(digest));
+
+    let path = known_hosts_path();
+    let mut known_hosts = session.known_hosts()?;
+    if path.exists() {
+        known_hosts.read_file(&path, KnownHostFileKind::OpenSSH)?;
+    }
+
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(CrustError {
+            code: ExitCode::Ssh,
+            message: format!(
+                "Host key of {} has changed ({fingerprint}). It may be a man-in-the-middle attack - verify the key and update {}",
+                host_entry(host, port),
+                path.display()
+            ),
+        }),
+        CheckResult::Failure => Err(CrustError {
+            code: ExitCode::Ssh,
+            message: format!(
+                "Host key of {} ({fingerprint}) can not be checked with {}",
+                host_entry(host, port),
+                path.display()
+            ),
+        }),
+        CheckResult::NotFound if policy == HostKeyPolicy::Yes => {
+            Err(CrustError {
+                code: ExitCode::Ssh,
+                message: format!(
+                    "Host key of {} ({fingerprint}) is not known. Add it to {} or use --host-key-policy accept-new",
+                    host_entry(host, port),
+                    path.display()
+                ),
+            })
+        }
+        CheckResult::NotFound => {
+            let name = key_type_name(key_type);
+            output::write_err_line(&format!(
+                "Accepting new host key of {}\n{name} key fingerprint is {fingerprint}\n{}",
+                host_entry(host, port),
+                randomart(digest, name, "SHA256")
+            ));
+
+            known_hosts.add(&host_entry(host, port), key, "", key_type.into())?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            known_hosts.write_file(&path, KnownHostFileKind::OpenSSH)?;
+
+            let user = std::env::var("USER").unwrap_or_else(|_| String::from("unknown"));
+            session::record(SessionEvent {
+                kind: EventKind::HostKey,
+                started: Utc::now(),
+                duration: std::time::Duration::ZERO,
+                target: host_entry(host, port),
+                description: format!("{name} {fingerprint} accepted by {user}"),
+                stdout: String::new(),
+                stderr: String::new(),
+                retcode: 0,
+            });
+            Ok(())
+        }
+    }
+}
+
+fn key_type_name(key_type: HostKeyType) -> &'static str {
+    match key_type {
+        HostKeyType::Rsa => "RSA",
+        HostKeyType::Dss => "DSA",
+        HostKeyType::Ecdsa256 | HostKeyType::Ecdsa384 | HostKeyType::Ecdsa521 => "ECDSA",
+        HostKeyType::Ed25519 => "ED25519",
+        HostKeyType::Unknown => "UNKNOWN",
+    }
+}
+
+/// Standard base64 without padding (format of OpenSSH fingerprints).
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for idx in 0..=chunk.len() {
+            let sextet = (triple >> (18 - 6 * idx)) & 0x3f;
+            encoded.push(ALPHABET[sextet as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Visual host key (OpenSSH "drunken bishop" randomart) of passed digest.
+pub fn randomart(digest: &[u8], title: &str, hash_name: &str) -> String {
+    const WIDTH: usize = 17;
+    const HEIGHT: usize = 9;
+    const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+
+    let mut field = [[0usize; WIDTH]; HEIGHT];
+    let (mut x, mut y) = (WIDTH / 2, HEIGHT / 2);
+    for byte in digest {
+        let mut input = *byte;
+        for _ in 0..4 {
+            x = match input & 0x1 {
+                0 => x.saturating_sub(1),
+                _ => (x + 1).min(WIDTH - 1),
+            };
+            y = match input & 0x2 {
+                0 => y.saturating_sub(1),
+                _ => (y + 1).min(HEIGHT - 1),
+            };
+            field[y][x] = (field[y][x] + 1).min(SYMBOLS.len() - 3);
+            input >>= 2;
+        }
+    }
+    field[HEIGHT / 2][WIDTH / 2] = SYMBOLS.len() - 2;
+    field[y][x] = SYMBOLS.len() - 1;
+
+    let border = |label: &str| {
+        let label = format!("[{label}]");
+        let left = (WIDTH - label.len()) / 2;
+        format!(
+            "+{}{label}{}+",
+            "-".repeat(left),
+            "-".repeat(WIDTH - label.len() - left)
+        )
+    };
+
+    let mut lines = vec![border(title)];
+    for row in field {
+        let row = row.iter().map(|c| SYMBOLS[*c] as char).collect::<String>();
+        lines.push(format!("|{row}|"));
+    }
+    lines.push(border(hash_name));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_without_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg");
+        assert_eq!(base64(b"fo"), "Zm8");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe]), "//4");
+    }
+
+    #[test]
+    fn test_host_entry() {
+        assert_eq!(host_entry("10.0.0.1", 22), "10.0.0.1");
+        assert_eq!(host_entry("10.0.0.1", 2222), "[10.0.0.1]:2222");
+    }
+
+    #[test]
+    fn test_randomart_shape() {
+        let art = randomart(&[0u8; 32], "ED25519", "SHA256");
+        let lines = art.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "+----[ED25519]----+");
+        assert_eq!(lines[10], "+----[SHA256]-----+");
+        assert!(lines[1..10].iter().all(|l| l.len() == 19));
+    }
+
+    #[test]
+    fn test_randomart_walk() {
+        // Every step of zero byte goes up-left, so the bishop ends in the corner.
+        let art = randomart(&[0u8; 1], "RSA", "SHA256");
+        let lines = art.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[1], "|    E            |");
+        assert_eq!(lines[2], "|     .           |");
+        assert_eq!(lines[4], "|       .         |");
+        assert_eq!(lines[5], "|        S        |");
+    }
+
+    #[test]
+    fn test_randomart_is_deterministic() {
+        let digest = (0..32).collect::<Vec<u8>>();
+
+        assert_eq!(
+            randomart(&digest, "RSA", "SHA256"),
+            randomart(&digest, "RSA", "SHA256")
+        );
+        assert_ne!(
+            randomart(&digest, "RSA", "SHA256"),
+            randomart(&[0u8; 32], "RSA", "SHA256")
+        );
+    }
+
+    #[test]
+    fn test_policy_of_operation() {
+        assert_eq!(policy(), HostKeyPolicy::No);
+
+        let (inner, other) = within(Some(HostKeyPolicy::Yes), || {
+            let other = std::thread::spawn(policy).join().unwrap();
+            (policy(), other)
+        });
+
+        assert_eq!(inner, HostKeyPolicy::Yes);
+        assert_eq!(other, HostKeyPolicy::No);
+        assert_eq!(policy(), HostKeyPolicy::No);
+    }
+}
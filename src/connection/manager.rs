@@ -38,8 +38,14 @@ pub trait MachinesManagerMethods {
     }
 }
 
+/// Minimal number of uses after which machine is treated as frequently
+/// used (and gets standby sessions).
+pub const STANDBY_MIN_USES: usize = 2;
+
 pub struct MachinesManager {
     store: HashMap<MachineID, Rc<RefCell<Box<dyn Machine>>>>,
+    usage: HashMap<MachineID, usize>,
+    standby: usize,
     //TODO: in the future add map for related subconnections
 }
 
@@ -47,6 +53,8 @@ impl MachinesManager {
     pub fn new() -> Self {
         Self {
             store: HashMap::new(),
+            usage: HashMap::new(),
+            standby: 0,
         }
     }
 
@@ -54,6 +62,56 @@ impl MachinesManager {
     pub fn size(&self) -> usize {
         self.store.len()
     }
+
+    /// Sets number of standby sessions kept for every frequently used
+    /// machine (0 disables standby sessions).
+    pub fn set_standby(&mut self, count: usize) {
+        self.standby = count;
+    }
+
+    /// Registers usage of machine - required to find frequently used ones.
+    pub fn register_usage(&mut self, id: &MachineID) {
+        *self.usage.entry(id.clone()).or_insert(0) += 1;
+    }
+
+    /// Gets IDs of stored machines used at least `STANDBY_MIN_USES` times.
+    pub fn frequently_used(&self) -> Vec<MachineID> {
+        self.usage
+            .iter()
+            .filter(|(id, uses)| **uses >= STANDBY_MIN_USES && self.store.contains_key(id))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Gets IDs of stored machines connected (or to be connected) to host.
+    pub fn ids_of_host(&self, host: &str) -> Vec<MachineID> {
+        self.store
+            .iter()
+            .filter(|(_, machine)| {
+                machine
+                    .borrow()
+                    .connect_args()
+                    .is_some_and(|args| args.hostname() == host)
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Keeps standby sessions of frequently used machines. Nothing is
+    /// connected here - sessions are probed and refilled by refresher of
+    /// standby pool (see `standby::start_refresher`).
+    pub fn refresh_standby(&mut self) {
+        if self.standby == 0 {
+            return;
+        }
+
+        for id in self.frequently_used() {
+            let machine = &self.store[&id];
+            if let Err(e) = machine.borrow_mut().warm_standby(self.standby) {
+                log::warn!("Can not keep standby sessions for {id}: {e}");
+            }
+        }
+    }
 }
 
 impl MachinesManagerMethods for MachinesManager {
@@ -80,6 +138,7 @@ impl MachinesManagerMethods for MachinesManager {
             });
         }
         self.store.remove(&id);
+        self.usage.remove(&id);
         log::debug!("Removed machine ({id})");
         Ok(())
     }
@@ -160,6 +219,46 @@ mod tests {
         assert_eq!(manager.size(), 1);
     }
 
+    #[test]
+    fn test_frequently_used_machines() {
+        let mut manager = MachinesManager::new();
+        let id1 = MachineID::new(Some(String::from("a")), Some(String::from("b")), Some(1));
+        let id2 = MachineID::default();
+
+        manager.add_machine(Box::new(MockMachine {
+            id: id1.clone(),
+            tmpdir: None,
+        }));
+        manager.add_machine(Box::new(MockMachine {
+            id: id2.clone(),
+            tmpdir: None,
+        }));
+
+        manager.register_usage(&id1);
+        manager.register_usage(&id2);
+        assert!(manager.frequently_used().is_empty());
+
+        manager.register_usage(&id1);
+        assert_eq!(manager.frequently_used(), vec![id1.clone()]);
+
+        manager.remove_machine(id1).unwrap();
+        assert!(manager.frequently_used().is_empty());
+    }
+
+    #[test]
+    fn test_refresh_standby_for_machines_without_connection() {
+        let mut manager = MachinesManager::new();
+        manager.set_standby(2);
+
+        let machine = LocalMachine::get_or_create(&mut manager);
+        let id = machine.borrow().get_id().clone();
+        manager.register_usage(&id);
+        manager.register_usage(&id);
+
+        manager.refresh_standby();
+        assert_eq!(manager.frequently_used(), vec![id]);
+    }
+
     #[test]
     fn test_get_machine_from_store() {
         let mut manager = MachinesManager::new();
@@ -173,6 +272,6 @@ mod tests {
 
         let machine = manager.get_machine(&MachineID::default()).unwrap().borrow();
 
-        assert_eq!(machine.exec("cmd").unwrap().is_success(), true);
+        assert!(machine.exec("cmd").unwrap().is_success());
     }
 }
@@ -1,14 +1,30 @@
+pub mod channels;
+pub mod crypto;
+pub mod endpoint;
+pub mod hostkey;
+pub mod key;
 pub mod manager;
+pub mod parallel;
 pub mod parser;
+pub mod request;
+pub mod settings;
+pub mod standby;
+pub mod timing;
 
 use crate::exec::BUFF_SIZE;
+use crate::interfaces::output;
 use crate::interfaces::response::CrustResult;
-use ssh2::Session;
-use std::io::Read;
-use std::net::TcpStream;
+use ssh2::{Channel, Session};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
 use std::path::PathBuf;
 
 use super::error::{CrustError, ExitCode};
+use crate::machine::os::HostOs;
+use channels::{ChannelGuard, ChannelKind};
+use key::InlineKey;
+use settings::SessionSettings;
+use timing::Phase;
 
 /// Providing required methods for connecting to a remote server
 pub trait SSH {
@@ -25,6 +41,9 @@ pub trait SSH {
     /// Remote version of `std::process::Command`.
     fn execute(&self, command: &str) -> Result<CrustResult, CrustError>;
 
+    /// Remote version of execute with `input` written to stdin of command.
+    fn execute_with_input(&self, command: &str, input: &str) -> Result<CrustResult, CrustError>;
+
     /// Remote version of execute (real-time).
     fn execute_rt(&self, command: &str, merge_pipes: bool) -> Result<CrustResult, CrustError>;
 
@@ -39,83 +58,88 @@ pub trait SSH {
 }
 
 /// Represents arguments neccessary for connection.
+/// - inline_key: private key kept in memory (used instead of file and
+///   zeroized when machine is dropped)
 #[derive(Clone)]
 pub struct ConnectArgs {
     username: String,
     hostname: String,
     private_key: Option<PathBuf>,
+    inline_key: Option<InlineKey>,
     password: Option<String>,
     port: u16,
 }
 
 /// Main structure used in RemoteMachine
+/// - session: main session used to execute commands (broken one is
+///   replaced by a standby session, see `standby`)
+/// - settings: per-machine settings applied to every executed command
+/// - os: operating system of connected machine (detected on connect)
 #[derive(Clone)]
 pub struct SshConnection {
     session: Option<Session>,
+    settings: SessionSettings,
+    os: HostOs,
     pub connect_args: Option<ConnectArgs>,
 }
 
-impl SSH for SshConnection {
-    fn new(
-        username: &str,
-        hostname: &str,
-        private_key: Option<PathBuf>,
-        password: Option<String>,
-        port: u16,
-    ) -> Self {
-        let connect_args = ConnectArgs {
-            username: String::from(username),
-            hostname: String::from(hostname),
-            private_key,
-            password,
-            port,
-        };
-        Self {
-            session: None,
-            connect_args: Some(connect_args),
-        }
+impl ConnectArgs {
+    /// Getter for name of user.
+    pub fn username(&self) -> &str {
+        &self.username
     }
 
-    fn session(&self) -> Session {
-        self.session.clone().expect("Session was not created")
+    /// Getter for address of machine.
+    pub fn hostname(&self) -> &str {
+        &self.hostname
     }
 
-    fn is_connected(&self) -> bool {
-        match &self.session {
-            None => false,
-            Some(ses) => match ses.channel_session() {
-                Ok(mut channel) => match channel.exec("") {
-                    Ok(_) => {
-                        let _ = channel.send_eof();
-                        let _ = channel.wait_close();
-                        true
-                    }
-                    Err(_) => false,
-                },
-                Err(_) => false,
-            },
-        }
+    /// Key of machine in timing breakdown (`user@host`).
+    pub fn target(&self) -> String {
+        format!("{}@{}", self.username, self.hostname)
     }
 
-    fn connect(&mut self) -> Result<(), CrustError> {
-        let conn_args = match &self.connect_args {
-            Some(args) => args,
-            None => {
-                return Err(CrustError {
-                    code: ExitCode::Ssh,
-                    message: "Did not define connection arguments for session".to_string(),
-                })
-            }
-        };
+    /// Getter for SSH port.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
 
-        let tcp = TcpStream::connect((conn_args.hostname.as_ref(), conn_args.port))?;
+impl SshConnection {
+    /// Opens a new authenticated session with passed arguments.
+    pub(crate) fn open_session(conn_args: &ConnectArgs) -> Result<Session, CrustError> {
+        let target = conn_args.target();
+        let addrs = timing::measure(&target, Phase::Dns, || {
+            (conn_args.hostname.as_str(), conn_args.port)
+                .to_socket_addrs()
+                .map(Vec::from_iter)
+        })?;
+        let tcp = timing::measure(&target, Phase::TcpConnect, || {
+            TcpStream::connect(addrs.as_slice())
+        })?;
         let mut session = Session::new()?;
+        crypto::apply_preferences(&session)?;
         session.set_tcp_stream(tcp);
-        session.handshake()?;
+        timing::measure(&target, Phase::Handshake, || -> Result<(), CrustError> {
+            session.handshake()?;
+            hostkey::verify(&session, &conn_args.hostname, conn_args.port)
+        })?;
+
+        timing::measure(&target, Phase::Auth, || {
+            SshConnection::authorize(&session, conn_args)
+        })?;
+        Ok(session)
+    }
 
+    /// Authorizes session with the first available method (password,
+    /// in-memory key, key file).
+    fn authorize(session: &Session, conn_args: &ConnectArgs) -> Result<(), CrustError> {
         if let Some(pswd) = conn_args.password.as_ref() {
             log::debug!("Auth method - password");
             session.userauth_password(conn_args.username.as_str(), pswd.as_str())?;
+        } else if let Some(key) = conn_args.inline_key.as_ref() {
+            log::debug!("Auth method - in-memory private key");
+            session.userauth_pubkey_memory(conn_args.username.as_str(), None, key.data(), None)?;
         } else if let Some(pkey) = conn_args.private_key.as_ref() {
             log::debug!("Auth method - private key");
             session.userauth_pubkey_file(
@@ -138,30 +162,103 @@ impl SSH for SshConnection {
                 message: "Authentication failed".to_string(),
             });
         }
-        log::debug!(
-            "Session to '{}@{}' created",
-            conn_args.username,
-            conn_args.hostname
-        );
-        self.session = Some(session);
         Ok(())
     }
 
-    fn execute(&self, command: &str) -> Result<CrustResult, CrustError> {
-        let mut channel = self
-            .session
-            .as_ref()
-            .expect("Call `.connect()` method first")
-            .channel_session()?;
+    /// Health probe - checks whether session is still able to open
+    /// a channel and execute an (empty) command.
+    fn probe(session: &Session) -> bool {
+        match session.channel_session() {
+            Ok(mut channel) => match channel.exec("") {
+                Ok(_) => {
+                    let _ = channel.send_eof();
+                    let _ = channel.wait_close();
+                    true
+                }
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
 
-        channel.exec(command)?;
+    /// Keeps `count` standby sessions of machine in pool shared by all
+    /// commands (they are opened by refresher of pool).
+    pub fn warm_standby(&self, count: usize) {
+        if let Some(args) = &self.connect_args {
+            standby::keep(args, count);
+        }
+    }
+
+    /// Replaces the main session with a healthy standby one.
+    /// Returns false if there was no healthy session to rotate.
+    pub fn rotate(&mut self) -> bool {
+        match self.connect_args.as_ref().and_then(standby::take) {
+            Some(session) => {
+                log::debug!("Rotated session of '{}' to standby one", self);
+                self.session = Some(session);
+                true
+            }
+            None => false,
+        }
+    }
 
-        let mut stdout = String::new();
-        let mut stderr = String::new();
+    /// Sets settings applied to every executed command.
+    pub fn set_settings(&mut self, settings: SessionSettings) {
+        log::debug!("Session settings for '{}': {:?}", self, settings);
+        self.settings = settings;
+    }
 
-        channel.read_to_string(&mut stdout)?;
-        channel.stderr().read_to_string(&mut stderr)?;
-        channel.wait_close()?;
+    /// Getter for settings applied to every executed command.
+    pub fn settings(&self) -> &SessionSettings {
+        &self.settings
+    }
+
+    /// Starts command with applied settings on channel. Command run as
+    /// another user gets password of connected account on stdin (it is
+    /// consumed by sudo and never appears in the command line).
+    fn start(&self, channel: &mut Channel, command: &str) -> Result<(), CrustError> {
+        let password = match (&self.settings.run_as, self.os) {
+            (Some(_), HostOs::Unix) => self.connect_args.as_ref().and_then(|a| a.password.as_ref()),
+            _ => None,
+        };
+
+        match password {
+            Some(password) => {
+                channel.exec(&self.settings.wrap_with_password(command))?;
+                channel.write_all(format!("{password}\n").as_bytes())?;
+            }
+            None => channel.exec(&self.os.wrap(&self.settings, command)?)?,
+        }
+        Ok(())
+    }
+
+    /// Executes command and captures its output. Passed input is written
+    /// to stdin of command (which is closed afterwards).
+    fn run(&self, command: &str, input: Option<&str>) -> Result<CrustResult, CrustError> {
+        let session = self
+            .session
+            .as_ref()
+            .expect("Call `.connect()` method first");
+        let target = self.to_string();
+        let mut channel =
+            timing::measure(&target, Phase::ChannelOpen, || session.channel_session())?;
+        let guard = ChannelGuard::open(&target, ChannelKind::Exec, command);
+
+        let started = std::time::Instant::now();
+        self.start(&mut channel, command)?;
+        if let Some(input) = input {
+            channel.write_all(input.as_bytes())?;
+            channel.send_eof()?;
+        }
+        let (stdout, stderr) = self.polling(&mut channel, |channel| {
+            let stdout = guard.read_to_string(channel)?;
+            timing::record(&target, Phase::Command, started.elapsed());
+            let stderr = timing::measure(&target, Phase::Drain, || {
+                guard.read_to_string(&mut channel.stderr())
+            })?;
+            Ok((stdout, stderr))
+        })?;
+        timing::measure(&target, Phase::Close, || channel.wait_close())?;
 
         // TODO: Workaround to register unknown command as failure
         let retcode = match stderr.is_empty() {
@@ -172,58 +269,184 @@ impl SSH for SshConnection {
         Ok(CrustResult::new(&stdout, &stderr, retcode))
     }
 
+    /// Runs reads of channel with a poll timeout, so force-close requested
+    /// via channel registry is noticed (channel is closed then).
+    fn polling<T>(
+        &self,
+        channel: &mut Channel,
+        body: impl FnOnce(&mut Channel) -> Result<T, CrustError>,
+    ) -> Result<T, CrustError> {
+        let result = channels::polling(&[self.session()], || body(channel));
+
+        if result.is_err() {
+            let _ = channel.close();
+        }
+        result
+    }
+
+    /// Sets private key (kept in memory) used to authorize. Key is kept
+    /// for the life of machine, as standby sessions and reconnects need it
+    /// too (it is zeroized when dropped). Key passed to already connected
+    /// machine is not needed and is dropped at once.
+    pub fn set_inline_key(&mut self, key: InlineKey) {
+        if let (None, Some(args)) = (&self.session, self.connect_args.as_mut()) {
+            args.inline_key = Some(key);
+        }
+    }
+
+    /// Arguments of connection which was not established yet (None if
+    /// session already exists).
+    pub fn pending_connection(&self) -> Option<ConnectArgs> {
+        match self.session {
+            Some(_) => None,
+            None => self.connect_args.clone(),
+        }
+    }
+
+    /// Sets session opened outside of connection (e.g. in parallel with
+    /// other machines) as the main one.
+    pub fn set_session(&mut self, session: Session) {
+        log::debug!("Session to '{}' created", self);
+        self.os = HostOs::detect(&session);
+        self.session = Some(session);
+    }
+
+    /// Operating system of connected machine.
+    pub fn os(&self) -> HostOs {
+        self.os
+    }
+
+    /// Gets a number of currently ready standby sessions.
+    pub fn standby_size(&self) -> usize {
+        self.connect_args.as_ref().map_or(0, standby::size)
+    }
+}
+
+impl SSH for SshConnection {
+    fn new(
+        username: &str,
+        hostname: &str,
+        private_key: Option<PathBuf>,
+        password: Option<String>,
+        port: u16,
+    ) -> Self {
+        let connect_args = ConnectArgs {
+            username: String::from(username),
+            hostname: String::from(hostname),
+            private_key,
+            inline_key: None,
+            password,
+            port,
+        };
+        Self {
+            session: None,
+            settings: SessionSettings::default(),
+            os: HostOs::Unix,
+            connect_args: Some(connect_args),
+        }
+    }
+
+    fn session(&self) -> Session {
+        self.session.clone().expect("Session was not created")
+    }
+
+    fn is_connected(&self) -> bool {
+        match &self.session {
+            None => false,
+            Some(ses) => SshConnection::probe(ses),
+        }
+    }
+
+    fn connect(&mut self) -> Result<(), CrustError> {
+        let conn_args = match &self.connect_args {
+            Some(args) => args,
+            None => {
+                return Err(CrustError {
+                    code: ExitCode::Ssh,
+                    message: "Did not define connection arguments for session".to_string(),
+                })
+            }
+        };
+
+        // Standby session (if any is ready) saves a handshake
+        let session = match standby::take(conn_args) {
+            Some(session) => session,
+            None => SshConnection::open_session(conn_args)?,
+        };
+        self.set_session(session);
+        Ok(())
+    }
+
+    fn execute(&self, command: &str) -> Result<CrustResult, CrustError> {
+        self.run(command, None)
+    }
+
+    fn execute_with_input(&self, command: &str, input: &str) -> Result<CrustResult, CrustError> {
+        self.run(command, Some(input))
+    }
+
     fn execute_rt(&self, command: &str, merge_pipes: bool) -> Result<CrustResult, CrustError> {
-        let mut channel = self
+        let session = self
             .session
             .as_ref()
-            .expect("Call `.connect()` method first")
-            .channel_session()?;
+            .expect("Call `.connect()` method first");
+        let target = self.to_string();
+        let mut channel =
+            timing::measure(&target, Phase::ChannelOpen, || session.channel_session())?;
+        let guard = ChannelGuard::open(&target, ChannelKind::Exec, command);
+        let started = std::time::Instant::now();
 
         match merge_pipes {
             true => {
-                channel.exec(&format!("{command} 2>&1"))?;
+                self.start(&mut channel, &format!("{command} 2>&1"))?;
 
-                let mut buffer = [0; BUFF_SIZE];
+                self.polling(&mut channel, |channel| {
+                    let mut buffer = [0; BUFF_SIZE];
 
-                loop {
-                    let size = channel.read(&mut buffer)?;
+                    loop {
+                        let size = guard.read(channel, &mut buffer)?;
 
-                    if size == 0 {
-                        break;
-                    }
+                        if size == 0 {
+                            break;
+                        }
 
-                    print!("{}", String::from_utf8(buffer[..size].to_vec())?);
-                }
+                        output::write(&String::from_utf8(buffer[..size].to_vec())?);
+                    }
+                    Ok(())
+                })?;
             }
             false => {
-                channel.exec(command)?;
+                self.start(&mut channel, command)?;
 
-                let mut out_buffer = [0; BUFF_SIZE];
-                let mut err_buffer = [0; BUFF_SIZE];
+                self.polling(&mut channel, |channel| {
+                    let mut out_buffer = [0; BUFF_SIZE];
+                    let mut err_buffer = [0; BUFF_SIZE];
 
-                loop {
-                    let out_size = channel.read(&mut out_buffer)?;
-                    let err_size = channel.stderr().read(&mut err_buffer)?;
+                    loop {
+                        let out_size = guard.read(channel, &mut out_buffer)?;
+                        let err_size = guard.read(&mut channel.stderr(), &mut err_buffer)?;
 
-                    if out_size == 0 && err_size == 0 {
-                        break;
-                    }
+                        if out_size == 0 && err_size == 0 {
+                            break;
+                        }
 
-                    print!("{}", String::from_utf8(out_buffer[..out_size].to_vec())?);
-                    log::error!("{}", String::from_utf8(err_buffer[..err_size].to_vec())?);
-                }
+                        output::write(&String::from_utf8(out_buffer[..out_size].to_vec())?);
+                        log::error!("{}", String::from_utf8(err_buffer[..err_size].to_vec())?);
+                    }
+                    Ok(())
+                })?;
             }
         };
+        timing::record(&target, Phase::Command, started.elapsed());
 
-        channel.wait_close()?;
+        timing::measure(&target, Phase::Close, || channel.wait_close())?;
         Ok(CrustResult::default())
     }
 }
 
 impl std::fmt::Display for SshConnection {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let conn_args = self.connect_args.clone().unwrap();
-        write!(f, "{}@{}", conn_args.username, conn_args.hostname)
+        write!(f, "{}", self.connect_args.as_ref().unwrap().target())
     }
 }
 
@@ -255,6 +478,7 @@ mod tests {
         assert_eq!(args.hostname, String::from("hostname"));
         assert_eq!(args.password, None);
         assert_eq!(args.private_key, None);
+        assert!(args.inline_key.is_none());
         assert_eq!(args.port, 22);
     }
 
@@ -263,6 +487,8 @@ mod tests {
         let mut ssh = SshConnection {
             connect_args: None,
             session: None,
+            settings: SessionSettings::default(),
+            os: HostOs::Unix,
         };
         let result = ssh.connect();
 
@@ -325,6 +551,8 @@ mod tests {
     fn test_execute_cmd_without_connection() {
         let ssh = SshConnection {
             session: None,
+            settings: SessionSettings::default(),
+            os: HostOs::Unix,
             connect_args: None,
         };
 
@@ -349,6 +577,8 @@ mod tests {
     fn test_execute_rt_cmd_without_connection() {
         let ssh = SshConnection {
             session: None,
+            settings: SessionSettings::default(),
+            os: HostOs::Unix,
             connect_args: None,
         };
 
@@ -360,6 +590,8 @@ mod tests {
     fn test_get_session_before_connect() {
         let ssh = SshConnection {
             session: None,
+            settings: SessionSettings::default(),
+            os: HostOs::Unix,
             connect_args: None,
         };
         let _ = ssh.session();
@@ -375,12 +607,47 @@ mod tests {
             22,
         );
 
-        assert_eq!(ssh.is_connected(), false);
+        assert!(!ssh.is_connected());
 
         let result = ssh.connect();
 
         assert!(result.is_ok());
-        assert_eq!(ssh.is_connected(), true);
+        assert!(ssh.is_connected());
+    }
+
+    #[test]
+    fn test_warm_standby_without_connect_args() {
+        let ssh = SshConnection {
+            session: None,
+            settings: SessionSettings::default(),
+            os: HostOs::Unix,
+            connect_args: None,
+        };
+
+        ssh.warm_standby(2);
+        assert_eq!(ssh.standby_size(), 0);
+    }
+
+    #[test]
+    fn test_rotate_without_standby_sessions() {
+        let mut ssh = SshConnection::new("user", "host", None, None, 22);
+
+        assert!(!ssh.rotate());
+        assert!(ssh.session.is_none());
+    }
+
+    #[test]
+    fn test_warm_standby_sessions() {
+        let mut ssh = connected_client();
+
+        ssh.warm_standby(2);
+        standby::refresh();
+        assert_eq!(ssh.standby_size(), 2);
+
+        assert!(ssh.rotate());
+        assert_eq!(ssh.standby_size(), 1);
+        assert!(ssh.is_connected());
+        ssh.warm_standby(0);
     }
 
     #[test]
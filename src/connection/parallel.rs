@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use ssh2::Session;
+
+use super::{hostkey, timing, ConnectArgs, SshConnection};
+use crate::error::CrustError;
+use crate::machine::Machine;
+
+/// Default number of handshakes performed at the same time.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Opens authenticated sessions for passed arguments, running at most
+/// `concurrency` handshakes at once. Results keep order of arguments.
+pub fn open_sessions(args: &[ConnectArgs], concurrency: usize) -> Vec<Result<Session, CrustError>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..args.len()).map(|_| None).collect::<Vec<_>>());
+
+    // Handshakes are recorded in trace of operation which opens sessions
+    // and verified with its host key policy
+    let trace = timing::current();
+    let policy = hostkey::current();
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, args.len().max(1)) {
+            scope.spawn(|| {
+                timing::within(trace, || {
+                    hostkey::within(policy, || loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        if idx >= args.len() {
+                            break;
+                        }
+                        let result = SshConnection::open_session(&args[idx]);
+                        results.lock().unwrap()[idx] = Some(result);
+                    })
+                })
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("Every session is opened by a worker"))
+        .collect()
+}
+
+/// Connects passed machines up-front and in parallel, so following
+/// operations do not wait for handshakes one by one. Machines which are
+/// already connected (or do not need connection) are skipped.
+/// Returns errors of machines which could not connect (with their index).
+pub fn connect_machines(
+    machines: &[Rc<RefCell<Box<dyn Machine>>>],
+    concurrency: usize,
+) -> Vec<(usize, CrustError)> {
+    let mut pending: Vec<(Vec<usize>, ConnectArgs)> = Vec::new();
+    for (idx, machine) in machines.iter().enumerate() {
+        // The same machine can be used by many targets - connect it once
+        if let Some(first) = machines[..idx].iter().position(|m| Rc::ptr_eq(m, machine)) {
+            if let Some(entry) = pending.iter_mut().find(|(idxs, _)| idxs[0] == first) {
+                entry.0.push(idx);
+            }
+            continue;
+        }
+        if let Some(args) = machine.borrow().pending_connection() {
+            pending.push((vec![idx], args));
+        }
+    }
+    log::debug!("Connecting {} machines in parallel", pending.len());
+
+    let args = pending.iter().map(|(_, a)| a.clone()).collect::<Vec<_>>();
+    let mut failures = Vec::new();
+    for ((idxs, _), result) in pending.into_iter().zip(open_sessions(&args, concurrency)) {
+        match result {
+            Ok(session) => machines[idxs[0]].borrow_mut().attach_session(session),
+            Err(e) => failures.extend(idxs.into_iter().map(|idx| (idx, e.clone()))),
+        }
+    }
+    failures.sort_by_key(|(idx, _)| *idx);
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ExitCode;
+    use crate::machine::local::LocalMachine;
+    use crate::machine::remote::RemoteMachine;
+
+    #[test]
+    fn test_open_sessions_collects_failures_in_order() {
+        let args = (0..5)
+            .map(|i| ConnectArgs {
+                username: String::from("user"),
+                hostname: String::from("127.0.0.1"),
+                private_key: None,
+                inline_key: None,
+                password: Some(String::from("1234")),
+                port: 1 + i,
+            })
+            .collect::<Vec<_>>();
+
+        let results = open_sessions(&args, 2);
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|r| r.is_err()));
+        assert!(open_sessions(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn test_connect_machines_skips_local_and_duplicates() {
+        let remote: Rc<RefCell<Box<dyn Machine>>> = Rc::new(RefCell::new(Box::new(
+            RemoteMachine::new("user", "127.0.0.1", Some(String::from("1")), None, 1),
+        )));
+        let local: Rc<RefCell<Box<dyn Machine>>> =
+            Rc::new(RefCell::new(Box::new(LocalMachine::default())));
+
+        let failures = connect_machines(&[remote.clone(), local, remote], 4);
+        assert_eq!(
+            failures.iter().map(|(idx, _)| *idx).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert_eq!(failures[0].1.code, ExitCode::Std);
+    }
+}
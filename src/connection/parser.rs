@@ -1,7 +1,10 @@
 use std::path::PathBuf;
 
+use crate::connection::endpoint::Endpoint;
+use crate::connection::key::KeySource;
+use crate::connection::settings::SettingsUpdate;
 use crate::error::{CrustError, ExitCode};
-use crate::interfaces::parser::Validation;
+use crate::interfaces::parser::{Problem, Validation};
 use clap::Args;
 
 /// Interface to sub struct with connection args.
@@ -12,11 +15,30 @@ pub trait BaseConnArgs {
     fn pkey(&self) -> Option<&PathBuf>;
     fn alias(&self) -> Option<&String>;
 
+    /// Source of private key passed directly (env:<NAME> or fd:<N>).
+    fn pkey_inline(&self) -> Option<&String> {
+        None
+    }
+
+    /// Change of settings applied to every command executed on machine.
+    fn settings(&self) -> SettingsUpdate {
+        SettingsUpdate::default()
+    }
+
     /// Split address to get user and host.
-    /// Assumes that address was passed.
+    /// Assumes that valid address was passed.
     fn split_addr(&self) -> (String, String) {
-        let (u, h) = self.addr().as_ref().unwrap().split_once('@').unwrap();
-        (u.to_string(), h.to_string())
+        let endpoint = Endpoint::parse_address(self.addr().unwrap()).unwrap();
+        (endpoint.user.unwrap(), endpoint.host.unwrap())
+    }
+
+    /// Port of machine - the one passed in address (<user>@<host>:<port>)
+    /// wins over port argument.
+    fn ssh_port(&self) -> Option<u16> {
+        self.addr()
+            .and_then(|addr| Endpoint::parse_address(addr).ok())
+            .and_then(|endpoint| endpoint.port)
+            .or(self.port())
     }
 }
 
@@ -24,7 +46,8 @@ pub trait BaseConnArgs {
 #[derive(Debug, Args, Clone)]
 pub struct ConnectionArgsTo {
     #[clap(long)]
-    /// Address to remote machine (<user>@<host>)
+    /// Address to remote machine (<user>@<host>[:<port>], ipv6 host
+    /// in brackets when port is passed)
     pub addr_to: Option<String>,
 
     #[clap(long, default_value = "22")]
@@ -39,9 +62,35 @@ pub struct ConnectionArgsTo {
     /// Path to private ssh-key to remote server
     pub pkey_to: Option<PathBuf>,
 
+    #[clap(long, value_name = "SOURCE")]
+    /// Private ssh-key to remote server read into memory from env:<NAME>
+    /// variable or fd:<N> descriptor (never written to disk)
+    pub pkey_inline_to: Option<String>,
+
     #[clap(long)]
     /// Alias for remote machine to use instead of all passing all args
     pub alias_to: Option<String>,
+
+    #[clap(long)]
+    /// Default working directory of commands on remote machine
+    pub cwd_to: Option<String>,
+
+    #[clap(long)]
+    /// Umask of commands on remote machine (octal, e.g. 022)
+    pub umask_to: Option<String>,
+
+    #[clap(long, action)]
+    /// Run commands on remote machine in strict mode (`set -eu`, with `pipefail` where supported)
+    pub strict_to: bool,
+
+    #[clap(long, action, conflicts_with = "strict_to")]
+    /// Turn off strict mode of commands on remote machine
+    pub no_strict_to: bool,
+
+    #[clap(long, action)]
+    /// Restore default settings of remote machine (directory, umask, strict
+    /// mode) before applying passed ones
+    pub reset_settings_to: bool,
 }
 
 impl BaseConnArgs for ConnectionArgsTo {
@@ -57,34 +106,111 @@ impl BaseConnArgs for ConnectionArgsTo {
     fn pkey(&self) -> Option<&PathBuf> {
         self.pkey_to.as_ref()
     }
+    fn pkey_inline(&self) -> Option<&String> {
+        self.pkey_inline_to.as_ref()
+    }
     fn port(&self) -> Option<u16> {
         self.port_to
     }
+    fn settings(&self) -> SettingsUpdate {
+        SettingsUpdate {
+            reset: self.reset_settings_to,
+            cwd: self.cwd_to.clone(),
+            umask: self.umask_to.clone(),
+            strict: match (self.strict_to, self.no_strict_to) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+        }
+    }
 }
 
-impl Validation for ConnectionArgsTo {
-    fn validate(&mut self) -> Result<(), CrustError> {
-        if self.alias_to.is_some() {
-            return Ok(());
+/// Connection argument which is invalid or missing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConnFlag {
+    Addr,
+    Auth,
+    Target,
+}
+
+/// Problems of connection arguments (in order of checking) with argument
+/// which causes them.
+/// Machine with alias can be already registered in manager, so
+/// address and authorization are required only without alias.
+fn check_connection(args: &impl BaseConnArgs) -> Vec<(CrustError, Option<ConnFlag>)> {
+    let mut errors = Vec::new();
+    if let Some(addr) = args.addr() {
+        if let Err(e) = Endpoint::parse_address(addr) {
+            errors.push((e, Some(ConnFlag::Addr)));
         }
+    }
+    if let Err(e) = args.settings().validate() {
+        errors.push((e, None));
+    }
+    if let Some(Err(e)) = args.pkey_inline().map(|source| source.parse::<KeySource>()) {
+        errors.push((e, None));
+    }
 
-        if self.password_to.is_none() && self.pkey_to.is_none() {
-            return Err(CrustError {
+    if args.alias().is_none() {
+        if args.password().is_none() && args.pkey().is_none() && args.pkey_inline().is_none() {
+            let e = CrustError {
                 code: ExitCode::Parser,
                 message: "Neither password nor pkey provided".to_string(),
-            });
+            };
+            errors.push((e, Some(ConnFlag::Auth)));
         }
 
-        if let Some(addr) = &self.addr_to {
-            let parts = addr.split('@').collect::<Vec<&str>>();
-            if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
-                return Err(CrustError {
-                    code: ExitCode::Parser,
-                    message: "Invalid address pattern. Use <user>@<host>".to_string(),
-                });
-            }
+        if args.addr().is_none() {
+            let e = CrustError {
+                code: ExitCode::Parser,
+                message: "Neither address nor alias provided".to_string(),
+            };
+            errors.push((e, Some(ConnFlag::Target)));
         }
-        Ok(())
+    }
+    errors
+}
+
+/// Common validation of connection arguments, shared by CLI parsers
+/// and library requests.
+pub fn validate_connection(args: &impl BaseConnArgs) -> Result<(), CrustError> {
+    match check_connection(args).into_iter().next() {
+        Some((e, _)) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// All problems of connection arguments, with hints naming flags of
+/// machine (with passed suffix, e.g. `to` for `--addr-to`).
+pub fn connection_problems(args: &impl BaseConnArgs, suffix: &str) -> Vec<Problem> {
+    check_connection(args)
+        .into_iter()
+        .map(|(e, flag)| {
+            let hint = match flag {
+                Some(ConnFlag::Addr) => format!(
+                    "use --addr-{suffix} <user>@<host>[:<port>], e.g. deploy@10.0.0.5 or deploy@[fe80::1]:2222"
+                ),
+                Some(ConnFlag::Auth) => format!(
+                    "pass --password-{suffix}, --pkey-{suffix} or --pkey-inline-{suffix} (or --alias-{suffix} of machine registered in background session)"
+                ),
+                Some(ConnFlag::Target) => format!(
+                    "pass --addr-{suffix} <user>@<host> (or --alias-{suffix} of machine registered in background session)"
+                ),
+                None => return Problem::from(e),
+            };
+            Problem::from(e).hint(&hint)
+        })
+        .collect()
+}
+
+impl Validation for ConnectionArgsTo {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        validate_connection(self)
+    }
+
+    fn problems(&mut self) -> Vec<Problem> {
+        connection_problems(self, "to")
     }
 }
 
@@ -95,7 +221,8 @@ impl Validation for ConnectionArgsTo {
 #[derive(Debug, Args, Clone)]
 pub struct ConnectionArgsFrom {
     #[clap(long)]
-    /// Address to remote machine which is a source machine (<user>@<host>)
+    /// Address to remote machine which is a source machine
+    /// (<user>@<host>[:<port>], ipv6 host in brackets when port is passed)
     pub addr_from: Option<String>,
 
     #[clap(long, default_value = "22")]
@@ -110,9 +237,35 @@ pub struct ConnectionArgsFrom {
     /// Path to private ssh-key to source remote server
     pub pkey_from: Option<PathBuf>,
 
+    #[clap(long, value_name = "SOURCE")]
+    /// Private ssh-key to source remote server read into memory from env:<NAME>
+    /// variable or fd:<N> descriptor (never written to disk)
+    pub pkey_inline_from: Option<String>,
+
     #[clap(long)]
     /// Alias for remote machine to use instead of all passing all args
     pub alias_from: Option<String>,
+
+    #[clap(long)]
+    /// Default working directory of commands on source remote machine
+    pub cwd_from: Option<String>,
+
+    #[clap(long)]
+    /// Umask of commands on source remote machine (octal, e.g. 022)
+    pub umask_from: Option<String>,
+
+    #[clap(long, action)]
+    /// Run commands on source remote machine in strict mode (`set -eu`, with `pipefail` where supported)
+    pub strict_from: bool,
+
+    #[clap(long, action, conflicts_with = "strict_from")]
+    /// Turn off strict mode of commands on source remote machine
+    pub no_strict_from: bool,
+
+    #[clap(long, action)]
+    /// Restore default settings of source remote machine (directory, umask, strict
+    /// mode) before applying passed ones
+    pub reset_settings_from: bool,
 }
 
 impl BaseConnArgs for ConnectionArgsFrom {
@@ -128,29 +281,32 @@ impl BaseConnArgs for ConnectionArgsFrom {
     fn pkey(&self) -> Option<&PathBuf> {
         self.pkey_from.as_ref()
     }
+    fn pkey_inline(&self) -> Option<&String> {
+        self.pkey_inline_from.as_ref()
+    }
     fn port(&self) -> Option<u16> {
         self.port_from
     }
+    fn settings(&self) -> SettingsUpdate {
+        SettingsUpdate {
+            reset: self.reset_settings_from,
+            cwd: self.cwd_from.clone(),
+            umask: self.umask_from.clone(),
+            strict: match (self.strict_from, self.no_strict_from) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+        }
+    }
 }
 
 impl Validation for ConnectionArgsFrom {
     fn validate(&mut self) -> Result<(), CrustError> {
-        if let Some(addr) = &self.addr_from {
-            let parts = addr.split('@').collect::<Vec<&str>>();
-            if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
-                return Err(CrustError {
-                    code: ExitCode::Parser,
-                    message: "Invalid address pattern. Use <user>@<host>".to_string(),
-                });
-            }
-
-            if self.password_from.is_none() && self.pkey_from.is_none() {
-                return Err(CrustError {
-                    code: ExitCode::Parser,
-                    message: "Neither password nor pkey provided".to_string(),
-                });
-            }
-        }
-        Ok(())
+        validate_connection(self)
+    }
+
+    fn problems(&mut self) -> Vec<Problem> {
+        connection_problems(self, "from")
     }
 }
@@ -0,0 +1,295 @@
+use std::path::PathBuf;
+
+use crate::connection::parser::{
+    validate_connection, BaseConnArgs, ConnectionArgsFrom, ConnectionArgsTo,
+};
+use crate::connection::settings::SettingsUpdate;
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+
+/// Plain (clap independent) representation of remote machine used in
+/// library requests. Allows to point machine by address with authorization
+/// or by alias of machine already registered in manager.
+/// # Example
+/// ```
+/// use crust::connection::request::RemoteTarget;
+///
+/// let target = RemoteTarget::new("user@10.10.10.10")
+///     .password("1234")
+///     .port(2222);
+/// let by_alias = RemoteTarget::with_alias("backend");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteTarget {
+    addr: Option<String>,
+    port: u16,
+    password: Option<String>,
+    pkey: Option<PathBuf>,
+    pkey_inline: Option<String>,
+    alias: Option<String>,
+    settings: SettingsUpdate,
+}
+
+impl RemoteTarget {
+    /// Checks whether machine can be connected with target alone (without
+    /// machine registered in manager under target's alias).
+    pub fn is_self_contained(&self) -> bool {
+        self.alias.is_none()
+            || (self.addr.is_some()
+                && (self.password.is_some() || self.pkey.is_some() || self.pkey_inline.is_some()))
+    }
+
+    /// Creates a target from address (<user>@<host>) with default port.
+    pub fn new(addr: &str) -> Self {
+        Self {
+            addr: Some(addr.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a target which refers to machine registered under alias.
+    pub fn with_alias(alias: &str) -> Self {
+        Self {
+            alias: Some(alias.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Sets ssh port of remote machine.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets password used to authorize.
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Sets path to private key used to authorize.
+    pub fn pkey(mut self, pkey: impl Into<PathBuf>) -> Self {
+        self.pkey = Some(pkey.into());
+        self
+    }
+
+    /// Sets source of private key read into memory (`env:<NAME>` or
+    /// `fd:<N>`), used instead of key file.
+    pub fn pkey_inline(mut self, source: &str) -> Self {
+        self.pkey_inline = Some(source.to_string());
+        self
+    }
+
+    /// Registers (or looks up) machine under passed alias.
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.alias = Some(alias.to_string());
+        self
+    }
+
+    /// Sets default working directory of executed commands.
+    pub fn cwd(mut self, cwd: &str) -> Self {
+        self.settings.cwd = Some(cwd.to_string());
+        self
+    }
+
+    /// Sets umask (octal, e.g. `022`) of executed commands.
+    pub fn umask(mut self, umask: &str) -> Self {
+        self.settings.umask = Some(umask.to_string());
+        self
+    }
+
+    /// Turns on (or off) strict mode of executed commands (`set -eu`,
+    /// with `pipefail` where supported).
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.settings.strict = Some(strict);
+        self
+    }
+
+    /// Restores default settings of machine before applying passed ones.
+    /// Settings which are not passed are otherwise kept by machine.
+    pub fn reset_settings(mut self) -> Self {
+        self.settings.reset = true;
+        self
+    }
+}
+
+impl Default for RemoteTarget {
+    fn default() -> Self {
+        Self {
+            addr: None,
+            port: 22,
+            password: None,
+            pkey: None,
+            pkey_inline: None,
+            alias: None,
+            settings: SettingsUpdate::default(),
+        }
+    }
+}
+
+impl BaseConnArgs for RemoteTarget {
+    fn addr(&self) -> Option<&String> {
+        self.addr.as_ref()
+    }
+    fn alias(&self) -> Option<&String> {
+        self.alias.as_ref()
+    }
+    fn password(&self) -> Option<&String> {
+        self.password.as_ref()
+    }
+    fn pkey(&self) -> Option<&PathBuf> {
+        self.pkey.as_ref()
+    }
+    fn pkey_inline(&self) -> Option<&String> {
+        self.pkey_inline.as_ref()
+    }
+    fn port(&self) -> Option<u16> {
+        Some(self.port)
+    }
+    fn settings(&self) -> SettingsUpdate {
+        self.settings.clone()
+    }
+}
+
+impl Validation for RemoteTarget {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        validate_connection(self)
+    }
+}
+
+/// Rewrites parsed CLI arguments into a plain target.
+fn from_conn_args(args: &impl BaseConnArgs) -> RemoteTarget {
+    RemoteTarget {
+        addr: args.addr().cloned(),
+        port: args.ssh_port().unwrap_or(22),
+        password: args.password().cloned(),
+        pkey: args.pkey().cloned(),
+        pkey_inline: args.pkey_inline().cloned(),
+        alias: args.alias().cloned(),
+        settings: args.settings(),
+    }
+}
+
+impl From<&ConnectionArgsTo> for RemoteTarget {
+    fn from(args: &ConnectionArgsTo) -> Self {
+        from_conn_args(args)
+    }
+}
+
+impl From<&ConnectionArgsFrom> for RemoteTarget {
+    fn from(args: &ConnectionArgsFrom) -> Self {
+        from_conn_args(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ExitCode;
+
+    #[test]
+    fn test_create_target_with_defaults() {
+        let target = RemoteTarget::new("user@host");
+
+        assert_eq!(target.addr(), Some(&String::from("user@host")));
+        assert_eq!(BaseConnArgs::port(&target), Some(22));
+        assert_eq!(BaseConnArgs::alias(&target), None);
+        assert_eq!(
+            target.split_addr(),
+            (String::from("user"), String::from("host"))
+        );
+    }
+
+    #[test]
+    fn test_validate_target_success() {
+        let mut target = RemoteTarget::new("user@host").pkey("/id_rsa").port(2222);
+
+        assert!(target.validate().is_ok());
+        assert_eq!(BaseConnArgs::port(&target), Some(2222));
+    }
+
+    #[test]
+    fn test_validate_target_without_auth() {
+        let mut target = RemoteTarget::new("user@host");
+
+        let err = target.validate().err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(err.message, "Neither password nor pkey provided");
+    }
+
+    #[test]
+    fn test_validate_target_with_inline_key() {
+        let mut target = RemoteTarget::new("user@host").pkey_inline("env:CI_SSH_KEY");
+        assert!(target.validate().is_ok());
+
+        let mut target = RemoteTarget::new("user@host").pkey_inline("/id_rsa");
+        let err = target.validate().err().unwrap();
+        assert_eq!(
+            err.message,
+            "Invalid private key source '/id_rsa'. Use env:<NAME> or fd:<N>"
+        );
+    }
+
+    #[test]
+    fn test_validate_target_invalid_addr() {
+        let mut target = RemoteTarget::new("host").password("1234");
+
+        let err = target.validate().err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(err.message, "Invalid address pattern. Use <user>@<host>");
+    }
+
+    #[test]
+    fn test_validate_target_with_alias_only() {
+        let mut target = RemoteTarget::with_alias("backend");
+
+        assert!(target.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_target_without_addr() {
+        let mut target = RemoteTarget::default().password("1234");
+
+        let err = target.validate().err().unwrap();
+        assert_eq!(err.message, "Neither address nor alias provided");
+    }
+
+    #[test]
+    fn test_convert_cli_args_into_target() {
+        let args = ConnectionArgsTo {
+            addr_to: Some(String::from("user@host")),
+            port_to: Some(23),
+            password_to: Some(String::from("1234")),
+            pkey_to: None,
+            pkey_inline_to: None,
+            alias_to: Some(String::from("alias")),
+            cwd_to: Some(String::from("/srv")),
+            umask_to: None,
+            strict_to: true,
+            no_strict_to: false,
+            reset_settings_to: false,
+        };
+
+        let target = RemoteTarget::from(&args);
+        assert_eq!(
+            target,
+            RemoteTarget::new("user@host")
+                .port(23)
+                .password("1234")
+                .alias("alias")
+                .cwd("/srv")
+                .strict(true)
+        );
+    }
+
+    #[test]
+    fn test_validate_target_settings() {
+        let mut target = RemoteTarget::with_alias("backend").umask("999");
+
+        let err = target.validate().err().unwrap();
+        assert_eq!(
+            err.message,
+            "Invalid umask '999'. Use octal value, e.g. 022"
+        );
+    }
+}
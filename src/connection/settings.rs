@@ -0,0 +1,339 @@
+use std::path::Path;
+
+use crate::error::{CrustError, ExitCode};
+use crate::utils::shell::{quote, quote_path};
+
+/// Per-machine settings applied to every command executed through
+/// connection, so commands behave the same regardless of the remote
+/// account's dotfiles.
+/// - cwd: default working directory
+/// - umask: mask of created files (octal, e.g. `022`)
+/// - strict: run commands with `set -eu` (and `pipefail` when shell
+///   supports it - dash and older busybox sh do not)
+/// - run_as: execute commands as another user (e.g. service account)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionSettings {
+    pub cwd: Option<String>,
+    pub umask: Option<String>,
+    pub strict: bool,
+    pub run_as: Option<String>,
+}
+
+/// Strict mode of shell. `pipefail` is probed in subshell first, as shells
+/// without it stop the whole script on unknown option.
+const STRICT: &str = "set -eu && if (set -o pipefail) 2>/dev/null; then set -o pipefail; fi";
+
+impl SessionSettings {
+    /// Checks whether any setting was passed.
+    pub fn is_empty(&self) -> bool {
+        self == &SessionSettings::default()
+    }
+
+    /// Checks whether passed values can be used in shell.
+    pub fn validate(&self) -> Result<(), CrustError> {
+        if let Some(umask) = &self.umask {
+            let is_octal = umask.chars().all(|c| ('0'..='7').contains(&c));
+            if !is_octal || !(3..=4).contains(&umask.len()) {
+                return Err(CrustError {
+                    code: ExitCode::Parser,
+                    message: format!("Invalid umask '{umask}'. Use octal value, e.g. 022"),
+                });
+            }
+        }
+
+        if let Some(user) = &self.run_as {
+            validate_user(user)?;
+        }
+
+        if let Some(cwd) = &self.cwd {
+            if cwd.is_empty() {
+                return Err(CrustError {
+                    code: ExitCode::Parser,
+                    message: "Default directory can not be empty".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Prepends command with settings (nothing is changed when there
+    /// are no settings).
+    pub fn wrap(&self, command: &str) -> String {
+        self.build(command, false)
+    }
+
+    /// Same as `wrap`, but command run as another user reads a password
+    /// of connected account from stdin first, to authorize sudo.
+    pub fn wrap_with_password(&self, command: &str) -> String {
+        self.build(command, true)
+    }
+
+    fn build(&self, command: &str, password: bool) -> String {
+        let mut prefix = Vec::new();
+        if self.strict {
+            prefix.push(String::from(STRICT));
+        }
+        if let Some(umask) = &self.umask {
+            prefix.push(format!("umask {umask}"));
+        }
+        if let Some(cwd) = &self.cwd {
+            prefix.push(format!("cd {}", quote_path(Path::new(cwd))));
+        }
+
+        // Command is not run when any setting fails (e.g. `cd` to missing
+        // directory), so it does not run in a wrong place
+        let command = match prefix.is_empty() {
+            true => command.to_string(),
+            false => format!("{} && {{\n{command}\n}}", prefix.join(" && ")),
+        };
+        match &self.run_as {
+            Some(user) => run_as(user, &command, password),
+            None => command,
+        }
+    }
+}
+
+/// Change of settings passed with a single command. Only passed values
+/// replace settings kept by machine, the rest stays as it was - `reset`
+/// restores defaults first (user of commands is set per command and is
+/// always kept).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SettingsUpdate {
+    pub reset: bool,
+    pub cwd: Option<String>,
+    pub umask: Option<String>,
+    pub strict: Option<bool>,
+}
+
+impl SettingsUpdate {
+    /// Checks whether update changes anything.
+    pub fn is_empty(&self) -> bool {
+        self == &SettingsUpdate::default()
+    }
+
+    /// Checks whether passed values can be used in shell.
+    pub fn validate(&self) -> Result<(), CrustError> {
+        self.apply(&SessionSettings::default()).validate()
+    }
+
+    /// Settings after update of `current` ones.
+    pub fn apply(&self, current: &SessionSettings) -> SessionSettings {
+        let mut settings = match self.reset {
+            true => SessionSettings {
+                run_as: current.run_as.clone(),
+                ..Default::default()
+            },
+            false => current.clone(),
+        };
+        if let Some(cwd) = &self.cwd {
+            settings.cwd = Some(cwd.clone());
+        }
+        if let Some(umask) = &self.umask {
+            settings.umask = Some(umask.clone());
+        }
+        if let Some(strict) = self.strict {
+            settings.strict = strict;
+        }
+        settings
+    }
+}
+
+/// Checks whether passed user name can be safely used in shell.
+pub fn validate_user(user: &str) -> Result<(), CrustError> {
+    let valid = !user.is_empty()
+        && !user.starts_with('-')
+        && user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    match valid {
+        true => Ok(()),
+        false => Err(CrustError {
+            code: ExitCode::Parser,
+            message: format!("Invalid user name '{user}'"),
+        }),
+    }
+}
+
+/// Wraps command to be executed as another user with `sudo -u <user> -H`.
+/// Machines without sudo are served only when logged in as root (e.g.
+/// containers) - root switches user with `su -` without password, other
+/// accounts fail with error instead of waiting for password of `su`.
+/// With `password`, the first line of stdin is used to authorize sudo,
+/// so the password never shows up in the process list.
+pub fn run_as(user: &str, command: &str, password: bool) -> String {
+    let auth = match password {
+        true => "IFS= read -r CRUST_PASSWORD; printf '%s\\n' \"$CRUST_PASSWORD\" | sudo -S -p '' -v 2>/dev/null; unset CRUST_PASSWORD; ",
+        false => "",
+    };
+    format!(
+        "{auth}if command -v sudo >/dev/null 2>&1; then {}; \
+         elif [ \"$(id -u)\" = 0 ]; then su - {} -c {}; \
+         else echo {} >&2; exit 1; fi",
+        sudo(user, command),
+        quote(user),
+        quote(command),
+        quote(&format!(
+            "sudo is not available, can not run command as {user}"
+        ))
+    )
+}
+
+/// Non-interactive `sudo` invocation - fails instead of asking for password
+/// when current user is not allowed to act as `user`.
+pub fn sudo(user: &str, command: &str) -> String {
+    format!("sudo -n -u {} -H sh -c {}", quote(user), quote(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_without_settings() {
+        let settings = SessionSettings::default();
+
+        assert!(settings.is_empty());
+        assert_eq!(settings.wrap("ls -la"), "ls -la");
+    }
+
+    #[test]
+    fn test_wrap_with_all_settings() {
+        let settings = SessionSettings {
+            cwd: Some(String::from("/srv/app")),
+            umask: Some(String::from("027")),
+            strict: true,
+            run_as: None,
+        };
+
+        assert_eq!(
+            settings.wrap("ls | wc -l"),
+            "set -eu && if (set -o pipefail) 2>/dev/null; then set -o pipefail; fi \
+             && umask 027 && cd '/srv/app' && {\nls | wc -l\n}"
+        );
+    }
+
+    #[test]
+    fn test_wrap_escapes_cwd() {
+        let settings = SessionSettings {
+            cwd: Some(String::from("/srv/it's")),
+            ..Default::default()
+        };
+
+        assert_eq!(settings.wrap("pwd"), "cd '/srv/it'\\''s' && {\npwd\n}");
+    }
+
+    #[test]
+    fn test_validate_umask() {
+        let mut settings = SessionSettings {
+            umask: Some(String::from("0022")),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+
+        settings.umask = Some(String::from("abc"));
+        let err = settings.validate().err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(
+            err.message,
+            "Invalid umask 'abc'. Use octal value, e.g. 022"
+        );
+
+        settings.umask = Some(String::from("08"));
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_wrap_run_as() {
+        let settings = SessionSettings {
+            cwd: Some(String::from("/srv/app")),
+            run_as: Some(String::from("app")),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            settings.wrap("whoami"),
+            "if command -v sudo >/dev/null 2>&1; then sudo -n -u 'app' -H sh -c 'cd '\\''/srv/app'\\'' && {\nwhoami\n}'; \
+             elif [ \"$(id -u)\" = 0 ]; then su - 'app' -c 'cd '\\''/srv/app'\\'' && {\nwhoami\n}'; \
+             else echo 'sudo is not available, can not run command as app' >&2; exit 1; fi"
+        );
+        assert!(settings.wrap_with_password("whoami").starts_with(
+            "IFS= read -r CRUST_PASSWORD; printf '%s\\n' \"$CRUST_PASSWORD\" | sudo -S -p '' -v"
+        ));
+    }
+
+    #[test]
+    fn test_validate_run_as_user() {
+        for user in ["app", "svc-backup", "www.data", "_apt"] {
+            assert!(validate_user(user).is_ok());
+        }
+        for user in ["", "-u", "app user", "app;id", "app'"] {
+            let err = validate_user(user).err().unwrap();
+            assert_eq!(err.code, ExitCode::Parser);
+            assert_eq!(err.message, format!("Invalid user name '{user}'"));
+        }
+    }
+
+    #[test]
+    fn test_validate_empty_cwd() {
+        let settings = SessionSettings {
+            cwd: Some(String::new()),
+            ..Default::default()
+        };
+
+        let err = settings.validate().err().unwrap();
+        assert_eq!(err.message, "Default directory can not be empty");
+    }
+
+    #[test]
+    fn test_update_keeps_not_passed_settings() {
+        let current = SessionSettings {
+            cwd: Some(String::from("/srv/app")),
+            umask: Some(String::from("027")),
+            strict: true,
+            run_as: Some(String::from("app")),
+        };
+
+        let update = SettingsUpdate {
+            umask: Some(String::from("022")),
+            ..Default::default()
+        };
+        assert_eq!(
+            update.apply(&current),
+            SessionSettings {
+                umask: Some(String::from("022")),
+                ..current.clone()
+            }
+        );
+
+        let update = SettingsUpdate {
+            strict: Some(false),
+            ..Default::default()
+        };
+        assert!(!update.apply(&current).strict);
+        assert!(SettingsUpdate::default().is_empty());
+    }
+
+    #[test]
+    fn test_update_resets_settings() {
+        let current = SessionSettings {
+            cwd: Some(String::from("/srv/app")),
+            umask: Some(String::from("027")),
+            strict: true,
+            run_as: Some(String::from("app")),
+        };
+        let update = SettingsUpdate {
+            reset: true,
+            cwd: Some(String::from("/tmp")),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            update.apply(&current),
+            SessionSettings {
+                cwd: Some(String::from("/tmp")),
+                run_as: Some(String::from("app")),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use ssh2::Session;
+
+use super::{ConnectArgs, SshConnection};
+
+/// Interval in which standby sessions are probed and refilled.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Pre-authenticated sessions of background process, shared by all its
+/// commands (main loop and commands run aside), so a burst of parallel
+/// commands to the same machine does not wait for handshakes.
+/// - wanted: arguments of machines and number of sessions kept for them
+/// - sessions: ready sessions of machines
+///
+/// Both are keyed by `user@host:port` of connection.
+struct Pool {
+    wanted: BTreeMap<String, (ConnectArgs, usize)>,
+    sessions: BTreeMap<String, Vec<Session>>,
+}
+
+static POOL: Mutex<Pool> = Mutex::new(Pool {
+    wanted: BTreeMap::new(),
+    sessions: BTreeMap::new(),
+});
+
+fn key(args: &ConnectArgs) -> String {
+    format!("{}:{}", args.target(), args.port)
+}
+
+/// Keeps `count` standby sessions for machine (0 stops keeping them).
+/// Sessions are opened by the next refresh.
+pub fn keep(args: &ConnectArgs, count: usize) {
+    let mut pool = POOL.lock().unwrap();
+    match count {
+        0 => {
+            pool.wanted.remove(&key(args));
+            pool.sessions.remove(&key(args));
+        }
+        _ => {
+            pool.wanted.insert(key(args), (args.clone(), count));
+        }
+    }
+}
+
+/// Takes healthy standby session of machine (if any is ready). Taken
+/// session is replaced by the next refresh.
+pub fn take(args: &ConnectArgs) -> Option<Session> {
+    loop {
+        let session = POOL.lock().unwrap().sessions.get_mut(&key(args))?.pop()?;
+        if SshConnection::probe(&session) {
+            log::debug!("Took standby session of '{}'", args.target());
+            return Some(session);
+        }
+    }
+}
+
+/// Drops standby sessions of host and stops keeping them (e.g. when host
+/// is locked).
+pub fn forget(host: &str) {
+    let mut pool = POOL.lock().unwrap();
+    let keys = pool
+        .wanted
+        .iter()
+        .filter(|(_, (args, _))| args.hostname == host)
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    for key in keys {
+        pool.wanted.remove(&key);
+        pool.sessions.remove(&key);
+    }
+}
+
+/// Number of ready standby sessions of machine.
+pub fn size(args: &ConnectArgs) -> usize {
+    POOL.lock()
+        .unwrap()
+        .sessions
+        .get(&key(args))
+        .map_or(0, Vec::len)
+}
+
+/// Probes ready sessions and opens missing ones. Handshakes are made
+/// without holding the pool, so commands can take sessions meanwhile.
+/// Failures are only logged, as machine could be temporarily unavailable.
+pub fn refresh() {
+    let wanted = POOL.lock().unwrap().wanted.clone();
+    for (key, (args, count)) in wanted {
+        let ready = POOL
+            .lock()
+            .unwrap()
+            .sessions
+            .remove(&key)
+            .unwrap_or_default();
+        let mut healthy = ready
+            .into_iter()
+            .filter(SshConnection::probe)
+            .take(count)
+            .collect::<Vec<_>>();
+        while healthy.len() < count {
+            match SshConnection::open_session(&args) {
+                Ok(session) => healthy.push(session),
+                Err(e) => {
+                    log::warn!("Can not warm standby sessions for '{key}': {e}");
+                    break;
+                }
+            }
+        }
+
+        let mut pool = POOL.lock().unwrap();
+        // Machine could be forgotten during handshakes
+        if pool.wanted.contains_key(&key) {
+            log::debug!("Standby sessions for '{key}': {}", healthy.len());
+            pool.sessions.insert(key, healthy);
+        }
+    }
+}
+
+/// Starts thread which refreshes standby sessions every
+/// `REFRESH_INTERVAL`.
+pub fn start_refresher() {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(REFRESH_INTERVAL);
+        refresh();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::SSH;
+
+    fn args(host: &str) -> ConnectArgs {
+        SshConnection::new("user", host, None, None, 22)
+            .connect_args
+            .unwrap()
+    }
+
+    #[test]
+    fn test_keep_and_forget_machine() {
+        keep(&args("standby-1"), 2);
+        keep(&args("standby-2"), 1);
+        assert!(POOL
+            .lock()
+            .unwrap()
+            .wanted
+            .contains_key("user@standby-1:22"));
+
+        forget("standby-1");
+        keep(&args("standby-2"), 0);
+        let pool = POOL.lock().unwrap();
+        assert!(!pool.wanted.contains_key("user@standby-1:22"));
+        assert!(!pool.wanted.contains_key("user@standby-2:22"));
+    }
+
+    #[test]
+    fn test_take_without_ready_sessions() {
+        keep(&args("standby-3"), 1);
+
+        assert!(take(&args("standby-3")).is_none());
+        assert_eq!(size(&args("standby-3")), 0);
+        forget("standby-3");
+    }
+}
@@ -0,0 +1,246 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Phases recorded by traces in progress (by their ids). Every operation
+/// has its own trace, so operations running at the same time (e.g. a job
+/// and a command of background process) do not mix their phases.
+static TRACES: Mutex<BTreeMap<u64, Vec<PhaseTiming>>> = Mutex::new(BTreeMap::new());
+
+static NEXT_TRACE: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Trace to which phases of this thread are recorded.
+    static CURRENT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Phase of work with remote machine.
+/// - command: from sending command until its stdout is closed
+/// - transfer: copying data of file
+/// - drain: reading the rest of output (stderr) after command finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Dns,
+    TcpConnect,
+    Handshake,
+    Auth,
+    ChannelOpen,
+    Command,
+    Transfer,
+    Drain,
+    Close,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Phase::Dns => "dns",
+            Phase::TcpConnect => "tcp connect",
+            Phase::Handshake => "handshake",
+            Phase::Auth => "auth",
+            Phase::ChannelOpen => "channel open",
+            Phase::Command => "command",
+            Phase::Transfer => "transfer",
+            Phase::Drain => "stream drain",
+            Phase::Close => "close",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Duration of a single phase on machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseTiming {
+    pub target: String,
+    pub phase: Phase,
+    pub duration: Duration,
+}
+
+/// Trace of a single operation. Phases are recorded while it lives - by
+/// thread which started it and by threads which joined it (see `within`).
+pub struct Trace {
+    id: u64,
+    previous: Option<u64>,
+}
+
+impl Trace {
+    /// Stops recording and returns phases recorded since `start`.
+    pub fn finish(self) -> Vec<PhaseTiming> {
+        TRACES.lock().unwrap().remove(&self.id).unwrap_or_default()
+    }
+}
+
+impl Drop for Trace {
+    fn drop(&mut self) {
+        TRACES.lock().unwrap().remove(&self.id);
+        CURRENT.with(|current| current.set(self.previous));
+    }
+}
+
+/// Starts recording phases of operation run by current thread.
+pub fn start() -> Trace {
+    let id = NEXT_TRACE.fetch_add(1, Ordering::Relaxed);
+    TRACES.lock().unwrap().insert(id, Vec::new());
+    Trace {
+        id,
+        previous: CURRENT.with(|current| current.replace(Some(id))),
+    }
+}
+
+/// Id of trace to which current thread records (if any). Pass it to
+/// `within` in threads working for the same operation.
+pub fn current() -> Option<u64> {
+    CURRENT.with(Cell::get)
+}
+
+/// Runs body recording its phases to passed trace.
+pub fn within<T>(trace: Option<u64>, body: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|current| current.replace(trace));
+    let result = body();
+    CURRENT.with(|current| current.set(previous));
+    result
+}
+
+/// Runs body and records its duration as phase of target (if tracing
+/// is on). Failed phases are recorded as well - slow failures are often
+/// what is looked for.
+pub fn measure<T>(target: &str, phase: Phase, body: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = body();
+    record(target, phase, started.elapsed());
+    result
+}
+
+/// Records duration of phase of target (if tracing is on).
+pub fn record(target: &str, phase: Phase, duration: Duration) {
+    let Some(id) = current() else {
+        return;
+    };
+    if let Some(records) = TRACES.lock().unwrap().get_mut(&id) {
+        records.push(PhaseTiming {
+            target: target.to_string(),
+            phase,
+            duration,
+        });
+    }
+}
+
+/// Breakdown of recorded phases per machine (in order of the first use).
+/// Repeated phases (e.g. many commands) are summed up.
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use crust::connection::timing::{render, Phase, PhaseTiming};
+///
+/// let timing = |phase, ms| PhaseTiming {
+///     target: String::from("user@host"),
+///     phase,
+///     duration: Duration::from_millis(ms),
+/// };
+/// assert_eq!(
+///     render(&[timing(Phase::Auth, 120), timing(Phase::Command, 5), timing(Phase::Command, 7)]),
+///     "Timing breakdown:\n  user@host\n    auth              120.0 ms\n    command            12.0 ms (x2)\n    total             132.0 ms"
+/// );
+/// ```
+pub fn render(records: &[PhaseTiming]) -> String {
+    if records.is_empty() {
+        return String::from("Timing breakdown: no remote operations");
+    }
+
+    let mut targets: Vec<&str> = Vec::new();
+    for record in records {
+        if !targets.contains(&record.target.as_str()) {
+            targets.push(&record.target);
+        }
+    }
+
+    let mut lines = vec![String::from("Timing breakdown:")];
+    for target in targets {
+        lines.push(format!("  {target}"));
+        let mut phases: Vec<(Phase, Duration, usize)> = Vec::new();
+        for record in records.iter().filter(|r| r.target == target) {
+            match phases.iter_mut().find(|(p, _, _)| *p == record.phase) {
+                Some((_, duration, count)) => {
+                    *duration += record.duration;
+                    *count += 1;
+                }
+                None => phases.push((record.phase, record.duration, 1)),
+            }
+        }
+        phases.sort_by_key(|(phase, _, _)| *phase);
+
+        for (phase, duration, count) in &phases {
+            let repeated = match count {
+                1 => String::new(),
+                _ => format!(" (x{count})"),
+            };
+            lines.push(format!(
+                "    {:<14}{}{repeated}",
+                phase.to_string(),
+                millis(*duration)
+            ));
+        }
+        let total = phases.iter().map(|(_, d, _)| *d).sum();
+        lines.push(format!("    {:<14}{}", "total", millis(total)));
+    }
+    lines.join("\n")
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:>9.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_only_while_tracing() {
+        record("a@b", Phase::Dns, Duration::from_millis(1));
+        let trace = start();
+        let value = measure("a@b", Phase::TcpConnect, || 7);
+        let records = trace.finish();
+        record("a@b", Phase::Close, Duration::from_millis(1));
+
+        assert_eq!(value, 7);
+        assert_eq!(
+            records.iter().map(|r| r.phase).collect::<Vec<_>>(),
+            vec![Phase::TcpConnect]
+        );
+        assert!(current().is_none());
+    }
+
+    #[test]
+    fn test_traces_of_operations_are_separated() {
+        let trace = start();
+        let id = current();
+        let other = std::thread::spawn(move || {
+            let other = start();
+            record("c@d", Phase::Auth, Duration::from_millis(1));
+            within(id, || {
+                record("a@b", Phase::Transfer, Duration::from_millis(2))
+            });
+            other.finish()
+        })
+        .join()
+        .unwrap();
+        record("a@b", Phase::Command, Duration::from_millis(3));
+
+        let phases = |records: &[PhaseTiming]| {
+            records
+                .iter()
+                .map(|r| (r.target.clone(), r.phase))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(phases(&other), vec![(String::from("c@d"), Phase::Auth)]);
+        assert_eq!(
+            phases(&trace.finish()),
+            vec![
+                (String::from("a@b"), Phase::Transfer),
+                (String::from("a@b"), Phase::Command)
+            ]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::connection::crypto::NegotiatedAlgorithms;
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::response::CrustResult;
+use crate::machine::Machine;
+
+pub mod parser;
+
+/// Connects to machine and reports algorithms negotiated during handshake.
+/// Fails (retcode 1) when any of them is not an approved one.
+pub fn crypto_report(machine: &Rc<RefCell<Box<dyn Machine>>>) -> Result<CrustResult, CrustError> {
+    machine.borrow_mut().connect()?;
+
+    let session = match machine.borrow().get_session() {
+        Some(session) => session,
+        None => {
+            return Err(CrustError {
+                code: ExitCode::Internal,
+                message: format!("{} does not use ssh session", machine.borrow()),
+            })
+        }
+    };
+
+    let algorithms = NegotiatedAlgorithms::from_session(&session);
+    let report = algorithms.report(&machine.borrow().to_string());
+
+    match algorithms.is_approved() {
+        true => Ok(CrustResult::new(&report, "", 0)),
+        false => Ok(CrustResult::new(
+            &report,
+            &format!("{report}\nNegotiated algorithms are not approved"),
+            1,
+        )),
+    }
+}
@@ -0,0 +1,62 @@
+use clap::Args;
+
+use crate::connection::parser::ConnectionArgsTo;
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::parser::Validation;
+
+#[derive(Debug, Clone, Args)]
+pub struct DoctorArgs {
+    /// Report algorithms negotiated with remote machine
+    #[clap(long, default_value = "false")]
+    pub crypto: bool,
+
+    #[clap(flatten)]
+    pub remote: Option<ConnectionArgsTo>,
+}
+
+impl Validation for DoctorArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        if !self.crypto {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "No check was requested. Use e.g. --crypto".to_string(),
+            });
+        }
+
+        match self.remote.as_mut() {
+            Some(remote) => remote.validate(),
+            None => Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Crypto check requires a remote machine".to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_without_checks() {
+        let mut args = DoctorArgs {
+            crypto: false,
+            remote: None,
+        };
+
+        let err = args.validate().err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(err.message, "No check was requested. Use e.g. --crypto");
+    }
+
+    #[test]
+    fn test_validate_crypto_without_remote() {
+        let mut args = DoctorArgs {
+            crypto: true,
+            remote: None,
+        };
+
+        let err = args.validate().err().unwrap();
+        assert_eq!(err.message, "Crypto check requires a remote machine");
+    }
+}
@@ -1,5 +1,6 @@
 use text_colorizer::Colorize;
 
+use crate::interfaces::output;
 use crate::interfaces::response::CrustResult;
 
 /// Handles all possible errors from application.
@@ -24,15 +25,15 @@ pub struct DefaultExitHandler {}
 
 impl ExitHandler for DefaultExitHandler {
     fn error(err: CrustError) -> ! {
-        eprintln!("{err}");
+        output::write_err_line(&err.to_string());
         std::process::exit(err.code.to_int());
     }
 
     fn success(result: CrustResult) -> ! {
         if result.is_success() {
-            println!("{}", result.stdout().green());
+            output::write_line(&result.stdout().green().to_string());
         } else {
-            println!("{}", result.stderr().red());
+            output::write_line(&result.stderr().red().to_string());
         }
         std::process::exit(result.retcode());
     }
@@ -113,6 +114,17 @@ impl From<std::io::Error> for CrustError {
     }
 }
 
+/// Handler for errors of local SQLite database.
+#[cfg(feature = "results")]
+impl From<rusqlite::Error> for CrustError {
+    fn from(error: rusqlite::Error) -> Self {
+        CrustError {
+            code: ExitCode::Local,
+            message: error.to_string(),
+        }
+    }
+}
+
 /// Handler for std::string standard error.
 impl From<std::string::FromUtf8Error> for CrustError {
     fn from(error: std::string::FromUtf8Error) -> Self {
@@ -222,7 +234,7 @@ mod tests {
     #[cfg(not(feature = "CI"))]
     #[test]
     fn test_converts_stdio_error_into_crust_error() {
-        let io_error = std::io::Error::new(std::io::ErrorKind::Other, "Custom IO error");
+        let io_error = std::io::Error::other("Custom IO error");
         let crust_error: CrustError = io_error.into();
 
         assert_eq!(
@@ -0,0 +1,136 @@
+use crate::error::{CrustError, ExitCode};
+use crate::utils::shell::quote;
+
+/// Environment of executed command.
+/// - clean: command does not inherit any variable (`env -i`), so it
+///   behaves the same regardless of profile scripts of remote account
+/// - vars: variables explicitly passed to command
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecEnv {
+    pub clean: bool,
+    pub vars: Vec<(String, String)>,
+}
+
+impl ExecEnv {
+    /// Parses variable passed as `KEY=VALUE`.
+    pub fn parse_var(var: &str) -> Result<(String, String), CrustError> {
+        match var.split_once('=') {
+            Some((key, value)) if is_valid_name(key) => Ok((key.to_string(), value.to_string())),
+            _ => Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!("Invalid environment variable '{var}'. Use KEY=VALUE"),
+            }),
+        }
+    }
+
+    /// Checks whether names of all variables can be used in shell.
+    pub fn validate(&self) -> Result<(), CrustError> {
+        match self.vars.iter().find(|(key, _)| !is_valid_name(key)) {
+            Some((key, _)) => Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!("Invalid environment variable name '{key}'"),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Wraps command with `env` invocation (nothing is changed when
+    /// environment is not modified).
+    pub fn wrap(&self, command: &str) -> String {
+        if !self.clean && self.vars.is_empty() {
+            return command.to_string();
+        }
+
+        let mut parts = vec![String::from("env")];
+        if self.clean {
+            parts.push(String::from("-i"));
+        }
+        for (key, value) in &self.vars {
+            parts.push(format!("{key}={}", quote(value)));
+        }
+        parts.push(format!("sh -c {}", quote(command)));
+        parts.join(" ")
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_without_changes() {
+        assert_eq!(ExecEnv::default().wrap("ls -la"), "ls -la");
+    }
+
+    #[test]
+    fn test_wrap_clean_env() {
+        let env = ExecEnv {
+            clean: true,
+            vars: vec![(String::from("LANG"), String::from("C"))],
+        };
+
+        assert_eq!(
+            env.wrap("echo $LANG | wc -c"),
+            "env -i LANG='C' sh -c 'echo $LANG | wc -c'"
+        );
+    }
+
+    #[test]
+    fn test_wrap_escapes_quotes() {
+        let env = ExecEnv {
+            clean: false,
+            vars: vec![(String::from("MSG"), String::from("it's"))],
+        };
+
+        assert_eq!(
+            env.wrap("echo 'a'"),
+            "env MSG='it'\\''s' sh -c 'echo '\\''a'\\'''"
+        );
+    }
+
+    #[test]
+    fn test_parse_var() {
+        assert_eq!(
+            ExecEnv::parse_var("PATH=/bin:/usr/bin").unwrap(),
+            (String::from("PATH"), String::from("/bin:/usr/bin"))
+        );
+        assert_eq!(
+            ExecEnv::parse_var("EMPTY=").unwrap(),
+            (String::from("EMPTY"), String::new())
+        );
+
+        for var in ["NOVALUE", "=x", "1A=x", "A-B=x"] {
+            let err = ExecEnv::parse_var(var).err().unwrap();
+            assert_eq!(err.code, ExitCode::Parser);
+            assert_eq!(
+                err.message,
+                format!("Invalid environment variable '{var}'. Use KEY=VALUE")
+            );
+        }
+    }
+
+    #[test]
+    fn test_clean_env_runs_locally() {
+        let env = ExecEnv {
+            clean: true,
+            vars: vec![(String::from("ONLY"), String::from("1"))],
+        };
+
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(env.wrap("env"))
+            .env("LEAKED", "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert!(stdout.contains("ONLY=1"));
+        assert!(!stdout.contains("LEAKED"));
+    }
+}
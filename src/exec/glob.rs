@@ -0,0 +1,303 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CrustError, ExitCode};
+use crate::machine::Machine;
+use crate::utils::shell::quote_path;
+
+const PREFIX: &str = "{glob:";
+
+/// Finds `{glob:<pattern>}` placeholders in command.
+fn placeholders(cmd: &str) -> Result<Vec<(Range<usize>, &str)>, CrustError> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = cmd[offset..].find(PREFIX).map(|idx| idx + offset) {
+        let pattern_start = start + PREFIX.len();
+        let end = cmd[pattern_start..]
+            .find('}')
+            .map(|idx| idx + pattern_start)
+            .ok_or_else(|| CrustError {
+                code: ExitCode::Parser,
+                message: format!("Unterminated glob placeholder in '{cmd}'"),
+            })?;
+        let pattern = &cmd[pattern_start..end];
+        if pattern.is_empty() {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!("Empty glob placeholder in '{cmd}'"),
+            });
+        }
+        found.push((start..end + 1, pattern));
+        offset = end + 1;
+    }
+    Ok(found)
+}
+
+/// Checks whether command contains any glob placeholder.
+pub fn has_placeholders(cmd: &str) -> bool {
+    cmd.contains(PREFIX)
+}
+
+/// Validates syntax of glob placeholders in command.
+pub fn validate(cmd: &str) -> Result<(), CrustError> {
+    placeholders(cmd).map(|_| ())
+}
+
+/// Substitutes every `{glob:<pattern>}` placeholder with (sorted and
+/// quoted) paths matching the pattern. Directories are listed with
+/// `list_dir`, so the pattern is expanded on the machine which runs
+/// the command, not by its shell.
+/// # Example
+/// ```
+/// use crust::exec::glob::expand;
+///
+/// let cmd = expand("wc -l {glob:/logs/*.log}", |_| {
+///     Ok(vec![String::from("b.log"), String::from("a b.log"), String::from("c.txt")])
+/// })
+/// .unwrap();
+/// assert_eq!(cmd, "wc -l '/logs/a b.log' '/logs/b.log'");
+/// ```
+pub fn expand(
+    cmd: &str,
+    mut list_dir: impl FnMut(&Path) -> Result<Vec<String>, CrustError>,
+) -> Result<String, CrustError> {
+    let mut expanded = String::new();
+    let mut last = 0;
+    for (range, pattern) in placeholders(cmd)? {
+        let mut paths = matching_paths(pattern, &mut list_dir)?;
+        if paths.is_empty() {
+            return Err(CrustError {
+                code: ExitCode::Remote,
+                message: format!("Pattern '{pattern}' did not match any file"),
+            });
+        }
+        paths.sort();
+        let quoted = paths.iter().map(|p| quote_path(p)).collect::<Vec<_>>();
+
+        expanded.push_str(&cmd[last..range.start]);
+        expanded.push_str(&quoted.join(" "));
+        last = range.end;
+    }
+    expanded.push_str(&cmd[last..]);
+    Ok(expanded)
+}
+
+/// Walks pattern component by component, listing only directories
+/// which components with wildcards refer to. Candidates matched by
+/// previous wildcards which can not be listed (e.g. files) are skipped.
+fn matching_paths(
+    pattern: &str,
+    list_dir: &mut impl FnMut(&Path) -> Result<Vec<String>, CrustError>,
+) -> Result<Vec<PathBuf>, CrustError> {
+    let mut paths = vec![PathBuf::new()];
+    let mut wildcard_seen = false;
+    for component in pattern.split('/') {
+        if component.is_empty() {
+            if paths == [PathBuf::new()] {
+                paths = vec![PathBuf::from("/")];
+            }
+            continue;
+        }
+        if !has_wildcards(component) {
+            paths.iter_mut().for_each(|p| p.push(component));
+            continue;
+        }
+
+        let mut matched = Vec::new();
+        for dir in paths {
+            let listed = match dir.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => dir.as_path(),
+            };
+            let names = match list_dir(listed) {
+                Ok(names) => names,
+                Err(_) if wildcard_seen => continue,
+                Err(e) => return Err(e),
+            };
+            for name in names {
+                if wildcard_match(component, &name) {
+                    matched.push(dir.join(name));
+                }
+            }
+        }
+        paths = matched;
+        wildcard_seen = true;
+    }
+    Ok(paths)
+}
+
+fn has_wildcards(component: &str) -> bool {
+    component.contains(['*', '?', '['])
+}
+
+/// Matches file name against shell-like wildcard (`*`, `?`, `[abc]`,
+/// `[a-z]`, `[!a]`). Hidden files match only patterns starting with dot.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => match (name.first(), class_end(pattern)) {
+            (Some(c), Some(end)) => {
+                class_matches(&pattern[1..end], *c) && match_from(&pattern[end + 1..], &name[1..])
+            }
+            (Some(c), None) => *c == '[' && match_from(&pattern[1..], &name[1..]),
+            (None, _) => false,
+        },
+        Some(p) => name.first() == Some(p) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Index of `]` closing character class started at the beginning of pattern.
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let first = match pattern.get(1) {
+        Some('!') => 3,
+        _ => 2,
+    };
+    (first..pattern.len()).find(|idx| pattern[*idx] == ']')
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut idx = 0;
+    let mut found = false;
+    while idx < class.len() {
+        if idx + 2 < class.len() && class[idx + 1] == '-' {
+            found |= class[idx] <= c && c <= class[idx + 2];
+            idx += 3;
+        } else {
+            found |= class[idx] == c;
+            idx += 1;
+        }
+    }
+    found != negated
+}
+
+/// Lists names of entries in directory of passed machine (via sftp on
+/// machines behind connection). Leading `~` points to home directory.
+/// Relative directories are listed from default working directory of
+/// machine (if it is set), as command runs there.
+pub fn list_dir(machine: &dyn Machine, dir: &Path) -> Result<Vec<String>, CrustError> {
+    let dir = match machine.settings().cwd {
+        Some(cwd) if dir.is_relative() && !dir.starts_with("~") => Path::new(&cwd).join(dir),
+        _ => dir.to_path_buf(),
+    };
+    let dir = dir.as_path();
+    let relative = match dir.strip_prefix("~") {
+        Ok(rest) if rest.as_os_str().is_empty() => Some(PathBuf::from(".")),
+        Ok(rest) => Some(rest.to_path_buf()),
+        Err(_) => None,
+    };
+
+    let names = match machine.get_session() {
+        Some(session) => session
+            .sftp()?
+            .readdir(relative.as_deref().unwrap_or(dir))?
+            .into_iter()
+            .filter_map(|(path, _)| path.file_name().map(|n| n.to_string_lossy().to_string()))
+            .collect(),
+        None => {
+            let dir = match relative {
+                Some(rest) => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(rest),
+                None => dir.to_path_buf(),
+            };
+            std::fs::read_dir(dir)?
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+                .collect::<Result<Vec<_>, std::io::Error>>()?
+        }
+    };
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Lister of fake directory tree.
+    fn tree() -> impl FnMut(&Path) -> Result<Vec<String>, CrustError> {
+        let dirs = HashMap::from([
+            (
+                "/var/log",
+                vec!["syslog", "auth.log", "kern.log", ".hidden.log", "nginx"],
+            ),
+            ("/var/log/nginx", vec!["access.log", "error.log"]),
+            (".", vec!["it's here.txt", "notes.md"]),
+        ]);
+        move |dir| match dirs.get(dir.to_str().unwrap()) {
+            Some(names) => Ok(names.iter().map(|n| n.to_string()).collect()),
+            None => Err(CrustError {
+                code: ExitCode::Remote,
+                message: format!("No such directory {}", dir.display()),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_expand_absolute_pattern() {
+        assert_eq!(
+            expand("wc -l {glob:/var/log/*.log}", tree()).unwrap(),
+            "wc -l '/var/log/auth.log' '/var/log/kern.log'"
+        );
+        assert_eq!(
+            expand("tail {glob:/var/log/*/[a-e]*.log} -n 1", tree()).unwrap(),
+            "tail '/var/log/nginx/access.log' '/var/log/nginx/error.log' -n 1"
+        );
+    }
+
+    #[test]
+    fn test_expand_relative_pattern() {
+        assert_eq!(
+            expand("cat {glob:*.txt}; ls {glob:notes.m?}", tree()).unwrap(),
+            "cat 'it'\\''s here.txt'; ls 'notes.md'"
+        );
+    }
+
+    #[test]
+    fn test_expand_without_match() {
+        let err = expand("rm {glob:/var/log/*.gz}", tree()).err().unwrap();
+
+        assert_eq!(err.code, ExitCode::Remote);
+        assert_eq!(
+            err.message,
+            "Pattern '/var/log/*.gz' did not match any file"
+        );
+    }
+
+    #[test]
+    fn test_invalid_placeholders() {
+        assert_eq!(
+            validate("ls {glob:/var/*").err().unwrap().message,
+            "Unterminated glob placeholder in 'ls {glob:/var/*'"
+        );
+        assert_eq!(
+            validate("ls {glob:}").err().unwrap().message,
+            "Empty glob placeholder in 'ls {glob:}'"
+        );
+        assert!(validate("echo {a,b} ${HOME}").is_ok());
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.log", "a.log"));
+        assert!(!wildcard_match("*.log", ".a.log"));
+        assert!(wildcard_match(".*", ".bashrc"));
+        assert!(wildcard_match("file[0-9]", "file7"));
+        assert!(!wildcard_match("file[!0-9]", "file7"));
+        assert!(wildcard_match("[]]x", "]x"));
+        assert!(wildcard_match("a[b", "a[b"));
+        assert!(!wildcard_match("?", ""));
+    }
+}
@@ -1,5 +1,10 @@
-use crate::{error::CrustError, interfaces::response::CrustResult};
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::response::CrustResult;
+pub mod env;
+pub mod glob;
 pub mod parser;
+pub mod request;
+pub mod truncate;
 
 pub const BUFF_SIZE: usize = 4096;
 
@@ -16,4 +21,13 @@ pub trait Exec {
     /// both pipes are merged into one (stderr > stdout). Otherwise you will
     /// get stdout as info!, stderr as error!.
     fn exec_rt(&self, cmd: &str, merge_pipes: bool) -> Result<CrustResult, CrustError>;
+
+    /// Execute command on machine with `input` passed on its stdin (e.g.
+    /// data too long for command line). Captures stdout & stderr as `exec`.
+    fn exec_with_input(&self, cmd: &str, _input: &str) -> Result<CrustResult, CrustError> {
+        Err(CrustError {
+            code: ExitCode::Local,
+            message: format!("Passing input to command is not supported ({cmd})"),
+        })
+    }
 }
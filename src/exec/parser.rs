@@ -1,12 +1,17 @@
 use clap::Args;
 
 use crate::connection::parser::ConnectionArgsTo;
-use crate::error::CrustError;
-use crate::interfaces::parser::Validation;
+use crate::connection::settings::validate_user;
+use crate::error::{CrustError, ExitCode};
+use crate::exec::env::ExecEnv;
+use crate::exec::truncate::parse_size;
+use crate::interfaces::parser::{Problem, Validation};
+use crate::machine::which::command_name;
 
 #[derive(Debug, Clone, Args)]
 pub struct ExecArgs {
-    /// Command to execute
+    /// Command to execute ({glob:<pattern>} is replaced with matching paths
+    /// listed on target machine)
     #[clap(value_delimiter = ' ', num_args = 1..)]
     pub cmd: Option<Vec<String>>,
 
@@ -20,12 +25,83 @@ pub struct ExecArgs {
     /// Merge streams (stderr into stdout)
     #[clap(short, long, default_value = "false")]
     pub merge: bool,
+
+    /// Run command without inherited environment (only variables from --env)
+    #[clap(long, default_value = "false")]
+    pub clean_env: bool,
+
+    /// Environment variable passed to command (can be used many times)
+    #[clap(long = "env", value_name = "KEY=VALUE")]
+    pub env: Vec<String>,
+
+    /// Execute command as another user (sudo -u <USER> -H; without sudo only root can switch user, with su -)
+    #[clap(long, value_name = "USER")]
+    pub run_as: Option<String>,
+
+    /// Truncates captured output to passed size (e.g. 64K, 1M) - full
+    /// output is saved to a temporary file reported in result. Real time
+    /// output stops being printed after passed size
+    #[clap(long, value_name = "SIZE")]
+    pub max_output: Option<String>,
+
+    /// Checks that command exists in PATH of machine before running it
+    /// (missing command fails early with suggestions of similar ones)
+    #[clap(long, default_value = "false")]
+    pub resolve: bool,
 }
 
 impl Validation for ExecArgs {
     fn validate(&mut self) -> Result<(), CrustError> {
-        if self.remote.is_some() {
-            self.remote.as_mut().unwrap().validate()?;
+        match self.problems().into_iter().next() {
+            Some(problem) => Err(problem.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn problems(&mut self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        for var in &self.env {
+            if let Err(e) = ExecEnv::parse_var(var) {
+                problems.push(Problem::from(e).context("--env"));
+            }
+        }
+        if let Some(Err(e)) = self.run_as.as_deref().map(validate_user) {
+            problems.push(Problem::from(e).context("--run-as"));
+        }
+        if let Some(Err(e)) = self.max_output.as_deref().map(parse_size) {
+            problems.push(Problem::from(e).context("--max-output"));
+        }
+        if let Some(remote) = self.remote.as_mut() {
+            problems.extend(remote.problems().into_iter().map(|p| p.context("machine")));
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct WhichArgs {
+    /// Names of commands resolved against PATH of machine
+    #[clap(required = true)]
+    pub names: Vec<String>,
+
+    #[clap(flatten)]
+    pub remote: Option<ConnectionArgsTo>,
+}
+
+impl Validation for WhichArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        if let Some(name) = self
+            .names
+            .iter()
+            .find(|name| command_name(name) != Some(name.as_str()))
+        {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!("Invalid command name '{name}'"),
+            });
+        }
+        if let Some(remote) = self.remote.as_mut() {
+            remote.validate()?;
         }
         Ok(())
     }
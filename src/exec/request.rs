@@ -0,0 +1,345 @@
+use crate::connection::request::RemoteTarget;
+use crate::connection::settings::validate_user;
+use crate::error::{CrustError, ExitCode};
+use crate::exec::env::ExecEnv;
+use crate::exec::glob;
+use crate::exec::parser::ExecArgs;
+use crate::exec::truncate::parse_size;
+use crate::interfaces::parser::Validation;
+use crate::machine::which::command_name;
+use crate::machine::Machine;
+
+/// Validated request to execute command on local or remote machine.
+/// Created only by `ExecRequestBuilder`, so it can be passed to
+/// `crust::run_exec` without any further checks.
+/// # Example
+/// ```
+/// use crust::connection::manager::MachinesManager;
+/// use crust::exec::request::ExecRequest;
+///
+/// let mut manager = MachinesManager::default();
+/// let request = ExecRequest::builder("echo 'test'").build().unwrap();
+///
+/// let result = crust::run_exec(&request, &mut manager).unwrap();
+/// assert_eq!(result.stdout(), "test\n");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecRequest {
+    cmd: String,
+    remote: Option<RemoteTarget>,
+    rt: bool,
+    merge: bool,
+    env: ExecEnv,
+    run_as: Option<String>,
+    max_output: Option<u64>,
+    resolve: bool,
+}
+
+impl ExecRequest {
+    /// Starts building a request for passed command.
+    pub fn builder(cmd: &str) -> ExecRequestBuilder {
+        ExecRequestBuilder {
+            cmd: cmd.to_string(),
+            remote: None,
+            rt: false,
+            merge: false,
+            env: ExecEnv::default(),
+            run_as: None,
+            max_output: None,
+            resolve: false,
+        }
+    }
+
+    /// Getter for command to execute.
+    pub fn cmd(&self) -> &str {
+        &self.cmd
+    }
+
+    /// Getter for target machine (None means local machine).
+    pub fn remote(&self) -> Option<&RemoteTarget> {
+        self.remote.as_ref()
+    }
+
+    /// Checks whether output should be collected in real time.
+    pub fn rt(&self) -> bool {
+        self.rt
+    }
+
+    /// Checks whether stderr should be merged into stdout.
+    pub fn merge(&self) -> bool {
+        self.merge
+    }
+
+    /// Getter for environment of command.
+    pub fn env(&self) -> &ExecEnv {
+        &self.env
+    }
+
+    /// Getter for user executing command (None means the connected one).
+    pub fn run_as(&self) -> Option<&str> {
+        self.run_as.as_deref()
+    }
+
+    /// Getter for size (in bytes) to which captured output is truncated.
+    pub fn max_output(&self) -> Option<u64> {
+        self.max_output
+    }
+
+    /// Checks whether command should be resolved against PATH of machine
+    /// before it is run.
+    pub fn resolve(&self) -> bool {
+        self.resolve
+    }
+
+    /// Command with applied environment - the one sent to machine.
+    pub fn command_line(&self) -> String {
+        self.env.wrap(&self.cmd)
+    }
+
+    /// Like `command_line`, but `{glob:<pattern>}` placeholders are
+    /// replaced with paths matching pattern on passed machine.
+    pub fn expanded_command_line(&self, machine: &dyn Machine) -> Result<String, CrustError> {
+        match glob::has_placeholders(&self.cmd) {
+            true => Ok(self.env.wrap(&glob::expand(&self.cmd, |dir| {
+                glob::list_dir(machine, dir)
+            })?)),
+            false => Ok(self.command_line()),
+        }
+    }
+}
+
+/// Builder of `ExecRequest`.
+pub struct ExecRequestBuilder {
+    cmd: String,
+    remote: Option<RemoteTarget>,
+    rt: bool,
+    merge: bool,
+    env: ExecEnv,
+    run_as: Option<String>,
+    max_output: Option<u64>,
+    resolve: bool,
+}
+
+impl ExecRequestBuilder {
+    /// Executes command on remote machine instead of local.
+    pub fn remote(mut self, target: RemoteTarget) -> Self {
+        self.remote = Some(target);
+        self
+    }
+
+    /// Collects output in real time mode.
+    pub fn rt(mut self, rt: bool) -> Self {
+        self.rt = rt;
+        self
+    }
+
+    /// Merges streams (stderr into stdout).
+    pub fn merge(mut self, merge: bool) -> Self {
+        self.merge = merge;
+        self
+    }
+
+    /// Runs command without inherited environment variables.
+    pub fn clean_env(mut self, clean: bool) -> Self {
+        self.env.clean = clean;
+        self
+    }
+
+    /// Passes environment variable to command.
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.vars.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Executes command as another user (e.g. service account).
+    pub fn run_as(mut self, user: &str) -> Self {
+        self.run_as = Some(user.to_string());
+        self
+    }
+
+    /// Truncates captured output longer than `bytes` - full output is
+    /// saved to a temporary file reported in result. Real time output
+    /// stops being written after `bytes` (see `truncate::limit_rt`).
+    pub fn max_output(mut self, bytes: u64) -> Self {
+        self.max_output = Some(bytes);
+        self
+    }
+
+    /// Resolves command against PATH of machine (`command -v`) before it
+    /// is run - missing command fails early with suggestions of similar
+    /// ones.
+    pub fn resolve(mut self, resolve: bool) -> Self {
+        self.resolve = resolve;
+        self
+    }
+
+    /// Validates collected values and creates a request.
+    pub fn build(mut self) -> Result<ExecRequest, CrustError> {
+        if self.cmd.trim().is_empty() {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Command to execute was not provided".to_string(),
+            });
+        }
+
+        if self.max_output == Some(0) {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Output limit must be greater than 0".to_string(),
+            });
+        }
+        if self.resolve && command_name(&self.cmd).is_none() {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!(
+                    "Command '{}' can not be resolved - it does not start with plain name or path",
+                    self.cmd
+                ),
+            });
+        }
+        self.env.validate()?;
+        glob::validate(&self.cmd)?;
+        if let Some(user) = &self.run_as {
+            validate_user(user)?;
+        }
+        if let Some(remote) = self.remote.as_mut() {
+            remote.validate()?;
+        }
+
+        Ok(ExecRequest {
+            cmd: self.cmd,
+            remote: self.remote,
+            rt: self.rt,
+            merge: self.merge,
+            env: self.env,
+            run_as: self.run_as,
+            max_output: self.max_output,
+            resolve: self.resolve,
+        })
+    }
+}
+
+impl TryFrom<&ExecArgs> for ExecRequest {
+    type Error = CrustError;
+
+    fn try_from(args: &ExecArgs) -> Result<Self, Self::Error> {
+        let cmd = args.cmd.as_ref().map(|c| c.join(" ")).unwrap_or_default();
+        let mut builder = ExecRequest::builder(&cmd)
+            .rt(args.rt)
+            .merge(args.merge)
+            .clean_env(args.clean_env)
+            .resolve(args.resolve);
+        for var in &args.env {
+            let (key, value) = ExecEnv::parse_var(var)?;
+            builder = builder.env(&key, &value);
+        }
+        if let Some(user) = &args.run_as {
+            builder = builder.run_as(user);
+        }
+        if let Some(size) = &args.max_output {
+            builder = builder.max_output(parse_size(size)?);
+        }
+        if let Some(remote) = &args.remote {
+            builder = builder.remote(RemoteTarget::from(remote));
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_local_request() {
+        let request = ExecRequest::builder("ls -la").rt(true).build().unwrap();
+
+        assert_eq!(request.cmd(), "ls -la");
+        assert_eq!(request.remote(), None);
+        assert!(request.rt());
+        assert!(!request.merge());
+    }
+
+    #[test]
+    fn test_build_request_without_command() {
+        let result = ExecRequest::builder(" ").build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(err.message, "Command to execute was not provided");
+    }
+
+    #[test]
+    fn test_build_remote_request_validates_target() {
+        let result = ExecRequest::builder("pwd")
+            .remote(RemoteTarget::new("user@host"))
+            .build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.message, "Neither password nor pkey provided");
+    }
+
+    #[test]
+    fn test_convert_cli_args_into_request() {
+        let args = ExecArgs {
+            cmd: Some(vec![String::from("echo"), String::from("a")]),
+            remote: None,
+            rt: false,
+            merge: true,
+            clean_env: true,
+            env: vec![String::from("LANG=C")],
+            run_as: Some(String::from("app")),
+            max_output: Some(String::from("64K")),
+            resolve: true,
+        };
+
+        let request = ExecRequest::try_from(&args).unwrap();
+        assert_eq!(
+            request,
+            ExecRequest::builder("echo a")
+                .merge(true)
+                .clean_env(true)
+                .env("LANG", "C")
+                .run_as("app")
+                .max_output(64 * 1024)
+                .resolve(true)
+                .build()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_build_request_with_invalid_run_as() {
+        let result = ExecRequest::builder("id").run_as("root;id").build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.message, "Invalid user name 'root;id'");
+    }
+
+    #[test]
+    fn test_build_request_with_max_output_in_real_time() {
+        let request = ExecRequest::builder("yes").rt(true).max_output(10).build();
+
+        assert_eq!(request.unwrap().max_output(), Some(10));
+    }
+
+    #[test]
+    fn test_build_request_with_invalid_env() {
+        let result = ExecRequest::builder("env").env("A B", "1").build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.message, "Invalid environment variable name 'A B'");
+    }
+
+    #[test]
+    fn test_command_line_with_env() {
+        let request = ExecRequest::builder("env")
+            .clean_env(true)
+            .env("A", "1")
+            .build()
+            .unwrap();
+
+        assert_eq!(request.cmd(), "env");
+        assert_eq!(request.command_line(), "env -i A='1' sh -c 'env'");
+    }
+}
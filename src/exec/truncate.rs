@@ -0,0 +1,198 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use indicatif::HumanBytes;
+use uuid::Uuid;
+
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::output;
+use crate::interfaces::response::CrustResult;
+
+/// Parses size of output - number of bytes with optional binary suffix
+/// (`K`, `M`, `G`).
+/// # Example
+/// ```
+/// use crust::exec::truncate::parse_size;
+///
+/// assert_eq!(parse_size("64K").unwrap(), 64 * 1024);
+/// assert_eq!(parse_size("1m").unwrap(), 1024 * 1024);
+/// assert_eq!(parse_size("500").unwrap(), 500);
+/// assert!(parse_size("1T").is_err());
+/// assert!(parse_size("18446744073709551615G").is_err());
+/// ```
+pub fn parse_size(value: &str) -> Result<u64, CrustError> {
+    let invalid = || CrustError {
+        code: ExitCode::Parser,
+        message: format!("Invalid output size '{value}'. Use bytes or K, M, G suffix (e.g. 64K)"),
+    };
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], 1024),
+        Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    match number
+        .parse::<u64>()
+        .map(|size| size.checked_mul(multiplier))
+    {
+        Ok(Some(size)) if size > 0 => Ok(size),
+        _ => Err(invalid()),
+    }
+}
+
+/// Truncates streams of result longer than `max` bytes. Full stream is
+/// spilled to a file in temporary directory (readable by owner only) and
+/// its path is reported at the end of truncated stream.
+pub fn limit(result: CrustResult, max: u64) -> Result<CrustResult, CrustError> {
+    let stdout = limit_stream(result.stdout(), max, "stdout")?;
+    let stderr = limit_stream(result.stderr(), max, "stderr")?;
+    Ok(CrustResult::new(&stdout, &stderr, result.retcode()))
+}
+
+fn limit_stream(stream: &str, max: u64, name: &str) -> Result<String, CrustError> {
+    if stream.len() as u64 <= max {
+        return Ok(stream.to_string());
+    }
+
+    let path = spill(stream, name)?;
+    let mut end = max as usize;
+    while !stream.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut truncated = stream[..end].to_string();
+    if !truncated.ends_with('\n') {
+        truncated.push('\n');
+    }
+    truncated.push_str(&format!(
+        "[{name} truncated to {} of {} - full output in {}]\n",
+        HumanBytes(max),
+        HumanBytes(stream.len() as u64),
+        path.display()
+    ));
+    Ok(truncated)
+}
+
+/// Runs command with real time output limited to `max` bytes. Output
+/// over the limit is not written - full output is spilled to a file in
+/// temporary directory and its path is reported after command finishes.
+/// Stderr which is not merged into output is logged and not limited.
+pub fn limit_rt(
+    max: u64,
+    run: impl FnOnce() -> Result<CrustResult, CrustError>,
+) -> Result<CrustResult, CrustError> {
+    let (path, file) = spill_file("output")?;
+    output::capture(max, Box::new(file));
+    let result = run();
+    let captured = output::release();
+
+    if captured.written <= max {
+        let _ = std::fs::remove_file(&path);
+        return result;
+    }
+    if !captured.complete_line {
+        output::write("\n");
+    }
+    output::write_line(&format!(
+        "[output truncated to {} of {} - full output in {}]",
+        HumanBytes(max),
+        HumanBytes(captured.written),
+        path.display()
+    ));
+    result
+}
+
+/// Writes full stream into a new file.
+fn spill(stream: &str, name: &str) -> Result<PathBuf, CrustError> {
+    let (path, mut file) = spill_file(name)?;
+    file.write_all(stream.as_bytes())?;
+    log::debug!(
+        "Full {name} ({} bytes) saved in {}",
+        stream.len(),
+        path.display()
+    );
+    Ok(path)
+}
+
+/// Creates a new file for stream (readable by owner only).
+fn spill_file(name: &str) -> Result<(PathBuf, File), CrustError> {
+    let path = std::env::temp_dir().join(format!("crust-{}.{name}", Uuid::new_v4().simple()));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(&path)?;
+    Ok((path, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::output::OutputSink;
+    use serial_test::serial;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer which shares written data with test.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_limit_spills_full_output() {
+        let stdout = "żółw\n".repeat(100);
+        let result = limit(CrustResult::new(&stdout, "short", 3), 10).unwrap();
+
+        let (kept, note) = result.stdout().split_once("[stdout truncated").unwrap();
+        assert_eq!(kept, "żółw\nż\n");
+        assert!(note.starts_with(" to 10 B of 800 B - full output in "));
+        let path = note
+            .trim_end()
+            .trim_end_matches(']')
+            .rsplit(' ')
+            .next()
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), stdout);
+        assert_eq!(result.stderr(), "short");
+        assert_eq!(result.retcode(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_limit_real_time_output() {
+        let buffer = Buffer::default();
+        output::set_sink(OutputSink::Writer(Box::new(buffer.clone())));
+
+        let result = limit_rt(6, || {
+            output::write_line("line 1");
+            output::write_line("line 2");
+            Ok(CrustResult::new("", "", 4))
+        });
+        limit_rt(100, || {
+            output::write_line("short");
+            Ok(CrustResult::default())
+        })
+        .unwrap();
+        output::set_sink(OutputSink::Silent);
+
+        assert_eq!(result.unwrap().retcode(), 4);
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let (kept, note) = written.split_once("[output truncated").unwrap();
+        assert_eq!(kept, "line 1\n");
+        assert!(note.starts_with(" to 6 B of 14 B - full output in "));
+        let (note, rest) = note.split_once('\n').unwrap();
+        assert_eq!(rest, "short\n");
+        let path = note.trim_end_matches(']').rsplit(' ').next().unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "line 1\nline 2\n");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+//! Examples of using crust as a library. Every example runs against
+//! [`FakeTransport`](super::FakeTransport), so it is compiled and executed
+//! as a doctest - the same code works with real machines once fakes are
+//! replaced with targets of reachable hosts.
+//!
+//! # Manager
+//! Machines are kept by [`MachinesManager`](crate::connection::manager::MachinesManager)
+//! and reused by every request pointing to them (by address or alias).
+//! ```
+//! use crust::connection::manager::{MachinesManager, MachinesManagerMethods};
+//! use crust::connection::request::RemoteTarget;
+//! use crust::exec::request::ExecRequest;
+//! use crust::fake::FakeTransport;
+//!
+//! let mut manager = MachinesManager::new();
+//! manager.add_machine(Box::new(FakeTransport::new("deploy@web-1")));
+//! manager.add_machine(Box::new(FakeTransport::with_alias("db")));
+//! assert_eq!(manager.size(), 2);
+//!
+//! let request = ExecRequest::builder("echo ok")
+//!     .remote(RemoteTarget::with_alias("db"))
+//!     .build()
+//!     .unwrap();
+//! let result = crust::run_exec(&request, &mut manager).unwrap();
+//! assert_eq!(result.stdout(), "ok\n");
+//! ```
+//!
+//! # Exec
+//! Scripted responses stand for commands which can not be run locally,
+//! history shows what was sent to machine. Authorization of target is
+//! required by request, but fake machine does not use it.
+//! ```
+//! use crust::connection::manager::{MachinesManager, MachinesManagerMethods};
+//! use crust::connection::request::RemoteTarget;
+//! use crust::exec::request::ExecRequest;
+//! use crust::fake::FakeTransport;
+//!
+//! let fake = FakeTransport::new("deploy@web-1").respond("systemctl is-active app", "active\n", 0);
+//! let history = fake.history();
+//! let mut manager = MachinesManager::new();
+//! manager.add_machine(Box::new(fake));
+//!
+//! let request = ExecRequest::builder("systemctl is-active app")
+//!     .remote(RemoteTarget::new("deploy@web-1").password("secret"))
+//!     .build()
+//!     .unwrap();
+//! let result = crust::run_exec(&request, &mut manager).unwrap();
+//! assert!(result.is_success());
+//! assert_eq!(result.stdout(), "active\n");
+//! assert_eq!(*history.borrow(), vec!["systemctl is-active app"]);
+//! ```
+//!
+//! # Pooled exec
+//! Fleet execution connects all hosts up-front (at most `concurrency` at
+//! once) and collects outcome of every host, failures included.
+//! ```
+//! use crust::connection::manager::{MachinesManager, MachinesManagerMethods};
+//! use crust::connection::request::RemoteTarget;
+//! use crust::exec::request::ExecRequest;
+//! use crust::fake::FakeTransport;
+//!
+//! let mut manager = MachinesManager::new();
+//! let mut plan = Vec::new();
+//! for (host, retcode) in [("web-1", 0), ("web-2", 0), ("web-3", 2)] {
+//!     let addr = format!("deploy@{host}");
+//!     manager.add_machine(Box::new(FakeTransport::new(&addr).respond("deploy", "", retcode)));
+//!     let request = ExecRequest::builder("deploy")
+//!         .remote(RemoteTarget::new(&addr).password("secret"))
+//!         .build()
+//!         .unwrap();
+//!     plan.push((host.to_string(), request));
+//! }
+//!
+//! let report = crust::run_fleet_exec(&plan, &mut manager, 2);
+//! assert!(!report.is_success());
+//! let failed = report
+//!     .outcomes
+//!     .iter()
+//!     .filter(|o| !o.is_success())
+//!     .map(|o| o.host.as_str())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(failed, vec!["web-3"]);
+//! ```
+//!
+//! # Scp
+//! Trees are copied file by file and summarized with a report.
+//! ```
+//! use crust::connection::manager::{MachinesManager, MachinesManagerMethods};
+//! use crust::connection::request::RemoteTarget;
+//! use crust::fake::FakeTransport;
+//! use crust::scp::request::TransferRequest;
+//!
+//! let root = std::env::temp_dir().join(format!("crust-scp-{}", std::process::id()));
+//! std::fs::create_dir_all(root.join("site/css")).unwrap();
+//! std::fs::write(root.join("site/index.html"), "<html/>").unwrap();
+//! std::fs::write(root.join("site/css/main.css"), "body {}").unwrap();
+//!
+//! let mut manager = MachinesManager::new();
+//! manager.add_machine(Box::new(FakeTransport::with_alias("web")));
+//! let request = TransferRequest::builder(root.join("site"), root.join("www"))
+//!     .dst_remote(RemoteTarget::with_alias("web"))
+//!     .build()
+//!     .unwrap();
+//! let result = crust::run_transfer(&request, &mut manager).unwrap();
+//! assert!(result.stdout().starts_with("Transferred 2/2 files"));
+//! assert_eq!(std::fs::read_to_string(root.join("www/css/main.css")).unwrap(), "body {}");
+//! # std::fs::remove_dir_all(root).unwrap();
+//! ```
+//!
+//! # Sync
+//! With `sync` only files missing or different on destination are copied.
+//! ```
+//! use crust::connection::manager::{MachinesManager, MachinesManagerMethods};
+//! use crust::connection::request::RemoteTarget;
+//! use crust::fake::FakeTransport;
+//! use crust::scp::request::TransferRequest;
+//!
+//! let root = std::env::temp_dir().join(format!("crust-sync-{}", std::process::id()));
+//! std::fs::create_dir_all(root.join("site")).unwrap();
+//! std::fs::write(root.join("site/index.html"), "<html/>").unwrap();
+//! std::fs::write(root.join("site/about.html"), "<p/>").unwrap();
+//!
+//! let mut manager = MachinesManager::new();
+//! manager.add_machine(Box::new(FakeTransport::with_alias("web")));
+//! let request = TransferRequest::builder(root.join("site"), root.join("www"))
+//!     .dst_remote(RemoteTarget::with_alias("web"))
+//!     .sync(true)
+//!     .build()
+//!     .unwrap();
+//! crust::run_transfer(&request, &mut manager).unwrap();
+//!
+//! std::fs::write(root.join("site/about.html"), "<p>About</p>").unwrap();
+//! let result = crust::run_transfer(&request, &mut manager).unwrap();
+//! assert!(result.stdout().starts_with("Transferred 1/1 files"));
+//! assert!(result.stdout().contains("1 unchanged"));
+//! # std::fs::remove_dir_all(root).unwrap();
+//! ```
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::DirBuilder;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::connection::endpoint::Endpoint;
+use crate::error::{CrustError, ExitCode};
+use crate::exec::Exec;
+use crate::interfaces::{output, response::CrustResult, tmpdir::TemporaryDirectory};
+use crate::machine::local::LocalMachine;
+use crate::machine::{Machine, MachineID, MachineType};
+use crate::scp::tree::TreeEntry;
+use crate::scp::{Scp, TransferFile};
+
+pub mod examples;
+
+/// Remote machine without any connection (available with `test-util`
+/// feature). It is registered in manager under the same id as real
+/// remote machine, so requests targeting its address (or alias) use it.
+/// - responses: scripted results of commands (exact command match)
+/// - history: every executed command, shared with handles from `history`
+///
+/// Commands without scripted response are executed by local shell and
+/// files are kept on local filesystem (file operations of transfers are
+/// done by local machine) - so transfers, sync and verification work as
+/// with a real machine.
+pub struct FakeTransport {
+    id: MachineID,
+    name: String,
+    responses: HashMap<String, CrustResult>,
+    history: Rc<RefCell<Vec<String>>>,
+    tmpdir: Option<PathBuf>,
+}
+
+impl FakeTransport {
+    /// Creates a fake of machine with address (<user>@<host>[:<port>], port
+    /// 22 by default).
+    /// # Example
+    /// ```
+    /// use crust::fake::FakeTransport;
+    ///
+    /// let fake = FakeTransport::new("user@backend");
+    /// assert_eq!(fake.to_string(), "FakeTransport<user@backend>");
+    /// ```
+    pub fn new(addr: &str) -> Self {
+        let endpoint = Endpoint::parse_address(addr).unwrap_or_else(|_| Endpoint {
+            user: Some(String::new()),
+            host: Some(addr.to_string()),
+            port: None,
+            alias: None,
+            path: None,
+        });
+        Self {
+            id: MachineID::Default(
+                endpoint.user,
+                endpoint.host,
+                Some(endpoint.port.unwrap_or(22)),
+            ),
+            name: addr.to_string(),
+            responses: HashMap::new(),
+            history: Rc::new(RefCell::new(Vec::new())),
+            tmpdir: None,
+        }
+    }
+
+    /// Creates a fake of machine registered under alias.
+    pub fn with_alias(alias: &str) -> Self {
+        let mut fake = Self::new(alias);
+        fake.id = MachineID::Custom(alias.to_string());
+        fake
+    }
+
+    /// Scripts result of command (instead of executing it by local shell).
+    pub fn respond(mut self, cmd: &str, stdout: &str, retcode: i32) -> Self {
+        self.responses
+            .insert(cmd.to_string(), CrustResult::new(stdout, "", retcode));
+        self
+    }
+
+    /// Handle to commands executed on machine (it stays valid after
+    /// machine is moved into manager).
+    pub fn history(&self) -> Rc<RefCell<Vec<String>>> {
+        Rc::clone(&self.history)
+    }
+
+    fn run(&self, cmd: &str) -> Result<CrustResult, CrustError> {
+        self.history.borrow_mut().push(cmd.to_string());
+        match self.responses.get(cmd) {
+            Some(r) => Ok(CrustResult::new(r.stdout(), r.stderr(), r.retcode())),
+            None => LocalMachine::default().exec(cmd),
+        }
+    }
+}
+
+impl Machine for FakeTransport {
+    fn mtype(&self) -> MachineType {
+        MachineType::RemoteMachine
+    }
+
+    fn get_session(&self) -> Option<ssh2::Session> {
+        None
+    }
+
+    fn get_id(&self) -> &MachineID {
+        &self.id
+    }
+
+    fn connect(&mut self) -> Result<(), CrustError> {
+        Ok(())
+    }
+
+    fn list_tree(&self, root: &Path) -> Result<Vec<TreeEntry>, CrustError> {
+        LocalMachine::default().list_tree(root)
+    }
+
+    fn path_exists(&self, path: &Path) -> Result<bool, CrustError> {
+        LocalMachine::default().path_exists(path)
+    }
+
+    fn create_dir(&self, dir: &Path) -> Result<(), CrustError> {
+        LocalMachine::default().create_dir(dir)
+    }
+
+    fn open_file(&self, path: &Path) -> Result<(TransferFile, u64), CrustError> {
+        LocalMachine::default().open_file(path)
+    }
+
+    fn create_file(&self, path: &Path, size: u64) -> Result<TransferFile, CrustError> {
+        LocalMachine::default().create_file(path, size)
+    }
+}
+
+impl TemporaryDirectory for FakeTransport {
+    fn can_be_removed(&self) -> bool {
+        true
+    }
+
+    fn tmpdir_exists(&self) -> bool {
+        self.tmpdir.is_some()
+    }
+
+    fn get_tmpdir(&self) -> &PathBuf {
+        self.tmpdir
+            .as_ref()
+            .expect("Temporary directory was not created")
+    }
+
+    fn create_tmpdir(&mut self) -> Result<PathBuf, CrustError> {
+        if let Some(tmpdir) = &self.tmpdir {
+            return Ok(tmpdir.clone());
+        }
+        let path = PathBuf::from(format!("/tmp/tmp.{}", Uuid::new_v4().as_u128()));
+        DirBuilder::new().create(&path)?;
+        self.tmpdir = Some(path.clone());
+        Ok(path)
+    }
+
+    fn create_tmpdir_content(&self, filename: &str) -> Result<PathBuf, CrustError> {
+        let Some(tmpdir) = &self.tmpdir else {
+            return Err(CrustError {
+                code: ExitCode::Remote,
+                message: "You wanted to create tempfile, but you have not created tempdir!"
+                    .to_string(),
+            });
+        };
+        let path = tmpdir.join(filename);
+        std::fs::File::create(&path)?;
+        Ok(path)
+    }
+
+    fn remove_tmpdir(&self) -> Result<(), CrustError> {
+        if let Some(tmpdir) = &self.tmpdir {
+            std::fs::remove_dir_all(tmpdir)?;
+        }
+        Ok(())
+    }
+}
+
+impl Exec for FakeTransport {
+    fn exec(&self, cmd: &str) -> Result<CrustResult, CrustError> {
+        self.run(cmd)
+    }
+
+    fn exec_rt(&self, cmd: &str, merge_pipes: bool) -> Result<CrustResult, CrustError> {
+        let result = self.run(cmd)?;
+        result.stdout().lines().for_each(output::write_line);
+        if merge_pipes {
+            result.stderr().lines().for_each(output::write_line);
+        }
+        Ok(CrustResult::default())
+    }
+}
+
+impl Scp for FakeTransport {
+    fn get_machine(&self) -> MachineType {
+        self.mtype()
+    }
+}
+
+impl Drop for FakeTransport {
+    fn drop(&mut self) {
+        let _ = self.remove_tmpdir();
+    }
+}
+
+impl std::fmt::Display for FakeTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "FakeTransport<{}>", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_and_shell_commands() {
+        let fake = FakeTransport::new("user@host").respond("uname", "FakeOS\n", 0);
+        let history = fake.history();
+
+        assert_eq!(fake.exec("uname").unwrap().stdout(), "FakeOS\n");
+        assert_eq!(fake.exec("echo 1").unwrap().stdout(), "1\n");
+        assert_eq!(*history.borrow(), vec!["uname", "echo 1"]);
+        assert_eq!(
+            *fake.get_id(),
+            MachineID::new(
+                Some(String::from("user")),
+                Some(String::from("host")),
+                Some(22)
+            )
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use crate::error::{CrustError, ExitCode};
+use crate::exec::request::ExecRequest;
+use crate::interfaces::response::CrustResult;
+use crate::inventory::Inventory;
+use crate::plan::{classify_command, PlannedAction};
+
+pub mod parser;
+pub mod run;
+
+/// Creates exec requests for every host matching target expression.
+/// Fails before anything is executed, so a typo in target does not
+/// leave a fleet partially changed.
+pub fn plan_exec(
+    inventory: &Inventory,
+    target: &str,
+    cmd: &str,
+    merge: bool,
+    max_output: Option<u64>,
+) -> Result<Vec<(String, ExecRequest)>, CrustError> {
+    let hosts = inventory.resolve(target)?;
+    if hosts.is_empty() {
+        return Err(CrustError {
+            code: ExitCode::Parser,
+            message: format!("No hosts match target '{target}'"),
+        });
+    }
+
+    hosts
+        .into_iter()
+        .map(|host| {
+            let mut builder = ExecRequest::builder(cmd)
+                .remote(inventory.target(&host)?)
+                .merge(merge);
+            if let Some(max) = max_output {
+                builder = builder.max_output(max);
+            }
+            let request = builder.build()?;
+            Ok((host, request))
+        })
+        .collect()
+}
+
+/// Actions of planned exec (one per host) with risk of executed command.
+pub fn plan_actions(plan: &[(String, ExecRequest)]) -> Vec<PlannedAction> {
+    plan.iter()
+        .map(|(host, request)| {
+            let (risk, reason) = classify_command(request.cmd());
+            PlannedAction {
+                action: String::from("exec"),
+                target: host.clone(),
+                description: request.cmd().to_string(),
+                size: None,
+                risk,
+                reason,
+            }
+        })
+        .collect()
+}
+
+/// Outcome of operation on a single host.
+#[derive(Debug)]
+pub struct HostOutcome {
+    pub host: String,
+    pub result: Result<CrustResult, CrustError>,
+}
+
+impl HostOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(&self.result, Ok(r) if r.is_success())
+    }
+}
+
+/// Collected outcomes of multi-host operation.
+/// - cancelled: id of cancelled run with hosts which were not started
+#[derive(Debug, Default)]
+pub struct FleetReport {
+    pub outcomes: Vec<HostOutcome>,
+    pub cancelled: Option<(u64, Vec<String>)>,
+}
+
+impl FleetReport {
+    pub fn push(&mut self, host: &str, result: Result<CrustResult, CrustError>) {
+        self.outcomes.push(HostOutcome {
+            host: host.to_string(),
+            result,
+        });
+    }
+
+    /// Marks report as partial - run was cancelled before passed hosts
+    /// were started.
+    pub fn cancel(&mut self, run: u64, skipped: Vec<String>) {
+        self.cancelled = Some((run, skipped));
+    }
+
+    /// Checks whether operation succeeded on every host.
+    pub fn is_success(&self) -> bool {
+        self.cancelled.is_none() && self.outcomes.iter().all(HostOutcome::is_success)
+    }
+
+    /// Human readable output of every host with a summary at the end.
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
+        for outcome in &self.outcomes {
+            match &outcome.result {
+                Ok(r) => {
+                    lines.push(format!(
+                        "=== {} (retcode {}) ===",
+                        outcome.host,
+                        r.retcode()
+                    ));
+                    let output = match r.is_success() {
+                        true => r.stdout(),
+                        false => r.stderr(),
+                    };
+                    if !output.is_empty() {
+                        lines.push(output.trim_end().to_string());
+                    }
+                }
+                Err(e) => {
+                    lines.push(format!("=== {} (error) ===", outcome.host));
+                    lines.push(e.to_string());
+                }
+            }
+        }
+
+        let succeeded = self.outcomes.iter().filter(|o| o.is_success()).count();
+        let skipped = match &self.cancelled {
+            Some((run, skipped)) => {
+                lines.push(format!(
+                    "Run {run} was cancelled - not started on: {}",
+                    skipped.join(", ")
+                ));
+                skipped.len()
+            }
+            None => 0,
+        };
+        lines.push(format!(
+            "{succeeded}/{} hosts succeeded",
+            self.outcomes.len() + skipped
+        ));
+        lines.join("\n")
+    }
+}
+
+impl From<FleetReport> for CrustResult {
+    fn from(report: FleetReport) -> Self {
+        let output = report.render();
+        match report.is_success() {
+            true => CrustResult::new(&output, "", 0),
+            false => CrustResult::new(&output, &output, ExitCode::Remote.to_int()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory() -> Inventory {
+        Inventory::from_yaml(
+            "
+hosts:
+  web-1: {addr: a@web1, password: '1', tags: [canary]}
+  web-2: {addr: a@web2, password: '1'}
+groups:
+  web: {hosts: [web-1, web-2]}
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan_exec() {
+        let plan = plan_exec(
+            &inventory(),
+            "web and not tag:canary",
+            "uptime",
+            false,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].0, "web-2");
+        assert_eq!(plan[0].1.cmd(), "uptime");
+        assert_eq!(
+            plan[0].1.remote(),
+            Some(&inventory().target("web-2").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_plan_exec_without_hosts() {
+        let err = plan_exec(&inventory(), "web and not web", "uptime", false, None)
+            .err()
+            .unwrap();
+
+        assert_eq!(err.message, "No hosts match target 'web and not web'");
+    }
+
+    #[test]
+    fn test_plan_actions() {
+        let plan = plan_exec(&inventory(), "web", "rm -rf /tmp/build", false, None).unwrap();
+
+        let actions = plan_actions(&plan);
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[1].target, "web-2");
+        assert_eq!(actions[1].risk, crate::plan::Risk::High);
+        assert_eq!(
+            actions[1].reason.as_deref(),
+            Some("removes files recursively")
+        );
+    }
+
+    #[test]
+    fn test_report_with_failed_host() {
+        let mut report = FleetReport::default();
+        report.push("web-1", Ok(CrustResult::new("up\n", "", 0)));
+        report.push(
+            "web-2",
+            Err(CrustError {
+                code: ExitCode::Ssh,
+                message: String::from("timeout"),
+            }),
+        );
+
+        assert!(!report.is_success());
+        let output = report.render();
+        assert!(output.starts_with("=== web-1 (retcode 0) ===\nup\n=== web-2 (error) ===\n"));
+        assert!(output.ends_with("1/2 hosts succeeded"));
+
+        let result = CrustResult::from(report);
+        assert_eq!(result.retcode(), 1);
+    }
+
+    #[test]
+    fn test_report_of_cancelled_run() {
+        let mut report = FleetReport::default();
+        report.push("web-1", Ok(CrustResult::new("up\n", "", 0)));
+        report.cancel(4, vec![String::from("web-2"), String::from("web-3")]);
+
+        assert!(!report.is_success());
+        assert!(report
+            .render()
+            .ends_with("Run 4 was cancelled - not started on: web-2, web-3\n1/3 hosts succeeded"));
+    }
+}
@@ -0,0 +1,175 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+
+use crate::connection::parallel::DEFAULT_CONCURRENCY;
+use crate::error::{CrustError, ExitCode};
+use crate::exec::truncate::parse_size;
+use crate::interfaces::parser::Validation;
+use crate::inventory::expression::Expression;
+use crate::plan::parser::PlanArgs;
+
+#[derive(Debug, Clone, Args)]
+pub struct FleetArgs {
+    #[clap(subcommand)]
+    pub action: FleetAction,
+}
+
+impl Validation for FleetArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        match &mut self.action {
+            FleetAction::Exec(args) => args.validate(),
+            FleetAction::Hosts(args) => args.validate(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RunArgs {
+    #[clap(subcommand)]
+    pub action: RunAction,
+}
+
+impl Validation for RunArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        Ok(())
+    }
+}
+
+/// Multi-host runs are served by main loop of background process, so
+/// these commands should be sent to its control pipe (`runner.sh -c`).
+#[derive(Debug, Clone, Subcommand)]
+pub enum RunAction {
+    /// Lists multi-host runs in progress
+    List,
+
+    /// Stops dispatching new hosts and closes in-flight commands of run
+    Cancel {
+        /// Id of run to cancel
+        id: u64,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum FleetAction {
+    /// Executes command on every machine matching target expression
+    Exec(FleetExecArgs),
+
+    /// Lists machines matching target expression
+    Hosts(TargetArgs),
+}
+
+/// Selection of machines from inventory.
+#[derive(Debug, Clone, Args)]
+pub struct TargetArgs {
+    /// Target expression, e.g. 'web and not canary' or 'tag:ssd'
+    #[clap(long)]
+    pub target: String,
+
+    /// Path to inventory file (default: $CRUST_INVENTORY or
+    /// ~/.config/crust/inventory.yaml)
+    #[clap(long)]
+    pub inventory: Option<PathBuf>,
+}
+
+impl Validation for TargetArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        Expression::parse(&self.target)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct FleetExecArgs {
+    /// Command to execute
+    #[clap(value_delimiter = ' ', num_args = 1..)]
+    pub cmd: Vec<String>,
+
+    #[clap(flatten)]
+    pub selection: TargetArgs,
+
+    /// Merge streams (stderr into stdout)
+    #[clap(short, long, default_value = "false")]
+    pub merge: bool,
+
+    /// Truncates captured output of every host to passed size (e.g. 64K)
+    /// - full output is saved to a temporary file reported in result
+    #[clap(long, value_name = "SIZE")]
+    pub max_output: Option<String>,
+
+    /// Number of machines connected at the same time before execution
+    #[clap(long, default_value_t = DEFAULT_CONCURRENCY)]
+    pub connect_concurrency: usize,
+
+    /// Saves result of every host (as soon as it finishes) into local
+    /// SQLite database, see `results query`
+    #[cfg(feature = "results")]
+    #[clap(long, value_name = "FILE")]
+    pub store: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub plan: PlanArgs,
+}
+
+impl Validation for FleetExecArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        if self.cmd.is_empty() {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Command to execute was not provided".to_string(),
+            });
+        }
+        if self.connect_concurrency == 0 {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Connect concurrency must be greater than 0".to_string(),
+            });
+        }
+        self.max_output()?;
+        self.selection.validate()
+    }
+}
+
+impl FleetExecArgs {
+    /// Parsed limit of captured output (in bytes).
+    pub fn max_output(&self) -> Result<Option<u64>, CrustError> {
+        self.max_output.as_deref().map(parse_size).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_invalid_target() {
+        let mut args = TargetArgs {
+            target: String::from("web and"),
+            inventory: None,
+        };
+
+        let err = args.validate().err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(err.message, "Invalid target expression: unexpected end");
+    }
+
+    #[test]
+    fn test_validate_exec_without_command() {
+        let mut args = FleetExecArgs {
+            cmd: vec![],
+            selection: TargetArgs {
+                target: String::from("all"),
+                inventory: None,
+            },
+            merge: false,
+            max_output: None,
+            connect_concurrency: DEFAULT_CONCURRENCY,
+            #[cfg(feature = "results")]
+            store: None,
+            plan: PlanArgs::default(),
+        };
+
+        let err = args.validate().err().unwrap();
+        assert_eq!(err.message, "Command to execute was not provided");
+    }
+}
@@ -0,0 +1,190 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+
+use crate::connection::channels;
+use crate::error::{CrustError, ExitCode};
+
+/// Multi-host runs in progress. In background mode they can be listed
+/// (and cancelled) via control pipe while main loop is busy with them.
+static RUNS: Mutex<Vec<RunInfo>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Run which is being dispatched by this thread - channels opened by
+    /// it in the meantime belong to the run. Operations running aside (on
+    /// other threads) are not affected by cancelling the run.
+    static CURRENT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Single multi-host run.
+/// - done: number of hosts already finished
+/// - cancelled: user asked to stop the run (no further hosts are started)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunInfo {
+    pub id: u64,
+    pub description: String,
+    pub started: DateTime<Utc>,
+    pub total: usize,
+    pub done: usize,
+    pub cancelled: bool,
+}
+
+/// Registration of running operation - it is removed from registry on drop.
+pub struct RunGuard {
+    id: u64,
+}
+
+impl RunGuard {
+    /// Registers a new run over `total` hosts.
+    pub fn start(description: &str, total: usize) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        log::info!("Started run {id}: {description}");
+        RUNS.lock().unwrap().push(RunInfo {
+            id,
+            description: description.to_string(),
+            started: Utc::now(),
+            total,
+            done: 0,
+            cancelled: false,
+        });
+        CURRENT.set(Some(id));
+        channels::set_run_hook(current);
+        Self { id }
+    }
+
+    /// Getter for run id.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Checks whether user requested to cancel the run.
+    pub fn is_cancelled(&self) -> bool {
+        RUNS.lock()
+            .unwrap()
+            .iter()
+            .any(|r| r.id == self.id && r.cancelled)
+    }
+
+    /// Marks another host as finished.
+    pub fn host_done(&self) {
+        if let Some(run) = RUNS.lock().unwrap().iter_mut().find(|r| r.id == self.id) {
+            run.done += 1;
+        }
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        RUNS.lock().unwrap().retain(|r| r.id != self.id);
+        if CURRENT.get() == Some(self.id) {
+            CURRENT.set(None);
+        }
+    }
+}
+
+/// Id of run being dispatched by this thread (if any).
+pub fn current() -> Option<u64> {
+    CURRENT.get()
+}
+
+/// Gets a copy of runs in progress.
+pub fn list() -> Vec<RunInfo> {
+    RUNS.lock().unwrap().clone()
+}
+
+/// Stops dispatching hosts of the run and force-closes its in-flight
+/// channels. Returns a number of closed channels.
+pub fn cancel(id: u64) -> Result<usize, CrustError> {
+    match RUNS.lock().unwrap().iter_mut().find(|r| r.id == id) {
+        Some(run) => run.cancelled = true,
+        None => {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!("There is no active run with id {id}"),
+            })
+        }
+    }
+    Ok(channels::request_close_run(id))
+}
+
+/// Table of passed runs.
+pub fn render(runs: &[RunInfo]) -> String {
+    if runs.is_empty() {
+        return String::from("No active runs");
+    }
+
+    let now = Utc::now();
+    let mut lines = vec![format!("{} active runs", runs.len())];
+    for run in runs {
+        let age = (now - run.started).num_seconds();
+        let state = match run.cancelled {
+            true => " [cancelling]",
+            false => "",
+        };
+        lines.push(format!(
+            "  #{} {}/{} hosts ({age}s): {}{state}",
+            run.id, run.done, run.total, run.description
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::channels::{ChannelGuard, ChannelKind};
+    use serial_test::serial;
+
+    #[serial]
+    #[test]
+    fn test_cancel_run_closes_its_channels() {
+        let run = RunGuard::start("fleet exec 'sleep 100' on web", 3);
+        run.host_done();
+        let channel = ChannelGuard::open("a@web2", ChannelKind::Exec, "sleep 100");
+        assert_eq!(current(), Some(run.id()));
+        assert!(render(&list()).contains(&format!(
+            "#{} 1/3 hosts (0s): fleet exec 'sleep 100' on web",
+            run.id()
+        )));
+
+        assert_eq!(cancel(run.id()).unwrap(), 1);
+        assert!(run.is_cancelled());
+        assert!(channel.check().is_err());
+        assert!(render(&list()).ends_with("[cancelling]"));
+
+        let id = run.id();
+        std::mem::drop(run);
+        assert_eq!(current(), None);
+        assert!(list().iter().all(|r| r.id != id));
+    }
+
+    #[serial]
+    #[test]
+    fn test_channels_of_other_threads_do_not_belong_to_run() {
+        let run = RunGuard::start("fleet exec 'uptime' on db", 1);
+        let aside = std::thread::spawn(|| {
+            let channel = ChannelGuard::open("b@db1", ChannelKind::Scp, "backup.tar");
+            (current(), channel)
+        });
+        let (aside_run, channel) = aside.join().unwrap();
+
+        assert_eq!(aside_run, None);
+        assert_eq!(cancel(run.id()).unwrap(), 0);
+        assert!(channel.check().is_ok());
+    }
+
+    #[serial]
+    #[test]
+    fn test_cancel_unknown_run() {
+        let err = cancel(u64::MAX).err().unwrap();
+
+        assert_eq!(
+            err.message,
+            format!("There is no active run with id {}", u64::MAX)
+        );
+        assert_eq!(render(&[]), "No active runs");
+    }
+}
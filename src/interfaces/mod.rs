@@ -1,3 +1,4 @@
+pub mod output;
 pub mod parser;
 pub mod progress_bar;
 pub mod response;
@@ -0,0 +1,200 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Destination of human readable output (results, real-time output of
+/// commands, logs). Library does not print anything by default - CLI
+/// sets `Console`, embedding applications can redirect it to own writer.
+pub enum OutputSink {
+    /// Output is dropped.
+    Silent,
+    /// Output goes to stdout, errors go to stderr.
+    Console,
+    /// Output and errors go to passed writer.
+    Writer(Box<dyn Write + Send>),
+}
+
+static SINK: Mutex<OutputSink> = Mutex::new(OutputSink::Silent);
+
+/// Capture of text passed to `write` by one thread - only the first `max`
+/// bytes reach sink, whole text is written to `copy`.
+struct Capture {
+    max: u64,
+    copy: Box<dyn Write + Send>,
+    captured: Captured,
+}
+
+/// Summary of capture returned by `release`.
+/// - written: number of bytes passed to `write`
+/// - complete_line: whether text which reached sink ends with new line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Captured {
+    pub written: u64,
+    pub complete_line: bool,
+}
+
+thread_local! {
+    /// Capture started by this thread. Output of other threads (e.g.
+    /// operations running aside in background process) is not affected.
+    static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+/// Sets destination of all further output.
+pub fn set_sink(sink: OutputSink) {
+    *SINK.lock().unwrap() = sink;
+}
+
+/// Limits text written by `write` of the current thread to `max` bytes
+/// until `release`. The whole text is written to `copy`.
+pub fn capture(max: u64, copy: Box<dyn Write + Send>) {
+    CAPTURE.set(Some(Capture {
+        max,
+        copy,
+        captured: Captured {
+            written: 0,
+            complete_line: true,
+        },
+    }));
+}
+
+/// Stops capture started by `capture` (in the current thread).
+pub fn release() -> Captured {
+    match CAPTURE.take() {
+        Some(mut capture) => {
+            let _ = capture.copy.flush();
+            capture.captured
+        }
+        None => Captured {
+            written: 0,
+            complete_line: true,
+        },
+    }
+}
+
+/// Part of text which fits into capture (the whole one without capture).
+fn captured(text: &str) -> &str {
+    CAPTURE.with_borrow_mut(|capture| {
+        let Some(capture) = capture.as_mut() else {
+            return text;
+        };
+        let _ = capture.copy.write_all(text.as_bytes());
+
+        let left = capture.max.saturating_sub(capture.captured.written);
+        capture.captured.written += text.len() as u64;
+        let mut end = (left as usize).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let text = &text[..end];
+        if !text.is_empty() {
+            capture.captured.complete_line = text.ends_with('\n');
+        }
+        text
+    })
+}
+
+/// Writes text as it is (without new line) and flushes it immediately.
+pub fn write(text: &str) {
+    let text = captured(text);
+    if text.is_empty() {
+        return;
+    }
+    match &mut *SINK.lock().unwrap() {
+        OutputSink::Silent => {}
+        OutputSink::Console => {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(text.as_bytes());
+            let _ = stdout.flush();
+        }
+        OutputSink::Writer(writer) => {
+            let _ = writer.write_all(text.as_bytes());
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Writes a single line of output.
+pub fn write_line(text: &str) {
+    write(&format!("{text}\n"));
+}
+
+/// Writes a single line of error (stderr in console).
+pub fn write_err_line(text: &str) {
+    match &mut *SINK.lock().unwrap() {
+        OutputSink::Silent => {}
+        OutputSink::Console => eprintln!("{text}"),
+        OutputSink::Writer(writer) => {
+            let _ = writeln!(writer, "{text}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::sync::Arc;
+
+    /// Writer which shares written data with test.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn content(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_redirect_output_to_writer() {
+        let buffer = Buffer::default();
+        set_sink(OutputSink::Writer(Box::new(buffer.clone())));
+
+        write("a");
+        write_line("b");
+        write_err_line("c");
+        set_sink(OutputSink::Silent);
+
+        assert_eq!(buffer.content(), "ab\nc\n");
+    }
+
+    #[test]
+    #[serial]
+    fn test_capture_only_output_of_its_thread() {
+        let buffer = Buffer::default();
+        let copy = Buffer::default();
+        set_sink(OutputSink::Writer(Box::new(buffer.clone())));
+
+        capture(3, Box::new(copy.clone()));
+        write("abcdef");
+        std::thread::spawn(|| write_line("aside")).join().unwrap();
+        let captured = release();
+        set_sink(OutputSink::Silent);
+
+        assert_eq!(buffer.content(), "abcaside\n");
+        assert_eq!(copy.content(), "abcdef");
+        assert_eq!(captured.written, 6);
+    }
+
+    #[test]
+    #[serial]
+    fn test_silent_output() {
+        let buffer = Buffer::default();
+        set_sink(OutputSink::Writer(Box::new(buffer.clone())));
+        set_sink(OutputSink::Silent);
+
+        write_line("dropped");
+
+        assert_eq!(buffer.content(), "");
+    }
+}
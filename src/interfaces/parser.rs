@@ -1,8 +1,131 @@
-use crate::error::CrustError;
+use crate::error::{CrustError, ExitCode};
 
 /// Interface for custom parsers validation.
 /// Allows to check complex arguments or
 /// transformates some args to others.
 pub trait Validation {
     fn validate(&mut self) -> Result<(), CrustError>;
+
+    /// Collects all problems of arguments instead of stopping at the first
+    /// one (see `validate_all`). By default it is the first problem found
+    /// by `validate`.
+    fn problems(&mut self) -> Vec<Problem> {
+        match self.validate() {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![Problem::from(e)],
+        }
+    }
+}
+
+/// Single problem of arguments.
+/// - context: part of command which is invalid (e.g. destination machine)
+/// - hint: suggestion how to fix it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub code: ExitCode,
+    pub context: Option<String>,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl Problem {
+    pub fn new(message: &str) -> Self {
+        Self {
+            code: ExitCode::Parser,
+            context: None,
+            message: message.to_string(),
+            hint: None,
+        }
+    }
+
+    /// Sets part of command which is invalid (kept if it was already set,
+    /// so the most specific one wins).
+    pub fn context(mut self, context: &str) -> Self {
+        self.context.get_or_insert_with(|| context.to_string());
+        self
+    }
+
+    /// Sets suggestion how to fix problem.
+    pub fn hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+}
+
+impl From<CrustError> for Problem {
+    fn from(error: CrustError) -> Self {
+        Self {
+            code: error.code,
+            ..Problem::new(&error.message)
+        }
+    }
+}
+
+impl From<Problem> for CrustError {
+    fn from(problem: Problem) -> Self {
+        CrustError {
+            code: problem.code,
+            message: problem.message,
+        }
+    }
+}
+
+/// Validates arguments reporting all their problems (with hints) in a
+/// single error.
+/// # Example
+/// ```
+/// use crust::error::CrustError;
+/// use crust::interfaces::parser::{validate_all, Problem, Validation};
+///
+/// struct Ports(Vec<u32>);
+///
+/// impl Validation for Ports {
+///     fn validate(&mut self) -> Result<(), CrustError> {
+///         match self.problems().into_iter().next() {
+///             Some(problem) => Err(problem.into()),
+///             None => Ok(()),
+///         }
+///     }
+///
+///     fn problems(&mut self) -> Vec<Problem> {
+///         self.0
+///             .iter()
+///             .filter(|port| **port > 65535)
+///             .map(|port| Problem::new(&format!("Invalid port {port}")).hint("use 1-65535"))
+///             .collect()
+///     }
+/// }
+///
+/// let err = validate_all(&mut Ports(vec![22, 70000, 80000])).err().unwrap();
+/// assert_eq!(
+///     err.message,
+///     "Found 2 problems with arguments:\n\
+///      - Invalid port 70000\n    hint: use 1-65535\n\
+///      - Invalid port 80000\n    hint: use 1-65535"
+/// );
+/// ```
+pub fn validate_all(args: &mut impl Validation) -> Result<(), CrustError> {
+    let problems = args.problems();
+    let Some(first) = problems.first() else {
+        return Ok(());
+    };
+
+    let noun = match problems.len() {
+        1 => "problem",
+        _ => "problems",
+    };
+    let mut lines = vec![format!("Found {} {noun} with arguments:", problems.len())];
+    for problem in &problems {
+        match &problem.context {
+            Some(context) => lines.push(format!("- {context}: {}", problem.message)),
+            None => lines.push(format!("- {}", problem.message)),
+        }
+        if let Some(hint) = &problem.hint {
+            lines.push(format!("    hint: {hint}"));
+        }
+    }
+    Err(CrustError {
+        code: first.code.clone(),
+        message: lines.join("\n"),
+    })
 }
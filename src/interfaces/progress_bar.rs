@@ -1,7 +1,26 @@
+use std::sync::OnceLock;
+
 use indicatif;
 
+/// Bars of all transfers (from every thread) are drawn together, so they
+/// do not overwrite each other and logs can be written above them.
+static BARS: OnceLock<indicatif::MultiProgress> = OnceLock::new();
+
+fn bars() -> &'static indicatif::MultiProgress {
+    BARS.get_or_init(indicatif::MultiProgress::new)
+}
+
+/// Runs `write` with active progress bars cleared from terminal - lines
+/// written by it stay above bars, which are drawn again afterwards.
+/// Bars are not touched when nothing is written by `write`.
+pub fn suspend<R>(write: impl FnOnce() -> R) -> R {
+    bars().suspend(write)
+}
+
 /// Wrapper to indicatif::ProgressBar.
+/// Clones refer to the same bar, so it can be shared between threads.
 /// TODO?: add customization
+#[derive(Clone)]
 pub struct ProgressBar {
     pb: indicatif::ProgressBar,
 }
@@ -9,7 +28,7 @@ pub struct ProgressBar {
 impl ProgressBar {
     /// Creates a new progress bar with configured styles.
     pub fn new(size: u64) -> Self {
-        let pb = indicatif::ProgressBar::new(size);
+        let pb = bars().add(indicatif::ProgressBar::new(size));
         pb.set_style(
             indicatif::ProgressStyle::with_template(
                 "[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})",
@@ -36,3 +55,88 @@ impl ProgressBar {
         self.pb.finish();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use indicatif::{ProgressDrawTarget, TermLike};
+
+    use super::*;
+
+    /// Terminal which records written lines and drawn bars.
+    #[derive(Debug, Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl TermLike for Recorder {
+        fn width(&self) -> u16 {
+            80
+        }
+
+        fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn write_line(&self, s: &str) -> io::Result<()> {
+            self.write_str(s)
+        }
+
+        fn write_str(&self, s: &str) -> io::Result<()> {
+            if !s.is_empty() {
+                self.0.lock().unwrap().push(s.to_string());
+            }
+            Ok(())
+        }
+
+        fn clear_line(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_suspend_bars_updated_by_threads() {
+        let term = Recorder::default();
+        bars().set_draw_target(ProgressDrawTarget::term_like(Box::new(term.clone())));
+        let bar = ProgressBar::new(1000);
+        let workers = (0..4)
+            .map(|_| {
+                let bar = bar.clone();
+                let term = term.clone();
+                std::thread::spawn(move || {
+                    (0..50).for_each(|i| {
+                        bar.inc(5);
+                        suspend(|| term.write_line(&format!("log {i}")).unwrap());
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        workers.into_iter().for_each(|w| w.join().unwrap());
+        // Bar is drawn again below the line written while it was suspended
+        suspend(|| term.write_line("last log").unwrap());
+        let lines = term.0.lock().unwrap().clone();
+        bar.finish();
+        bars().set_draw_target(ProgressDrawTarget::hidden());
+
+        let log = lines.iter().position(|l| l == "last log").unwrap();
+        assert!(lines[log + 1..].iter().any(|l| l.contains("B/1000 B")));
+        assert_eq!(lines.iter().filter(|l| l.starts_with("log ")).count(), 200);
+        assert_eq!(bar.pb.position(), 1000);
+    }
+}
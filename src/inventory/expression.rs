@@ -0,0 +1,259 @@
+use std::collections::BTreeSet;
+
+use crate::error::{CrustError, ExitCode};
+use crate::inventory::Inventory;
+
+/// Parsed target expression. Supported syntax:
+/// - `name` - host or group (with nested groups) from inventory
+/// - `tag:name` - hosts with a given tag
+/// - `all` - every host from inventory
+/// - `a and b`, `a or b`, `not a` and parentheses (`not` binds the
+///   strongest, then `and`, then `or`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    All,
+    Name(String),
+    Tag(String),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Word(String),
+}
+
+fn parse_error(message: String) -> CrustError {
+    CrustError {
+        code: ExitCode::Parser,
+        message: format!("Invalid target expression: {message}"),
+    }
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let flush = |word: &mut String, tokens: &mut Vec<Token>| {
+        if !word.is_empty() {
+            tokens.push(match word.as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "not" => Token::Not,
+                _ => Token::Word(word.clone()),
+            });
+            word.clear();
+        }
+    };
+
+    for c in input.chars() {
+        match c {
+            '(' | ')' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(match c {
+                    '(' => Token::LeftParen,
+                    _ => Token::RightParen,
+                });
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut tokens),
+            c => word.push(c),
+        }
+    }
+    flush(&mut word, &mut tokens);
+    tokens
+}
+
+/// Recursive descent parser over tokens.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expression, CrustError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expression::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expression, CrustError> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expression::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expression, CrustError> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expression::Not(Box::new(self.unary()?))),
+            Some(Token::LeftParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(expr),
+                    _ => Err(parse_error(String::from("missing ')'"))),
+                }
+            }
+            Some(Token::Word(word)) => match word.split_once(':') {
+                Some(("tag", tag)) if !tag.is_empty() => Ok(Expression::Tag(tag.to_string())),
+                Some(_) => Err(parse_error(format!("unknown selector '{word}'"))),
+                None if word == "all" => Ok(Expression::All),
+                None => Ok(Expression::Name(word)),
+            },
+            Some(token) => Err(parse_error(format!("unexpected {token:?}"))),
+            None => Err(parse_error(String::from("unexpected end"))),
+        }
+    }
+}
+
+impl Expression {
+    /// Parses expression from text, e.g. `web and not tag:canary`.
+    pub fn parse(input: &str) -> Result<Self, CrustError> {
+        let mut parser = Parser {
+            tokens: tokenize(input),
+            pos: 0,
+        };
+        let expr = parser.or()?;
+
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(parse_error(format!("unexpected {token:?}"))),
+        }
+    }
+
+    /// Computes names of hosts matching expression.
+    pub fn evaluate(&self, inventory: &Inventory) -> Result<BTreeSet<String>, CrustError> {
+        Ok(match self {
+            Expression::All => inventory.all_hosts(),
+            Expression::Tag(tag) => inventory.tagged(tag),
+            Expression::Name(name) => match inventory.groups.contains_key(name) {
+                true => inventory.group_members(name)?,
+                false if inventory.hosts.contains_key(name) => BTreeSet::from([name.clone()]),
+                false => {
+                    return Err(CrustError {
+                        code: ExitCode::Parser,
+                        message: format!("Unknown host or group '{name}'"),
+                    })
+                }
+            },
+            Expression::Not(expr) => inventory
+                .all_hosts()
+                .difference(&expr.evaluate(inventory)?)
+                .cloned()
+                .collect(),
+            Expression::And(left, right) => left
+                .evaluate(inventory)?
+                .intersection(&right.evaluate(inventory)?)
+                .cloned()
+                .collect(),
+            Expression::Or(left, right) => left
+                .evaluate(inventory)?
+                .union(&right.evaluate(inventory)?)
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory() -> Inventory {
+        Inventory::from_yaml(
+            "
+hosts:
+  web-1: {addr: a@web1, tags: [ssd]}
+  web-2: {addr: a@web2, tags: [ssd, canary]}
+  db-1: {addr: a@db1}
+groups:
+  web: {hosts: [web-1, web-2]}
+  canary: {hosts: [web-2]}
+  prod: {children: [web], hosts: [db-1]}
+",
+        )
+        .unwrap()
+    }
+
+    fn resolve(target: &str) -> Vec<String> {
+        inventory().resolve(target).unwrap()
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let expr = Expression::parse("a or not b and c").unwrap();
+
+        assert_eq!(
+            expr,
+            Expression::Or(
+                Box::new(Expression::Name(String::from("a"))),
+                Box::new(Expression::And(
+                    Box::new(Expression::Not(Box::new(Expression::Name(String::from(
+                        "b"
+                    ))))),
+                    Box::new(Expression::Name(String::from("c")))
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (input, message) in [
+            ("", "unexpected end"),
+            ("(web", "missing ')'"),
+            ("web and", "unexpected end"),
+            ("web db", "unexpected Word(\"db\")"),
+            ("group:web", "unknown selector 'group:web'"),
+            ("tag:", "unknown selector 'tag:'"),
+        ] {
+            let err = Expression::parse(input).err().unwrap();
+            assert_eq!(err.code, ExitCode::Parser);
+            assert_eq!(err.message, format!("Invalid target expression: {message}"));
+        }
+    }
+
+    #[test]
+    fn test_resolve_group_and_not() {
+        assert_eq!(resolve("web and not canary"), vec!["web-1"]);
+    }
+
+    #[test]
+    fn test_resolve_tag() {
+        assert_eq!(resolve("tag:ssd"), vec!["web-1", "web-2"]);
+        assert_eq!(resolve("tag:hdd"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_resolve_nested_group_with_parentheses() {
+        assert_eq!(resolve("prod and not (web and tag:ssd)"), vec!["db-1"]);
+        assert_eq!(resolve("db-1 or web-2"), vec!["db-1", "web-2"]);
+        assert_eq!(resolve("all"), vec!["db-1", "web-1", "web-2"]);
+    }
+
+    #[test]
+    fn test_resolve_unknown_name() {
+        let err = inventory().resolve("web or cache").err().unwrap();
+
+        assert_eq!(err.message, "Unknown host or group 'cache'");
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::connection::endpoint::Endpoint;
+use crate::connection::request::RemoteTarget;
+use crate::error::{CrustError, ExitCode};
+
+pub mod expression;
+pub mod parser;
+pub mod registry;
+
+use expression::Expression;
+
+fn default_port() -> u16 {
+    22
+}
+
+/// Single machine described in inventory. Default directory, umask and
+/// strict mode are settings of machine applied to every command (flags
+/// passed with command override them).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostEntry {
+    pub addr: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkey: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub umask: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+/// Named group of hosts. Group can contain other groups (children),
+/// so e.g. `prod` can be built from `web` and `db` groups.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupEntry {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<String>,
+}
+
+/// Set of known machines with their groups and tags, stored in yaml file.
+/// # Example
+/// ```yaml
+/// hosts:
+///   web-1:
+///     addr: deploy@10.0.0.1
+///     pkey: /home/deploy/.ssh/id_rsa
+///     tags: [ssd, canary]
+///     cwd: /srv/app
+///     strict: true
+/// groups:
+///   web:
+///     hosts: [web-1]
+///   prod:
+///     children: [web]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostEntry>,
+    #[serde(default)]
+    pub groups: BTreeMap<String, GroupEntry>,
+}
+
+impl Inventory {
+    /// Default location of inventory: `$CRUST_INVENTORY`,
+    /// `$XDG_CONFIG_HOME/crust/inventory.yaml` or
+    /// `$HOME/.config/crust/inventory.yaml` (in this order).
+    pub fn default_path() -> PathBuf {
+        if let Ok(path) = std::env::var("CRUST_INVENTORY") {
+            return PathBuf::from(path);
+        }
+        if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
+            return PathBuf::from(dir).join("crust").join("inventory.yaml");
+        }
+        let home = std::env::var("HOME").unwrap_or_else(|_| String::from("/tmp"));
+        PathBuf::from(home)
+            .join(".config")
+            .join("crust")
+            .join("inventory.yaml")
+    }
+
+    /// Reads inventory from yaml file. Groups are checked up-front (see
+    /// `validate`).
+    pub fn load(path: &Path) -> Result<Self, CrustError> {
+        let content = std::fs::read_to_string(path).map_err(|e| CrustError {
+            code: ExitCode::Local,
+            message: format!("Can not read inventory '{}': {e}", path.display()),
+        })?;
+        let inventory = Self::from_yaml(&content)?;
+        inventory.validate()?;
+        Ok(inventory)
+    }
+
+    /// Parses inventory from yaml content.
+    pub fn from_yaml(content: &str) -> Result<Self, CrustError> {
+        serde_yaml::from_str(content).map_err(|e| CrustError {
+            code: ExitCode::Parser,
+            message: format!("Invalid inventory: {e}"),
+        })
+    }
+
+    /// Names of all hosts in inventory.
+    pub fn all_hosts(&self) -> BTreeSet<String> {
+        self.hosts.keys().cloned().collect()
+    }
+
+    /// Names of hosts tagged with passed tag.
+    pub fn tagged(&self, tag: &str) -> BTreeSet<String> {
+        self.hosts
+            .iter()
+            .filter(|(_, host)| host.tags.iter().any(|t| t == tag))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Hostname (taken from address) of inventory host.
+    pub fn hostname(&self, name: &str) -> Result<String, CrustError> {
+        let host = self.hosts.get(name).ok_or_else(|| CrustError {
+            code: ExitCode::Parser,
+            message: format!("Unknown host '{name}'"),
+        })?;
+        Endpoint::parse_address(&host.addr)?
+            .host
+            .ok_or_else(|| CrustError {
+                code: ExitCode::Parser,
+                message: format!("No hostname in address of host '{name}'"),
+            })
+    }
+
+    /// Checks whether every group refers only to known hosts and groups
+    /// and has no cycles.
+    pub fn validate(&self) -> Result<(), CrustError> {
+        for group in self.groups.keys() {
+            self.group_members(group)?;
+        }
+        Ok(())
+    }
+
+    /// Names of hosts which belong to group (directly or via children).
+    pub fn group_members(&self, group: &str) -> Result<BTreeSet<String>, CrustError> {
+        let mut members = BTreeSet::new();
+        self.collect_members(group, &mut Vec::new(), &mut members)?;
+        Ok(members)
+    }
+
+    fn collect_members(
+        &self,
+        group: &str,
+        path: &mut Vec<String>,
+        members: &mut BTreeSet<String>,
+    ) -> Result<(), CrustError> {
+        if path.iter().any(|g| g == group) {
+            path.push(group.to_string());
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!("Cycle in inventory groups: {}", path.join(" -> ")),
+            });
+        }
+
+        let entry = self.groups.get(group).ok_or_else(|| CrustError {
+            code: ExitCode::Parser,
+            message: format!("Unknown group '{group}'"),
+        })?;
+
+        for host in &entry.hosts {
+            if !self.hosts.contains_key(host) {
+                return Err(CrustError {
+                    code: ExitCode::Parser,
+                    message: format!("Group '{group}' refers to unknown host '{host}'"),
+                });
+            }
+            members.insert(host.clone());
+        }
+
+        path.push(group.to_string());
+        for child in &entry.children {
+            self.collect_members(child, path, members)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
+    /// Resolves target expression (e.g. `web and not tag:canary`) into
+    /// sorted names of matching hosts.
+    pub fn resolve(&self, target: &str) -> Result<Vec<String>, CrustError> {
+        let hosts = Expression::parse(target)?.evaluate(self)?;
+        Ok(hosts.into_iter().collect())
+    }
+
+    /// Creates a connection target of host. Host name is used as alias,
+    /// so machine is reused by manager in background mode.
+    pub fn target(&self, name: &str) -> Result<RemoteTarget, CrustError> {
+        let host = self.hosts.get(name).ok_or_else(|| CrustError {
+            code: ExitCode::Parser,
+            message: format!("Unknown host '{name}'"),
+        })?;
+
+        let mut target = RemoteTarget::new(&host.addr).port(host.port).alias(name);
+        if let Some(password) = &host.password {
+            target = target.password(password);
+        }
+        if let Some(pkey) = &host.pkey {
+            target = target.pkey(pkey);
+        }
+        if let Some(cwd) = &host.cwd {
+            target = target.cwd(cwd);
+        }
+        if let Some(umask) = &host.umask {
+            target = target.umask(umask);
+        }
+        if let Some(strict) = host.strict {
+            target = target.strict(strict);
+        }
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub const INVENTORY: &str = "
+hosts:
+  web-1:
+    addr: deploy@10.0.0.1
+    password: '1234'
+    tags: [ssd]
+  web-2:
+    addr: deploy@10.0.0.2
+    port: 2222
+    pkey: /id_rsa
+    tags: [ssd, canary]
+    cwd: /srv/app
+    umask: '027'
+    strict: true
+  db-1:
+    addr: postgres@10.0.1.1
+    password: '1234'
+groups:
+  web:
+    hosts: [web-1, web-2]
+  db:
+    hosts: [db-1]
+  prod:
+    children: [web, db]
+";
+
+    #[test]
+    fn test_parse_inventory() {
+        let inventory = Inventory::from_yaml(INVENTORY).unwrap();
+
+        assert_eq!(inventory.hosts.len(), 3);
+        assert_eq!(inventory.hosts["web-1"].port, 22);
+        assert_eq!(inventory.hosts["web-2"].port, 2222);
+        assert_eq!(inventory.groups["prod"].children, vec!["web", "db"]);
+    }
+
+    #[test]
+    fn test_parse_invalid_inventory() {
+        let err = Inventory::from_yaml("hosts: [a, b]").err().unwrap();
+
+        assert_eq!(err.code, ExitCode::Parser);
+        assert!(err.message.starts_with("Invalid inventory: "));
+    }
+
+    #[test]
+    fn test_nested_group_members() {
+        let inventory = Inventory::from_yaml(INVENTORY).unwrap();
+
+        assert_eq!(
+            inventory.group_members("prod").unwrap(),
+            BTreeSet::from([
+                String::from("db-1"),
+                String::from("web-1"),
+                String::from("web-2")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_group_cycle() {
+        let mut inventory = Inventory::from_yaml(INVENTORY).unwrap();
+        inventory
+            .groups
+            .get_mut("web")
+            .unwrap()
+            .children
+            .push(String::from("prod"));
+
+        let err = inventory.group_members("prod").err().unwrap();
+        assert_eq!(
+            err.message,
+            "Cycle in inventory groups: prod -> web -> prod"
+        );
+    }
+
+    #[test]
+    fn test_group_with_unknown_host() {
+        let mut inventory = Inventory::from_yaml(INVENTORY).unwrap();
+        inventory
+            .groups
+            .get_mut("db")
+            .unwrap()
+            .hosts
+            .push(String::from("db-2"));
+
+        let err = inventory.group_members("db").err().unwrap();
+        assert_eq!(err.message, "Group 'db' refers to unknown host 'db-2'");
+        assert_eq!(inventory.validate().err().unwrap().message, err.message);
+    }
+
+    #[test]
+    fn test_host_target() {
+        let inventory = Inventory::from_yaml(INVENTORY).unwrap();
+
+        assert_eq!(
+            inventory.target("web-2").unwrap(),
+            RemoteTarget::new("deploy@10.0.0.2")
+                .port(2222)
+                .alias("web-2")
+                .pkey("/id_rsa")
+                .cwd("/srv/app")
+                .umask("027")
+                .strict(true)
+        );
+        assert!(inventory.target("web-3").is_err());
+    }
+
+    #[test]
+    fn test_host_hostname() {
+        let inventory = Inventory::from_yaml(INVENTORY).unwrap();
+
+        assert_eq!(inventory.hostname("web-2").unwrap(), "10.0.0.2");
+        assert!(inventory.hostname("web-3").is_err());
+    }
+}
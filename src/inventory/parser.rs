@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+use crate::inventory::registry::ConflictStrategy;
+
+#[derive(Debug, Clone, Args)]
+pub struct MachineArgs {
+    #[clap(subcommand)]
+    pub action: MachineAction,
+}
+
+impl Validation for MachineArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum MachineAction {
+    /// Exports registry of machines (inventory) to share it with a team
+    Export(ExportArgs),
+
+    /// Imports machines from shared registry into local inventory
+    Import(ImportArgs),
+
+    /// Lists open SSH channels (and force-closes stuck ones via control
+    /// pipe of background process)
+    Channels(ChannelsArgs),
+
+    /// Locks inventory machine of background process (drops its connections), so it
+    /// can not be used until it is unlocked. Machines tagged `sensitive`
+    /// are locked automatically after `--idle-lock` minutes without use
+    Lock(LockArgs),
+
+    /// Unlocks machine after re-authentication (asks for password)
+    Unlock(LockArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportArgs {
+    /// Output file (registry is printed when not passed)
+    #[clap(long)]
+    pub file: Option<PathBuf>,
+
+    /// Skip passwords and private keys (they stay per-user)
+    #[clap(long, default_value = "false")]
+    pub no_secrets: bool,
+
+    /// Path to inventory file (default: $CRUST_INVENTORY or
+    /// ~/.config/crust/inventory.yaml)
+    #[clap(long)]
+    pub inventory: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ChannelsArgs {
+    /// Id of channel to force-close
+    #[clap(long, value_name = "ID")]
+    pub close: Option<u64>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct LockArgs {
+    /// Name of machine in inventory
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ImportArgs {
+    /// Shared registry to import
+    pub file: PathBuf,
+
+    /// What to do with hosts defined differently in both registries
+    #[clap(long, value_enum, default_value = "fail")]
+    pub on_conflict: ConflictStrategy,
+
+    /// Path to inventory file (default: $CRUST_INVENTORY or
+    /// ~/.config/crust/inventory.yaml)
+    #[clap(long)]
+    pub inventory: Option<PathBuf>,
+}
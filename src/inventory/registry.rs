@@ -0,0 +1,324 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::response::CrustResult;
+use crate::inventory::{HostEntry, Inventory};
+
+/// What to do with imported host which is already defined differently.
+/// - Fail: nothing is imported, conflicts are reported
+/// - Keep: local definition is kept
+/// - Overwrite: imported definition replaces local one (local secrets are kept)
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ConflictStrategy {
+    Fail,
+    Keep,
+    Overwrite,
+}
+
+/// Changes made (or which would be made) by import.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub secrets_dropped: Vec<String>,
+}
+
+impl ImportSummary {
+    /// Human readable list of changes.
+    pub fn render(&self) -> String {
+        let mut lines = vec![format!(
+            "Added {}, updated {}, unchanged {}, conflicts {}",
+            self.added.len(),
+            self.updated.len(),
+            self.unchanged.len(),
+            self.conflicts.len()
+        )];
+        for (label, hosts) in [
+            ("added", &self.added),
+            ("updated", &self.updated),
+            ("conflict", &self.conflicts),
+            ("secrets dropped", &self.secrets_dropped),
+        ] {
+            lines.extend(hosts.iter().map(|host| format!("  {label}: {host}")));
+        }
+        lines.join("\n")
+    }
+}
+
+impl From<ImportSummary> for CrustResult {
+    fn from(summary: ImportSummary) -> Self {
+        CrustResult::new(&summary.render(), "", 0)
+    }
+}
+
+/// Compares only shareable part of hosts (without secrets).
+fn same_definition(a: &HostEntry, b: &HostEntry) -> bool {
+    a.addr == b.addr
+        && a.port == b.port
+        && a.tags == b.tags
+        && a.cwd == b.cwd
+        && a.umask == b.umask
+        && a.strict == b.strict
+}
+
+impl Inventory {
+    /// Copy of inventory which can be shared with a team - passwords
+    /// and paths of private keys stay with the user.
+    pub fn without_secrets(&self) -> Inventory {
+        let mut shared = self.clone();
+        for host in shared.hosts.values_mut() {
+            host.password = None;
+            host.pkey = None;
+        }
+        shared
+    }
+
+    /// Serializes inventory into yaml.
+    pub fn to_yaml(&self) -> Result<String, CrustError> {
+        serde_yaml::to_string(self).map_err(|e| CrustError {
+            code: ExitCode::Internal,
+            message: format!("Can not serialize inventory: {e}"),
+        })
+    }
+
+    /// Writes inventory into yaml file (parent directories are created).
+    pub fn save(&self, path: &Path) -> Result<(), CrustError> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_yaml()?).map_err(|e| CrustError {
+            code: ExitCode::Local,
+            message: format!("Can not write inventory '{}': {e}", path.display()),
+        })
+    }
+
+    /// Merges other (e.g. team) inventory into this one. Groups are merged
+    /// by their members; hosts defined differently on both sides are
+    /// resolved with passed strategy. With `Fail`, inventory is not changed
+    /// when there is any conflict. Secrets of imported hosts are never
+    /// taken - passwords and keys stay local - and inventory is not changed
+    /// when merged groups are invalid (see `validate`).
+    pub fn import(
+        &mut self,
+        other: &Inventory,
+        strategy: ConflictStrategy,
+    ) -> Result<ImportSummary, CrustError> {
+        let mut summary = ImportSummary::default();
+        let mut merged = self.clone();
+
+        for (name, host) in &other.hosts {
+            match merged.hosts.get_mut(name) {
+                None => {
+                    let mut host = host.clone();
+                    let (password, pkey) = (host.password.take(), host.pkey.take());
+                    if password.is_some() || pkey.is_some() {
+                        log::warn!("Secrets of imported host '{name}' are not imported");
+                        summary.secrets_dropped.push(name.clone());
+                    }
+                    merged.hosts.insert(name.clone(), host);
+                    summary.added.push(name.clone());
+                }
+                Some(local) if same_definition(local, host) => summary.unchanged.push(name.clone()),
+                Some(local) => match strategy {
+                    ConflictStrategy::Overwrite => {
+                        local.addr = host.addr.clone();
+                        local.port = host.port;
+                        local.tags = host.tags.clone();
+                        local.cwd = host.cwd.clone();
+                        local.umask = host.umask.clone();
+                        local.strict = host.strict;
+                        summary.updated.push(name.clone());
+                    }
+                    ConflictStrategy::Keep | ConflictStrategy::Fail => {
+                        summary.conflicts.push(name.clone())
+                    }
+                },
+            }
+        }
+
+        if strategy == ConflictStrategy::Fail && !summary.conflicts.is_empty() {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!(
+                    "Hosts defined differently in imported inventory: {}. Use --on-conflict keep or overwrite",
+                    summary.conflicts.join(", ")
+                ),
+            });
+        }
+
+        for (name, group) in &other.groups {
+            let local = merged.groups.entry(name.clone()).or_default();
+            for host in &group.hosts {
+                if !local.hosts.contains(host) {
+                    local.hosts.push(host.clone());
+                }
+            }
+            for child in &group.children {
+                if !local.children.contains(child) {
+                    local.children.push(child.clone());
+                }
+            }
+        }
+
+        merged.validate().map_err(|e| CrustError {
+            code: e.code,
+            message: format!("Imported inventory can not be merged: {}", e.message),
+        })?;
+        *self = merged;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::GroupEntry;
+
+    const LOCAL: &str = "
+hosts:
+  web-1:
+    addr: deploy@10.0.0.1
+    password: '1234'
+  db-1:
+    addr: postgres@10.0.1.1
+    pkey: /home/me/.ssh/id_rsa
+groups:
+  web:
+    hosts: [web-1]
+";
+
+    const TEAM: &str = "
+hosts:
+  web-1:
+    addr: deploy@10.0.0.1
+  web-2:
+    addr: deploy@10.0.0.2
+    tags: [canary]
+  db-1:
+    addr: postgres@10.0.1.10
+    port: 2222
+    cwd: /var/lib/postgresql
+groups:
+  web:
+    hosts: [web-1, web-2]
+";
+
+    #[test]
+    fn test_export_without_secrets() {
+        let inventory = Inventory::from_yaml(LOCAL).unwrap();
+
+        let yaml = inventory.without_secrets().to_yaml().unwrap();
+        assert!(!yaml.contains("1234"));
+        assert!(!yaml.contains("id_rsa"));
+        assert_eq!(
+            Inventory::from_yaml(&yaml).unwrap().hosts["db-1"].addr,
+            "postgres@10.0.1.1"
+        );
+    }
+
+    #[test]
+    fn test_import_with_conflict_fails() {
+        let mut inventory = Inventory::from_yaml(LOCAL).unwrap();
+        let before = inventory.clone();
+
+        let err = inventory
+            .import(&Inventory::from_yaml(TEAM).unwrap(), ConflictStrategy::Fail)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message,
+            "Hosts defined differently in imported inventory: db-1. Use --on-conflict keep or overwrite"
+        );
+        assert_eq!(inventory, before);
+    }
+
+    #[test]
+    fn test_import_keep_local_definitions() {
+        let mut inventory = Inventory::from_yaml(LOCAL).unwrap();
+
+        let summary = inventory
+            .import(&Inventory::from_yaml(TEAM).unwrap(), ConflictStrategy::Keep)
+            .unwrap();
+        assert_eq!(summary.added, vec!["web-2"]);
+        assert_eq!(summary.unchanged, vec!["web-1"]);
+        assert_eq!(summary.conflicts, vec!["db-1"]);
+        assert_eq!(inventory.hosts["db-1"].addr, "postgres@10.0.1.1");
+        assert_eq!(
+            inventory.groups["web"],
+            GroupEntry {
+                hosts: vec![String::from("web-1"), String::from("web-2")],
+                children: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_import_overwrite_keeps_secrets() {
+        let mut inventory = Inventory::from_yaml(LOCAL).unwrap();
+
+        let summary = inventory
+            .import(
+                &Inventory::from_yaml(TEAM).unwrap(),
+                ConflictStrategy::Overwrite,
+            )
+            .unwrap();
+        assert_eq!(summary.updated, vec!["db-1"]);
+
+        let db = &inventory.hosts["db-1"];
+        assert_eq!(db.addr, "postgres@10.0.1.10");
+        assert_eq!(db.port, 2222);
+        assert_eq!(db.cwd.as_deref(), Some("/var/lib/postgresql"));
+        assert_eq!(db.pkey, Some("/home/me/.ssh/id_rsa".into()));
+        assert_eq!(
+            summary.render(),
+            "Added 1, updated 1, unchanged 1, conflicts 0\n  added: web-2\n  updated: db-1"
+        );
+    }
+
+    #[test]
+    fn test_import_drops_secrets_of_new_hosts() {
+        let mut inventory = Inventory::from_yaml(LOCAL).unwrap();
+        let team = "
+hosts:
+  web-3:
+    addr: deploy@10.0.0.3
+    password: team-secret
+    pkey: /home/other/.ssh/id_rsa
+";
+
+        let summary = inventory
+            .import(&Inventory::from_yaml(team).unwrap(), ConflictStrategy::Fail)
+            .unwrap();
+
+        let web = &inventory.hosts["web-3"];
+        assert_eq!(web.password, None);
+        assert_eq!(web.pkey, None);
+        assert_eq!(summary.secrets_dropped, vec!["web-3"]);
+        assert!(summary.render().ends_with("  secrets dropped: web-3"));
+    }
+
+    #[test]
+    fn test_import_invalid_groups_fails() {
+        let mut inventory = Inventory::from_yaml(LOCAL).unwrap();
+        let before = inventory.clone();
+        let team = "
+groups:
+  web:
+    hosts: [web-9]
+";
+
+        let err = inventory
+            .import(&Inventory::from_yaml(team).unwrap(), ConflictStrategy::Keep)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message,
+            "Imported inventory can not be merged: Group 'web' refers to unknown host 'web-9'"
+        );
+        assert_eq!(inventory, before);
+    }
+}
@@ -0,0 +1,223 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::error::{CrustError, ExitCode};
+use crate::scp::request::TransferRequest;
+
+pub mod parser;
+pub mod schedule;
+
+use schedule::Schedule;
+
+/// Jobs waiting for their time in the current (background) process.
+static QUEUE: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+
+/// Id of the next scheduled job. Ids are never reused, so `job run <id>`
+/// fired for a removed job can not run another one.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Transfer queued to be run later by background process.
+/// - spec: schedule as passed by user
+/// - next_run: the nearest moment of run
+/// - fired: job was passed to main loop and waits for its turn
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: u64,
+    pub spec: String,
+    pub schedule: Schedule,
+    pub next_run: DateTime<Local>,
+    pub request: TransferRequest,
+    pub fired: bool,
+}
+
+impl Job {
+    /// Short description of scheduled transfer.
+    pub fn describe(&self) -> String {
+        let side = |path: &PathBuf, remote: bool| match remote {
+            true => format!("remote:{}", path.display()),
+            false => path.display().to_string(),
+        };
+        let (src, dst) = (self.request.src(), self.request.dst());
+        format!(
+            "scp {} -> {}",
+            side(&src.path, src.remote.is_some()),
+            side(&dst.path, dst.remote.is_some())
+        )
+    }
+}
+
+/// Queues transfer to be run at moments described by schedule spec.
+pub fn schedule(request: TransferRequest, spec: &str) -> Result<Job, CrustError> {
+    let schedule = spec.parse::<Schedule>()?;
+    let next_run = schedule
+        .next_after(&Local::now())
+        .ok_or_else(|| CrustError {
+            code: ExitCode::Parser,
+            message: format!("Schedule '{spec}' does not occur within a year"),
+        })?;
+
+    let mut queue = QUEUE.lock().unwrap();
+    let job = Job {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        spec: spec.to_string(),
+        schedule,
+        next_run,
+        request,
+        fired: false,
+    };
+    queue.push(job.clone());
+    Ok(job)
+}
+
+/// Gets a copy of pending jobs (sorted by next run).
+pub fn list() -> Vec<Job> {
+    let mut jobs = QUEUE.lock().unwrap().clone();
+    jobs.sort_by_key(|j| j.next_run);
+    jobs
+}
+
+/// Takes fired job to run it. Recurring job stays in queue with
+/// the next moment of run, one-shot job is removed.
+pub fn take(id: u64) -> Result<Job, CrustError> {
+    let mut queue = QUEUE.lock().unwrap();
+    let idx = queue
+        .iter()
+        .position(|j| j.id == id)
+        .ok_or_else(|| CrustError {
+            code: ExitCode::Parser,
+            message: format!("There is no scheduled job with id {id}"),
+        })?;
+
+    let job = queue[idx].clone();
+    match job.schedule.next_after(&Local::now()) {
+        Some(next_run) if job.schedule.is_recurring() => {
+            queue[idx].next_run = next_run;
+            queue[idx].fired = false;
+        }
+        _ => {
+            queue.remove(idx);
+        }
+    }
+    Ok(job)
+}
+
+/// Marks due jobs as fired and returns their ids.
+fn fire_due(now: DateTime<Local>) -> Vec<u64> {
+    QUEUE
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .filter(|j| !j.fired && j.next_run <= now)
+        .map(|j| {
+            j.fired = true;
+            j.id
+        })
+        .collect()
+}
+
+/// Watches queue in a separate thread. Due jobs are passed to the main
+/// loop of background process as `job run <id>` commands written into its
+/// fifo, so they are run with the same machines manager as other commands.
+pub fn start_scheduler(fifo: PathBuf) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(1));
+        for id in fire_due(Local::now()) {
+            log::info!("Scheduled job {id} is due");
+            let written = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&fifo)
+                .and_then(|mut f| writeln!(f, "job run {id}"));
+            if let Err(e) = written {
+                log::error!("Can not pass scheduled job {id} to main loop: {e}");
+            }
+        }
+    });
+}
+
+/// Table of pending jobs.
+pub fn render(jobs: &[Job]) -> String {
+    if jobs.is_empty() {
+        return String::from("No scheduled jobs");
+    }
+
+    let mut lines = vec![format!("{} scheduled jobs", jobs.len())];
+    for job in jobs {
+        lines.push(format!(
+            "  #{} [{}] next run {}: {}",
+            job.id,
+            job.spec,
+            job.next_run.format("%Y-%m-%d %H:%M"),
+            job.describe()
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    fn request() -> TransferRequest {
+        TransferRequest::builder("/data/a", "/backup/a")
+            .build()
+            .unwrap()
+    }
+
+    #[serial]
+    #[test]
+    fn test_schedule_and_run_one_shot_job() {
+        QUEUE.lock().unwrap().clear();
+        let job = schedule(request(), "02:00").unwrap();
+
+        assert!(job.next_run > Local::now());
+        assert!(fire_due(Local::now()).is_empty());
+        assert_eq!(fire_due(job.next_run), vec![job.id]);
+        assert!(fire_due(job.next_run).is_empty());
+
+        assert_eq!(take(job.id).unwrap().id, job.id);
+        assert!(list().is_empty());
+    }
+
+    #[serial]
+    #[test]
+    fn test_recurring_job_stays_in_queue() {
+        QUEUE.lock().unwrap().clear();
+        let job = schedule(request(), "*/5 * * * *").unwrap();
+        fire_due(job.next_run);
+
+        take(job.id).unwrap();
+        let jobs = list();
+        assert_eq!(jobs.len(), 1);
+        assert!(!jobs[0].fired);
+        assert!(render(&jobs).contains("[*/5 * * * *] next run"));
+        assert!(render(&jobs).ends_with("scp /data/a -> /backup/a"));
+    }
+
+    #[serial]
+    #[test]
+    fn test_ids_are_not_reused() {
+        QUEUE.lock().unwrap().clear();
+        let first = schedule(request(), "02:00").unwrap();
+        take(first.id).unwrap();
+
+        let second = schedule(request(), "02:00").unwrap();
+        assert!(second.id > first.id);
+        QUEUE.lock().unwrap().clear();
+    }
+
+    #[serial]
+    #[test]
+    fn test_take_unknown_job() {
+        QUEUE.lock().unwrap().clear();
+
+        let err = take(7).err().unwrap();
+        assert_eq!(err.message, "There is no scheduled job with id 7");
+        assert_eq!(render(&list()), "No scheduled jobs");
+    }
+}
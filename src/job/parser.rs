@@ -0,0 +1,29 @@
+use clap::{Args, Subcommand};
+
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+
+#[derive(Debug, Clone, Args)]
+pub struct JobArgs {
+    #[clap(subcommand)]
+    pub action: JobAction,
+}
+
+impl Validation for JobArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum JobAction {
+    /// Lists pending scheduled jobs
+    List,
+
+    /// Runs scheduled job (used internally by scheduler)
+    #[clap(hide = true)]
+    Run {
+        /// Id of scheduled job
+        id: u64,
+    },
+}
@@ -0,0 +1,224 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Timelike};
+
+use crate::error::{CrustError, ExitCode};
+
+/// Maximal number of minutes searched for the next cron occurrence.
+const SEARCH_LIMIT: i64 = 366 * 24 * 60;
+
+/// When scheduled job should be run.
+/// - At: once, at the nearest given time of day (`HH:MM`)
+/// - Cron: every time matching cron-like spec (`min hour day month weekday`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    At(NaiveTime),
+    Cron(CronSpec),
+}
+
+/// Parsed cron-like spec. Every field contains allowed values.
+/// - either_day: both day of month and day of week are restricted (none
+///   of them starts with `*`) - as in cron, day matches when any of them
+///   does
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSpec {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    either_day: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = CrustError;
+
+    /// Parses `HH:MM` or cron-like spec with 5 fields, which support `*`,
+    /// numbers, ranges (`1-5`), lists (`1,15`) and steps (`*/10`).
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        if let Ok(time) = NaiveTime::parse_from_str(spec.trim(), "%H:%M") {
+            return Ok(Schedule::At(time));
+        }
+
+        let fields = spec.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(invalid(spec));
+        }
+        Ok(Schedule::Cron(CronSpec {
+            minutes: parse_field(fields[0], 0, 59).ok_or_else(|| invalid(spec))?,
+            hours: parse_field(fields[1], 0, 23).ok_or_else(|| invalid(spec))?,
+            days: parse_field(fields[2], 1, 31).ok_or_else(|| invalid(spec))?,
+            months: parse_field(fields[3], 1, 12).ok_or_else(|| invalid(spec))?,
+            weekdays: parse_field(fields[4], 0, 6).ok_or_else(|| invalid(spec))?,
+            either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        }))
+    }
+}
+
+fn invalid(spec: &str) -> CrustError {
+    CrustError {
+        code: ExitCode::Parser,
+        message: format!(
+            "Invalid schedule '{spec}'. Use HH:MM or cron-like 'min hour day month weekday'"
+        ),
+    }
+}
+
+/// Parses single cron field into sorted allowed values.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<Vec<u32>> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (from.parse().ok()?, to.parse().ok()?),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return None;
+        }
+        values.extend((from..=to).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Some(values)
+}
+
+impl Schedule {
+    /// Checks whether job should be run again after the next occurrence.
+    pub fn is_recurring(&self) -> bool {
+        matches!(self, Schedule::Cron(_))
+    }
+
+    /// The first occurrence after passed moment (None if there is no
+    /// such moment within a year, e.g. for 31st of February).
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        match self {
+            Schedule::At(time) => (0..2)
+                .map(|day| start.clone() + Duration::days(day))
+                .filter_map(|date| {
+                    date.with_hour(time.hour())?
+                        .with_minute(time.minute())
+                        .filter(|at| at >= &start)
+                })
+                .next(),
+            Schedule::Cron(spec) => (0..SEARCH_LIMIT)
+                .map(|minute| start.clone() + Duration::minutes(minute))
+                .find(|at| spec.matches(at)),
+        }
+    }
+}
+
+impl CronSpec {
+    fn matches<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> bool {
+        let day = self.days.contains(&at.day());
+        let weekday = self.weekdays.contains(&at.weekday().num_days_from_sunday());
+        let day_matches = match self.either_day {
+            true => day || weekday,
+            false => day && weekday,
+        };
+        self.minutes.contains(&at.minute())
+            && self.hours.contains(&at.hour())
+            && self.months.contains(&at.month())
+            && day_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_time_of_day() {
+        let schedule = "02:00".parse::<Schedule>().unwrap();
+
+        assert!(!schedule.is_recurring());
+        assert_eq!(
+            schedule.next_after(&at("2024-03-10T01:15:30Z")),
+            Some(at("2024-03-10T02:00:00Z"))
+        );
+        assert_eq!(
+            schedule.next_after(&at("2024-03-10T02:00:00Z")),
+            Some(at("2024-03-11T02:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_next_cron_occurrence() {
+        let schedule = "*/15 1-3 * * 1-5".parse::<Schedule>().unwrap();
+
+        assert!(schedule.is_recurring());
+        // 2024-03-09 is Saturday - the next match is on Monday
+        assert_eq!(
+            schedule.next_after(&at("2024-03-09T12:00:00Z")),
+            Some(at("2024-03-11T01:00:00Z"))
+        );
+        assert_eq!(
+            schedule.next_after(&at("2024-03-11T01:00:00Z")),
+            Some(at("2024-03-11T01:15:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_cron_day_of_month_or_week() {
+        // 1st of month or Monday (2024-03-04 is Monday)
+        let schedule = "0 6 1 * 1".parse::<Schedule>().unwrap();
+        assert_eq!(
+            schedule.next_after(&at("2024-02-27T12:00:00Z")),
+            Some(at("2024-03-01T06:00:00Z"))
+        );
+        assert_eq!(
+            schedule.next_after(&at("2024-03-01T12:00:00Z")),
+            Some(at("2024-03-04T06:00:00Z"))
+        );
+
+        // Unrestricted day of month - only Mondays
+        let schedule = "0 6 */1 * 1".parse::<Schedule>().unwrap();
+        assert_eq!(
+            schedule.next_after(&at("2024-02-27T12:00:00Z")),
+            Some(at("2024-03-04T06:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_impossible_cron_date() {
+        let schedule = "0 0 31 2 *".parse::<Schedule>().unwrap();
+
+        assert_eq!(schedule.next_after(&at("2024-01-01T00:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_parse_invalid_schedule() {
+        for spec in [
+            "25:00",
+            "* * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a b c d e",
+        ] {
+            let err = spec.parse::<Schedule>().err().unwrap();
+            assert_eq!(err.code, ExitCode::Parser);
+            assert_eq!(
+                err.message,
+                format!("Invalid schedule '{spec}'. Use HH:MM or cron-like 'min hour day month weekday'")
+            );
+        }
+    }
+}
@@ -1,16 +1,27 @@
 use std::cell::RefCell;
-use std::io::{self, BufRead, Write};
+use std::collections::HashMap;
+use std::io::{self, BufRead, IsTerminal, Write};
 use std::path::PathBuf;
 use std::rc::Rc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
 use clap::Parser;
 use text_colorizer::Colorize;
 
+pub mod archive;
+pub mod budget;
+pub mod cache;
 pub mod connection;
+pub mod doctor;
 pub mod error;
 pub mod exec;
+#[cfg(any(test, doctest, feature = "test-util"))]
+pub mod fake;
+pub mod fleet;
 pub mod interfaces;
+pub mod inventory;
+pub mod job;
 pub mod logger;
 pub mod machine;
 pub mod utils;
@@ -18,19 +29,58 @@ pub mod utils;
 #[cfg(test)]
 pub mod mocks;
 pub mod parser;
+pub mod plan;
+#[cfg(feature = "results")]
+pub mod results;
 pub mod scp;
+pub mod session;
+pub mod stage;
 
-use connection::manager::MachinesManager;
+use archive::parser::{ArchiveAction, ArchiveArgs};
+use budget::OperationClass;
+use cache::parser::CacheAction;
+use cache::DownloadCache;
+use connection::channels;
+use connection::endpoint::Endpoint;
+use connection::hostkey::HostKeyPolicy;
+use connection::key::{InlineKey, KeySource};
+use connection::manager::{MachinesManager, MachinesManagerMethods};
 use connection::parser::BaseConnArgs;
+use connection::request::RemoteTarget;
+use connection::settings::SessionSettings;
+use connection::ConnectArgs;
 use error::{handle_result, CrustError, DefaultExitHandler};
+use exec::env::ExecEnv;
+use exec::parser::WhichArgs;
+use exec::request::ExecRequest;
+use fleet::parser::{FleetAction, FleetExecArgs, RunAction, RunArgs};
+use fleet::run::{self, RunGuard};
+use fleet::FleetReport;
+use interfaces::output::{self, OutputSink};
 use interfaces::parser::Validation;
 use interfaces::response::CrustResult;
+use interfaces::tmpdir::TemporaryDirectory;
+use inventory::parser::{ChannelsArgs, LockArgs, MachineAction, MachineArgs};
+use inventory::Inventory;
+use job::parser::{JobAction, JobArgs};
 use logger::Logger;
 use machine::local::LocalMachine;
 use machine::remote::RemoteMachine;
-use machine::Machine;
+use machine::which;
+use machine::{Machine, MachineID};
 use parser::{AppArgs, Operation};
+use plan::parser::{ApplyArgs, PlanArgs, PlanFormat};
+use plan::Plan;
+#[cfg(feature = "results")]
+use results::parser::ResultsAction;
+#[cfg(feature = "results")]
+use results::ResultStore;
+use scp::request::TransferRequest;
 use scp::scp;
+use session::parser::SessionAction;
+use session::{EventKind, SessionEvent};
+use stage::parser::WithArgs;
+use stage::{RemoteFile, StagedFile};
 use utils::shell_manager::ShellManager;
 
 static LOGGER: Logger = Logger;
@@ -43,6 +93,25 @@ fn get_or_create_remote_machine(
     args: impl BaseConnArgs,
     manager: &mut MachinesManager,
 ) -> Result<Rc<RefCell<Box<dyn Machine>>>, CrustError> {
+    // Locks are kept per host, so locked machine can not be reached via
+    // another alias nor via its address
+    let host = match args
+        .alias()
+        .and_then(|alias| manager.get_machine(&MachineID::Custom(alias.to_string())))
+    {
+        Some(machine) => machine
+            .borrow()
+            .connect_args()
+            .map(|conn| conn.hostname().to_string()),
+        None => args
+            .addr()
+            .and_then(|addr| Endpoint::parse_address(addr).ok())
+            .and_then(|endpoint| endpoint.host),
+    };
+    if let Some(host) = host {
+        session::lock::guard(&host)?;
+    }
+
     let machine = match &args.alias() {
         None => {
             log::trace!("Creating remote machine (without alias)");
@@ -52,7 +121,7 @@ fn get_or_create_remote_machine(
                 host,
                 args.password().map(|s| s.to_owned()),
                 args.pkey().map(|pb| pb.to_owned()),
-                args.port().unwrap(),
+                args.ssh_port().unwrap(),
                 None,
                 manager,
             )
@@ -65,7 +134,10 @@ fn get_or_create_remote_machine(
                     log::trace!(
                         "No machine with the given alias ({alias}) was found in the manager. Trying to create a new one..."
                     );
-                    if args.addr().is_some() && (args.password().is_some() || args.pkey().is_some())
+                    if args.addr().is_some()
+                        && (args.password().is_some()
+                            || args.pkey().is_some()
+                            || args.pkey_inline().is_some())
                     {
                         log::trace!(
                             "Required args to create machine are found - creating a new one"
@@ -76,7 +148,7 @@ fn get_or_create_remote_machine(
                             host,
                             args.password().map(|s| s.to_owned()),
                             args.pkey().map(|pb| pb.to_owned()),
-                            args.port().unwrap(),
+                            args.ssh_port().unwrap(),
                             args.alias().map(|s| s.to_owned()),
                             manager,
                         )
@@ -90,17 +162,522 @@ fn get_or_create_remote_machine(
             }
         }
     };
+
+    // Key is needed (and read from its source) only for a new connection.
+    // In background mode it was read by client and passed with command -
+    // it is taken anyway, so it is not kept longer than needed
+    if let Some(source) = args.pkey_inline() {
+        let key_source = source.parse::<KeySource>()?;
+        let passed = match ShellManager::is_background_mode() {
+            true => Some(session::secret::take(source)?),
+            false => None,
+        };
+        if machine.borrow().pending_connection().is_some() {
+            let key = match passed {
+                Some(mut data) => {
+                    data.push('\n');
+                    InlineKey::try_from(data)?
+                }
+                None => InlineKey::load(&key_source)?,
+            };
+            machine.borrow_mut().inline_key(key);
+        }
+    }
+
+    let update = args.settings();
+    if !update.is_empty() {
+        let settings = update.apply(&machine.borrow().settings());
+        machine.borrow_mut().apply_settings(&settings);
+    }
+    Ok(machine)
+}
+
+/// Saves invoked operation with its result in session journal.
+fn record_event(
+    kind: EventKind,
+    started: DateTime<Utc>,
+    timer: Instant,
+    target: String,
+    description: String,
+    result: &Result<CrustResult, CrustError>,
+) {
+    let (stdout, stderr, retcode) = match result {
+        Ok(r) => (r.stdout().to_string(), r.stderr().to_string(), r.retcode()),
+        Err(e) => (String::new(), e.message.clone(), e.code.to_int()),
+    };
+    session::record(SessionEvent {
+        kind,
+        started,
+        duration: timer.elapsed(),
+        target,
+        description,
+        stdout,
+        stderr,
+        retcode,
+    });
+}
+
+/// Gets machine pointed by request target (local machine if target was not passed).
+fn get_or_create_machine(
+    target: Option<&RemoteTarget>,
+    manager: &mut MachinesManager,
+) -> Result<Rc<RefCell<Box<dyn Machine>>>, CrustError> {
+    let machine = match target {
+        Some(target) => get_or_create_remote_machine(target.clone(), manager)?,
+        None => LocalMachine::get_or_create(manager),
+    };
+    manager.register_usage(machine.borrow().get_id());
     Ok(machine)
 }
 
+/// Executes requested command. Machine is taken from manager (or
+/// created and registered if it does not exist yet).
+pub fn run_exec(
+    request: &ExecRequest,
+    manager: &mut MachinesManager,
+) -> Result<CrustResult, CrustError> {
+    let machine = get_or_create_machine(request.remote(), manager)?;
+    machine.borrow_mut().run_as(request.run_as());
+
+    let (started, timer) = (Utc::now(), Instant::now());
+    let result = exec_on_machine(request, &machine);
+    // Machine is kept by manager - further commands run as current user
+    if request.run_as().is_some() {
+        machine.borrow_mut().run_as(None);
+    }
+    let target = machine.borrow().to_string();
+    let cmd = match request.run_as() {
+        Some(user) => format!("{} (as {user})", request.cmd()),
+        None => request.cmd().to_string(),
+    };
+    record_event(EventKind::Exec, started, timer, target, cmd, &result);
+    result
+}
+
+/// Executes requested command on already prepared machine.
+fn exec_on_machine(
+    request: &ExecRequest,
+    machine: &Rc<RefCell<Box<dyn Machine>>>,
+) -> Result<CrustResult, CrustError> {
+    let command = match exec::glob::has_placeholders(request.cmd()) {
+        true => {
+            machine.borrow_mut().connect()?;
+            request.expanded_command_line(machine.borrow().as_ref())?
+        }
+        false => request.command_line(),
+    };
+    if let (true, Some(name)) = (request.resolve(), which::command_name(request.cmd())) {
+        let path = which::which(machine.borrow().as_ref(), name, request.env())?;
+        log::debug!("Command '{name}' resolved to {path}");
+    }
+    let run_rt = || machine.borrow().exec_rt(&command, request.merge());
+    match (request.rt(), request.max_output()) {
+        (true, Some(max)) => exec::truncate::limit_rt(max, run_rt),
+        (true, None) => run_rt(),
+        (false, Some(max)) => exec::truncate::limit(machine.borrow().exec(&command)?, max),
+        (false, None) => machine.borrow().exec(&command),
+    }
+}
+
+/// Executes planned requests one host after another. Failure on one host
+/// does not stop the others - it is reported in returned report.
+/// Cancelled run (see `run cancel`) does not start remaining hosts and
+/// returns a partial report.
+/// All hosts are connected up-front (at most `concurrency` at once), hosts
+/// which can not be connected are reported without executing anything.
+pub fn run_fleet_exec(
+    plan: &[(String, ExecRequest)],
+    manager: &mut MachinesManager,
+    concurrency: usize,
+) -> FleetReport {
+    fleet_exec(plan, manager, concurrency, |_, _, _, _| {})
+}
+
+/// Executes planned requests as `run_fleet_exec` does and saves result of
+/// every host in store as soon as it finishes.
+#[cfg(feature = "results")]
+pub fn run_fleet_exec_stored(
+    plan: &[(String, ExecRequest)],
+    manager: &mut MachinesManager,
+    concurrency: usize,
+    store: &ResultStore,
+) -> FleetReport {
+    fleet_exec(
+        plan,
+        manager,
+        concurrency,
+        |host, request, started, result| {
+            if let Err(e) = store.record(host, request.cmd(), started, result) {
+                log::error!("Can not save result of {host} in results store: {e}");
+            }
+        },
+    )
+}
+
+/// Executes planned requests (see `run_fleet_exec`), passing result of
+/// every finished host with its start time to `finished`.
+fn fleet_exec(
+    plan: &[(String, ExecRequest)],
+    manager: &mut MachinesManager,
+    concurrency: usize,
+    mut finished: impl FnMut(&str, &ExecRequest, DateTime<Utc>, &Result<CrustResult, CrustError>),
+) -> FleetReport {
+    let mut report = FleetReport::default();
+    let description = match plan.first() {
+        Some((_, request)) => format!("exec '{}'", request.cmd()),
+        None => String::from("exec"),
+    };
+    // Run is registered before connecting, so the slowest phase is listed
+    // and can be cancelled too
+    let run = RunGuard::start(&description, plan.len());
+    let mut failed = connect_fleet(plan, manager, concurrency, &run);
+    for (idx, (host, request)) in plan.iter().enumerate() {
+        if run.is_cancelled() {
+            let skipped = plan[idx..].iter().map(|(h, _)| h.clone()).collect();
+            report.cancel(run.id(), skipped);
+            break;
+        }
+        let started = Utc::now();
+        let result = match failed.remove(host) {
+            Some(e) => Err(e),
+            None => {
+                log::debug!("Executing '{}' on {host}", request.cmd());
+                run_exec(request, manager)
+            }
+        };
+        finished(host, request, started, &result);
+        report.push(host, result);
+        run.host_done();
+    }
+    report
+}
+
+/// Runs planned `fleet exec`, saving results in store when it was passed.
+fn run_fleet(
+    plan: &[(String, ExecRequest)],
+    args: &FleetExecArgs,
+    manager: &mut MachinesManager,
+) -> Result<FleetReport, CrustError> {
+    #[cfg(feature = "results")]
+    if let Some(path) = &args.store {
+        let store = ResultStore::open(path)?;
+        return Ok(run_fleet_exec_stored(
+            plan,
+            manager,
+            args.connect_concurrency,
+            &store,
+        ));
+    }
+    Ok(run_fleet_exec(plan, manager, args.connect_concurrency))
+}
+
+/// Connects machines of planned hosts in parallel, in batches of
+/// `concurrency` machines. Remaining batches are not connected when run
+/// was cancelled. Returns errors of hosts which could not be connected.
+fn connect_fleet(
+    plan: &[(String, ExecRequest)],
+    manager: &mut MachinesManager,
+    concurrency: usize,
+    run: &RunGuard,
+) -> HashMap<String, CrustError> {
+    let mut failed = HashMap::new();
+    let mut hosts = Vec::new();
+    let mut machines = Vec::new();
+    for (host, request) in plan {
+        let Some(target) = request.remote() else {
+            continue;
+        };
+        match get_or_create_remote_machine(target.clone(), manager) {
+            Ok(machine) => {
+                hosts.push(host);
+                machines.push(machine);
+            }
+            Err(e) => {
+                failed.insert(host.clone(), e);
+            }
+        }
+    }
+
+    let batch = concurrency.max(1);
+    for (start, machines) in (0..).step_by(batch).zip(machines.chunks(batch)) {
+        if run.is_cancelled() {
+            log::info!("Run {} cancelled while connecting", run.id());
+            break;
+        }
+        for (idx, e) in connection::parallel::connect_machines(machines, concurrency) {
+            failed.insert(hosts[start + idx].clone(), e);
+        }
+    }
+    failed
+}
+
+/// Resolves names against PATH of machine - found ones are printed with
+/// their paths, missing ones are reported with suggestions.
+fn run_which(args: &WhichArgs, manager: &mut MachinesManager) -> Result<CrustResult, CrustError> {
+    let target = args.remote.as_ref().map(RemoteTarget::from);
+    let machine = get_or_create_machine(target.as_ref(), manager)?;
+    let machine = machine.borrow();
+    let names = args.names.iter().map(String::as_str).collect::<Vec<_>>();
+    let env = ExecEnv::default();
+
+    let (mut found, mut missing) = (Vec::new(), Vec::new());
+    for resolution in which::resolve(machine.as_ref(), &names, &env)? {
+        match resolution.path {
+            Some(path) => found.push(format!("{}: {path}", resolution.name)),
+            None => {
+                missing.push(which::not_found(machine.as_ref(), &resolution.name, &env).message)
+            }
+        }
+    }
+    let retcode = i32::from(!missing.is_empty());
+    Ok(CrustResult::new(
+        &found.join("\n"),
+        &missing.join("\n"),
+        retcode,
+    ))
+}
+
+/// Lists or extracts members of remote archive.
+fn run_archive(
+    args: &ArchiveArgs,
+    manager: &mut MachinesManager,
+) -> Result<CrustResult, CrustError> {
+    match &args.action {
+        ArchiveAction::Ls(ls_args) => {
+            let file = RemoteFile::parse(&ls_args.file, &ls_args.auth)?;
+            let machine = get_or_create_machine(Some(&file.target), manager)?;
+            let members = archive::list(machine.borrow().as_ref(), &file.path)?;
+            Ok(CrustResult::new(&members.join("\n"), "", 0))
+        }
+        ArchiveAction::Extract(extract_args) => {
+            let file = RemoteFile::parse(&extract_args.file, &extract_args.auth)?;
+            let machine = get_or_create_machine(Some(&file.target), manager)?;
+            let local = LocalMachine::get_or_create(manager);
+            archive::extract(
+                &machine,
+                &local,
+                &file.path,
+                &extract_args.member,
+                &extract_args.to,
+            )
+        }
+    }
+}
+
+/// Downloads remote files into local temporary directory, runs local
+/// command on them and (optionally) uploads modified files back.
+fn run_with(args: &WithArgs, manager: &mut MachinesManager) -> Result<CrustResult, CrustError> {
+    let mut local = LocalMachine::new();
+    let tmpdir = local.create_tmpdir()?;
+
+    let mut staged = Vec::new();
+    for (idx, spec) in args.files.iter().enumerate() {
+        let remote = RemoteFile::parse(spec, &args.auth)?;
+        let file_name = remote.path.file_name().ok_or_else(|| CrustError {
+            code: error::ExitCode::Parser,
+            message: format!("'{spec}' does not point to a file"),
+        })?;
+        let dir = tmpdir.join(idx.to_string());
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(file_name);
+
+        let request = TransferRequest::builder(&remote.path, &path)
+            .src_remote(remote.target.clone())
+            .build()?;
+        run_transfer(&request, manager)?;
+
+        staged.push(StagedFile {
+            fingerprint: stage::fingerprint(&path)?,
+            remote,
+            local: path,
+        });
+    }
+
+    let paths = staged.iter().map(|s| s.local.clone()).collect::<Vec<_>>();
+    let cmd = stage::substitute(&args.cmd, &paths)?;
+    log::debug!("Running local command: {cmd}");
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(&cmd)
+        .status()?;
+
+    let mut uploaded = Vec::new();
+    if args.upload_back {
+        for file in &staged {
+            if !file.is_modified()? {
+                continue;
+            }
+            let request = TransferRequest::builder(&file.local, &file.remote.path)
+                .dst_remote(file.remote.target.clone())
+                .build()?;
+            run_transfer(&request, manager)?;
+            uploaded.push(file.remote.path.display().to_string());
+        }
+    }
+
+    let stdout = match uploaded.is_empty() {
+        true => String::new(),
+        false => format!("Uploaded back: {}", uploaded.join(", ")),
+    };
+    let retcode = status.code().unwrap_or(1);
+    let stderr = match status.success() {
+        true => String::new(),
+        false => format!("Local command failed with code {retcode}"),
+    };
+    Ok(CrustResult::new(&stdout, &stderr, retcode))
+}
+
+/// Copies data between machines from request. Machines are taken from
+/// manager (or created and registered if they do not exist yet).
+pub fn run_transfer(
+    request: &TransferRequest,
+    manager: &mut MachinesManager,
+) -> Result<CrustResult, CrustError> {
+    let src_machine = get_or_create_machine(request.src().remote.as_ref(), manager)?;
+    let dst_machine = get_or_create_machine(request.dst().remote.as_ref(), manager)?;
+
+    let (started, timer) = (Utc::now(), Instant::now());
+    let result = scp(
+        &src_machine,
+        &dst_machine,
+        request.src().path.clone(),
+        request.dst().path.clone(),
+        request.options(),
+    );
+    let target = format!("{} -> {}", src_machine.borrow(), dst_machine.borrow());
+    let description = format!(
+        "{} -> {}",
+        request.src().path.display(),
+        request.dst().path.display()
+    );
+    record_event(EventKind::Scp, started, timer, target, description, &result);
+    result
+}
+
+/// Builds plan of operation which supports dry-run (None for others).
+fn plan_operation(
+    operation: &Operation,
+    manager: &mut MachinesManager,
+) -> Result<Option<Plan>, CrustError> {
+    let actions = match operation {
+        Operation::Scp(scp_args) => {
+            let request = TransferRequest::try_from(scp_args.as_ref())?;
+            let src_machine = get_or_create_machine(request.src().remote.as_ref(), manager)?;
+            let dst_machine = get_or_create_machine(request.dst().remote.as_ref(), manager)?;
+            scp::plan(
+                &src_machine,
+                &dst_machine,
+                &request.src().path,
+                &request.dst().path,
+            )?
+        }
+        Operation::Fleet(fleet_args) => match &fleet_args.action {
+            FleetAction::Exec(exec_args) => {
+                let selection = &exec_args.selection;
+                let inventory = load_inventory(selection.inventory.as_ref())?;
+                let plan = fleet::plan_exec(
+                    &inventory,
+                    &selection.target,
+                    &exec_args.cmd.join(" "),
+                    exec_args.merge,
+                    exec_args.max_output()?,
+                )?;
+                fleet::plan_actions(&plan)
+            }
+            FleetAction::Hosts(_) => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(Plan::new(actions)))
+}
+
+/// Shows plan of operation (and saves it if requested) instead of
+/// running it.
+fn show_plan(
+    args: &AppArgs,
+    plan_args: &PlanArgs,
+    manager: &mut MachinesManager,
+) -> Result<CrustResult, CrustError> {
+    let operation = args.get_operation().unwrap();
+    let mut plan = plan_operation(operation, manager)?.ok_or_else(|| CrustError {
+        code: error::ExitCode::Parser,
+        message: "Operation does not support dry-run".to_string(),
+    })?;
+    plan.command = plan::parser::strip_plan_flags(&args.command);
+
+    if let Some(file) = &plan_args.plan_out {
+        plan.save(file)?;
+        log::info!("Plan saved to {}", file.display());
+    }
+    let output = match plan_args.plan_format {
+        PlanFormat::Text => plan.render(),
+        PlanFormat::Json => plan.to_json()?,
+    };
+    Ok(CrustResult::new(&output, "", 0))
+}
+
+/// Runs operation from saved plan, but only if it still describes what
+/// operation is going to do.
+fn apply_plan(args: &ApplyArgs, manager: &mut MachinesManager) -> Result<CrustResult, CrustError> {
+    let saved = Plan::load(&args.file)?;
+    let path_exe = std::env::current_exe().expect("No executable path");
+    let input = std::iter::once(path_exe.to_string_lossy().to_string())
+        .chain(saved.command.iter().cloned());
+    let mut planned = AppArgs::try_parse_from(input).map_err(|e| CrustError {
+        code: error::ExitCode::Parser,
+        message: format!("Invalid command in plan: {e}"),
+    })?;
+    planned.validate()?;
+    planned.command = saved.command.clone();
+
+    let current = match planned.get_operation() {
+        Some(operation) => plan_operation(operation, manager)?,
+        None => None,
+    };
+    match current {
+        Some(current) => saved.check_current(&current)?,
+        None => {
+            return Err(CrustError {
+                code: error::ExitCode::Parser,
+                message: "Plan does not describe operation which can be applied".to_string(),
+            })
+        }
+    }
+    log::info!("Applying plan {}", args.file.display());
+    single_run(planned, Some(manager))
+}
+
 /// Entrypoint for CLI invoke.
 fn single_run(
+    args: AppArgs,
+    manager_opt: Option<&mut MachinesManager>,
+) -> Result<CrustResult, CrustError> {
+    // Background process serves commands with different policies
+    let policy = args.host_key_policy;
+    connection::hostkey::within(policy, || {
+        if !args.trace_timing {
+            return run_operation(args, manager_opt);
+        }
+
+        let trace = connection::timing::start();
+        let result = run_operation(args, manager_opt);
+        output::write_err_line(&connection::timing::render(&trace.finish()));
+        result
+    })
+}
+
+/// Runs operation requested by arguments.
+fn run_operation(
     mut args: AppArgs,
     manager_opt: Option<&mut MachinesManager>,
 ) -> Result<CrustResult, CrustError> {
-    args.validate()?;
+    match args.all_errors {
+        true => interfaces::parser::validate_all(&mut args)?,
+        false => args.validate()?,
+    }
     log::trace!("Validated args: {:#?}", args);
 
+    let in_background = manager_opt.is_some();
     let mut default_manager = MachinesManager::default();
     let manager = match manager_opt {
         Some(man) => man,
@@ -115,47 +692,362 @@ fn single_run(
         return Ok(CrustResult::default());
     }
 
+    if let Some(plan_args) = operation.and_then(Operation::plan_args) {
+        if plan_args.is_requested() {
+            return show_plan(&args, plan_args, manager);
+        }
+    }
+
     let result = match operation.unwrap() {
-        Operation::Exec(exec_args) => {
-            let machine = match &exec_args.remote {
-                Some(_args) => get_or_create_remote_machine(_args.clone(), manager)?,
-                None => LocalMachine::get_or_create(manager),
-            };
+        Operation::Exec(exec_args) => run_exec(&ExecRequest::try_from(exec_args)?, manager)?,
+        Operation::Scp(scp_args) => {
+            let request = TransferRequest::try_from(scp_args.as_ref())?;
+            match &scp_args.schedule {
+                Some(spec) => {
+                    if !in_background || !ShellManager::is_background_mode() {
+                        return Err(CrustError {
+                            code: error::ExitCode::Parser,
+                            message: "Scheduling is available only in background mode".to_string(),
+                        });
+                    }
+                    let job = job::schedule(request, spec)?;
+                    let message = format!(
+                        "Scheduled job #{} (next run {})",
+                        job.id,
+                        job.next_run.format("%Y-%m-%d %H:%M")
+                    );
+                    CrustResult::new(&message, "", 0)
+                }
+                None => run_transfer(&request, manager)?,
+            }
+        }
+        Operation::Job(job_args) => match job_args.action {
+            JobAction::List => CrustResult::new(&job::render(&job::list()), "", 0),
+            JobAction::Run { id } => {
+                let job = job::take(id)?;
+                log::info!("Running scheduled job #{id}: {}", job.describe());
+                run_transfer(&job.request, manager)?
+            }
+        },
+        Operation::Run(run_args) => run_runs(run_args)?,
+        Operation::Apply(apply_args) => apply_plan(apply_args, manager)?,
+        Operation::Archive(archive_args) => run_archive(archive_args, manager)?,
+        Operation::Which(which_args) => run_which(which_args, manager)?,
+        #[cfg(feature = "results")]
+        Operation::Results(results_args) => match &results_args.action {
+            ResultsAction::Query(query_args) => {
+                let store = ResultStore::open(&query_args.store)?;
+                let filter = query_args.filter()?;
+                let output = match query_args.stats {
+                    true => results::render_stats(&store.stats(&filter)?),
+                    false => results::render(&store.query(&filter)?),
+                };
+                CrustResult::new(&output, "", 0)
+            }
+        },
+        Operation::Cache(cache_args) => match cache_args.action {
+            CacheAction::Clear => {
+                let removed = DownloadCache::default().clear()?;
+                CrustResult::new(&format!("Removed {removed} cached files"), "", 0)
+            }
+        },
+        Operation::Doctor(doctor_args) => {
+            let target = doctor_args.remote.as_ref().map(RemoteTarget::from);
+            let machine = get_or_create_machine(target.as_ref(), manager)?;
+            doctor::crypto_report(&machine)?
+        }
+        Operation::Fleet(fleet_args) => match &fleet_args.action {
+            FleetAction::Exec(exec_args) => {
+                let selection = &exec_args.selection;
+                let inventory = load_inventory(selection.inventory.as_ref())?;
+                let plan = fleet::plan_exec(
+                    &inventory,
+                    &selection.target,
+                    &exec_args.cmd.join(" "),
+                    exec_args.merge,
+                    exec_args.max_output()?,
+                )?;
+                run_fleet(&plan, exec_args, manager)?.into()
+            }
+            FleetAction::Hosts(selection) => {
+                let inventory = load_inventory(selection.inventory.as_ref())?;
+                let hosts = inventory.resolve(&selection.target)?;
+                CrustResult::new(&hosts.join("\n"), "", 0)
+            }
+        },
+        Operation::With(with_args) => run_with(with_args, manager)?,
+        Operation::Machine(machine_args) => match &machine_args.action {
+            MachineAction::Export(export_args) => {
+                let mut inventory = load_inventory(export_args.inventory.as_ref())?;
+                if export_args.no_secrets {
+                    inventory = inventory.without_secrets();
+                }
+                match &export_args.file {
+                    Some(file) => {
+                        inventory.save(file)?;
+                        let message = format!(
+                            "Exported {} hosts to {}",
+                            inventory.hosts.len(),
+                            file.display()
+                        );
+                        CrustResult::new(&message, "", 0)
+                    }
+                    None => CrustResult::new(&inventory.to_yaml()?, "", 0),
+                }
+            }
+            MachineAction::Import(import_args) => {
+                let path = import_args
+                    .inventory
+                    .clone()
+                    .unwrap_or_else(Inventory::default_path);
+                let mut inventory = match path.exists() {
+                    true => Inventory::load(&path)?,
+                    false => Inventory::default(),
+                };
+                let shared = Inventory::load(&import_args.file)?;
+                let summary = inventory.import(&shared, import_args.on_conflict)?;
+                inventory.save(&path)?;
+                summary.into()
+            }
+            MachineAction::Channels(channels_args) => run_channels(channels_args)?,
+            MachineAction::Lock(LockArgs { name }) if in_background => lock_machine(name, manager)?,
+            MachineAction::Unlock(LockArgs { name }) if in_background => {
+                unlock_machine(name, manager)?
+            }
+            MachineAction::Lock(_) | MachineAction::Unlock(_) => {
+                return Err(CrustError {
+                    code: error::ExitCode::Parser,
+                    message: "Machines can be locked only in background mode".to_string(),
+                });
+            }
+        },
+        Operation::Session(session_args) => match &session_args.action {
+            SessionAction::Export(export_args) => {
+                if !in_background {
+                    return Err(CrustError {
+                        code: error::ExitCode::Parser,
+                        message: "Session export is available only in background mode".to_string(),
+                    });
+                }
 
-            let cmd = exec_args.cmd.as_ref().unwrap().join(" ");
-            match exec_args.rt {
-                true => machine.borrow().exec_rt(&cmd, exec_args.merge)?,
-                false => machine.borrow().exec(&cmd)?,
+                let report = session::report::render(
+                    &session::events(),
+                    session::dropped(),
+                    export_args.format,
+                );
+                match &export_args.output {
+                    Some(path) => {
+                        std::fs::write(path, report)?;
+                        let msg = format!("Session report saved to '{}'", path.display());
+                        CrustResult::new(&msg, "", 0)
+                    }
+                    None => CrustResult::new(&report, "", 0),
+                }
             }
+        },
+    };
+
+    Ok(result)
+}
+
+/// Locks machine and drops its connections (with credentials), so it
+/// has to be unlocked before further use. Every machine connected to
+/// host of inventory `name` is dropped, whatever alias it has.
+fn lock_machine(name: &str, manager: &mut MachinesManager) -> Result<CrustResult, CrustError> {
+    let started = Utc::now();
+    let timer = Instant::now();
+    let result = load_inventory(None)
+        .and_then(|inventory| inventory.hostname(name))
+        .and_then(|host| {
+            session::lock::lock(&host, name);
+            drop_host_machines(&host, manager)?;
+            Ok(CrustResult::new(&format!("Machine '{name}' locked"), "", 0))
+        });
+    let description = String::from("lock");
+    record_event(
+        EventKind::Lock,
+        started,
+        timer,
+        name.into(),
+        description,
+        &result,
+    );
+    result
+}
+
+/// Removes every machine connected to host from manager.
+fn drop_host_machines(host: &str, manager: &mut MachinesManager) -> Result<(), CrustError> {
+    connection::standby::forget(host);
+    for id in manager.ids_of_host(host) {
+        manager.remove_machine(id)?;
+    }
+    Ok(())
+}
+
+/// Unlocks machine after successful re-authentication - user is asked
+/// for password (empty one uses credentials of inventory host).
+fn unlock_machine(name: &str, manager: &mut MachinesManager) -> Result<CrustResult, CrustError> {
+    let started = Utc::now();
+    let timer = Instant::now();
+    let result = reauthenticate(name, manager);
+    let description = String::from("unlock");
+    record_event(
+        EventKind::Lock,
+        started,
+        timer,
+        name.into(),
+        description,
+        &result,
+    );
+    result
+}
+
+fn reauthenticate(name: &str, manager: &mut MachinesManager) -> Result<CrustResult, CrustError> {
+    let inventory = load_inventory(None)?;
+    let host = inventory.hostname(name)?;
+    if !session::lock::is_locked(&host) {
+        return Err(CrustError {
+            code: error::ExitCode::Parser,
+            message: format!("Machine '{name}' is not locked"),
+        });
+    }
+    let mut target = inventory.target(name)?;
+    let password = read_secret(&format!(
+        "Password for {name} (empty to use inventory credentials): "
+    ))?;
+    if !password.is_empty() {
+        target = target.password(&password);
+    }
+
+    session::lock::unlock(&host)?;
+    let connected = get_or_create_remote_machine(target, manager)
+        .and_then(|machine| machine.borrow_mut().connect());
+    if let Err(e) = connected {
+        drop_host_machines(&host, manager)?;
+        session::lock::lock(&host, name);
+        return Err(CrustError {
+            code: e.code,
+            message: format!("Machine '{name}' stays locked: {}", e.message),
+        });
+    }
+    Ok(CrustResult::new(
+        &format!("Machine '{name}' unlocked"),
+        "",
+        0,
+    ))
+}
+
+/// Lists open channels or force-closes one of them.
+fn run_channels(args: &ChannelsArgs) -> Result<CrustResult, CrustError> {
+    match args.close {
+        Some(id) => {
+            channels::request_close(id)?;
+            Ok(CrustResult::new(
+                &format!("Channel {id} will be closed"),
+                "",
+                0,
+            ))
         }
-        Operation::Scp(scp_args) => {
-            let src_machine = match &scp_args.src.remote_params {
-                None => LocalMachine::get_or_create(manager),
-                Some(_args) => get_or_create_remote_machine(_args.clone(), manager)?,
+        None => Ok(CrustResult::new(
+            &channels::render(&channels::list()),
+            "",
+            0,
+        )),
+    }
+}
+
+/// Lists multi-host runs in progress or cancels one of them.
+fn run_runs(args: &RunArgs) -> Result<CrustResult, CrustError> {
+    match args.action {
+        RunAction::List => Ok(CrustResult::new(
+            &format!("{}\n{}", run::render(&run::list()), budget::render()),
+            "",
+            0,
+        )),
+        RunAction::Cancel { id } => {
+            let closed = run::cancel(id)?;
+            Ok(CrustResult::new(
+                &format!("Run {id} will be cancelled ({closed} in-flight channels closed)"),
+                "",
+                0,
+            ))
+        }
+    }
+}
+
+/// Serves control pipe of background process in a separate thread, so
+/// channels and runs can be inspected (and closed) while main loop waits
+/// for a stuck operation. Only `machine channels` and `run` commands are
+/// accepted. Result of every command is passed back to client (runner.sh)
+/// via reply pipe.
+fn listen_control() {
+    let control = background_dir().join("control");
+    loop {
+        let file = match std::fs::File::open(&control) {
+            Ok(file) => file,
+            Err(_) => {
+                std::thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+
+        for line in io::BufReader::new(file).lines().map_while(Result::ok) {
+            let path_exe = std::env::current_exe().expect("No executable path");
+            let input = std::iter::once(path_exe.to_string_lossy().to_string())
+                .chain(line.split_whitespace().map(String::from));
+            let result = match AppArgs::try_parse_from(input) {
+                Ok(args) => match args.get_operation() {
+                    Some(Operation::Machine(MachineArgs {
+                        action: MachineAction::Channels(channels_args),
+                    })) => run_channels(channels_args),
+                    Some(Operation::Run(run_args)) => run_runs(run_args),
+                    _ => Err(CrustError {
+                        code: error::ExitCode::Parser,
+                        message: "Only 'machine channels' and 'run' can be sent to control pipe"
+                            .to_string(),
+                    }),
+                },
+                Err(e) => Err(CrustError {
+                    code: error::ExitCode::Parser,
+                    message: e.to_string(),
+                }),
             };
 
-            let dst_machine = match &scp_args.dst.remote_params {
-                None => LocalMachine::get_or_create(manager),
-                Some(_args) => get_or_create_remote_machine(_args.clone(), manager)?,
+            let reply = match result {
+                Ok(cr) => cr.stdout().to_string(),
+                Err(e) => e.to_string(),
             };
+            reply_control(reply);
+        }
+    }
+}
 
-            scp(
-                &src_machine,
-                &dst_machine,
-                PathBuf::from(&scp_args.src.path_from),
-                PathBuf::from(&scp_args.dst.path_to),
-                scp_args.progress,
-            )?
+/// Writes reply to command of control pipe. It is written aside, so
+/// control pipe is served even if client does not read the reply.
+fn reply_control(reply: String) {
+    let pipe = background_dir().join("control_reply");
+    std::thread::spawn(move || {
+        let written = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&pipe)
+            .and_then(|mut f| writeln!(f, "{reply}"));
+        if let Err(e) = written {
+            log::error!("Can not reply to control command: {e}");
         }
-    };
+    });
+}
 
-    Ok(result)
+/// Reads inventory from passed path (or from the default location).
+fn load_inventory(path: Option<&std::path::PathBuf>) -> Result<Inventory, CrustError> {
+    match path {
+        Some(path) => Inventory::load(path),
+        None => Inventory::load(&Inventory::default_path()),
+    }
 }
 
 /// Read data from standard input (used in manual invoke).
 fn read_stdin() -> String {
-    print!("\n[q to exit]>> ");
-    io::stdout().flush().unwrap();
+    output::write("\n[q to exit]>> ");
 
     let mut input = String::new();
     io::stdin()
@@ -164,6 +1056,38 @@ fn read_stdin() -> String {
     input
 }
 
+/// Asks user for a secret on standard input of process. Typed characters
+/// are not echoed. In background mode secret is typed in client process
+/// (runner.sh) and passed with command (see `session::secret`), as
+/// terminal of background process can be shared by other programs.
+fn read_secret(prompt: &str) -> Result<String, CrustError> {
+    if ShellManager::is_background_mode() {
+        return Ok(session::secret::take(session::secret::PASSWORD)?.to_string());
+    }
+    output::write(prompt);
+
+    let terminal = io::stdin().is_terminal();
+    let stty = |flag: &str| std::process::Command::new("stty").arg(flag).status();
+    if terminal {
+        if let Err(e) = stty("-echo") {
+            log::debug!("Can not disable echo of terminal: {e}");
+        }
+    }
+    let mut input = String::new();
+    let read = io::stdin().read_line(&mut input);
+    if terminal {
+        let _ = stty("echo");
+    }
+    read?;
+    output::write("\n");
+    Ok(input.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Directory with pipes of background process (created by runner.sh).
+fn background_dir() -> PathBuf {
+    PathBuf::from(format!("/tmp/tmp_crust_{}", std::process::id()))
+}
+
 /// Read data from specific FIFO pipe (only in shell invoke).
 /// Potential race condition - if it is a first invoke, shell script
 /// has to create tmp_dir and pipe, but in the meanwhile crust will try
@@ -173,7 +1097,7 @@ fn read_fifo() -> String {
     let mut input = String::new();
     log::warn!("waiting for fifo...");
 
-    let fifo = format!("/tmp/tmp_crust_{}/fifo", std::process::id());
+    let fifo = background_dir().join("fifo");
 
     let timeout = 5;
     let start = std::time::Instant::now();
@@ -191,27 +1115,146 @@ fn read_fifo() -> String {
     input
 }
 
+/// Writes result of operation invoked in loop of background process.
+fn print_result(result: Result<CrustResult, CrustError>) {
+    match result {
+        Ok(cr) => match cr.is_success() {
+            true => output::write_line(&cr.stdout().green().to_string()),
+            false => output::write_line(&cr.stderr().red().to_string()),
+        },
+        Err(e) => log::error!("{e}"),
+    };
+}
+
+/// Checks whether operation is a bulk one which can run aside of main
+/// loop - with its own connections. Machines registered in shared manager
+/// under aliases used by operation are copied (not connected), so the
+/// operation does not need the manager. Returns None when operation has to
+/// run on main loop.
+fn aside_machines(args: &AppArgs, manager: &mut MachinesManager) -> Option<Vec<AsideMachine>> {
+    let operation = args.get_operation()?;
+    if operation.class() != OperationClass::Bulk {
+        return None;
+    }
+
+    let targets = match operation {
+        Operation::Scp(scp_args) => {
+            transfer_targets(&TransferRequest::try_from(scp_args.as_ref()).ok()?)
+        }
+        Operation::Job(JobArgs {
+            action: JobAction::Run { id },
+        }) => transfer_targets(&job::list().into_iter().find(|j| j.id == *id)?.request),
+        Operation::With(with_args) => with_args
+            .files
+            .iter()
+            .map(|spec| RemoteFile::parse(spec, &with_args.auth).map(|f| f.target))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?,
+        Operation::Archive(ArchiveArgs {
+            action: ArchiveAction::Extract(extract_args),
+        }) => vec![
+            RemoteFile::parse(&extract_args.file, &extract_args.auth)
+                .ok()?
+                .target,
+        ],
+        _ => return None,
+    };
+
+    let mut machines: Vec<AsideMachine> = Vec::new();
+    for target in targets.iter().filter(|t| !t.is_self_contained()) {
+        let alias = target.alias()?;
+        if machines.iter().any(|m| &m.alias == alias) {
+            continue;
+        }
+        let machine = RemoteMachine::get(alias, manager)?;
+        let machine = machine.borrow();
+        machines.push(AsideMachine {
+            alias: alias.clone(),
+            args: machine.connect_args()?,
+            settings: machine.settings(),
+        });
+    }
+    Some(machines)
+}
+
+/// Machine of shared manager copied for operation running aside.
+struct AsideMachine {
+    alias: String,
+    args: ConnectArgs,
+    settings: SessionSettings,
+}
+
+/// Remote targets of transfer request.
+fn transfer_targets(request: &TransferRequest) -> Vec<RemoteTarget> {
+    [&request.src().remote, &request.dst().remote]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect()
+}
+
+/// Runs bulk operation in a separate thread, so main loop keeps serving
+/// interactive operations. Operation waits for a free bulk slot first
+/// and connects machines on its own (copies of passed ones).
+fn run_aside(args: AppArgs, machines: Vec<AsideMachine>) -> std::thread::JoinHandle<()> {
+    log::debug!("Running bulk operation aside: {}", args.command.join(" "));
+    std::thread::spawn(move || {
+        let _permit = budget::acquire(OperationClass::Bulk);
+        let mut manager = MachinesManager::default();
+        for machine in machines {
+            let copy = RemoteMachine::copy_of(&machine.alias, machine.args, &machine.settings);
+            manager.add_machine(Box::new(copy));
+        }
+        print_result(single_run(args, Some(&mut manager)));
+    })
+}
+
 /// Allows to run in background mode (store connections).
 /// Supports two ways of invoke: via command line or bash script
 /// manager (should be used in external scripts).
 fn multi_runs(args: AppArgs) {
     let mut manager = MachinesManager::default();
+    manager.set_standby(args.standby);
+    if args.standby > 0 {
+        connection::standby::start_refresher();
+    }
+    session::lock::set_timeout(args.idle_lock.map(|m| Duration::from_secs(m * 60)));
     let mut curr_args = args.clone();
     let read_input = match ShellManager::is_background_mode() {
         true => read_fifo,
         false => read_stdin,
     };
+    if ShellManager::is_background_mode() {
+        budget::set_budget(args.budget());
+        std::thread::spawn(listen_control);
+        job::start_scheduler(background_dir().join("fifo"));
+        if args.idle_lock.is_some() {
+            session::lock::start_watcher(background_dir().join("fifo"));
+        }
+    }
     loop {
-        let result = single_run(curr_args, Some(&mut manager));
-
-        match result {
-            Ok(cr) => match cr.is_success() {
-                true => println!("{}", cr.stdout().green()),
-                false => println!("{}", cr.stderr().red()),
-            },
-            Err(e) => log::error!("{e}"),
+        // Without fifo (manual invoke) idle machines are locked before
+        // the next command
+        for name in session::lock::lock_idle(Instant::now()) {
+            print_result(lock_machine(&name, &mut manager));
+        }
+
+        let aside = match ShellManager::is_background_mode() {
+            true => aside_machines(&curr_args, &mut manager),
+            false => None,
         };
+        match aside {
+            Some(machines) => {
+                run_aside(curr_args, machines);
+            }
+            None => {
+                // Main loop is the interactive lane
+                let _permit = budget::acquire(OperationClass::Interactive);
+                print_result(single_run(curr_args, Some(&mut manager)));
+            }
+        }
 
+        manager.refresh_standby();
         let input = read_input();
 
         if input == "q\n" {
@@ -223,14 +1266,23 @@ fn multi_runs(args: AppArgs) {
         let mut iter = input.split(' ').map(|x| x.trim()).collect::<Vec<&str>>();
         base_input.append(&mut iter);
         log::debug!("user cmd: {:?}", base_input);
-        curr_args = AppArgs::parse_from(base_input);
+        curr_args = AppArgs::parse_from(&base_input);
+        curr_args.command = base_input[1..].iter().map(|w| w.to_string()).collect();
+        if ShellManager::is_background_mode() && session::secret::expected(&curr_args.command) {
+            if let Err(e) = session::secret::receive(&background_dir().join("secret")) {
+                log::error!("{e}");
+            }
+        }
 
         logger::init(&curr_args.verbose.log_level_filter()); //TODO: for background invoke from shell, it's first initialization
     }
 }
 
 pub fn main() {
-    let args = parser::AppArgs::parse();
+    let mut args = parser::AppArgs::parse();
+    args.command = std::env::args().skip(1).collect();
+    output::set_sink(OutputSink::Console);
+    connection::hostkey::set_policy(args.host_key_policy.unwrap_or(HostKeyPolicy::No));
 
     if !(ShellManager::is_background_mode() && ShellManager::is_shell_invoke()) {
         logger::init(&args.verbose.log_level_filter());
@@ -244,3 +1296,55 @@ pub fn main() {
         true => multi_runs(args),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    fn manager_with_alias(alias: &str) -> MachinesManager {
+        let mut manager = MachinesManager::default();
+        RemoteMachine::get_or_create(
+            String::from("test_user"),
+            String::from("10.10.10.10"),
+            Some(String::from("1234")),
+            None,
+            22,
+            Some(alias.to_string()),
+            &mut manager,
+        );
+        manager
+    }
+
+    #[test]
+    fn test_aside_machines_copy_aliases() {
+        let mut manager = manager_with_alias("backend");
+
+        let with = AppArgs::parse_from(["crust", "with", "backend:/etc/hostname", "--", "true"]);
+        let machines = aside_machines(&with, &mut manager).unwrap();
+        assert_eq!(machines.len(), 1);
+        assert_eq!(machines[0].alias, "backend");
+        assert_eq!(machines[0].args.target(), "test_user@10.10.10.10");
+
+        let unknown =
+            AppArgs::parse_from(["crust", "with", "frontend:/etc/hostname", "--", "true"]);
+        assert!(aside_machines(&unknown, &mut manager).is_none());
+        let exec = AppArgs::parse_from(["crust", "exec", "hostname", "--alias-to", "backend"]);
+        assert!(aside_machines(&exec, &mut manager).is_none());
+    }
+
+    #[serial]
+    #[test]
+    fn test_exec_finishes_while_alias_transfer_runs_aside() {
+        let mut manager = manager_with_alias("backend");
+        let with =
+            AppArgs::parse_from(["crust", "with", "backend:/etc/hostname", "--", "sleep", "3"]);
+        let machines = aside_machines(&with, &mut manager).unwrap();
+
+        let aside = run_aside(with, machines);
+        let exec = AppArgs::parse_from(["crust", "exec", "hostname", "--alias-to", "backend"]);
+        assert!(single_run(exec, Some(&mut manager)).unwrap().is_success());
+        assert!(!aside.is_finished());
+        aside.join().unwrap();
+    }
+}
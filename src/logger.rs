@@ -5,9 +5,12 @@ use text_colorizer::Colorize;
 
 static INIT: Once = Once::new();
 
+use crate::interfaces::{output, progress_bar};
 use crate::LOGGER;
 
-/// Main, custom logger in application.
+/// Main, custom logger in application. Logs are written to output
+/// facade (see `interfaces::output`) above active progress bars, so
+/// logs of worker threads do not break bars.
 pub struct Logger;
 
 /// Set log level of Logger with requested enum-value.
@@ -31,21 +34,22 @@ impl Log for Logger {
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
             let message = format!("{}", record.args());
-            match record.level() {
-                Level::Info => println!("{}", message),
-                Level::Warn => println!("{}", message.yellow()),
-                Level::Error => println!("{}", message.red()),
-                Level::Debug => println!(
+            let line = match record.level() {
+                Level::Info => message,
+                Level::Warn => message.yellow().to_string(),
+                Level::Error => message.red().to_string(),
+                Level::Debug => format!(
                     "[{}] {}",
                     Utc::now().format("%Y-%m-%d %H:%M:%S"),
                     message.magenta()
                 ),
-                Level::Trace => println!(
+                Level::Trace => format!(
                     "[{}] {}",
                     Utc::now().format("%Y-%m-%d %H:%M:%S"),
                     message.blue()
                 ),
-            }
+            };
+            progress_bar::suspend(|| output::write_line(&line));
         }
     }
 
@@ -1,28 +1,33 @@
 use std::cell::RefCell;
 use std::fs::DirBuilder;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::rc::Rc;
 
 use uuid::Uuid;
 
 use crate::connection::manager::{MachinesManager, MachinesManagerMethods};
+use crate::connection::settings;
 use crate::error::{CrustError, ExitCode};
 use crate::exec::Exec;
-use crate::interfaces::{response::CrustResult, tmpdir::TemporaryDirectory};
+use crate::interfaces::{output, response::CrustResult, tmpdir::TemporaryDirectory};
 use crate::machine::{Machine, MachineID, MachineType};
-use crate::scp::Scp;
+use crate::scp::tree::{self, TreeEntry};
+use crate::scp::{Scp, TransferFile};
 
 /// Definition of LocalMachine with private fields.
 /// - id: machine id for MachinesManager
 /// - tmpdir: possible path to temporary directory
 /// - should_remove_tmpdir: determines whether dir
 ///   should be removed on dropping object
+/// - run_as: user executing commands (None means the current one)
 pub struct LocalMachine {
     id: MachineID,
     tmpdir: Option<PathBuf>,
     should_remove_tmpdir: bool,
+    run_as: Option<String>,
 }
 
 /// Set of unique methods for this LocalMachine structure.
@@ -34,6 +39,7 @@ impl LocalMachine {
             tmpdir: None,
             should_remove_tmpdir: true,
             id: LocalMachine::generate_id(),
+            run_as: None,
         }
     }
 
@@ -48,6 +54,18 @@ impl LocalMachine {
         }
     }
 
+    /// Command executed by shell - wrapped with non-interactive `sudo` if
+    /// it should be run as another user (it fails when current user may
+    /// not act as that user, instead of asking for password).
+    fn command(&self, cmd: &str) -> String {
+        match &self.run_as {
+            Some(user) if std::env::var("USER").ok().as_ref() != Some(user) => {
+                settings::sudo(user, cmd)
+            }
+            _ => cmd.to_string(),
+        }
+    }
+
     /// Private method to generate id for local machine.
     fn generate_id() -> MachineID {
         MachineID::default()
@@ -61,6 +79,7 @@ impl Default for LocalMachine {
             tmpdir: None,
             should_remove_tmpdir: true,
             id: LocalMachine::generate_id(),
+            run_as: None,
         }
     }
 }
@@ -85,6 +104,32 @@ impl Machine for LocalMachine {
     fn connect(&mut self) -> Result<(), CrustError> {
         Ok(())
     }
+
+    fn run_as(&mut self, user: Option<&str>) {
+        self.run_as = user.map(String::from);
+    }
+
+    fn list_tree(&self, root: &Path) -> Result<Vec<TreeEntry>, CrustError> {
+        tree::list_local(root)
+    }
+
+    fn path_exists(&self, path: &Path) -> Result<bool, CrustError> {
+        Ok(path.exists())
+    }
+
+    fn create_dir(&self, dir: &Path) -> Result<(), CrustError> {
+        Ok(std::fs::create_dir_all(dir)?)
+    }
+
+    fn open_file(&self, path: &Path) -> Result<(TransferFile, u64), CrustError> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        Ok((TransferFile::Local(file), size))
+    }
+
+    fn create_file(&self, path: &Path, _size: u64) -> Result<TransferFile, CrustError> {
+        Ok(TransferFile::Local(File::create(path)?))
+    }
 }
 
 /// Implementation of temporary directory handling.
@@ -138,7 +183,33 @@ impl TemporaryDirectory for LocalMachine {
 /// Add `execute` method for LocalMachine
 impl Exec for LocalMachine {
     fn exec(&self, cmd: &str) -> Result<CrustResult, CrustError> {
-        let result = Command::new("sh").arg("-c").arg(cmd).output()?;
+        let result = Command::new("sh")
+            .arg("-c")
+            .arg(self.command(cmd))
+            .output()?;
+
+        Ok(CrustResult::new(
+            &String::from_utf8(result.stdout)?,
+            &String::from_utf8(result.stderr)?,
+            result.status.code().unwrap_or(1),
+        ))
+    }
+
+    fn exec_with_input(&self, cmd: &str, input: &str) -> Result<CrustResult, CrustError> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(self.command(cmd))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Input is written aside, so command is not blocked on full pipes
+        let mut stdin = child.stdin.take().expect("Stdin is piped");
+        let input = input.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let result = child.wait_with_output()?;
+        let _ = writer.join();
 
         Ok(CrustResult::new(
             &String::from_utf8(result.stdout)?,
@@ -152,7 +223,7 @@ impl Exec for LocalMachine {
             true => {
                 let child = Command::new("sh")
                     .arg("-c")
-                    .arg(&format!("{cmd} 2>&1"))
+                    .arg(self.command(&format!("{cmd} 2>&1")))
                     .stdout(Stdio::piped())
                     .spawn()?;
 
@@ -160,7 +231,7 @@ impl Exec for LocalMachine {
                     BufReader::new(out)
                         .lines()
                         .map_while(Result::ok)
-                        .for_each(|line| println!("{line}"));
+                        .for_each(|line| output::write_line(&line));
                 } else {
                     return Err(CrustError {
                         code: ExitCode::Local,
@@ -171,21 +242,30 @@ impl Exec for LocalMachine {
             false => {
                 let child = Command::new("sh")
                     .arg("-c")
-                    .arg(cmd)
+                    .arg(self.command(cmd))
+                    .stdout(Stdio::piped())
                     .stderr(Stdio::piped())
                     .spawn()?;
 
-                if let Some(e) = child.stderr {
+                let (Some(out), Some(e)) = (child.stdout, child.stderr) else {
+                    return Err(CrustError {
+                        code: ExitCode::Local,
+                        message: String::from("STDOUT or STDERR is empty"),
+                    });
+                };
+                // Stdout goes through output (as on remote machine), so it
+                // can be limited - stderr is logged aside
+                let errors = std::thread::spawn(move || {
                     BufReader::new(e)
                         .lines()
                         .map_while(Result::ok)
                         .for_each(|line| log::error!("{line}"));
-                } else {
-                    return Err(CrustError {
-                        code: ExitCode::Local,
-                        message: String::from("STDERR is empty"),
-                    });
-                }
+                });
+                BufReader::new(out)
+                    .lines()
+                    .map_while(Result::ok)
+                    .for_each(|line| output::write_line(&line));
+                let _ = errors.join();
             }
         };
 
@@ -218,6 +298,7 @@ impl Clone for LocalMachine {
             tmpdir: self.tmpdir.clone(),
             should_remove_tmpdir: false,
             id: self.id.clone(),
+            run_as: self.run_as.clone(),
         }
     }
 }
@@ -277,6 +358,20 @@ mod tests {
         assert_eq!(res.retcode(), 0);
     }
 
+    #[test]
+    fn test_exec_localmachine_as_another_user() {
+        let mut machine = LocalMachine::new();
+        machine.run_as(Some("crust-nobody"));
+
+        assert_eq!(
+            machine.command("id -un"),
+            "sudo -n -u 'crust-nobody' -H sh -c 'id -un'"
+        );
+
+        machine.run_as(None);
+        assert_eq!(machine.command("id -un"), "id -un");
+    }
+
     #[test]
     fn test_clone_localmachine() {
         let machine = LocalMachine::new();
@@ -352,9 +447,9 @@ mod tests {
     fn test_create_localmachine_without_manager() {
         let machine = LocalMachine::new();
 
-        assert_eq!(machine.tmpdir_exists(), false);
+        assert!(!machine.tmpdir_exists());
         assert_eq!(machine.get_id(), &MachineID::new(None, None, None));
-        assert_eq!(machine.can_be_removed(), true);
+        assert!(machine.can_be_removed());
         assert_eq!(machine.mtype(), MachineType::LocalMachine);
     }
 
@@ -365,7 +460,7 @@ mod tests {
 
         let machine = LocalMachine::get_or_create(&mut manager);
         assert_eq!(manager.size(), 1);
-        assert_eq!(machine.borrow().exec("pwd").unwrap().is_success(), true);
+        assert!(machine.borrow().exec("pwd").unwrap().is_success());
     }
 
     #[test]
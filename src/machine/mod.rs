@@ -1,17 +1,27 @@
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
+use std::path::Path;
 
 use core::fmt::Debug;
 use ssh2::Session;
 
 pub mod local;
+pub mod os;
 pub mod remote;
+pub mod utilities;
+pub mod which;
 
-use crate::error::CrustError;
+use crate::connection::key::InlineKey;
+use crate::connection::settings::SessionSettings;
+use crate::connection::timing::{self, Phase};
+use crate::connection::ConnectArgs;
+use crate::error::{CrustError, ExitCode};
 use crate::exec::Exec;
 use crate::interfaces::tmpdir::TemporaryDirectory;
-use crate::scp::Scp;
+use crate::machine::os::HostOs;
+use crate::scp::tree::{self, TreeEntry};
+use crate::scp::{Scp, TransferFile};
 
 /// Set of common methods for local and remote machines. It could
 /// be seen as abstract class, which must be overriden by childs.
@@ -29,6 +39,102 @@ pub trait Machine: TemporaryDirectory + Exec + Scp + Display {
 
     /// Required to maintain a common interface.
     fn connect(&mut self) -> Result<(), CrustError>;
+
+    /// Keeps `count` pre-authenticated standby sessions, ready to replace
+    /// the broken main one or to serve parallel commands. Machines without
+    /// connection have nothing to keep.
+    fn warm_standby(&mut self, _count: usize) -> Result<(), CrustError> {
+        Ok(())
+    }
+
+    /// Applies per-machine settings (default cwd, umask, shell options)
+    /// to every further command. Only machines behind connection use them.
+    fn apply_settings(&mut self, _settings: &SessionSettings) {}
+
+    /// Settings applied to every command (see `apply_settings`).
+    fn settings(&self) -> SessionSettings {
+        SessionSettings::default()
+    }
+
+    /// Executes every further command as passed user (None restores
+    /// the current one).
+    fn run_as(&mut self, _user: Option<&str>) {}
+
+    /// Authorizes with private key kept in memory instead of file.
+    /// Only machines behind connection use it.
+    fn inline_key(&mut self, _key: InlineKey) {}
+
+    /// Arguments of connection which was not established yet. Machines
+    /// without connection have nothing to connect.
+    fn pending_connection(&self) -> Option<ConnectArgs> {
+        None
+    }
+
+    /// Uses session opened outside of machine (see `pending_connection`).
+    fn attach_session(&mut self, _session: Session) {}
+
+    /// Arguments used to connect machine (None for machines without
+    /// connection).
+    fn connect_args(&self) -> Option<ConnectArgs> {
+        None
+    }
+
+    /// Key of machine in timing breakdown - the same one is used by its
+    /// connection (see `ConnectArgs::target`).
+    fn timing_target(&self) -> String {
+        match self.connect_args() {
+            Some(args) => args.target(),
+            None => self.to_string(),
+        }
+    }
+
+    /// Operating system of machine (known after connection is made).
+    fn host_os(&self) -> HostOs {
+        HostOs::Unix
+    }
+
+    /// Session of connected machine (error for machines without one).
+    fn connected_session(&self) -> Result<Session, CrustError> {
+        self.get_session().ok_or_else(|| CrustError {
+            code: ExitCode::Ssh,
+            message: format!("{self} has no session - connect it first"),
+        })
+    }
+
+    /// Lists files of tree (via sftp of session by default).
+    fn list_tree(&self, root: &Path) -> Result<Vec<TreeEntry>, CrustError> {
+        tree::list_remote(&self.connected_session()?, root)
+    }
+
+    /// Checks whether path exists (via sftp of session by default).
+    fn path_exists(&self, path: &Path) -> Result<bool, CrustError> {
+        Ok(self.connected_session()?.sftp()?.stat(path).is_ok())
+    }
+
+    /// Creates directory with its parents (via sftp of session by default).
+    fn create_dir(&self, dir: &Path) -> Result<(), CrustError> {
+        tree::create_remote_dir(&self.connected_session()?.sftp()?, dir)
+    }
+
+    /// Opens file for reading by transfer and gets its size (scp channel
+    /// of session by default).
+    fn open_file(&self, path: &Path) -> Result<(TransferFile, u64), CrustError> {
+        let session = self.connected_session()?;
+        let (channel, stat) = timing::measure(&self.timing_target(), Phase::ChannelOpen, || {
+            session.scp_recv(path)
+        })?;
+        Ok((TransferFile::Remote(channel, session), stat.size()))
+    }
+
+    /// Creates file to be written with `size` bytes by transfer (scp
+    /// channel of session by default).
+    fn create_file(&self, path: &Path, size: u64) -> Result<TransferFile, CrustError> {
+        let session = self.connected_session()?;
+        let channel = timing::measure(&self.timing_target(), Phase::ChannelOpen, || {
+            session.scp_send(path, 0o644, size, None)
+        })?;
+        Ok(TransferFile::Remote(channel, session))
+    }
 }
 
 /// Hashable enum represents a machine ID. There are two options to make
@@ -0,0 +1,239 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use ssh2::Session;
+
+use crate::connection::settings::SessionSettings;
+use crate::error::{CrustError, ExitCode};
+
+/// Operating system of machine. It decides how commands are wrapped,
+/// where temporary directory is created and how paths are passed to sftp.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HostOs {
+    #[default]
+    Unix,
+    Windows(WindowsShell),
+}
+
+/// Default shell of Windows OpenSSH server (`cmd.exe` unless
+/// `DefaultShell` was changed in registry).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowsShell {
+    Cmd,
+    PowerShell,
+}
+
+/// Command printing `Windows_NT` in `cmd.exe` only (other shells do not
+/// expand `%VAR%`).
+const SHELL_PROBE: &str = "echo %OS%";
+
+impl HostOs {
+    /// Detects OS of machine behind freshly opened session. Windows
+    /// OpenSSH is recognized by its banner (so Unix machines do not pay
+    /// for any extra command), then its default shell is probed.
+    pub fn detect(session: &Session) -> Self {
+        let windows = session.banner().is_some_and(is_windows_banner);
+        if !windows {
+            return HostOs::Unix;
+        }
+
+        let shell = match probe(session, SHELL_PROBE) {
+            Some(output) => WindowsShell::from_probe(&output),
+            None => WindowsShell::Cmd,
+        };
+        log::debug!("Detected Windows machine with {shell:?} shell");
+        HostOs::Windows(shell)
+    }
+
+    /// Checks whether machine runs Windows.
+    pub fn is_windows(&self) -> bool {
+        matches!(self, HostOs::Windows(_))
+    }
+
+    /// Wraps command with session settings for shell of the machine.
+    pub fn wrap(&self, settings: &SessionSettings, command: &str) -> Result<String, CrustError> {
+        match self {
+            HostOs::Unix => Ok(settings.wrap(command)),
+            HostOs::Windows(shell) => shell.wrap(settings, command),
+        }
+    }
+
+    /// Converts path into form accepted by sftp server of the machine.
+    /// Windows paths (`C:\dir\file`) get forward slashes and a leading
+    /// slash before drive letter (`/C:/dir/file`), other paths are kept.
+    /// # Example
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use crust::machine::os::{HostOs, WindowsShell};
+    ///
+    /// let os = HostOs::Windows(WindowsShell::Cmd);
+    /// assert_eq!(os.sftp_path(Path::new("C:\\Users\\me")), PathBuf::from("/C:/Users/me"));
+    /// assert_eq!(HostOs::Unix.sftp_path(Path::new("C:\\a")), PathBuf::from("C:\\a"));
+    /// ```
+    pub fn sftp_path(&self, path: &Path) -> PathBuf {
+        if !self.is_windows() {
+            return path.to_path_buf();
+        }
+
+        let path = path.to_string_lossy().replace('\\', "/");
+        let bytes = path.as_bytes();
+        let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+        match has_drive {
+            true => PathBuf::from(format!("/{path}")),
+            false => PathBuf::from(path),
+        }
+    }
+}
+
+impl WindowsShell {
+    fn from_probe(output: &str) -> Self {
+        match output.trim() {
+            "Windows_NT" => WindowsShell::Cmd,
+            _ => WindowsShell::PowerShell,
+        }
+    }
+
+    /// Command printing temporary directory of connected user.
+    pub fn temp_dir_command(&self) -> &'static str {
+        match self {
+            WindowsShell::Cmd => "echo %TEMP%",
+            WindowsShell::PowerShell => "$env:TEMP",
+        }
+    }
+
+    /// Quotes value, so it is passed to shell as a single word.
+    pub fn quote(&self, value: &str) -> String {
+        match self {
+            WindowsShell::Cmd => format!("\"{}\"", value.replace('"', "")),
+            WindowsShell::PowerShell => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+
+    /// Prepends command with settings. Settings which do not exist on
+    /// Windows (umask, switching user, strict mode of cmd) are refused
+    /// instead of being silently ignored.
+    fn wrap(&self, settings: &SessionSettings, command: &str) -> Result<String, CrustError> {
+        let unsupported = |setting: &str| CrustError {
+            code: ExitCode::Parser,
+            message: format!("Setting '{setting}' is not supported on Windows machines"),
+        };
+        if settings.umask.is_some() {
+            return Err(unsupported("umask"));
+        }
+        if settings.run_as.is_some() {
+            return Err(unsupported("run-as"));
+        }
+
+        let mut prefix = Vec::new();
+        match (self, settings.strict) {
+            (_, false) => {}
+            (WindowsShell::PowerShell, true) => {
+                prefix.push(String::from("$ErrorActionPreference = 'Stop'"))
+            }
+            (WindowsShell::Cmd, true) => return Err(unsupported("strict")),
+        }
+        if let Some(cwd) = &settings.cwd {
+            prefix.push(match self {
+                WindowsShell::Cmd => format!("cd /d {}", self.quote(cwd)),
+                WindowsShell::PowerShell => {
+                    format!("Set-Location -LiteralPath {}", self.quote(cwd))
+                }
+            });
+        }
+
+        let separator = match self {
+            WindowsShell::Cmd => " && ",
+            WindowsShell::PowerShell => "; ",
+        };
+        Ok(match prefix.is_empty() {
+            true => command.to_string(),
+            false => format!("{}{separator}{command}", prefix.join(separator)),
+        })
+    }
+}
+
+/// Checks whether SSH banner belongs to Windows OpenSSH server
+/// (e.g. `SSH-2.0-OpenSSH_for_Windows_8.1`).
+fn is_windows_banner(banner: &str) -> bool {
+    banner.to_lowercase().contains("windows")
+}
+
+/// Executes command on session and returns its stdout (None on any error).
+fn probe(session: &Session, command: &str) -> Option<String> {
+    let mut channel = session.channel_session().ok()?;
+    channel.exec(command).ok()?;
+    let mut output = String::new();
+    channel.read_to_string(&mut output).ok()?;
+    let _ = channel.wait_close();
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_windows_banner() {
+        assert!(is_windows_banner("SSH-2.0-OpenSSH_for_Windows_8.1"));
+        assert!(!is_windows_banner("SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13"));
+        assert_eq!(
+            WindowsShell::from_probe("Windows_NT\r\n"),
+            WindowsShell::Cmd
+        );
+        assert_eq!(
+            WindowsShell::from_probe("%OS%\r\n"),
+            WindowsShell::PowerShell
+        );
+    }
+
+    #[test]
+    fn test_sftp_path() {
+        let os = HostOs::Windows(WindowsShell::PowerShell);
+
+        assert_eq!(
+            os.sftp_path(Path::new("C:\\Users\\me\\my file.txt")),
+            PathBuf::from("/C:/Users/me/my file.txt")
+        );
+        assert_eq!(os.sftp_path(Path::new("/C:/a")), PathBuf::from("/C:/a"));
+        assert_eq!(os.sftp_path(Path::new("dir\\a")), PathBuf::from("dir/a"));
+    }
+
+    #[test]
+    fn test_wrap_for_windows_shells() {
+        let settings = SessionSettings {
+            cwd: Some(String::from("C:\\it's here")),
+            strict: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            HostOs::Windows(WindowsShell::PowerShell)
+                .wrap(&settings, "dir")
+                .unwrap(),
+            "$ErrorActionPreference = 'Stop'; Set-Location -LiteralPath 'C:\\it''s here'; dir"
+        );
+        let err = HostOs::Windows(WindowsShell::Cmd)
+            .wrap(&settings, "dir")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message,
+            "Setting 'strict' is not supported on Windows machines"
+        );
+
+        let settings = SessionSettings {
+            cwd: Some(String::from("C:\\srv")),
+            ..Default::default()
+        };
+        assert_eq!(
+            HostOs::Windows(WindowsShell::Cmd)
+                .wrap(&settings, "dir")
+                .unwrap(),
+            "cd /d \"C:\\srv\" && dir"
+        );
+        assert_eq!(
+            HostOs::Unix.wrap(&settings, "ls").unwrap(),
+            settings.wrap("ls")
+        );
+    }
+}
@@ -1,15 +1,19 @@
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use uuid::Uuid;
 
+use crate::connection::key::InlineKey;
 use crate::connection::manager::{MachinesManager, MachinesManagerMethods};
-use crate::connection::{SshConnection, SSH};
+use crate::connection::settings::SessionSettings;
+use crate::connection::{ConnectArgs, SshConnection, SSH};
 use crate::error::{CrustError, ExitCode};
 use crate::exec::Exec;
 use crate::interfaces::response::CrustResult;
 use crate::interfaces::tmpdir::TemporaryDirectory;
+use crate::machine::os::HostOs;
+use crate::machine::utilities;
 use crate::machine::{Machine, MachineID, MachineType};
 use crate::scp::Scp;
 
@@ -83,6 +87,20 @@ impl RemoteMachine {
         }
     }
 
+    /// Creates a not connected copy of machine registered under alias, e.g.
+    /// for operation which runs aside of shared manager (on another thread).
+    pub fn copy_of(alias: &str, args: ConnectArgs, settings: &SessionSettings) -> Self {
+        let mut ssh = SshConnection::new(args.username(), args.hostname(), None, None, args.port());
+        ssh.connect_args = Some(args);
+        ssh.set_settings(settings.clone());
+        Self {
+            ssh: RefCell::new(ssh),
+            tmpdir: None,
+            should_remove_tmpdir: true,
+            id: RemoteMachine::generate_custom_id(alias),
+        }
+    }
+
     /// Tries to get a machine from manager by machine alias.
     /// In case when passed alias is not registered - return None, otherwise
     /// returns reference to machine from manager.
@@ -99,6 +117,17 @@ impl RemoteMachine {
         &self.ssh
     }
 
+    /// Makes sure that machine has a working session. Broken session is
+    /// replaced by a standby one (if any was kept), otherwise a new
+    /// connection is established.
+    fn ensure_connected(&self) -> Result<(), CrustError> {
+        let connected = self.ssh.borrow().is_connected();
+        if connected || self.ssh.borrow_mut().rotate() {
+            return Ok(());
+        }
+        self.ssh.borrow_mut().connect()
+    }
+
     /// Private method to generate id for remote machine.
     fn generate_default_id(user: &str, host: &str, port: u16) -> MachineID {
         MachineID::Default(
@@ -130,7 +159,48 @@ impl Machine for RemoteMachine {
     }
 
     fn connect(&mut self) -> Result<(), CrustError> {
-        self.ssh.borrow_mut().connect()
+        self.ensure_connected()
+    }
+
+    fn warm_standby(&mut self, count: usize) -> Result<(), CrustError> {
+        self.ssh.borrow().warm_standby(count);
+        Ok(())
+    }
+
+    fn apply_settings(&mut self, settings: &SessionSettings) {
+        self.ssh.borrow_mut().set_settings(settings.clone());
+    }
+
+    fn settings(&self) -> SessionSettings {
+        self.ssh.borrow().settings().clone()
+    }
+
+    fn run_as(&mut self, user: Option<&str>) {
+        let mut settings = self.ssh.borrow().settings().clone();
+        if settings.run_as.as_deref() != user {
+            settings.run_as = user.map(String::from);
+            self.ssh.borrow_mut().set_settings(settings);
+        }
+    }
+
+    fn inline_key(&mut self, key: InlineKey) {
+        self.ssh.borrow_mut().set_inline_key(key);
+    }
+
+    fn pending_connection(&self) -> Option<ConnectArgs> {
+        self.ssh.borrow().pending_connection()
+    }
+
+    fn attach_session(&mut self, session: ssh2::Session) {
+        self.ssh.borrow_mut().set_session(session);
+    }
+
+    fn connect_args(&self) -> Option<ConnectArgs> {
+        self.ssh.borrow().connect_args.clone()
+    }
+
+    fn host_os(&self) -> HostOs {
+        self.ssh.borrow().os()
     }
 }
 
@@ -157,7 +227,21 @@ impl TemporaryDirectory for RemoteMachine {
 
         let sftp = self.get_session().unwrap().sftp()?;
 
-        let temp_dir_path = PathBuf::from(format!("/tmp/tmp.{}", Uuid::new_v4().as_u128()));
+        let name = format!("tmp.{}", Uuid::new_v4().as_u128());
+        let temp_dir_path = match self.host_os() {
+            HostOs::Unix => PathBuf::from("/tmp").join(name),
+            HostOs::Windows(shell) => {
+                let output = self.exec(shell.temp_dir_command())?;
+                let temp = output.stdout().trim();
+                if !output.is_success() || temp.is_empty() {
+                    return Err(CrustError {
+                        code: ExitCode::Remote,
+                        message: format!("Can not find temporary directory on {self}"),
+                    });
+                }
+                self.host_os().sftp_path(Path::new(temp)).join(name)
+            }
+        };
         sftp.mkdir(&temp_dir_path, 0o755)?;
 
         self.tmpdir = Some(temp_dir_path.clone());
@@ -181,27 +265,26 @@ impl TemporaryDirectory for RemoteMachine {
     }
 
     fn remove_tmpdir(&self) -> Result<(), CrustError> {
-        //TODO: Workaround to remove direcotry with content
-        self.exec(&format!("rm -rf {}", self.get_tmpdir().display()))?;
-        Ok(())
+        utilities::remove_tree(self, self.get_tmpdir())
     }
 }
 
 /// Add `execute` method for RemoteMachine
 impl Exec for RemoteMachine {
     fn exec(&self, cmd: &str) -> Result<CrustResult, CrustError> {
-        if !self.ssh.borrow().is_connected() {
-            self.ssh.borrow_mut().connect()?;
-        }
+        self.ensure_connected()?;
         self.ssh.borrow().execute(cmd)
     }
 
     fn exec_rt(&self, cmd: &str, merge_pipes: bool) -> Result<CrustResult, CrustError> {
-        if !self.ssh.borrow().is_connected() {
-            self.ssh.borrow_mut().connect()?;
-        }
+        self.ensure_connected()?;
         self.ssh.borrow().execute_rt(cmd, merge_pipes)
     }
+
+    fn exec_with_input(&self, cmd: &str, input: &str) -> Result<CrustResult, CrustError> {
+        self.ensure_connected()?;
+        self.ssh.borrow().execute_with_input(cmd, input)
+    }
 }
 
 /// Add 'scp' method for RemoteMachine
@@ -414,12 +497,12 @@ mod tests {
         let (user, host, pass, pkey, port) = connect_args();
         let machine = RemoteMachine::new(&user, &host, pass, pkey, port);
 
-        assert_eq!(machine.tmpdir_exists(), false);
+        assert!(!machine.tmpdir_exists());
         assert_eq!(
             machine.get_id(),
             &MachineID::new(Some(user), Some(host), Some(port))
         );
-        assert_eq!(machine.can_be_removed(), true);
+        assert!(machine.can_be_removed());
         assert_eq!(machine.mtype(), MachineType::RemoteMachine);
     }
 
@@ -434,7 +517,7 @@ mod tests {
             RemoteMachine::get_or_create(user, host, pass, pkey, port, None, &mut manager);
 
         assert_eq!(manager.size(), 1);
-        assert_eq!(machine.borrow().exec("pwd").unwrap().is_success(), true);
+        assert!(machine.borrow().exec("pwd").unwrap().is_success());
     }
 
     #[serial]
@@ -0,0 +1,324 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{CrustError, ExitCode};
+use crate::exec::env::ExecEnv;
+use crate::machine::{which, Machine, MachineType};
+use crate::utils::shell::quote_path;
+
+/// Number of files hashed by a single command invoke.
+const HASH_BATCH: usize = 200;
+
+/// Utilities already probed on machines (by machine name), so probing
+/// is done once per machine.
+static PROBED: Mutex<Vec<(String, Utilities)>> = Mutex::new(Vec::new());
+
+/// Tool computing sha256 of files. Every command variant prints hash
+/// as the first word of line.
+/// - Sha256sum: coreutils and busybox
+/// - Shasum: perl script available on macOS
+/// - BsdSha256: `sha256 -r` of FreeBSD/OpenBSD
+/// - Openssl: `openssl dgst -sha256 -r`
+/// - Sftp: no tool available - files are read (via sftp) and hashed locally
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashTool {
+    Sha256sum,
+    Shasum,
+    BsdSha256,
+    Openssl,
+    Sftp,
+}
+
+impl HashTool {
+    /// Tools in order of preference with names of their executables.
+    const PROBED: &'static [(&'static str, HashTool)] = &[
+        ("sha256sum", HashTool::Sha256sum),
+        ("shasum", HashTool::Shasum),
+        ("sha256", HashTool::BsdSha256),
+        ("openssl", HashTool::Openssl),
+    ];
+
+    /// Command hashing passed (already quoted) files.
+    fn command(&self, files: &str) -> Option<String> {
+        match self {
+            HashTool::Sha256sum => Some(format!("sha256sum -- {files}")),
+            HashTool::Shasum => Some(format!("shasum -a 256 -- {files}")),
+            HashTool::BsdSha256 => Some(format!("sha256 -r -- {files}")),
+            HashTool::Openssl => Some(format!("openssl dgst -sha256 -r -- {files}")),
+            HashTool::Sftp => None,
+        }
+    }
+}
+
+/// Optional tools probed together with hash tools.
+const OPTIONAL: &[&str] = &["tar", "unzip"];
+
+/// Variants of core utilities used by crust which are available on
+/// machine (they differ between GNU, busybox and BSD userlands).
+/// - tar, unzip: archives can be listed and extracted on machine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Utilities {
+    pub hash: HashTool,
+    pub tar: bool,
+    pub unzip: bool,
+}
+
+impl Utilities {
+    /// Names of probed executables.
+    fn probed_names() -> Vec<&'static str> {
+        HashTool::PROBED
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(OPTIONAL.iter().copied())
+            .collect()
+    }
+
+    /// Selects the most preferred variants from names of found executables.
+    fn from_found(found: &[&str]) -> Self {
+        let hash = HashTool::PROBED
+            .iter()
+            .find(|(name, _)| found.contains(name))
+            .map_or(HashTool::Sftp, |(_, tool)| *tool);
+        Self {
+            hash,
+            tar: found.contains(&"tar"),
+            unzip: found.contains(&"unzip"),
+        }
+    }
+}
+
+/// Gets utilities available on machine (probed at the first use).
+/// Windows machines and machines which can not be probed get pure sftp
+/// fallbacks.
+pub fn utilities(machine: &dyn Machine) -> Utilities {
+    let name = machine.to_string();
+    if machine.host_os().is_windows() {
+        // POSIX tools are missing there, sftp works everywhere
+        return Utilities::from_found(&[]);
+    }
+    if let Some((_, utilities)) = PROBED.lock().unwrap().iter().find(|(n, _)| *n == name) {
+        return *utilities;
+    }
+
+    let utilities = match which::resolve(machine, &Utilities::probed_names(), &ExecEnv::default()) {
+        Ok(resolved) => {
+            let found = resolved
+                .iter()
+                .filter(|r| r.path.is_some())
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>();
+            Utilities::from_found(&found)
+        }
+        Err(e) => {
+            log::warn!("Can not probe utilities of {name}: {e}");
+            Utilities::from_found(&[])
+        }
+    };
+    log::debug!("Utilities of {name}: {utilities:?}");
+    PROBED.lock().unwrap().push((name, utilities));
+    utilities
+}
+
+/// Computes sha256 of passed files on machine (in the same order).
+pub fn sha256(machine: &dyn Machine, files: &[PathBuf]) -> Result<Vec<String>, CrustError> {
+    let tool = utilities(machine).hash;
+    let mut result = Vec::new();
+    for batch in files.chunks(HASH_BATCH) {
+        let args = batch
+            .iter()
+            .map(|f| quote_path(f))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let command = match tool.command(&args) {
+            Some(command) => command,
+            None => {
+                for file in batch {
+                    result.push(sha256_direct(machine, file)?);
+                }
+                continue;
+            }
+        };
+
+        let output = machine.exec(&command)?;
+        if !output.is_success() {
+            return Err(CrustError {
+                code: ExitCode::Remote,
+                message: format!("Can not compute hashes on {machine}: {}", output.stderr()),
+            });
+        }
+        let hashes = parse_hashes(output.stdout());
+        match hashes.len() == batch.len() {
+            true => result.extend(hashes),
+            false => {
+                // Names with new lines can not be matched with output lines
+                log::debug!(
+                    "Got {} hashes of {} files on {machine} - hashing them one by one",
+                    hashes.len(),
+                    batch.len()
+                );
+                for file in batch {
+                    result.push(sha256_direct(machine, file)?);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Hashes from output of hash tool. Tools escape names with backslash or
+/// new line and mark such lines with leading `\`, which is not a part of
+/// hash. Lines which do not start with hash (rest of unescaped name with
+/// new line) are skipped.
+fn parse_hashes(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|word| word.strip_prefix('\\').unwrap_or(word))
+        .filter(|word| word.len() == 64 && word.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(String::from)
+        .collect()
+}
+
+/// Reads file (via sftp on machines behind connection) and computes
+/// its sha256 locally.
+fn sha256_direct(machine: &dyn Machine, file: &Path) -> Result<String, CrustError> {
+    let mut reader: Box<dyn Read> = match machine.get_session() {
+        Some(session) => Box::new(session.sftp()?.open(file)?),
+        None => Box::new(std::fs::File::open(file)?),
+    };
+
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            len => hasher.update(&buf[..len]),
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Removes directory with its content. When `rm` can not be used (e.g.
+/// restricted shell or Windows machine), tree is removed entry by entry
+/// via sftp.
+pub fn remove_tree(machine: &dyn Machine, path: &Path) -> Result<(), CrustError> {
+    if !machine.host_os().is_windows() {
+        match machine.exec(&format!("rm -rf -- {}", quote_path(path))) {
+            Ok(output) if output.is_success() => return Ok(()),
+            Ok(output) => log::debug!("rm failed on {machine}: {}", output.stderr()),
+            Err(e) => log::debug!("rm failed on {machine}: {e}"),
+        }
+    }
+
+    match (machine.mtype(), machine.get_session()) {
+        (MachineType::RemoteMachine, Some(session)) => {
+            let sftp = session.sftp()?;
+            remove_tree_sftp(&sftp, path)
+        }
+        _ => Ok(std::fs::remove_dir_all(path)?),
+    }
+}
+
+fn remove_tree_sftp(sftp: &ssh2::Sftp, path: &Path) -> Result<(), CrustError> {
+    for (entry, stat) in sftp.readdir(path)? {
+        match stat.is_dir() {
+            true => remove_tree_sftp(sftp, &entry)?,
+            false => sftp.unlink(&entry)?,
+        }
+    }
+    sftp.rmdir(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::local::LocalMachine;
+
+    #[test]
+    fn test_select_hash_tool() {
+        assert_eq!(
+            Utilities::from_found(&["sha256sum", "openssl"]).hash,
+            HashTool::Sha256sum
+        );
+        assert_eq!(
+            Utilities::from_found(&["openssl", "sha256"]).hash,
+            HashTool::BsdSha256
+        );
+        assert_eq!(Utilities::from_found(&[]).hash, HashTool::Sftp);
+        assert_eq!(
+            Utilities::from_found(&["shasum", "tar"]),
+            Utilities {
+                hash: HashTool::Shasum,
+                tar: true,
+                unzip: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_probe_utilities() {
+        assert_eq!(
+            utilities(&LocalMachine::default()).hash,
+            HashTool::Sha256sum
+        );
+    }
+
+    #[test]
+    fn test_direct_hash_matches_tool() {
+        let path = PathBuf::from("/tmp/crust_test_utilities 'hash'");
+        std::fs::write(&path, "content of file\n").unwrap();
+        let machine = LocalMachine::default();
+
+        let direct = sha256_direct(&machine, &path).unwrap();
+        assert_eq!(
+            sha256(&machine, std::slice::from_ref(&path)).unwrap(),
+            vec![direct.clone()]
+        );
+        assert_eq!(
+            direct,
+            "1410fe2fd9a62638a6e005995805fbc76678f610a6dd2e4355174a0891c53f23"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_hash_of_escaped_names() {
+        let paths = [
+            PathBuf::from("/tmp/crust_test_utilities back\\slash"),
+            PathBuf::from("/tmp/crust_test_utilities new\nline"),
+            PathBuf::from("/tmp/crust_test_utilities plain"),
+        ];
+        paths
+            .iter()
+            .for_each(|p| std::fs::write(p, "content of file\n").unwrap());
+
+        let hashes = sha256(&LocalMachine::default(), &paths).unwrap();
+        paths.iter().for_each(|p| std::fs::remove_file(p).unwrap());
+        assert_eq!(
+            hashes,
+            vec!["1410fe2fd9a62638a6e005995805fbc76678f610a6dd2e4355174a0891c53f23"; 3]
+        );
+    }
+
+    #[test]
+    fn test_parse_hashes_of_unescaped_new_lines() {
+        let hash = "1410fe2fd9a62638a6e005995805fbc76678f610a6dd2e4355174a0891c53f23";
+        let stdout = format!("{hash} *new\nline\n\\{hash}  back\\\\slash\n");
+
+        assert_eq!(parse_hashes(&stdout), vec![hash, hash]);
+    }
+
+    #[test]
+    fn test_remove_tree() {
+        let dir = PathBuf::from("/tmp/crust_test_remove_tree");
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::write(dir.join("a/b/file"), "x").unwrap();
+
+        remove_tree(&LocalMachine::default(), &dir).unwrap();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::error::{CrustError, ExitCode};
+use crate::exec::env::ExecEnv;
+use crate::machine::Machine;
+use crate::utils::shell::quote;
+
+/// Number of names suggested for command which was not found.
+const SUGGESTIONS: usize = 3;
+
+/// Command name resolved against PATH of machine.
+/// - path: path of executable, name of shell builtin or definition of
+///   alias (as printed by `command -v`); None when name was not found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    pub name: String,
+    pub path: Option<String>,
+}
+
+/// Name of command run by command line (the first word after variable
+/// assignments). None when it is not a plain name or path (e.g. it is
+/// quoted, contains expansions or placeholders).
+/// # Example
+/// ```
+/// use crust::machine::which::command_name;
+///
+/// assert_eq!(command_name("LANG=C sort -u data.txt"), Some("sort"));
+/// assert_eq!(command_name("./deploy.sh --dry-run"), Some("./deploy.sh"));
+/// assert_eq!(command_name("$EDITOR notes"), None);
+/// ```
+pub fn command_name(cmd: &str) -> Option<&str> {
+    let word = cmd.split_whitespace().find(|word| !is_assignment(word))?;
+    word.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '+' | '/'))
+        .then_some(word)
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(key, _)| {
+        !key.is_empty()
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Resolves names against PATH of machine (`command -v`) - as they would
+/// be resolved by command run with passed environment.
+pub fn resolve(
+    machine: &dyn Machine,
+    names: &[&str],
+    env: &ExecEnv,
+) -> Result<Vec<Resolution>, CrustError> {
+    if machine.host_os().is_windows() {
+        return Err(CrustError {
+            code: ExitCode::Remote,
+            message: format!("Commands can not be resolved on Windows machine {machine}"),
+        });
+    }
+
+    let quoted = names.iter().map(|n| quote(n)).collect::<Vec<_>>().join(" ");
+    let command = format!(
+        "for name in {quoted}; do printf '%s\\t%s\\n' \"$name\" \"$(command -v \"$name\" 2>/dev/null)\"; done"
+    );
+    let output = machine.exec(&env.wrap(&command))?;
+    if !output.is_success() {
+        return Err(CrustError {
+            code: ExitCode::Remote,
+            message: format!(
+                "Can not resolve commands on {machine}: {}",
+                output.stderr().trim()
+            ),
+        });
+    }
+
+    Ok(output
+        .stdout()
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(name, path)| Resolution {
+            name: name.to_string(),
+            path: (!path.is_empty()).then(|| path.to_string()),
+        })
+        .collect())
+}
+
+/// Resolves a single command name. Missing command is reported with
+/// similar names available in PATH.
+pub fn which(machine: &dyn Machine, name: &str, env: &ExecEnv) -> Result<String, CrustError> {
+    let resolved = resolve(machine, &[name], env)?
+        .into_iter()
+        .find_map(|r| r.path);
+    match resolved {
+        Some(path) => Ok(path),
+        None => Err(not_found(machine, name, env)),
+    }
+}
+
+/// Error of command missing on machine, with suggestions of similar
+/// executables (when PATH can be listed).
+pub fn not_found(machine: &dyn Machine, name: &str, env: &ExecEnv) -> CrustError {
+    let listing =
+        "printf '%s\\n' \"$PATH\"; IFS=:; for dir in $PATH; do ls -1 \"$dir\" 2>/dev/null; done";
+    let (path, suggestions) = match machine.exec(&env.wrap(listing)) {
+        Ok(output) => {
+            let mut lines = output.stdout().lines();
+            let path = lines.next().unwrap_or_default().to_string();
+            (path, suggest(name, lines))
+        }
+        Err(e) => {
+            log::debug!("Can not list PATH of {machine}: {e}");
+            (String::new(), Vec::new())
+        }
+    };
+
+    let hint = match suggestions.is_empty() {
+        true => format!("PATH: {path}"),
+        false => format!("did you mean {}?", suggestions.join(", ")),
+    };
+    CrustError {
+        code: ExitCode::Remote,
+        message: format!("Command '{name}' not found on host {machine}: {hint}"),
+    }
+}
+
+/// The most similar names (by edit distance, names starting with passed
+/// one go first among equally distant ones).
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut scored = candidates
+        .map(|candidate| {
+            (
+                distance(name, candidate),
+                !candidate.starts_with(name),
+                candidate,
+            )
+        })
+        .filter(|(d, other_prefix, _)| *d <= max_distance || (!other_prefix && name.len() > 2))
+        .collect::<Vec<_>>();
+    scored.sort();
+    scored.dedup_by_key(|(_, _, candidate)| *candidate);
+    scored
+        .into_iter()
+        .take(SUGGESTIONS)
+        .map(|(_, _, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Edit distance of two words (insertions, deletions, substitutions and
+/// transpositions of adjacent characters - the usual typos).
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::local::LocalMachine;
+
+    #[test]
+    fn test_resolve_names_on_machine() {
+        let resolved = resolve(
+            &LocalMachine::default(),
+            &["sh", "cd", "crust-not-existing-command"],
+            &ExecEnv::default(),
+        )
+        .unwrap();
+
+        assert_eq!(resolved.len(), 3);
+        assert!(resolved[0].path.as_ref().unwrap().ends_with("/sh"));
+        assert_eq!(resolved[1].path.as_deref(), Some("cd"));
+        assert_eq!(resolved[2].path, None);
+    }
+
+    #[test]
+    fn test_missing_command_with_suggestions() {
+        let err = which(&LocalMachine::default(), "lss", &ExecEnv::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(err.code, ExitCode::Remote);
+        assert!(err.message.starts_with("Command 'lss' not found on host "));
+        let (_, suggestions) = err.message.split_once("did you mean ").unwrap();
+        assert!(suggestions
+            .trim_end_matches('?')
+            .split(", ")
+            .any(|s| s == "ls"));
+    }
+
+    #[test]
+    fn test_suggest_similar_names() {
+        let candidates = ["python3", "python3.11", "pydoc3", "perl", "python3"];
+
+        assert_eq!(suggest("pyhton3", candidates.into_iter()), vec!["python3"]);
+        assert_eq!(
+            suggest("python", candidates.into_iter()),
+            vec!["python3", "python3.11"]
+        );
+        assert_eq!(suggest("gti", ["git", "gzip"].into_iter()), vec!["git"]);
+        assert!(suggest("kubectl", candidates.into_iter()).is_empty());
+    }
+}
@@ -1,6 +1,19 @@
-use crate::exec::parser::ExecArgs;
-use crate::interfaces::parser::Validation;
+use crate::archive::parser::{ArchiveAction, ArchiveArgs};
+use crate::budget::{Budget, OperationClass};
+use crate::cache::parser::CacheArgs;
+use crate::connection::hostkey::HostKeyPolicy;
+use crate::doctor::parser::DoctorArgs;
+use crate::exec::parser::{ExecArgs, WhichArgs};
+use crate::fleet::parser::{FleetAction, FleetArgs, RunArgs};
+use crate::interfaces::parser::{Problem, Validation};
+use crate::inventory::parser::MachineArgs;
+use crate::job::parser::{JobAction, JobArgs};
+use crate::plan::parser::{ApplyArgs, PlanArgs};
+#[cfg(feature = "results")]
+use crate::results::parser::ResultsArgs;
 use crate::scp::parser::ScpArgs;
+use crate::session::parser::SessionArgs;
+use crate::stage::parser::WithArgs;
 use clap::{Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
 
@@ -16,21 +29,81 @@ pub struct AppArgs {
 
     #[clap(short, long, default_value = "false")]
     pub background: bool,
+
+    /// Number of pre-authenticated standby sessions kept for every
+    /// frequently used machine (background mode only)
+    #[clap(long, default_value = "0")]
+    pub standby: usize,
+
+    /// Number of operations run at once by background process (interactive
+    /// ones are served one by one by its main loop, bulk ones run aside)
+    #[clap(long, default_value = "4")]
+    pub slots: usize,
+
+    /// Number of bulk operations (transfers) run at once by background
+    /// process - the remaining slots are kept for interactive ones
+    #[clap(long, default_value = "2")]
+    pub bulk_slots: usize,
+
+    /// Prints timing breakdown of remote operations (dns, connect,
+    /// handshake, auth, channel open, command, transfer, stream drain, close)
+    #[clap(long, default_value = "false")]
+    pub trace_timing: bool,
+
+    /// Verification of remote host keys (with known_hosts file). Default
+    /// is `no`, in background mode the policy background process was
+    /// started with
+    #[clap(long, value_enum)]
+    pub host_key_policy: Option<HostKeyPolicy>,
+
+    /// Minutes without use after which machines tagged `sensitive` are
+    /// locked and need `machine unlock` (background mode only)
+    #[clap(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub idle_lock: Option<u64>,
+
+    /// Reports all problems of arguments at once (with hints how to fix
+    /// them) instead of stopping at the first one
+    #[clap(long, default_value = "false")]
+    pub all_errors: bool,
+
+    /// Words of invoked command (without executable), kept in saved plans
+    #[clap(skip)]
+    pub command: Vec<String>,
 }
 
 impl AppArgs {
     pub fn get_operation(&self) -> Option<&Operation> {
         self.operation.as_ref()
     }
+
+    /// Concurrency budget of background process.
+    pub fn budget(&self) -> Budget {
+        Budget {
+            slots: self.slots,
+            bulk: self.bulk_slots,
+        }
+    }
 }
 
 impl Validation for AppArgs {
     fn validate(&mut self) -> Result<(), crate::error::CrustError> {
-        if self.operation.is_some() {
-            self.operation.as_mut().unwrap().validate()?;
+        self.budget().validate()?;
+        if let Some(operation) = self.operation.as_mut() {
+            operation.validate()?;
         }
         Ok(())
     }
+
+    fn problems(&mut self) -> Vec<Problem> {
+        let mut problems = match self.budget().validate() {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![Problem::from(e)],
+        };
+        if let Some(operation) = self.operation.as_mut() {
+            problems.extend(operation.problems());
+        }
+        problems
+    }
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -39,7 +112,78 @@ pub enum Operation {
     Exec(ExecArgs),
 
     /// Copies data between two machines
-    Scp(ScpArgs),
+    Scp(Box<ScpArgs>),
+
+    /// Manages local download cache
+    Cache(CacheArgs),
+
+    /// Diagnoses connection with remote machine
+    Doctor(DoctorArgs),
+
+    /// Manages current background session
+    Session(SessionArgs),
+
+    /// Runs operations on many machines selected from inventory
+    Fleet(FleetArgs),
+
+    /// Runs local command on remote files (downloaded to temporary directory)
+    With(WithArgs),
+
+    /// Manages registry of machines (inventory)
+    Machine(MachineArgs),
+
+    /// Manages jobs scheduled in background process
+    Job(JobArgs),
+
+    /// Manages multi-host runs in progress
+    Run(RunArgs),
+
+    /// Runs operation from reviewed plan (saved with --plan-out)
+    Apply(ApplyArgs),
+
+    /// Lists and extracts members of remote archives
+    Archive(ArchiveArgs),
+
+    /// Resolves commands against PATH of machine
+    Which(WhichArgs),
+
+    /// Queries results stored by `fleet exec --store`
+    #[cfg(feature = "results")]
+    Results(ResultsArgs),
+}
+
+impl Operation {
+    /// Getter for plan arguments of operations which support dry-run.
+    pub fn plan_args(&self) -> Option<&PlanArgs> {
+        match self {
+            Operation::Scp(args) => Some(&args.plan),
+            Operation::Fleet(args) => match &args.action {
+                FleetAction::Exec(exec_args) => Some(&exec_args.plan),
+                FleetAction::Hosts(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Class of operation (in background process bulk ones run aside,
+    /// within their own budget).
+    pub fn class(&self) -> OperationClass {
+        match self {
+            Operation::Scp(args) if args.schedule.is_none() && !args.plan.is_requested() => {
+                OperationClass::Bulk
+            }
+            Operation::With(_) => OperationClass::Bulk,
+            Operation::Job(args) => match args.action {
+                JobAction::Run { .. } => OperationClass::Bulk,
+                JobAction::List => OperationClass::Interactive,
+            },
+            Operation::Archive(args) => match args.action {
+                ArchiveAction::Extract(_) => OperationClass::Bulk,
+                ArchiveAction::Ls(_) => OperationClass::Interactive,
+            },
+            _ => OperationClass::Interactive,
+        }
+    }
 }
 
 impl Validation for Operation {
@@ -47,7 +191,40 @@ impl Validation for Operation {
         match self {
             Operation::Exec(args) => args.validate()?,
             Operation::Scp(args) => args.validate()?,
+            Operation::Cache(args) => args.validate()?,
+            Operation::Doctor(args) => args.validate()?,
+            Operation::Session(args) => args.validate()?,
+            Operation::Fleet(args) => args.validate()?,
+            Operation::With(args) => args.validate()?,
+            Operation::Machine(args) => args.validate()?,
+            Operation::Job(args) => args.validate()?,
+            Operation::Run(args) => args.validate()?,
+            Operation::Apply(args) => args.validate()?,
+            Operation::Archive(args) => args.validate()?,
+            Operation::Which(args) => args.validate()?,
+            #[cfg(feature = "results")]
+            Operation::Results(args) => args.validate()?,
         }
         Ok(())
     }
+
+    fn problems(&mut self) -> Vec<Problem> {
+        match self {
+            Operation::Exec(args) => args.problems(),
+            Operation::Scp(args) => args.problems(),
+            Operation::Cache(args) => args.problems(),
+            Operation::Doctor(args) => args.problems(),
+            Operation::Session(args) => args.problems(),
+            Operation::Fleet(args) => args.problems(),
+            Operation::With(args) => args.problems(),
+            Operation::Machine(args) => args.problems(),
+            Operation::Job(args) => args.problems(),
+            Operation::Run(args) => args.problems(),
+            Operation::Apply(args) => args.problems(),
+            Operation::Archive(args) => args.problems(),
+            Operation::Which(args) => args.problems(),
+            #[cfg(feature = "results")]
+            Operation::Results(args) => args.problems(),
+        }
+    }
 }
@@ -0,0 +1,299 @@
+use std::path::Path;
+
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CrustError, ExitCode};
+
+pub mod parser;
+
+/// How dangerous planned action is.
+/// - Low: nothing existing is changed
+/// - Medium: action may take long or changes state of machine
+/// - High: existing data is overwritten or removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+impl std::fmt::Display for Risk {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Risk::Low => write!(f, "low"),
+            Risk::Medium => write!(f, "medium"),
+            Risk::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Single action which operation is going to perform.
+/// - action: kind of action (e.g. `copy`, `exec`)
+/// - target: machine(s) affected by action
+/// - size: number of transferred bytes (if any)
+/// - reason: why action got its risk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedAction {
+    pub action: String,
+    pub target: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub risk: Risk,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Actions intended by operation, produced instead of running it (dry-run).
+/// Saved plan keeps command which produced it, so it can be reviewed and
+/// run later with `crust apply`.
+/// # Example
+/// ```
+/// use crust::plan::{Plan, PlannedAction, Risk};
+///
+/// let plan = Plan::new(vec![PlannedAction {
+///     action: String::from("exec"),
+///     target: String::from("web-1"),
+///     description: String::from("uptime"),
+///     size: None,
+///     risk: Risk::Low,
+///     reason: None,
+/// }]);
+/// assert_eq!(plan.risk(), Risk::Low);
+/// assert!(plan.render().starts_with("Plan: 1 actions (risk: low)"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    #[serde(default)]
+    pub command: Vec<String>,
+    pub actions: Vec<PlannedAction>,
+}
+
+impl Plan {
+    /// Creates a plan of passed actions.
+    pub fn new(actions: Vec<PlannedAction>) -> Self {
+        Self {
+            command: Vec::new(),
+            actions,
+        }
+    }
+
+    /// The highest risk of planned actions.
+    pub fn risk(&self) -> Risk {
+        self.actions
+            .iter()
+            .map(|a| a.risk)
+            .max()
+            .unwrap_or(Risk::Low)
+    }
+
+    /// Human readable plan.
+    pub fn render(&self) -> String {
+        let mut lines = vec![format!(
+            "Plan: {} actions (risk: {})",
+            self.actions.len(),
+            self.risk()
+        )];
+        for action in &self.actions {
+            let mut line = format!(
+                "  [{}] {} {}: {}",
+                action.risk, action.action, action.target, action.description
+            );
+            if let Some(size) = action.size {
+                line.push_str(&format!(" ({})", HumanBytes(size)));
+            }
+            if let Some(reason) = &action.reason {
+                line.push_str(&format!(" - {reason}"));
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    /// Serializes plan into (pretty) JSON.
+    pub fn to_json(&self) -> Result<String, CrustError> {
+        serde_json::to_string_pretty(self).map_err(|e| CrustError {
+            code: ExitCode::Internal,
+            message: format!("Can not serialize plan: {e}"),
+        })
+    }
+
+    /// Reads plan saved as JSON.
+    pub fn load(path: &Path) -> Result<Self, CrustError> {
+        let content = std::fs::read_to_string(path).map_err(|e| CrustError {
+            code: ExitCode::Local,
+            message: format!("Can not read plan '{}': {e}", path.display()),
+        })?;
+        serde_json::from_str(&content).map_err(|e| CrustError {
+            code: ExitCode::Parser,
+            message: format!("Invalid plan '{}': {e}", path.display()),
+        })
+    }
+
+    /// Saves plan as JSON. Plans with passwords in command are refused,
+    /// as they would be written to disk in plain text.
+    pub fn save(&self, path: &Path) -> Result<(), CrustError> {
+        if self.command.iter().any(|w| w.starts_with("--password")) {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Plan with password in arguments can not be saved. Use private key or machine alias instead".to_string(),
+            });
+        }
+        std::fs::write(path, self.to_json()? + "\n")?;
+        Ok(())
+    }
+
+    /// Checks whether approved plan still describes what operation is
+    /// going to do (e.g. files were not changed in the meantime).
+    pub fn check_current(&self, current: &Plan) -> Result<(), CrustError> {
+        if self.actions == current.actions {
+            return Ok(());
+        }
+        let changed = self
+            .actions
+            .iter()
+            .filter(|a| !current.actions.contains(a))
+            .count()
+            + current
+                .actions
+                .iter()
+                .filter(|a| !self.actions.contains(a))
+                .count();
+        Err(CrustError {
+            code: ExitCode::Parser,
+            message: format!(
+                "Plan is outdated ({changed} actions differ from the current state). Create a new plan:\n{}",
+                current.render()
+            ),
+        })
+    }
+}
+
+/// Commands (or their parts) which make executed command risky.
+const RISKY_COMMANDS: &[(&str, Risk, &str)] = &[
+    ("rm -rf", Risk::High, "removes files recursively"),
+    ("rm -fr", Risk::High, "removes files recursively"),
+    ("rm -r", Risk::High, "removes files recursively"),
+    ("mkfs", Risk::High, "creates filesystem"),
+    ("dd ", Risk::High, "writes raw data"),
+    ("shutdown", Risk::High, "stops machine"),
+    ("reboot", Risk::High, "restarts machine"),
+    ("poweroff", Risk::High, "stops machine"),
+    ("kill", Risk::High, "kills processes"),
+    ("pkill", Risk::High, "kills processes"),
+    ("truncate", Risk::High, "truncates files"),
+    ("rm ", Risk::Medium, "removes files"),
+    ("mv ", Risk::Medium, "moves files"),
+    ("chmod", Risk::Medium, "changes permissions"),
+    ("chown", Risk::Medium, "changes owners"),
+    ("systemctl", Risk::Medium, "manages services"),
+    ("service ", Risk::Medium, "manages services"),
+    ("apt", Risk::Medium, "manages packages"),
+    ("yum", Risk::Medium, "manages packages"),
+    ("dnf", Risk::Medium, "manages packages"),
+    (">", Risk::Medium, "overwrites file by redirection"),
+];
+
+/// Classifies risk of shell command with a simple keyword lookup
+/// (the riskiest match wins). Keywords are matched at the beginning
+/// of words, so e.g. `git add` is not taken for `dd`.
+pub fn classify_command(cmd: &str) -> (Risk, Option<String>) {
+    let cmd = cmd.to_lowercase();
+    let starts_word = |idx: usize| {
+        cmd[..idx]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric() && c != '-' && c != '_')
+    };
+    RISKY_COMMANDS
+        .iter()
+        .filter(|(keyword, _, _)| {
+            cmd.match_indices(keyword)
+                .any(|(idx, _)| keyword.starts_with('>') || starts_word(idx))
+        })
+        .max_by_key(|(_, risk, _)| *risk)
+        .map(|(_, risk, reason)| (*risk, Some(reason.to_string())))
+        .unwrap_or((Risk::Low, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copy(description: &str, risk: Risk) -> PlannedAction {
+        PlannedAction {
+            action: String::from("copy"),
+            target: String::from("local -> user@host"),
+            description: description.to_string(),
+            size: Some(2048),
+            risk,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn test_render_plan() {
+        let mut overwrite = copy("/a -> /b", Risk::High);
+        overwrite.reason = Some(String::from("overwrites existing file"));
+        let plan = Plan::new(vec![copy("/c -> /d", Risk::Low), overwrite]);
+
+        assert_eq!(plan.risk(), Risk::High);
+        assert_eq!(
+            plan.render(),
+            "Plan: 2 actions (risk: high)\n  [low] copy local -> user@host: /c -> /d (2.00 KiB)\n  [high] copy local -> user@host: /a -> /b (2.00 KiB) - overwrites existing file"
+        );
+        assert_eq!(Plan::default().risk(), Risk::Low);
+    }
+
+    #[test]
+    fn test_save_and_load_plan() {
+        let path = Path::new("/tmp/crust_test_plan.json");
+        let mut plan = Plan::new(vec![copy("/c -> /d", Risk::Low)]);
+        plan.command = vec![String::from("scp"), String::from("/c"), String::from("/d")];
+
+        plan.save(path).unwrap();
+        assert!(std::fs::read_to_string(path)
+            .unwrap()
+            .contains("\"risk\": \"low\""));
+        assert_eq!(Plan::load(path).unwrap(), plan);
+        std::fs::remove_file(path).unwrap();
+
+        plan.command.push(String::from("--password-to"));
+        let err = plan.save(path).err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_check_outdated_plan() {
+        let plan = Plan::new(vec![copy("/a -> /b", Risk::Low)]);
+
+        assert!(plan.check_current(&plan.clone()).is_ok());
+        let err = plan
+            .check_current(&Plan::new(vec![copy("/a -> /b", Risk::High)]))
+            .err()
+            .unwrap();
+        assert!(err
+            .message
+            .starts_with("Plan is outdated (2 actions differ from the current state)"));
+    }
+
+    #[test]
+    fn test_classify_command() {
+        assert_eq!(classify_command("uptime"), (Risk::Low, None));
+        assert_eq!(
+            classify_command("git add . && git commit"),
+            (Risk::Low, None)
+        );
+        assert_eq!(
+            classify_command("sudo systemctl restart nginx"),
+            (Risk::Medium, Some(String::from("manages services")))
+        );
+        assert_eq!(
+            classify_command("cd /srv && rm -rf build"),
+            (Risk::High, Some(String::from("removes files recursively")))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+
+/// Format of shown plan.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum PlanFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Arguments of operations which can show their plan instead of running.
+#[derive(Debug, Clone, Default, Args)]
+pub struct PlanArgs {
+    /// Show plan of operation instead of running it
+    #[clap(long, default_value = "false")]
+    pub dry_run: bool,
+
+    /// Format of shown plan
+    #[clap(long, value_enum, default_value = "text")]
+    pub plan_format: PlanFormat,
+
+    /// Save plan (JSON) to be reviewed and run later with
+    /// `crust apply <FILE>` (implies --dry-run)
+    #[clap(long, value_name = "FILE")]
+    pub plan_out: Option<PathBuf>,
+}
+
+impl PlanArgs {
+    /// Checks whether plan was requested instead of running operation.
+    pub fn is_requested(&self) -> bool {
+        self.dry_run || self.plan_out.is_some()
+    }
+}
+
+/// Flags of `PlanArgs` with values.
+const VALUE_FLAGS: &[&str] = &["--plan-format", "--plan-out"];
+
+/// Removes plan flags from command, so saved command runs the operation.
+pub fn strip_plan_flags(command: &[String]) -> Vec<String> {
+    let mut stripped = Vec::new();
+    let mut words = command.iter();
+    while let Some(word) = words.next() {
+        if word == "--dry-run" {
+            continue;
+        }
+        if VALUE_FLAGS.contains(&word.as_str()) {
+            words.next();
+            continue;
+        }
+        if VALUE_FLAGS
+            .iter()
+            .any(|flag| word.starts_with(&format!("{flag}=")))
+        {
+            continue;
+        }
+        stripped.push(word.clone());
+    }
+    stripped
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ApplyArgs {
+    /// Plan saved with --plan-out
+    pub file: PathBuf,
+}
+
+impl Validation for ApplyArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_plan_flags() {
+        let command = "scp /a /b --addr-to u@h --dry-run --plan-format json --plan-out=/tmp/p.json"
+            .split(' ')
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            strip_plan_flags(&command),
+            vec!["scp", "/a", "/b", "--addr-to", "u@h"]
+        );
+    }
+}
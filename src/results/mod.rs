@@ -0,0 +1,399 @@
+use std::path::Path;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Row};
+use uuid::Uuid;
+
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::response::CrustResult;
+
+pub mod parser;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS results (
+    id INTEGER PRIMARY KEY,
+    run TEXT NOT NULL,
+    host TEXT NOT NULL,
+    command TEXT NOT NULL,
+    stdout TEXT NOT NULL,
+    stderr TEXT NOT NULL,
+    retcode INTEGER NOT NULL,
+    started TEXT NOT NULL,
+    finished TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS results_host_started ON results (host, started);
+";
+
+/// Result of command executed on a single host (row of results store).
+/// - run: id shared by all results of one fleet execution
+/// - started, finished: moments of execution (connection errors have
+///   both set to moment of failure)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultRecord {
+    pub run: String,
+    pub host: String,
+    pub command: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub retcode: i32,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+}
+
+impl ResultRecord {
+    /// Checks whether command has been completed successfuly.
+    pub fn is_success(&self) -> bool {
+        self.retcode == 0
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            run: row.get(0)?,
+            host: row.get(1)?,
+            command: row.get(2)?,
+            stdout: row.get(3)?,
+            stderr: row.get(4)?,
+            retcode: row.get(5)?,
+            started: parse_time(row.get(6)?, 6)?,
+            finished: parse_time(row.get(7)?, 7)?,
+        })
+    }
+}
+
+/// Aggregated results of a command on a host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultStats {
+    pub host: String,
+    pub command: String,
+    pub runs: usize,
+    pub failed: usize,
+    pub avg_duration: f64,
+    pub last_run: DateTime<Utc>,
+}
+
+/// Selection of stored results - passed conditions must be met all.
+/// - command: part of executed command
+/// - limit: number of the latest results (applies to `query` only)
+#[derive(Debug, Clone, Default)]
+pub struct ResultFilter {
+    pub host: Option<String>,
+    pub command: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub failed: bool,
+    pub limit: Option<usize>,
+}
+
+impl ResultFilter {
+    /// WHERE clause with its parameters.
+    fn condition(&self) -> (String, Vec<Value>) {
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+        if let Some(host) = &self.host {
+            clauses.push("host = ?");
+            params.push(Value::Text(host.clone()));
+        }
+        if let Some(command) = &self.command {
+            clauses.push("instr(command, ?) > 0");
+            params.push(Value::Text(command.clone()));
+        }
+        if let Some(since) = &self.since {
+            clauses.push("started >= ?");
+            params.push(Value::Text(format_time(since)));
+        }
+        if self.failed {
+            clauses.push("retcode != 0");
+        }
+
+        match clauses.is_empty() {
+            true => (String::new(), params),
+            false => (format!("WHERE {}", clauses.join(" AND ")), params),
+        }
+    }
+}
+
+/// Local SQLite database with results of fleet executions, so repeated
+/// commands (e.g. health checks) can be compared over time. Every opened
+/// store starts a new run.
+pub struct ResultStore {
+    conn: Connection,
+    run: String,
+}
+
+impl ResultStore {
+    /// Opens (or creates) database under passed path.
+    pub fn open(path: &Path) -> Result<Self, CrustError> {
+        let conn = Connection::open(path).map_err(|e| CrustError {
+            code: ExitCode::Local,
+            message: format!("Can not open results store '{}': {e}", path.display()),
+        })?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn,
+            run: Uuid::new_v4().to_string(),
+        })
+    }
+
+    /// Id of run of results saved by this store.
+    pub fn run(&self) -> &str {
+        &self.run
+    }
+
+    /// Saves result of command executed on host (finished now). Errors
+    /// are stored with empty stdout and their message as stderr.
+    pub fn record(
+        &self,
+        host: &str,
+        command: &str,
+        started: DateTime<Utc>,
+        result: &Result<CrustResult, CrustError>,
+    ) -> Result<(), CrustError> {
+        let (stdout, stderr, retcode) = match result {
+            Ok(r) => (r.stdout(), r.stderr(), r.retcode()),
+            Err(e) => ("", e.message.as_str(), e.code.to_int()),
+        };
+        self.conn.execute(
+            "INSERT INTO results (run, host, command, stdout, stderr, retcode, started, finished)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                self.run,
+                host,
+                command,
+                stdout,
+                stderr,
+                retcode,
+                format_time(&started),
+                format_time(&Utc::now()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The latest results matching filter (in order of execution).
+    pub fn query(&self, filter: &ResultFilter) -> Result<Vec<ResultRecord>, CrustError> {
+        let (condition, mut values) = filter.condition();
+        values.push(Value::Integer(
+            filter.limit.map_or(-1, |limit| limit as i64),
+        ));
+        let sql = format!(
+            "SELECT run, host, command, stdout, stderr, retcode, started, finished
+             FROM results {condition} ORDER BY started DESC, id DESC LIMIT ?"
+        );
+        let mut records = self
+            .conn
+            .prepare(&sql)?
+            .query_map(params_from_iter(values), ResultRecord::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        records.reverse();
+        Ok(records)
+    }
+
+    /// Results matching filter aggregated per host and command.
+    pub fn stats(&self, filter: &ResultFilter) -> Result<Vec<ResultStats>, CrustError> {
+        let (condition, values) = filter.condition();
+        let sql = format!(
+            "SELECT host, command, COUNT(*), SUM(retcode != 0),
+                    AVG((julianday(finished) - julianday(started)) * 86400.0), MAX(started)
+             FROM results {condition} GROUP BY host, command ORDER BY host, command"
+        );
+        let stats = self
+            .conn
+            .prepare(&sql)?
+            .query_map(params_from_iter(values), |row| {
+                Ok(ResultStats {
+                    host: row.get(0)?,
+                    command: row.get(1)?,
+                    runs: row.get(2)?,
+                    failed: row.get(3)?,
+                    avg_duration: row.get(4)?,
+                    last_run: parse_time(row.get(5)?, 5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(stats)
+    }
+}
+
+/// Moments are stored as RFC 3339 text in UTC, so they can be compared
+/// as strings and read by SQLite date functions.
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_time(value: String, column: usize) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(
+                column,
+                rusqlite::types::Type::Text,
+                Box::new(e),
+            )
+        })
+}
+
+/// Parses age of results - number with `s`, `m`, `h` or `d` suffix.
+/// # Example
+/// ```
+/// use chrono::Duration;
+/// use crust::results::parse_age;
+///
+/// assert_eq!(parse_age("90m").unwrap(), Duration::minutes(90));
+/// assert_eq!(parse_age("7d").unwrap(), Duration::days(7));
+/// assert!(parse_age("7").is_err());
+/// ```
+pub fn parse_age(value: &str) -> Result<chrono::Duration, CrustError> {
+    let invalid = || CrustError {
+        code: ExitCode::Parser,
+        message: format!("Invalid age '{value}'. Use number with s, m, h or d suffix (e.g. 24h)"),
+    };
+    let value = value.trim();
+    let Some(unit) = value.chars().last() else {
+        return Err(invalid());
+    };
+    let seconds = match unit.to_ascii_lowercase() {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    value[..value.len() - unit.len_utf8()]
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(seconds))
+        .filter(|age| *age > 0)
+        .and_then(|age| chrono::Duration::from_std(std::time::Duration::from_secs(age)).ok())
+        .ok_or_else(invalid)
+}
+
+/// Stored results with their output (stdout of succeeded commands,
+/// stderr of failed ones).
+pub fn render(records: &[ResultRecord]) -> String {
+    if records.is_empty() {
+        return String::from("No stored results");
+    }
+
+    let mut lines = Vec::new();
+    for record in records {
+        let duration = (record.finished - record.started).num_milliseconds() as f64 / 1000.0;
+        lines.push(format!(
+            "{} {} (retcode {}, {duration:.1}s): {}",
+            record.started.format("%Y-%m-%d %H:%M:%S"),
+            record.host,
+            record.retcode,
+            record.command
+        ));
+        let output = match record.is_success() {
+            true => &record.stdout,
+            false => &record.stderr,
+        };
+        for line in output.trim_end().lines() {
+            lines.push(format!("    {line}"));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Table of aggregated results.
+pub fn render_stats(stats: &[ResultStats]) -> String {
+    if stats.is_empty() {
+        return String::from("No stored results");
+    }
+
+    let mut lines = vec![format!("{} commands on hosts", stats.len())];
+    for s in stats {
+        lines.push(format!(
+            "  {} '{}': {} runs, {} failed ({:.0}% success), avg {:.1}s, last {}",
+            s.host,
+            s.command,
+            s.runs,
+            s.failed,
+            100.0 * (s.runs - s.failed) as f64 / s.runs as f64,
+            s.avg_duration,
+            s.last_run.format("%Y-%m-%d %H:%M")
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (ResultStore, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("crust-results-{}.db", Uuid::new_v4()));
+        (ResultStore::open(&path).unwrap(), path)
+    }
+
+    #[test]
+    fn test_record_and_query_results() {
+        let (store, path) = temp_store();
+        let started = Utc::now() - chrono::Duration::hours(2);
+        let ok = Ok(CrustResult::new("up 3 days\n", "", 0));
+        let failed = Ok(CrustResult::new("", "disk full\n", 1));
+        let unreachable = Err(CrustError {
+            code: ExitCode::Ssh,
+            message: String::from("Connection refused"),
+        });
+        store.record("web-1", "uptime", started, &ok).unwrap();
+        store
+            .record("web-2", "uptime", Utc::now(), &failed)
+            .unwrap();
+        store
+            .record("web-3", "df -h", Utc::now(), &unreachable)
+            .unwrap();
+
+        let all = store.query(&ResultFilter::default()).unwrap();
+        assert_eq!(
+            all.iter().map(|r| r.host.as_str()).collect::<Vec<_>>(),
+            vec!["web-1", "web-2", "web-3"]
+        );
+        assert!(all.iter().all(|r| r.run == store.run()));
+        assert_eq!(all[0].stdout, "up 3 days\n");
+        assert_eq!(all[2].stderr, "Connection refused");
+        assert_eq!(all[2].retcode, ExitCode::Ssh.to_int());
+
+        let filter = ResultFilter {
+            failed: true,
+            since: Some(Utc::now() - chrono::Duration::hours(1)),
+            command: Some(String::from("up")),
+            ..Default::default()
+        };
+        let failed = store.query(&filter).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].host, "web-2");
+
+        let latest = ResultFilter {
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(store.query(&latest).unwrap()[0].host, "web-3");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_stats_of_repeated_commands() {
+        let (store, path) = temp_store();
+        for retcode in [0, 0, 1, 0] {
+            let result = Ok(CrustResult::new("", "", retcode));
+            store
+                .record("db-1", "pg_isready", Utc::now(), &result)
+                .unwrap();
+        }
+        let reopened = ResultStore::open(&path).unwrap();
+        assert_ne!(reopened.run(), store.run());
+        let result = Ok(CrustResult::new("", "", 0));
+        reopened
+            .record("db-2", "pg_isready", Utc::now(), &result)
+            .unwrap();
+
+        let stats = reopened.stats(&ResultFilter::default()).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].host.as_str(), stats[0].runs), ("db-1", 4));
+        assert_eq!(stats[0].failed, 1);
+        assert_eq!((stats[1].host.as_str(), stats[1].failed), ("db-2", 0));
+        assert!(render_stats(&stats).contains("db-1 'pg_isready': 4 runs, 1 failed (75% success)"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use clap::{Args, Subcommand};
+
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::parser::Validation;
+use crate::results::{parse_age, ResultFilter};
+
+#[derive(Debug, Clone, Args)]
+pub struct ResultsArgs {
+    #[clap(subcommand)]
+    pub action: ResultsAction,
+}
+
+impl Validation for ResultsArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        match &mut self.action {
+            ResultsAction::Query(args) => args.validate(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ResultsAction {
+    /// Shows results saved by `fleet exec --store` (the latest ones or
+    /// statistics per host and command)
+    Query(QueryArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct QueryArgs {
+    /// Path to results store (SQLite database)
+    #[clap(long)]
+    pub store: PathBuf,
+
+    /// Only results of host
+    #[clap(long)]
+    pub host: Option<String>,
+
+    /// Only results of commands containing passed text
+    #[clap(long)]
+    pub command: Option<String>,
+
+    /// Only results not older than passed age (e.g. 30m, 24h, 7d)
+    #[clap(long, value_name = "AGE")]
+    pub since: Option<String>,
+
+    /// Only failed results (non-zero retcode or connection error)
+    #[clap(long, default_value = "false")]
+    pub failed: bool,
+
+    /// Number of the latest results shown
+    #[clap(long, default_value = "20")]
+    pub limit: usize,
+
+    /// Shows number of runs, failures and average duration per host and
+    /// command instead of single results
+    #[clap(long, default_value = "false")]
+    pub stats: bool,
+}
+
+impl Validation for QueryArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        if !self.store.exists() {
+            return Err(CrustError {
+                code: ExitCode::Local,
+                message: format!("Results store '{}' does not exist", self.store.display()),
+            });
+        }
+        self.filter()?;
+        Ok(())
+    }
+}
+
+impl QueryArgs {
+    /// Selection of results described by arguments.
+    pub fn filter(&self) -> Result<ResultFilter, CrustError> {
+        let since = match &self.since {
+            Some(age) => Some(Utc::now() - parse_age(age)?),
+            None => None,
+        };
+        Ok(ResultFilter {
+            host: self.host.clone(),
+            command: self.command.clone(),
+            since,
+            failed: self.failed,
+            limit: Some(self.limit),
+        })
+    }
+}
@@ -0,0 +1,708 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use indicatif::HumanBytes;
+use ssh2::{Session, Sftp};
+
+use crate::connection::channels::{self, ChannelGuard, ChannelKind};
+use crate::connection::timing::{self, Phase};
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::progress_bar::ProgressBar;
+use crate::machine::local::LocalMachine;
+use crate::machine::utilities;
+use crate::machine::Machine;
+use crate::scp::failure::FailureClass;
+use crate::scp::heartbeat::Heartbeat;
+use crate::scp::BUF_SIZE;
+
+/// Continuous byte range of file transferred by a single worker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub index: usize,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Splits file of `size` bytes into (at most) `count` chunks of similar
+/// length. The last chunk takes a remainder.
+pub fn split_chunks(size: u64, count: usize) -> Vec<Chunk> {
+    let count = (count.max(1) as u64).min(size.max(1));
+    let chunk_len = size / count;
+
+    (0..count)
+        .map(|idx| {
+            let offset = idx * chunk_len;
+            let len = match idx == count - 1 {
+                true => size - offset,
+                false => chunk_len,
+            };
+            Chunk {
+                index: idx as usize,
+                offset,
+                len,
+            }
+        })
+        .collect()
+}
+
+/// Attempts of a single chunk before it is reported as failed.
+const CHUNK_ATTEMPTS: usize = 3;
+
+/// Delay before the first retry of chunk (doubled with every attempt).
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait of idle worker for a chunk to retry.
+const IDLE_WAIT: Duration = Duration::from_millis(100);
+
+/// Chunk waiting for a worker.
+/// - done: bytes of chunk already written (retry continues after them)
+/// - failed_by: worker which failed the last attempt (retry goes to
+///   another worker, with its own channel, if there is any)
+#[derive(Debug, Clone)]
+struct Pending {
+    chunk: Chunk,
+    done: u64,
+    attempt: usize,
+    failed_by: Option<usize>,
+    ready_at: Instant,
+}
+
+/// Chunk which could not be downloaded in all attempts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedChunk {
+    pub chunk: Chunk,
+    pub done: u64,
+    pub error: String,
+}
+
+impl FailedChunk {
+    /// Range of file which is still missing.
+    fn remaining(&self) -> Chunk {
+        Chunk {
+            index: self.chunk.index,
+            offset: self.chunk.offset + self.done,
+            len: self.chunk.len - self.done,
+        }
+    }
+}
+
+/// What worker should do next.
+#[derive(Debug)]
+enum Next {
+    Chunk(Pending),
+    Wait(Duration),
+    Done,
+}
+
+/// Chunks shared by workers - failed attempts are queued again (after
+/// backoff) until they run out of attempts.
+#[derive(Debug)]
+struct ChunkQueue {
+    pending: Vec<Pending>,
+    running: usize,
+    workers: usize,
+    failed: Vec<FailedChunk>,
+}
+
+impl ChunkQueue {
+    fn new(chunks: Vec<Chunk>, workers: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            pending: chunks
+                .into_iter()
+                .map(|chunk| Pending {
+                    chunk,
+                    done: 0,
+                    attempt: 0,
+                    failed_by: None,
+                    ready_at: now,
+                })
+                .collect(),
+            running: 0,
+            workers,
+            failed: Vec::new(),
+        }
+    }
+
+    /// Gives the first ready chunk to worker. Chunk failed by the worker
+    /// is left for others, unless it is the last worker.
+    fn next(&mut self, worker: usize, now: Instant) -> Next {
+        let found = self
+            .pending
+            .iter()
+            .position(|p| p.ready_at <= now && (p.failed_by != Some(worker) || self.workers == 1));
+        match found {
+            Some(idx) => {
+                self.running += 1;
+                Next::Chunk(self.pending.remove(idx))
+            }
+            None if self.pending.is_empty() && self.running == 0 => Next::Done,
+            None => {
+                let ready_in = self
+                    .pending
+                    .iter()
+                    .map(|p| p.ready_at.saturating_duration_since(now))
+                    .filter(|wait| !wait.is_zero())
+                    .min()
+                    .unwrap_or(IDLE_WAIT);
+                Next::Wait(ready_in.min(IDLE_WAIT))
+            }
+        }
+    }
+
+    /// Records result of attempt - failed chunk is queued again or
+    /// reported as failed.
+    fn finish(&mut self, mut pending: Pending, worker: usize, result: Result<(), CrustError>) {
+        self.running -= 1;
+        let Err(error) = result else {
+            return;
+        };
+
+        pending.attempt += 1;
+        if pending.attempt < CHUNK_ATTEMPTS && FailureClass::of(&error).is_retryable() {
+            let delay = RETRY_DELAY * 2u32.pow(pending.attempt as u32 - 1);
+            log::warn!(
+                "Chunk {} (offset {}) failed ({}), retrying in {:.1}s",
+                pending.chunk.index,
+                pending.chunk.offset + pending.done,
+                error.message,
+                delay.as_secs_f64()
+            );
+            pending.failed_by = Some(worker);
+            pending.ready_at = Instant::now() + delay;
+            self.pending.push(pending);
+        } else {
+            self.failed.push(FailedChunk {
+                chunk: pending.chunk,
+                done: pending.done,
+                error: error.message,
+            });
+        }
+    }
+
+    /// Removes worker whose channel was closed. Chunks left without any
+    /// worker are reported as failed.
+    fn leave(&mut self) {
+        self.workers -= 1;
+        if self.workers == 0 {
+            for pending in self.pending.drain(..) {
+                self.failed.push(FailedChunk {
+                    chunk: pending.chunk,
+                    done: pending.done,
+                    error: String::from("all channels were closed"),
+                });
+            }
+        }
+    }
+}
+
+/// Downloads a single remote file using `chunks` parallel sftp channels.
+/// Every channel reads a distinct byte range and writes it directly at
+/// the right offset of pre-allocated local file. Failed chunks are
+/// retried (with backoff) by other channels, chunks failed in all
+/// attempts are saved next to local file, so the next download of the
+/// same file fetches only them. At the end, sha256 of the assembled file
+/// is compared with the remote one.
+pub fn download_chunked(
+    machine: &dyn Machine,
+    from: &Path,
+    to: &Path,
+    chunks: usize,
+    progress: bool,
+    heartbeat: Option<&Heartbeat>,
+) -> Result<(), CrustError> {
+    let session = machine.connected_session()?;
+    let stat = session.sftp()?.stat(from)?;
+    let (size, mtime) = (stat.size.unwrap_or(0), stat.mtime.unwrap_or(0));
+
+    let ranges = match load_ranges(to, size, mtime) {
+        Some(ranges) => {
+            log::info!(
+                "Fetching {} chunks of '{}' failed in previous download",
+                ranges.len(),
+                from.display()
+            );
+            ranges
+        }
+        None => {
+            File::create(to)?.set_len(size)?;
+            split_chunks(size, chunks)
+        }
+    };
+    let total = ranges.iter().map(|c| c.len).sum();
+
+    let progress_bar: Option<ProgressBar> = match progress {
+        true => Some(ProgressBar::new(total)),
+        false => None,
+    };
+
+    let workers_count = chunks.clamp(1, ranges.len().max(1));
+    let queue = Arc::new((
+        Mutex::new(ChunkQueue::new(ranges, workers_count)),
+        Condvar::new(),
+    ));
+    let target = machine.timing_target();
+    let transfer = Arc::new(Transfer {
+        session: session.clone(),
+        target: target.clone(),
+        trace: timing::current(),
+        from: from.to_path_buf(),
+        to: to.to_path_buf(),
+        progress_bar: progress_bar.clone(),
+        heartbeat: heartbeat.cloned(),
+    });
+    // Reads of workers poll for force-close of their channels
+    timing::measure(&target, Phase::Transfer, || {
+        channels::polling(std::slice::from_ref(&session), || {
+            let workers = (0..workers_count)
+                .map(|worker| {
+                    let queue = queue.clone();
+                    let transfer = transfer.clone();
+                    let guard = ChannelGuard::open(
+                        &target,
+                        ChannelKind::Sftp,
+                        &format!("chunk worker {worker} of {}", from.display()),
+                    );
+                    std::thread::spawn(move || {
+                        timing::within(transfer.trace, || {
+                            run_worker(worker, &queue, &transfer, &guard)
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            log::debug!(
+                "Started {} chunk workers for '{}'",
+                workers.len(),
+                from.display()
+            );
+            for worker in workers {
+                worker.join().expect("Chunk worker panicked");
+            }
+        })
+    });
+
+    if let Some(pb) = progress_bar {
+        pb.finish();
+    }
+
+    let mut failed = std::mem::take(&mut queue.0.lock().unwrap().failed);
+    if !failed.is_empty() {
+        failed.sort_by_key(|f| f.chunk.offset);
+        return Err(report_failed(from, to, size, mtime, &failed));
+    }
+    remove_ranges(to);
+
+    verify_hash(machine, from, to)
+}
+
+/// Data shared by workers of a single file.
+/// - target: key of machine in timing breakdown
+/// - trace: trace of operation to which workers record their phases
+struct Transfer {
+    session: Session,
+    target: String,
+    trace: Option<u64>,
+    from: PathBuf,
+    to: PathBuf,
+    progress_bar: Option<ProgressBar>,
+    heartbeat: Option<Heartbeat>,
+}
+
+/// Worker loop - takes chunks from queue until all of them are done.
+/// Worker stops when its channel is force-closed (its chunk is retried
+/// by other workers).
+fn run_worker(
+    worker: usize,
+    queue: &(Mutex<ChunkQueue>, Condvar),
+    transfer: &Transfer,
+    guard: &ChannelGuard,
+) {
+    let (queue, changed) = queue;
+    let mut sftp = None;
+    loop {
+        let mut pending = {
+            let mut state = queue.lock().unwrap();
+            loop {
+                match state.next(worker, Instant::now()) {
+                    Next::Chunk(pending) => break pending,
+                    Next::Wait(timeout) => state = changed.wait_timeout(state, timeout).unwrap().0,
+                    Next::Done => return,
+                }
+            }
+        };
+
+        let result = download_chunk(&mut sftp, transfer, &mut pending, guard);
+        if result.is_err() {
+            // Channel may be broken - the next attempt opens a new one
+            sftp = None;
+        }
+        let closed = guard.check().is_err();
+
+        let mut state = queue.lock().unwrap();
+        state.finish(pending, worker, result);
+        if closed {
+            state.leave();
+        }
+        changed.notify_all();
+        if closed {
+            return;
+        }
+    }
+}
+
+/// Copies (the rest of) single chunk of remote file into local file.
+fn download_chunk(
+    sftp: &mut Option<Sftp>,
+    transfer: &Transfer,
+    pending: &mut Pending,
+    guard: &ChannelGuard,
+) -> Result<(), CrustError> {
+    guard.check()?;
+    let sftp = match sftp {
+        Some(sftp) => sftp,
+        None => sftp.insert(timing::measure(
+            &transfer.target,
+            Phase::ChannelOpen,
+            || transfer.session.sftp(),
+        )?),
+    };
+    let (from, chunk) = (&transfer.from, &pending.chunk);
+    let mut remote = sftp.open(from)?;
+    remote.seek(SeekFrom::Start(chunk.offset + pending.done))?;
+
+    let mut local = OpenOptions::new().write(true).open(&transfer.to)?;
+    local.seek(SeekFrom::Start(chunk.offset + pending.done))?;
+
+    let mut buffer = [0; BUF_SIZE];
+    while pending.done < chunk.len {
+        guard.check()?;
+        let to_read = ((chunk.len - pending.done) as usize).min(BUF_SIZE);
+        let len = guard.read(&mut remote, &mut buffer[..to_read])?;
+        if len == 0 {
+            return Err(CrustError {
+                code: ExitCode::Remote,
+                message: format!(
+                    "Unexpected end of '{}' in chunk {} (offset {})",
+                    from.display(),
+                    chunk.index,
+                    chunk.offset + pending.done
+                ),
+            });
+        }
+
+        local.write_all(&buffer[..len])?;
+        pending.done += len as u64;
+
+        if let Some(pb) = &transfer.progress_bar {
+            pb.inc(len);
+        }
+        if let Some(hb) = &transfer.heartbeat {
+            hb.inc(len);
+        }
+    }
+    log::trace!("Chunk {} of '{}' downloaded", chunk.index, from.display());
+    Ok(())
+}
+
+/// File with ranges of local file which are still missing.
+fn ranges_path(to: &Path) -> PathBuf {
+    let mut name = to.file_name().unwrap_or_default().to_os_string();
+    name.push(".crust-chunks");
+    to.with_file_name(name)
+}
+
+/// Saves missing ranges (with size and modification time of remote file,
+/// to detect its change before the next download).
+fn save_ranges(
+    to: &Path,
+    size: u64,
+    mtime: u64,
+    failed: &[FailedChunk],
+) -> Result<PathBuf, CrustError> {
+    let path = ranges_path(to);
+    let mut content = format!("crust-chunks {size} {mtime}\n");
+    for range in failed.iter().map(FailedChunk::remaining) {
+        content.push_str(&format!("{} {}\n", range.offset, range.len));
+    }
+    std::fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Loads ranges missing since the previous download. Ranges are ignored
+/// when remote file changed or local file has a different size.
+fn load_ranges(to: &Path, size: u64, mtime: u64) -> Option<Vec<Chunk>> {
+    let content = std::fs::read_to_string(ranges_path(to)).ok()?;
+    let mut lines = content.lines();
+    if lines.next()? != format!("crust-chunks {size} {mtime}")
+        || std::fs::metadata(to).ok()?.len() != size
+    {
+        return None;
+    }
+    lines
+        .enumerate()
+        .map(|(index, line)| {
+            let (offset, len) = line.split_once(' ')?;
+            Some(Chunk {
+                index,
+                offset: offset.parse().ok()?,
+                len: len.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+fn remove_ranges(to: &Path) {
+    let path = ranges_path(to);
+    if path.exists() {
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("Can not remove '{}': {e}", path.display());
+        }
+    }
+}
+
+/// Error listing chunks failed in all attempts (saved for the next run).
+fn report_failed(
+    from: &Path,
+    to: &Path,
+    size: u64,
+    mtime: u64,
+    failed: &[FailedChunk],
+) -> CrustError {
+    let saved = match save_ranges(to, size, mtime, failed) {
+        Ok(path) => format!(
+            "next download fetches only them (saved in {})",
+            path.display()
+        ),
+        Err(e) => format!("can not save them for the next download: {}", e.message),
+    };
+    let chunks = failed
+        .iter()
+        .map(|f| {
+            let range = f.remaining();
+            format!(
+                "  chunk {} at offset {} ({} left): {}",
+                range.index,
+                range.offset,
+                HumanBytes(range.len),
+                f.error
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    CrustError {
+        code: ExitCode::Remote,
+        message: format!(
+            "{} chunks of '{}' failed in all attempts - {saved}:\n{chunks}",
+            failed.len(),
+            from.display()
+        ),
+    }
+}
+
+/// Compares sha256 of remote source and assembled local file.
+fn verify_hash(machine: &dyn Machine, from: &Path, to: &Path) -> Result<(), CrustError> {
+    let remote = utilities::sha256(machine, &[from.to_path_buf()])?;
+    let local = utilities::sha256(&LocalMachine::default(), &[to.to_path_buf()])?;
+
+    match (remote.into_iter().next(), local.into_iter().next()) {
+        (Some(r), Some(l)) if r == l => Ok(()),
+        (r, l) => Err(CrustError {
+            code: ExitCode::Local,
+            message: format!(
+                "Hash of downloaded '{}' does not match (remote: {}, local: {})",
+                to.display(),
+                r.unwrap_or_default(),
+                l.unwrap_or_default()
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_into_equal_chunks() {
+        let chunks = split_chunks(100, 4);
+
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|c| c.len == 25));
+        assert_eq!(chunks[3].offset, 75);
+    }
+
+    #[test]
+    fn test_last_chunk_takes_remainder() {
+        let chunks = split_chunks(10, 3);
+
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk {
+                    index: 0,
+                    offset: 0,
+                    len: 3
+                },
+                Chunk {
+                    index: 1,
+                    offset: 3,
+                    len: 3
+                },
+                Chunk {
+                    index: 2,
+                    offset: 6,
+                    len: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_small_file() {
+        let chunks = split_chunks(2, 8);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.iter().map(|c| c.len).sum::<u64>(), 2);
+    }
+
+    #[test]
+    fn test_split_empty_file() {
+        assert_eq!(
+            split_chunks(0, 4),
+            vec![Chunk {
+                index: 0,
+                offset: 0,
+                len: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_split_with_zero_count() {
+        assert_eq!(
+            split_chunks(5, 0),
+            vec![Chunk {
+                index: 0,
+                offset: 0,
+                len: 5
+            }]
+        );
+    }
+
+    fn error(message: &str) -> Result<(), CrustError> {
+        Err(CrustError {
+            code: ExitCode::Ssh,
+            message: message.to_string(),
+        })
+    }
+
+    fn take(queue: &mut ChunkQueue, worker: usize, now: Instant) -> Pending {
+        match queue.next(worker, now) {
+            Next::Chunk(pending) => pending,
+            next => panic!("Expected chunk, got {next:?}"),
+        }
+    }
+
+    #[test]
+    fn test_retry_chunk_on_another_worker_after_backoff() {
+        let mut queue = ChunkQueue::new(split_chunks(10, 1), 2);
+        let mut pending = take(&mut queue, 0, Instant::now());
+        pending.done = 4;
+        queue.finish(pending, 0, error("connection reset"));
+
+        assert!(matches!(queue.next(1, Instant::now()), Next::Wait(_)));
+        let later = Instant::now() + RETRY_DELAY;
+        assert!(matches!(queue.next(0, later), Next::Wait(_)));
+        let retry = take(&mut queue, 1, later);
+        assert_eq!((retry.attempt, retry.done), (1, 4));
+
+        queue.finish(retry, 1, Ok(()));
+        assert!(matches!(queue.next(0, later), Next::Done));
+        assert!(queue.failed.is_empty());
+    }
+
+    #[test]
+    fn test_report_chunk_failed_in_all_attempts() {
+        let mut queue = ChunkQueue::new(split_chunks(10, 1), 1);
+        let far = Instant::now() + Duration::from_secs(60);
+        for attempt in 0..CHUNK_ATTEMPTS {
+            let pending = take(&mut queue, 0, far);
+            assert_eq!(pending.attempt, attempt);
+            queue.finish(pending, 0, error("timed out"));
+        }
+
+        assert!(matches!(queue.next(0, far), Next::Done));
+        assert_eq!(queue.failed.len(), 1);
+        assert_eq!(queue.failed[0].error, "timed out");
+    }
+
+    #[test]
+    fn test_do_not_retry_permission_failure() {
+        let mut queue = ChunkQueue::new(split_chunks(10, 1), 2);
+        let pending = take(&mut queue, 0, Instant::now());
+        queue.finish(pending, 0, error("[SFTP(3)] permission denied"));
+
+        assert!(matches!(queue.next(1, Instant::now()), Next::Done));
+        assert_eq!(queue.failed[0].chunk.index, 0);
+    }
+
+    #[test]
+    fn test_retry_truncated_chunk() {
+        let mut queue = ChunkQueue::new(split_chunks(10, 1), 1);
+        let pending = take(&mut queue, 0, Instant::now());
+        queue.finish(
+            pending,
+            0,
+            error("Unexpected end of '/a' in chunk 0 (offset 3)"),
+        );
+
+        assert!(queue.failed.is_empty());
+        assert!(matches!(queue.next(0, Instant::now()), Next::Wait(_)));
+    }
+
+    #[test]
+    fn test_fail_pending_chunks_without_workers() {
+        let mut queue = ChunkQueue::new(split_chunks(10, 2), 1);
+        let pending = take(&mut queue, 0, Instant::now());
+        queue.finish(pending, 0, error("Channel 3 was force-closed"));
+        queue.leave();
+
+        assert!(matches!(queue.next(0, Instant::now()), Next::Done));
+        assert_eq!(queue.failed.len(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load_missing_ranges() {
+        let to = std::env::temp_dir().join(format!("crust-chunks-{}.iso", uuid::Uuid::new_v4()));
+        File::create(&to).unwrap().set_len(100).unwrap();
+        let failed = FailedChunk {
+            chunk: Chunk {
+                index: 3,
+                offset: 75,
+                len: 25,
+            },
+            done: 5,
+            error: String::from("timed out"),
+        };
+
+        let path = save_ranges(&to, 100, 7, &[failed]).unwrap();
+        assert_eq!(
+            load_ranges(&to, 100, 7),
+            Some(vec![Chunk {
+                index: 0,
+                offset: 80,
+                len: 20
+            }])
+        );
+        assert_eq!(load_ranges(&to, 100, 8), None);
+
+        remove_ranges(&to);
+        assert!(!path.exists());
+        assert_eq!(load_ranges(&to, 100, 7), None);
+        std::fs::remove_file(to).unwrap();
+    }
+}
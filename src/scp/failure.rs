@@ -0,0 +1,183 @@
+use std::path::Path;
+
+use crate::error::CrustError;
+
+/// Class of transfer failure, recognized from error (libssh2 sftp codes
+/// and OS errors are both reported as text).
+/// - MissingSource: source file does not exist (e.g. it was removed
+///   during transfer)
+/// - MissingParent: destination directory does not exist
+/// - ConnectionDrop: session or channel was lost during transfer
+/// - Integrity: copied data does not match the source one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureClass {
+    Permission,
+    MissingSource,
+    MissingParent,
+    DiskFull,
+    ConnectionDrop,
+    Integrity,
+    Other,
+}
+
+/// Fragments of (lowercase) error messages with their classes. The first
+/// matching fragment wins.
+const FRAGMENTS: &[(&str, FailureClass)] = &[
+    ("missing source", FailureClass::MissingSource),
+    ("permission denied", FailureClass::Permission),
+    ("write protected", FailureClass::Permission),
+    ("read-only file system", FailureClass::Permission),
+    ("operation not permitted", FailureClass::Permission),
+    ("no space", FailureClass::DiskFull),
+    ("quota exceeded", FailureClass::DiskFull),
+    ("file too large", FailureClass::DiskFull),
+    ("no such file", FailureClass::MissingParent),
+    ("no such path", FailureClass::MissingParent),
+    ("not a directory", FailureClass::MissingParent),
+    ("does not match", FailureClass::Integrity),
+    ("connection lost", FailureClass::ConnectionDrop),
+    ("no connection", FailureClass::ConnectionDrop),
+    ("connection reset", FailureClass::ConnectionDrop),
+    ("broken pipe", FailureClass::ConnectionDrop),
+    ("timed out", FailureClass::ConnectionDrop),
+    ("force-closed", FailureClass::ConnectionDrop),
+    ("socket", FailureClass::ConnectionDrop),
+    ("unable to send", FailureClass::ConnectionDrop),
+    ("failure while", FailureClass::ConnectionDrop),
+    ("unexpected end", FailureClass::ConnectionDrop),
+];
+
+impl FailureClass {
+    /// Classifies error of a single file.
+    /// # Example
+    /// ```
+    /// use crust::error::{CrustError, ExitCode};
+    /// use crust::scp::failure::FailureClass;
+    ///
+    /// let error = CrustError {
+    ///     code: ExitCode::Ssh,
+    ///     message: String::from("[SFTP(3)] permission denied"),
+    /// };
+    /// assert_eq!(FailureClass::of(&error), FailureClass::Permission);
+    /// ```
+    pub fn of(error: &CrustError) -> Self {
+        let message = error.message.to_lowercase();
+        FRAGMENTS
+            .iter()
+            .find(|(fragment, _)| message.contains(fragment))
+            .map_or(FailureClass::Other, |(_, class)| *class)
+    }
+
+    /// Checks whether repeating transfer of file may succeed without any
+    /// change on machines.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, FailureClass::ConnectionDrop | FailureClass::Integrity)
+    }
+
+    /// Suggested fix for failures of the class.
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            FailureClass::Permission => "check owner and mode of destination files and directories",
+            FailureClass::MissingSource => "check source path (file could be removed meanwhile)",
+            FailureClass::MissingParent => "create destination directory first",
+            FailureClass::DiskFull => "free space (or raise quota) on destination machine",
+            FailureClass::ConnectionDrop => {
+                "run transfer again (--heartbeat helps to find stalled connections)"
+            }
+            FailureClass::Integrity => "run transfer again and verify it with --verify full",
+            FailureClass::Other => "see errors of files above",
+        }
+    }
+}
+
+/// Marks error of reading source file, so missing file on source machine
+/// is not reported as missing parent of destination.
+/// # Example
+/// ```
+/// use std::path::Path;
+///
+/// use crust::error::{CrustError, ExitCode};
+/// use crust::scp::failure::{source_error, FailureClass};
+///
+/// let error = CrustError {
+///     code: ExitCode::Ssh,
+///     message: String::from("[SFTP(2)] no such file"),
+/// };
+/// let error = source_error(Path::new("/etc/app.conf"), error);
+/// assert_eq!(FailureClass::of(&error), FailureClass::MissingSource);
+/// ```
+pub fn source_error(path: &Path, error: impl Into<CrustError>) -> CrustError {
+    let error = error.into();
+    match FailureClass::of(&error) {
+        FailureClass::MissingParent => CrustError {
+            code: error.code,
+            message: format!("Missing source '{}': {}", path.display(), error.message),
+        },
+        _ => error,
+    }
+}
+
+impl std::fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            FailureClass::Permission => "permission",
+            FailureClass::MissingSource => "missing source",
+            FailureClass::MissingParent => "missing parent",
+            FailureClass::DiskFull => "disk full",
+            FailureClass::ConnectionDrop => "connection drop",
+            FailureClass::Integrity => "integrity",
+            FailureClass::Other => "other",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ExitCode;
+
+    fn error(message: &str) -> CrustError {
+        CrustError {
+            code: ExitCode::Std,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_classify_errors() {
+        let cases = [
+            ("[SFTP(2)] no such file", FailureClass::MissingParent),
+            (
+                "No such file or directory (os error 2)",
+                FailureClass::MissingParent,
+            ),
+            ("[SFTP(14)] no space on filesystem", FailureClass::DiskFull),
+            (
+                "No space left on device (os error 28)",
+                FailureClass::DiskFull,
+            ),
+            ("Permission denied (os error 13)", FailureClass::Permission),
+            (
+                "[Session(-7)] Unable to send FXP_OPEN command",
+                FailureClass::ConnectionDrop,
+            ),
+            ("Channel 4 was force-closed", FailureClass::ConnectionDrop),
+            (
+                "Hash of downloaded 'a' does not match (remote: 1, local: 2)",
+                FailureClass::Integrity,
+            ),
+            (
+                "Missing source '/a': No such file or directory (os error 2)",
+                FailureClass::MissingSource,
+            ),
+            ("Can not get file size", FailureClass::Other),
+        ];
+
+        for (message, class) in cases {
+            assert_eq!(FailureClass::of(&error(message)), class, "{message}");
+        }
+        assert!(FailureClass::ConnectionDrop.is_retryable());
+        assert!(!FailureClass::Permission.is_retryable());
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use indicatif::{HumanBytes, HumanDuration};
+
+use crate::interfaces::output;
+
+/// Periodic liveness report of a long transfer. Background thread writes
+/// a line (to stderr of console) every `interval`, also when no data
+/// flows, so it is visible that transfer is not hung. A single heartbeat
+/// covers all files of transfer - clones share the same counter and
+/// current file, the thread stops when the last clone is dropped.
+#[derive(Clone)]
+pub struct Heartbeat {
+    bytes: Arc<AtomicU64>,
+    file: Arc<Mutex<String>>,
+    _ticker: Arc<Sender<()>>,
+}
+
+impl Heartbeat {
+    /// Starts reporting progress of transfer with `total` bytes.
+    pub fn start(interval: Duration, total: u64) -> Self {
+        let bytes = Arc::new(AtomicU64::new(0));
+        let file = Arc::new(Mutex::new(String::new()));
+        let (ticker, stop) = mpsc::channel::<()>();
+
+        let (counter, current) = (bytes.clone(), file.clone());
+        let started = Instant::now();
+        std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+                let done = counter.load(Ordering::Relaxed);
+                let file = current.lock().unwrap().clone();
+                output::write_err_line(&describe(&file, done, total, started.elapsed()));
+            }
+        });
+
+        Self {
+            bytes,
+            file,
+            _ticker: Arc::new(ticker),
+        }
+    }
+
+    /// Sets file which is transferred now.
+    pub fn file(&self, file: &Path) {
+        *self.file.lock().unwrap() = file.display().to_string();
+    }
+
+    /// Increments number of transferred bytes.
+    pub fn inc(&self, len: usize) {
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Number of bytes transferred so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Single heartbeat line: current file, bytes so far, throughput and ETA.
+pub fn describe(file: &str, done: u64, total: u64, elapsed: Duration) -> String {
+    let percent = match total {
+        0 => 100,
+        _ => done * 100 / total,
+    };
+    let throughput = match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => done as f64 / secs,
+        _ => 0.0,
+    };
+    let eta = match throughput > 0.0 {
+        true => {
+            let left = total.saturating_sub(done) as f64 / throughput;
+            HumanDuration(Duration::from_secs_f64(left)).to_string()
+        }
+        false => String::from("unknown"),
+    };
+
+    format!(
+        "[heartbeat] '{file}': {}/{} ({percent}%), {}/s, ETA {eta}",
+        HumanBytes(done),
+        HumanBytes(total),
+        HumanBytes(throughput as u64),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_progress() {
+        assert_eq!(
+            describe(
+                "/data/a.img",
+                50 * 1024,
+                100 * 1024,
+                Duration::from_secs(10)
+            ),
+            "[heartbeat] '/data/a.img': 50.00 KiB/100.00 KiB (50%), 5.00 KiB/s, ETA 10 seconds"
+        );
+    }
+
+    #[test]
+    fn test_describe_stalled_transfer() {
+        assert_eq!(
+            describe("/a", 0, 1024, Duration::from_secs(5)),
+            "[heartbeat] '/a': 0 B/1.00 KiB (0%), 0 B/s, ETA unknown"
+        );
+    }
+
+    #[test]
+    fn test_describe_empty_file() {
+        assert!(describe("/a", 0, 0, Duration::ZERO).contains("(100%)"));
+    }
+
+    #[test]
+    fn test_clones_share_counter() {
+        let heartbeat = Heartbeat::start(Duration::from_secs(60), 10);
+        let cloned = heartbeat.clone();
+
+        heartbeat.file(Path::new("/a"));
+        heartbeat.inc(3);
+        cloned.file(Path::new("/b"));
+        cloned.inc(4);
+
+        assert_eq!(heartbeat.bytes(), 7);
+        assert_eq!(*heartbeat.file.lock().unwrap(), "/b");
+    }
+}
@@ -0,0 +1,211 @@
+use std::path::Path;
+
+use crate::error::{CrustError, ExitCode};
+use crate::machine::os::HostOs;
+use crate::machine::Machine;
+use crate::utils::shell::quote_path;
+
+/// Placeholder of hook replaced with (quoted) destination path of file.
+pub const PATH_PLACEHOLDER: &str = "{path}";
+
+/// Marker printed after every hook of a batch, followed by its exit status.
+const STATUS_MARKER: &str = "__crust_hook_status:";
+
+/// Commands executed on destination machine around every copied file,
+/// e.g. stopping a service before its binary is replaced.
+/// - before: runs before file is copied (file is skipped when it fails)
+/// - after: runs after file was copied successfully
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileHooks {
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl FileHooks {
+    /// Checks whether any hook was set.
+    pub fn is_empty(&self) -> bool {
+        self.before.is_none() && self.after.is_none()
+    }
+
+    /// Checks whether hooks can be executed.
+    pub fn validate(&self) -> Result<(), CrustError> {
+        for hook in [&self.before, &self.after].into_iter().flatten() {
+            if hook.trim().is_empty() {
+                return Err(CrustError {
+                    code: ExitCode::Parser,
+                    message: "Hook command can not be empty".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Rendered `before` hook of file (if set).
+    pub fn before(&self, path: &Path, os: HostOs) -> Option<String> {
+        self.before.as_ref().map(|hook| render(hook, path, os))
+    }
+
+    /// Rendered `after` hook of file (if set).
+    pub fn after(&self, path: &Path, os: HostOs) -> Option<String> {
+        self.after.as_ref().map(|hook| render(hook, path, os))
+    }
+}
+
+/// Replaces placeholder of hook with path quoted for shell of machine.
+/// # Example
+/// ```
+/// use std::path::Path;
+/// use crust::machine::os::HostOs;
+/// use crust::scp::hooks::render;
+///
+/// assert_eq!(
+///     render("chmod +x {path}", Path::new("/srv/my app"), HostOs::Unix),
+///     "chmod +x '/srv/my app'"
+/// );
+/// ```
+pub fn render(hook: &str, path: &Path, os: HostOs) -> String {
+    let quoted = match os {
+        HostOs::Unix => quote_path(path),
+        HostOs::Windows(shell) => shell.quote(&path.to_string_lossy()),
+    };
+    hook.replace(PATH_PLACEHOLDER, &quoted)
+}
+
+/// Runs hooks on machine and returns result of every one of them (in the
+/// same order). Hooks are sent together as a single command, so e.g. hook
+/// after one file and hook before the next one cost a single round trip.
+/// Windows shells get hooks one by one.
+pub fn run(machine: &dyn Machine, hooks: &[String]) -> Vec<Result<(), CrustError>> {
+    if hooks.len() == 1 || machine.host_os().is_windows() {
+        return hooks.iter().map(|hook| run_one(machine, hook)).collect();
+    }
+
+    // Status is captured explicitly, so a failing hook does not stop the
+    // batch when machine runs commands with `set -e`. Markers are printed
+    // to both streams to split stderr between hooks.
+    let script = hooks
+        .iter()
+        .map(|hook| {
+            format!(
+                "( {hook}\n) </dev/null && s=0 || s=$?; \
+                 printf '\\n{STATUS_MARKER}%s\\n' \"$s\"; \
+                 printf '\\n{STATUS_MARKER}%s\\n' \"$s\" >&2"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let output = match machine.exec(&script) {
+        Ok(output) => output,
+        Err(e) => return hooks.iter().map(|_| Err(e.clone())).collect(),
+    };
+
+    let statuses = output
+        .stdout()
+        .lines()
+        .filter_map(|line| line.strip_prefix(STATUS_MARKER))
+        .map(|status| status.trim().parse::<i32>().unwrap_or(-1))
+        .collect::<Vec<_>>();
+    let stderrs = split_stderr(output.stderr());
+    hooks
+        .iter()
+        .enumerate()
+        .map(|(idx, hook)| match statuses.get(idx) {
+            Some(0) => Ok(()),
+            status => Err(hook_error(
+                hook,
+                status.copied().unwrap_or(-1),
+                stderrs.get(idx).map(String::as_str).unwrap_or_default(),
+            )),
+        })
+        .collect()
+}
+
+/// Splits stderr of batch into stderr of every hook (by status markers).
+fn split_stderr(stderr: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    for line in stderr.lines() {
+        match line.starts_with(STATUS_MARKER) {
+            true => parts.push(String::new()),
+            false => {
+                let part = parts.last_mut().unwrap();
+                part.push_str(line);
+                part.push('\n');
+            }
+        }
+    }
+    parts
+}
+
+fn run_one(machine: &dyn Machine, hook: &str) -> Result<(), CrustError> {
+    let output = machine.exec(hook)?;
+    match output.is_success() {
+        true => Ok(()),
+        false => Err(hook_error(hook, output.retcode(), output.stderr())),
+    }
+}
+
+fn hook_error(hook: &str, status: i32, stderr: &str) -> CrustError {
+    let mut message = format!("Hook '{hook}' failed with status {status}");
+    if !stderr.trim().is_empty() {
+        message.push_str(&format!(": {}", stderr.trim()));
+    }
+    CrustError {
+        code: ExitCode::Remote,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::local::LocalMachine;
+
+    #[test]
+    fn test_render_hooks() {
+        let hooks = FileHooks {
+            before: Some(String::from("systemctl stop app")),
+            after: Some(String::from("chmod +x {path} && ls {path}")),
+        };
+
+        assert_eq!(
+            hooks.before(Path::new("/srv/app"), HostOs::Unix).unwrap(),
+            "systemctl stop app"
+        );
+        assert_eq!(
+            hooks.after(Path::new("/srv/it's"), HostOs::Unix).unwrap(),
+            "chmod +x '/srv/it'\\''s' && ls '/srv/it'\\''s'"
+        );
+        assert!(FileHooks {
+            before: Some(String::from(" ")),
+            after: None
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_run_hooks_in_batch() {
+        let hooks = vec![
+            String::from("echo first"),
+            String::from("echo oops >&2; exit 3"),
+            String::from("printf 'no newline'; echo late >&2; false"),
+        ];
+
+        let results = run(&LocalMachine::default(), &hooks);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().err().unwrap().message,
+            "Hook 'echo oops >&2; exit 3' failed with status 3: oops"
+        );
+        assert_eq!(
+            results[2].as_ref().err().unwrap().message,
+            "Hook 'printf 'no newline'; echo late >&2; false' failed with status 1: late"
+        );
+
+        let results = run(&LocalMachine::default(), &[String::from("false")]);
+        assert_eq!(
+            results[0].as_ref().err().unwrap().message,
+            "Hook 'false' failed with status 1"
+        );
+    }
+}
@@ -4,196 +4,597 @@ use std::io::Read;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Duration;
 
-use ssh2::Channel;
+use ssh2::{Channel, Session};
 
+use crate::cache::{CacheKey, DownloadCache};
+use crate::connection::channels::{self, ChannelGuard, ChannelKind};
+use crate::connection::timing::{self, Phase};
 use crate::error::{CrustError, ExitCode};
 use crate::interfaces::progress_bar::ProgressBar;
 use crate::interfaces::response::CrustResult;
-use crate::machine::local::LocalMachine;
 use crate::machine::{Machine, MachineType};
+use crate::plan::{PlannedAction, Risk};
+use heartbeat::Heartbeat;
+use hooks::FileHooks;
+use tree::{FileOutcome, TransferReport, TreeEntry};
+use verify::{VerifyMode, VerifyOptions};
 
+pub mod chunked;
+pub mod failure;
+pub mod heartbeat;
+pub mod hooks;
 pub mod parser;
+pub mod relay;
+pub mod request;
+pub mod sync;
+pub mod tree;
+pub mod verify;
 
 pub const BUF_SIZE: usize = 1024 * 10;
 
+/// Set of options which change the way of transfer.
+/// - progress: show progress bar
+/// - cache: serve unchanged remote files from local download cache
+/// - chunks: number of parallel channels used to download a single file
+/// - heartbeat: interval of periodic liveness reports of the whole transfer
+///   (None disables them)
+/// - verify: how copied files are compared with source ones
+/// - hooks: commands run on destination machine around every file
+/// - sync: copy only files which are missing or differ on destination
+/// - direct: source machine sends data to destination itself (transfer
+///   between remote machines only)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferOptions {
+    pub progress: bool,
+    pub cache: bool,
+    pub chunks: usize,
+    pub heartbeat: Option<Duration>,
+    pub verify: VerifyOptions,
+    pub hooks: FileHooks,
+    pub sync: bool,
+    pub direct: bool,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            progress: false,
+            cache: false,
+            chunks: 1,
+            heartbeat: None,
+            verify: VerifyOptions::default(),
+            hooks: FileHooks::default(),
+            sync: false,
+            direct: false,
+        }
+    }
+}
+
 /// Function enabling automatic selection of machines to
-/// perform the requested operation.
-/// TODO?: copying between two machines is done temporarily with the proxy machine
+/// perform the requested operation. Data between two remote machines is
+/// relayed by local machine (or sent directly, see `relay::direct`).
 pub fn scp(
     _machine_from: &Rc<RefCell<Box<dyn Machine>>>,
     _machine_to: &Rc<RefCell<Box<dyn Machine>>>,
     path_from: PathBuf,
     path_to: PathBuf,
-    progress: bool,
+    options: &TransferOptions,
 ) -> Result<CrustResult, CrustError> {
     let mut machine_from = _machine_from.borrow_mut();
     let mut machine_to = _machine_to.borrow_mut();
     match (machine_from.get_machine(), machine_to.get_machine()) {
-        (MachineType::LocalMachine, MachineType::RemoteMachine) => {
-            log::trace!("Run `upload` from {} to {}", machine_from, machine_to);
-            machine_from.upload(&mut machine_to, &path_from, &path_to, progress)
+        (MachineType::LocalMachine, MachineType::LocalMachine) => Err(CrustError {
+            code: ExitCode::Local,
+            message: "You want to copy files between local machines. Use 'exec' instead."
+                .to_string(),
+        }),
+        (MachineType::AbstractMachine, _) | (_, MachineType::AbstractMachine) => {
+            panic!("unsupported yet")
         }
-        (MachineType::RemoteMachine, MachineType::LocalMachine) => {
-            log::trace!("Run `download` from {} to {}", machine_to, machine_from);
-            machine_to.download(&mut machine_from, &path_from, &path_to, progress)
+        _ => {
+            log::trace!("Run transfer from {} to {}", machine_from, machine_to);
+            transfer_tree(
+                &mut machine_from,
+                &mut machine_to,
+                &path_from,
+                &path_to,
+                options,
+            )
         }
-        (MachineType::RemoteMachine, MachineType::RemoteMachine) => {
-            let mut local: Box<dyn Machine> = Box::<LocalMachine>::default();
-            local.create_tmpdir()?;
-            let file_path = local.create_tmpdir_content("tmp_scp")?;
-            log::trace!("Run `download` from {} to {}", machine_from, local);
-            local.download(&mut machine_from, &path_from, &file_path, progress)?;
-            log::trace!("Run `upload` from {} to {}", local, machine_to);
-            local.upload(&mut machine_to, &file_path, &path_to, progress)?;
+    }
+}
 
-            Ok(CrustResult::default())
-        }
-        (MachineType::LocalMachine, MachineType::LocalMachine) => Err(CrustError {
+/// Files bigger than that (in bytes) are medium risk in transfer plan.
+pub const BIG_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Lists copy actions of transfer without copying anything (dry-run).
+/// Every copied file is a separate action - overwriting an existing file
+/// is high risk, big files are medium risk.
+pub fn plan(
+    _machine_from: &Rc<RefCell<Box<dyn Machine>>>,
+    _machine_to: &Rc<RefCell<Box<dyn Machine>>>,
+    path_from: &Path,
+    path_to: &Path,
+) -> Result<Vec<PlannedAction>, CrustError> {
+    let mut machine_from = _machine_from.borrow_mut();
+    let mut machine_to = _machine_to.borrow_mut();
+    if let (MachineType::LocalMachine, MachineType::LocalMachine) =
+        (machine_from.get_machine(), machine_to.get_machine())
+    {
+        return Err(CrustError {
             code: ExitCode::Local,
             message: "You want to copy files between local machines. Use 'exec' instead."
                 .to_string(),
-        }),
+        });
+    }
+
+    let target = format!("{} -> {}", machine_from, machine_to);
+    list_tree(&mut machine_from, path_from)?
+        .iter()
+        .map(|entry| {
+            let (from, to) = (entry.join_to(path_from), entry.join_to(path_to));
+            let (risk, reason) = match path_exists(&mut machine_to, &to)? {
+                true => (Risk::High, Some("overwrites existing file")),
+                false if entry.size >= BIG_FILE_SIZE => (Risk::Medium, Some("big file")),
+                false => (Risk::Low, None),
+            };
+            Ok(PlannedAction {
+                action: String::from("copy"),
+                target: target.clone(),
+                description: format!("{} -> {}", from.display(), to.display()),
+                size: Some(entry.size),
+                risk,
+                reason: reason.map(String::from),
+            })
+        })
+        .collect()
+}
 
-        (_, _) => panic!("unsupported yet"),
+/// Connects remote machine and converts path into form used by its
+/// sftp server (see `HostOs::sftp_path`). Local paths are kept.
+fn native_path(machine: &mut Box<dyn Machine>, path: &Path) -> Result<PathBuf, CrustError> {
+    match machine.get_machine() {
+        MachineType::LocalMachine => Ok(path.to_path_buf()),
+        _ => {
+            machine.connect()?;
+            Ok(machine.host_os().sftp_path(path))
+        }
+    }
+}
+
+/// Lists files of tree on any machine.
+fn list_tree(machine: &mut Box<dyn Machine>, root: &Path) -> Result<Vec<TreeEntry>, CrustError> {
+    let root = native_path(machine, root)?;
+    machine.list_tree(&root)
+}
+
+/// Checks whether path exists on any machine.
+fn path_exists(machine: &mut Box<dyn Machine>, path: &Path) -> Result<bool, CrustError> {
+    let path = native_path(machine, path)?;
+    machine.path_exists(&path)
+}
+
+/// Copies a file or a whole directory tree between two machines (at least
+/// one of them remote), then verifies copied files if requested. With
+/// `sync` option only files missing or different on destination are
+/// copied. Single file keeps the plain result; trees are summarized with
+/// a report.
+fn transfer_tree(
+    src: &mut Box<dyn Machine>,
+    dst: &mut Box<dyn Machine>,
+    path_from: &Path,
+    path_to: &Path,
+    options: &TransferOptions,
+) -> Result<CrustResult, CrustError> {
+    let path_from = native_path(src, path_from)?;
+    let path_to = native_path(dst, path_to)?;
+    let (path_from, path_to) = (path_from.as_path(), path_to.as_path());
+    let entries = list_tree(src, path_from)?;
+    let single = entries.len() == 1 && entries[0].path.as_os_str().is_empty();
+
+    let mut report = TransferReport::default();
+    let copied = match options.sync {
+        true => sync::changed(src.as_ref(), path_from, dst.as_ref(), path_to, &entries)?,
+        false => entries.clone(),
+    };
+    report.unchanged = entries.len() - copied.len();
+
+    match options.direct {
+        true => relay::direct(
+            src.as_ref(),
+            dst.as_ref(),
+            path_from,
+            path_to,
+            &copied,
+            &options.hooks,
+        )?,
+        false => {
+            let total = copied.iter().map(|entry| entry.size).sum();
+            let heartbeat = options
+                .heartbeat
+                .map(|interval| Heartbeat::start(interval, total));
+            let results = copy_entries(
+                src,
+                dst,
+                path_from,
+                path_to,
+                &copied,
+                options,
+                heartbeat.as_ref(),
+            )?;
+            for (entry, result) in copied.iter().zip(results) {
+                match result {
+                    Err(e) if single => return Err(e),
+                    result => report.files.push(FileOutcome {
+                        path: entry.path.clone(),
+                        size: entry.size,
+                        error: result.err(),
+                    }),
+                }
+            }
+        }
+    }
+
+    if options.verify.mode != VerifyMode::None {
+        report.verification = Some(verify::verify(
+            src.as_ref(),
+            path_from,
+            dst.as_ref(),
+            path_to,
+            &entries,
+            &options.verify,
+        )?);
+    }
+
+    match (single, &report.verification) {
+        (true, None) => Ok(CrustResult::default()),
+        _ => Ok(report.into()),
+    }
+}
+
+/// Copies entries one by one, with hooks run on destination machine
+/// around each of them. Returns result of every entry (failed `after`
+/// hook fails its entry).
+fn copy_entries(
+    src: &mut Box<dyn Machine>,
+    dst: &mut Box<dyn Machine>,
+    path_from: &Path,
+    path_to: &Path,
+    entries: &[TreeEntry],
+    options: &TransferOptions,
+    heartbeat: Option<&Heartbeat>,
+) -> Result<Vec<Result<(), CrustError>>, CrustError> {
+    let single = entries.len() == 1 && entries[0].path.as_os_str().is_empty();
+    let os = dst.host_os();
+
+    let mut results = Vec::new();
+    // Hook after the previous file is sent together with hook before
+    // the next one (index of file in results, command)
+    let mut after: Option<(usize, String)> = None;
+    for entry in entries {
+        let (from, to) = (entry.join_to(path_from), entry.join_to(path_to));
+        let before = options.hooks.before(&to, os);
+        let before_result = match before.is_some() || after.is_some() {
+            true => run_hooks(&mut results, dst.as_ref(), after.take(), before),
+            false => Ok(()),
+        };
+
+        let result = before_result.and_then(|_| {
+            if let (false, Some(parent)) = (single, to.parent()) {
+                create_dir(dst, parent)?;
+            }
+            copy_file(src, dst, &from, &to, options, heartbeat)
+        });
+        if let (true, Some(hook)) = (result.is_ok(), options.hooks.after(&to, os)) {
+            after = Some((results.len(), hook));
+        }
+        results.push(result);
+    }
+    if after.is_some() {
+        run_hooks(&mut results, dst.as_ref(), after, None)?;
+    }
+    Ok(results)
+}
+
+/// Copies a single file between machines of any kind.
+fn copy_file(
+    src: &mut Box<dyn Machine>,
+    dst: &mut Box<dyn Machine>,
+    from: &Path,
+    to: &Path,
+    options: &TransferOptions,
+    heartbeat: Option<&Heartbeat>,
+) -> Result<(), CrustError> {
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.file(from);
+    }
+    match (src.get_machine(), dst.get_machine()) {
+        (MachineType::LocalMachine, _) => src.upload(dst, from, to, options, heartbeat).map(|_| ()),
+        (_, MachineType::LocalMachine) => {
+            dst.download(src, from, to, options, heartbeat).map(|_| ())
+        }
+        _ => relay::relay_file(src.as_ref(), dst.as_ref(), from, to, options, heartbeat),
+    }
+}
+
+/// Creates (with parents) directory on any machine.
+fn create_dir(machine: &mut Box<dyn Machine>, dir: &Path) -> Result<(), CrustError> {
+    machine.connect()?;
+    machine.create_dir(dir)
+}
+
+/// Runs hook after the previous file (its failure is recorded in results)
+/// and hook before the next file with a single command. Returns result
+/// of the `before` hook.
+fn run_hooks(
+    results: &mut [Result<(), CrustError>],
+    machine: &dyn Machine,
+    after: Option<(usize, String)>,
+    before: Option<String>,
+) -> Result<(), CrustError> {
+    let commands = after
+        .iter()
+        .map(|(_, hook)| hook.clone())
+        .chain(before.clone())
+        .collect::<Vec<_>>();
+    let mut outcomes = hooks::run(machine, &commands).into_iter();
+
+    if let Some((idx, _)) = after {
+        if let Some(Err(e)) = outcomes.next() {
+            results[idx] = Err(e);
+        }
+    }
+    match before {
+        Some(_) => outcomes.next().unwrap_or(Ok(())),
+        None => Ok(()),
     }
 }
 
 /// Private function for copying single-file data by bytes. Used by `upload`
-/// and `download` methods.
+/// and `download` methods. Stops when channel is force-closed (sessions
+/// of remote files poll for it, so stuck transfer can be closed too).
+/// Errors of reading are reported as errors of source file `from` (see
+/// `failure::source_error`).
 fn copy_data(
     mut file_source: TransferFile,
     mut file_target: TransferFile,
+    from: &Path,
     progress_bar: Option<ProgressBar>,
-) {
+    heartbeat: Option<&Heartbeat>,
+    guard: &ChannelGuard,
+) -> Result<(), CrustError> {
+    let sessions = [&file_source, &file_target]
+        .into_iter()
+        .filter_map(TransferFile::session)
+        .collect::<Vec<_>>();
     let mut buffer = [0; BUF_SIZE];
-    loop {
-        let len = file_source
-            .read(&mut buffer)
-            .expect("Failed to read from local file");
+    let copied = channels::polling(&sessions, || loop {
+        if let Err(e) = guard.check() {
+            break Err(e);
+        }
+
+        let len = match guard.read(&mut file_source, &mut buffer) {
+            Ok(len) => len,
+            Err(e) => break Err(failure::source_error(from, e)),
+        };
 
         if len == 0 {
-            break;
+            break Ok(());
         }
 
-        file_target
-            .write_all(&buffer[..len])
-            .expect("Failed to write to file");
+        if let Err(e) = guard.write_all(&mut file_target, &buffer[..len]) {
+            break Err(e);
+        }
 
         if let Some(ref pb) = progress_bar {
             pb.inc(len);
         }
-    }
+        if let Some(hb) = heartbeat {
+            hb.inc(len);
+        }
+    });
 
     if let Some(pb) = progress_bar {
         pb.finish();
     }
 
-    match (file_source, file_target) {
-        (TransferFile::Remote(mut remote), _) | (_, TransferFile::Remote(mut remote)) => {
-            remote.send_eof().unwrap();
-            remote.wait_eof().unwrap();
-            remote.close().unwrap();
-            remote.wait_close().unwrap();
-        }
-        _ => {}
-    }
+    // Both sides are remote channels when data is relayed
+    let closed = [file_source, file_target]
+        .into_iter()
+        .try_for_each(|file| match file {
+            TransferFile::Remote(mut remote, _) => close_channel(&mut remote),
+            TransferFile::Local(_) => Ok(()),
+        });
+    copied.and(closed.map_err(CrustError::from))
+}
+
+/// Finishes transfer over scp channel (waits until the other side gets
+/// the whole data).
+fn close_channel(channel: &mut Channel) -> Result<(), ssh2::Error> {
+    channel.send_eof()?;
+    channel.wait_eof()?;
+    channel.close()?;
+    channel.wait_close()
+}
+
+/// Opens file of machine for reading (with its size). Its errors are
+/// errors of source file (see `failure::source_error`).
+fn open_source(machine: &dyn Machine, path: &Path) -> Result<(TransferFile, u64), CrustError> {
+    machine
+        .open_file(path)
+        .map_err(|e| failure::source_error(path, e))
 }
 
 /// Represents a file which is source to get data in copy method.
-/// In 'download' case it relates to Channel from remote machine, in
-/// 'upload' it is a file located on local machine.
-enum TransferFile {
-    Remote(Channel),
+/// In 'download' case it relates to Channel from remote machine (with
+/// its session), in 'upload' it is a file located on local machine.
+pub enum TransferFile {
+    Remote(Channel, Session),
     Local(File),
 }
 
-/// Allows common interface in copy method.
 impl TransferFile {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+    /// Session of remote file.
+    fn session(&self) -> Option<Session> {
+        match self {
+            TransferFile::Remote(_, session) => Some(session.clone()),
+            TransferFile::Local(_) => None,
+        }
+    }
+}
+
+/// Allows common interface in copy method.
+impl Read for TransferFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
-            TransferFile::Remote(channel) => channel.read(buf),
+            TransferFile::Remote(channel, _) => channel.read(buf),
             TransferFile::Local(file) => file.read(buf),
         }
     }
+}
 
-    fn write_all(&mut self, buf: &[u8]) -> Result<(), std::io::Error> {
+impl Write for TransferFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
-            TransferFile::Remote(channel) => channel.write_all(buf),
-            TransferFile::Local(file) => file.write_all(buf),
+            TransferFile::Remote(channel, _) => channel.write(buf),
+            TransferFile::Local(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            TransferFile::Remote(channel, _) => channel.flush(),
+            TransferFile::Local(file) => file.flush(),
         }
     }
 }
 
 pub trait Scp {
-    /// TODO: add copy directories
     /// Allows to upload resource from local to remote.
     /// Supports [Box<dyn Machine>] objects and results from MachinesManager as well.
+    /// Copied bytes are counted by heartbeat of transfer (if any).
     fn upload(
         &self,
         machine: &mut Box<dyn Machine>,
         from: &Path,
         to: &Path,
-        progress: bool,
+        options: &TransferOptions,
+        heartbeat: Option<&Heartbeat>,
     ) -> Result<CrustResult, CrustError> {
         machine.connect()?;
 
-        let size: u64 = match std::fs::metadata(from) {
-            Ok(metadata) => metadata.len(),
-            Err(_) => {
-                return Err(CrustError {
-                    code: ExitCode::Local,
-                    message: "Can not get file size".to_string(),
-                });
-            }
-        };
-
-        let file_to_write = TransferFile::Remote(
-            machine
-                .get_session()
-                .unwrap()
-                .scp_send(to, 0o644, size, None)
-                .unwrap(),
-        );
+        let file_to_read = File::open(from).map_err(|e| failure::source_error(from, e))?;
+        let size = file_to_read
+            .metadata()
+            .map_err(|e| failure::source_error(from, e))?
+            .len();
 
-        let file_to_read =
-            TransferFile::Local(File::open(from).expect("Can not open file on local"));
+        let file_to_write = machine.create_file(to, size)?;
+        let file_to_read = TransferFile::Local(file_to_read);
 
-        let progress_bar: Option<ProgressBar> = match progress {
+        let progress_bar: Option<ProgressBar> = match options.progress {
             true => Some(ProgressBar::new(size)),
             false => None,
         };
 
-        copy_data(file_to_read, file_to_write, progress_bar);
+        let guard = ChannelGuard::open(
+            &machine.timing_target(),
+            ChannelKind::Scp,
+            &format!("upload {} -> {}", from.display(), to.display()),
+        );
+        timing::measure(&machine.timing_target(), Phase::Transfer, || {
+            copy_data(
+                file_to_read,
+                file_to_write,
+                from,
+                progress_bar,
+                heartbeat,
+                &guard,
+            )
+        })?;
 
         Ok(CrustResult::default())
     }
 
-    /// TODO: add copy directories
     /// Allows to download resource from remote to local.
     /// Supports [Box<dyn Machine>] objects and results from MachinesManager as well.
+    /// Copied bytes are counted by heartbeat of transfer (if any).
     fn download(
         &self,
         machine: &mut Box<dyn Machine>,
         from: &Path,
         to: &Path,
-        progress: bool,
+        options: &TransferOptions,
+        heartbeat: Option<&Heartbeat>,
     ) -> Result<CrustResult, CrustError> {
         machine.connect()?;
 
-        let (channel, stat) = machine.get_session().unwrap().scp_recv(from)?;
-        let file_to_read = TransferFile::Remote(channel);
-        let size = stat.size();
-
-        let file_to_write =
-            TransferFile::Local(std::fs::File::create(to).expect("Failed to create file"));
-
-        let progress_bar: Option<ProgressBar> = match progress {
-            true => Some(ProgressBar::new(size)),
+        let cache_entry = match options.cache {
+            true => {
+                let stat = machine.connected_session()?.sftp()?.stat(from)?;
+                let key = CacheKey::new(
+                    &machine.get_id().to_string(),
+                    from,
+                    stat.mtime.unwrap_or(0),
+                    stat.size.unwrap_or(0),
+                );
+                Some((DownloadCache::default(), key))
+            }
             false => None,
         };
 
-        copy_data(file_to_read, file_to_write, progress_bar);
+        if let Some((cache, key)) = &cache_entry {
+            if let Some(cached) = cache.get(key) {
+                log::info!("Served '{}' from local cache", from.display());
+                let copied = std::fs::copy(cached, to)?;
+                if let Some(heartbeat) = heartbeat {
+                    heartbeat.inc(copied as usize);
+                }
+                return Ok(CrustResult::default());
+            }
+        }
+
+        if options.chunks > 1 {
+            chunked::download_chunked(
+                machine.as_ref(),
+                from,
+                to,
+                options.chunks,
+                options.progress,
+                heartbeat,
+            )?;
+        } else {
+            let (file_to_read, size) = open_source(machine.as_ref(), from)?;
+
+            let file_to_write = TransferFile::Local(File::create(to)?);
+
+            let progress_bar: Option<ProgressBar> = match options.progress {
+                true => Some(ProgressBar::new(size)),
+                false => None,
+            };
+
+            let guard = ChannelGuard::open(
+                &machine.timing_target(),
+                ChannelKind::Scp,
+                &format!("download {} -> {}", from.display(), to.display()),
+            );
+            timing::measure(&machine.timing_target(), Phase::Transfer, || {
+                copy_data(
+                    file_to_read,
+                    file_to_write,
+                    from,
+                    progress_bar,
+                    heartbeat,
+                    &guard,
+                )
+            })?;
+        }
+
+        if let Some((cache, key)) = &cache_entry {
+            cache.store(key, to)?;
+        }
         Ok(CrustResult::default())
     }
 
@@ -2,7 +2,10 @@ use clap::Args;
 
 use crate::connection::parser::{ConnectionArgsFrom, ConnectionArgsTo};
 use crate::error::CrustError;
-use crate::interfaces::parser::Validation;
+use crate::interfaces::parser::{Problem, Validation};
+use crate::job::schedule::Schedule;
+use crate::plan::parser::PlanArgs;
+use crate::scp::verify::{VerifyMode, DEFAULT_THRESHOLD};
 
 /// Proxy struct to represent a source machine.
 #[derive(Debug, Args, Clone)]
@@ -15,11 +18,15 @@ pub struct ScpConnectionArgsFrom {
 
 impl Validation for ScpConnectionArgsFrom {
     fn validate(&mut self) -> Result<(), CrustError> {
-        if self.remote_params.is_some() {
-            self.remote_params.as_mut().unwrap().validate()?;
+        if let Some(remote_params) = self.remote_params.as_mut() {
+            remote_params.validate()?;
         }
         Ok(())
     }
+
+    fn problems(&mut self) -> Vec<Problem> {
+        endpoint_problems(&self.path_from, self.remote_params.as_mut(), "source")
+    }
 }
 
 /// Proxy struct to represent a target machine.
@@ -33,11 +40,33 @@ pub struct ScpConnectionArgsTo {
 
 impl Validation for ScpConnectionArgsTo {
     fn validate(&mut self) -> Result<(), CrustError> {
-        if self.remote_params.is_some() {
-            self.remote_params.as_mut().unwrap().validate()?;
+        if let Some(remote_params) = self.remote_params.as_mut() {
+            remote_params.validate()?;
         }
         Ok(())
     }
+
+    fn problems(&mut self) -> Vec<Problem> {
+        endpoint_problems(&self.path_to, self.remote_params.as_mut(), "destination")
+    }
+}
+
+/// Problems of path and machine on one side of transfer.
+fn endpoint_problems(path: &str, remote: Option<&mut impl Validation>, side: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if path.is_empty() {
+        problems.push(
+            Problem::new("Transfer path can not be empty")
+                .hint(&format!("pass path of {side} file or directory")),
+        );
+    }
+    if let Some(remote) = remote {
+        problems.extend(remote.problems());
+    }
+    problems
+        .into_iter()
+        .map(|p| p.context(&format!("{side} machine")))
+        .collect()
 }
 
 #[derive(Args, Clone, Debug)]
@@ -55,12 +84,198 @@ pub struct ScpArgs {
     #[clap(long, default_value = "false")]
     /// Show progress bar
     pub progress: bool,
+
+    #[clap(long, default_value = "false")]
+    /// Serve unchanged remote files from local download cache
+    /// (can be enabled for every call with CRUST_CACHE=true)
+    pub cache: bool,
+
+    #[clap(long, default_value = "false", conflicts_with = "cache")]
+    /// Always download files, even if the cache is enabled
+    pub no_cache: bool,
+
+    #[clap(long, default_value = "1")]
+    /// Number of parallel channels used to download a single file
+    /// (failed chunks are retried by other channels, chunks failed in all
+    /// attempts are fetched by the next download of the file)
+    pub chunks: usize,
+
+    #[clap(long, value_name = "SECONDS")]
+    /// Reports progress of the whole transfer (current file, bytes,
+    /// throughput, ETA) on stderr every N seconds
+    pub heartbeat: Option<u64>,
+
+    #[clap(long, default_value = "none", value_name = "MODE")]
+    /// Verify copied files with sha256: none, full or sample:<N>%
+    /// (random sample of files plus all files above --verify-threshold)
+    pub verify: String,
+
+    #[clap(long, value_name = "SEED")]
+    /// Seed of random sample (reported after verification, to repeat it)
+    pub verify_seed: Option<u64>,
+
+    #[clap(long, default_value_t = DEFAULT_THRESHOLD, value_name = "BYTES")]
+    /// Files bigger than this are always verified in sample mode
+    pub verify_threshold: u64,
+
+    #[clap(long, default_value = "false")]
+    /// Copy only files which are missing or differ (size or sha256) on
+    /// destination
+    pub sync: bool,
+
+    #[clap(long, default_value = "false")]
+    /// Transfer between remote machines: source machine sends data to
+    /// destination with its own ssh (requires passwordless access from
+    /// source to destination) instead of relaying it through this machine
+    pub direct: bool,
+
+    #[clap(long, value_name = "CMD")]
+    /// Command run on destination machine before every copied file,
+    /// e.g. 'systemctl stop app' ({path} is replaced with file path)
+    pub before_file: Option<String>,
+
+    #[clap(long, value_name = "CMD")]
+    /// Command run on destination machine after every copied file,
+    /// e.g. 'chmod +x {path}'
+    pub after_file: Option<String>,
+
+    #[clap(long, value_name = "SPEC")]
+    /// Run transfer later by background process: at HH:MM or repeatedly
+    /// with cron-like spec ('min hour day month weekday')
+    pub schedule: Option<String>,
+
+    #[clap(flatten)]
+    pub plan: PlanArgs,
 }
 
 impl Validation for ScpArgs {
     fn validate(&mut self) -> Result<(), CrustError> {
-        self.src.validate()?;
-        self.dst.validate()?;
-        Ok(())
+        match self.problems().into_iter().next() {
+            Some(problem) => Err(problem.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks arguments of machines and options which conflict with
+    /// them, so they are rejected before transfer request is built.
+    fn problems(&mut self) -> Vec<Problem> {
+        let mut problems = self.src.problems();
+        problems.extend(self.dst.problems());
+
+        if let Err(e) = self.verify.parse::<VerifyMode>() {
+            problems.push(Problem::from(e).context("--verify"));
+        }
+        if let Some(Err(e)) = self.schedule.as_ref().map(|spec| spec.parse::<Schedule>()) {
+            problems.push(Problem::from(e).context("--schedule"));
+        }
+        if self.chunks == 0 {
+            problems.push(
+                Problem::new("Number of chunks must be greater than 0")
+                    .context("--chunks")
+                    .hint("use 1 to download every file with a single channel"),
+            );
+        }
+        if self.heartbeat == Some(0) {
+            problems.push(
+                Problem::new("Heartbeat interval must be greater than 0")
+                    .context("--heartbeat")
+                    .hint("skip --heartbeat to disable progress reports"),
+            );
+        }
+        for (flag, hook) in [
+            ("--before-file", &self.before_file),
+            ("--after-file", &self.after_file),
+        ] {
+            if hook.as_ref().is_some_and(|hook| hook.trim().is_empty()) {
+                problems.push(
+                    Problem::new("Hook command can not be empty")
+                        .context(flag)
+                        .hint("skip the hook or pass a command, e.g. 'chmod +x {path}'"),
+                );
+            }
+        }
+        if self.direct && (self.src.remote_params.is_none() || self.dst.remote_params.is_none()) {
+            problems.push(
+                Problem::new("Direct transfer is available only between remote machines")
+                    .context("--direct")
+                    .hint("pass both source (--addr-from or --alias-from) and destination (--addr-to or --alias-to) machines, or skip --direct"),
+            );
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::error::ExitCode;
+    use crate::interfaces::parser::{validate_all, Validation};
+    use crate::parser::AppArgs;
+
+    fn parse(args: &[&str]) -> AppArgs {
+        AppArgs::try_parse_from(std::iter::once("crust").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_all_problems_of_transfer() {
+        let mut args = parse(&[
+            "scp",
+            "/tmp/a.txt",
+            "/srv/a.txt",
+            "--addr-from",
+            "deploy@",
+            "--password-from",
+            "1234",
+            "--addr-to",
+            "deploy@web-1",
+            "--chunks",
+            "0",
+            "--verify",
+            "half",
+        ]);
+
+        assert_eq!(
+            args.problems()
+                .iter()
+                .map(|p| p.context.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "source machine",
+                "destination machine",
+                "--verify",
+                "--chunks"
+            ]
+        );
+        let err = validate_all(&mut args).err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert!(err
+            .message
+            .starts_with("Found 4 problems with arguments:\n"));
+        assert!(err.message.contains(
+            "- destination machine: Neither password nor pkey provided\n    \
+             hint: pass --password-to, --pkey-to or --pkey-inline-to \
+             (or --alias-to of machine registered in background session)"
+        ));
+        assert!(err
+            .message
+            .contains("hint: use --addr-from <user>@<host>[:<port>]"));
+    }
+
+    #[test]
+    fn test_direct_transfer_from_local_machine() {
+        let mut args = parse(&["scp", "a.txt", "b.txt", "--alias-to", "web-1", "--direct"]);
+
+        assert_eq!(
+            args.validate().err().unwrap().message,
+            "Direct transfer is available only between remote machines"
+        );
+        let problems = args.problems();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].context.as_deref(), Some("--direct"));
+        assert_eq!(
+            problems[0].message,
+            "Direct transfer is available only between remote machines"
+        );
     }
 }
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use crate::connection::channels::{ChannelGuard, ChannelKind};
+use crate::connection::timing::{self, Phase};
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::progress_bar::ProgressBar;
+use crate::machine::Machine;
+use crate::scp::heartbeat::Heartbeat;
+use crate::scp::hooks::{self, FileHooks};
+use crate::scp::tree::TreeEntry;
+use crate::scp::{copy_data, open_source, TransferOptions};
+use crate::utils::shell::{quote, quote_path};
+
+/// Streams a file between two remote machines through local one - nothing
+/// is stored locally.
+pub fn relay_file(
+    src: &dyn Machine,
+    dst: &dyn Machine,
+    from: &Path,
+    to: &Path,
+    options: &TransferOptions,
+    heartbeat: Option<&Heartbeat>,
+) -> Result<(), CrustError> {
+    let (reader, size) = open_source(src, from)?;
+    let writer = dst.create_file(to, size)?;
+
+    let progress_bar = match options.progress {
+        true => Some(ProgressBar::new(size)),
+        false => None,
+    };
+    let target = dst.timing_target();
+    let guard = ChannelGuard::open(
+        &target,
+        ChannelKind::Scp,
+        &format!("relay {}:{} -> {}", src, from.display(), to.display()),
+    );
+    timing::measure(&target, Phase::Transfer, || {
+        copy_data(reader, writer, from, progress_bar, heartbeat, &guard)
+    })
+}
+
+/// Offloads transfer to source machine: it sends files with `tar` over
+/// its own `ssh` connection to destination, so data does not go through
+/// local machine at all. Source machine must be able to log in to
+/// destination without password (keys or agent forwarding) - hostname
+/// of destination is the same as used by local machine.
+pub fn direct(
+    src: &dyn Machine,
+    dst: &dyn Machine,
+    path_from: &Path,
+    path_to: &Path,
+    entries: &[TreeEntry],
+    file_hooks: &FileHooks,
+) -> Result<(), CrustError> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if src.host_os().is_windows() || dst.host_os().is_windows() {
+        return Err(CrustError {
+            code: ExitCode::Parser,
+            message: "Direct transfer is not supported for Windows machines".to_string(),
+        });
+    }
+    let args = dst.connect_args().ok_or_else(|| CrustError {
+        code: ExitCode::Parser,
+        message: format!("Direct transfer requires remote destination, got {dst}"),
+    })?;
+
+    let targets = entries
+        .iter()
+        .map(|e| e.join_to(path_to))
+        .collect::<Vec<_>>();
+    run_all(dst, &targets, |path| file_hooks.before(path, dst.host_os()))?;
+
+    let (command, list) = direct_command(
+        &format!("{}@{}", args.username(), args.hostname()),
+        args.port(),
+        path_from,
+        path_to,
+        entries,
+    );
+    log::debug!("Direct transfer on {src}: {command}");
+    let output = match list.is_empty() {
+        true => src.exec(&command)?,
+        false => src.exec_with_input(&command, &list)?,
+    };
+    if !output.is_success() {
+        return Err(CrustError {
+            code: ExitCode::Remote,
+            message: format!(
+                "Direct transfer from {src} to {dst} failed: {}",
+                output.stderr().trim()
+            ),
+        });
+    }
+
+    run_all(dst, &targets, |path| file_hooks.after(path, dst.host_os()))
+}
+
+/// Command sending entries from source machine to `address` (single file
+/// with `cat`, trees with `tar`) and list of entries passed on its stdin
+/// (empty for single file). Exit status of sending `tar` is checked too -
+/// pipeline alone reports only status of `ssh`.
+fn direct_command(
+    address: &str,
+    port: u16,
+    path_from: &Path,
+    path_to: &Path,
+    entries: &[TreeEntry],
+) -> (String, String) {
+    let ssh = format!("ssh -o BatchMode=yes -p {port} {}", quote(address));
+    if entries.len() == 1 && entries[0].path.as_os_str().is_empty() {
+        let receive = format!("cat > {}", quote_path(path_to));
+        let command = format!("{ssh} {} < {}", quote(&receive), quote_path(path_from));
+        return (command, String::new());
+    }
+
+    // Names are prefixed with `./`, so tar never reads them as options
+    let list = entries
+        .iter()
+        .map(|e| format!("./{}\n", e.path.to_string_lossy()))
+        .collect::<String>();
+    let receive = format!("mkdir -p {0} && tar -C {0} -xf -", quote_path(path_to));
+    let command = format!(
+        "cd {} && ( {{ s=$( {{ {{ tar -cf - -T -; echo $? >&3; }} | {ssh} {} >&4; }} 3>&1 ); r=$?; }} 4>&1; \
+[ \"$r\" = 0 ] || exit \"$r\"; [ \"$s\" = 0 ] || {{ echo \"tar failed with status $s\" >&2; exit 1; }} )",
+        quote_path(path_from),
+        quote(&receive)
+    );
+    (command, list)
+}
+
+/// Runs rendered hooks of all paths with a single command.
+fn run_all(
+    machine: &dyn Machine,
+    paths: &[PathBuf],
+    render: impl Fn(&Path) -> Option<String>,
+) -> Result<(), CrustError> {
+    let commands = paths.iter().filter_map(|p| render(p)).collect::<Vec<_>>();
+    if commands.is_empty() {
+        return Ok(());
+    }
+    hooks::run(machine, &commands).into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::Exec;
+    use crate::machine::local::LocalMachine;
+
+    fn entry(path: &str) -> TreeEntry {
+        TreeEntry {
+            path: PathBuf::from(path),
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn test_direct_command() {
+        assert_eq!(
+            direct_command(
+                "user@host",
+                2222,
+                Path::new("/src/file"),
+                Path::new("/dst/my file"),
+                &[entry("")]
+            ),
+            (
+                String::from("ssh -o BatchMode=yes -p 2222 'user@host' 'cat > '\\''/dst/my file'\\''' < '/src/file'"),
+                String::new()
+            )
+        );
+        let (command, list) = direct_command(
+            "user@host",
+            22,
+            Path::new("/src"),
+            Path::new("/dst"),
+            &[entry("a/b.txt"), entry("-c.txt")],
+        );
+        assert_eq!(
+            command,
+            "cd '/src' && ( { s=$( { { tar -cf - -T -; echo $? >&3; } | ssh -o BatchMode=yes -p 22 'user@host' 'mkdir -p '\\''/dst'\\'' && tar -C '\\''/dst'\\'' -xf -' >&4; } 3>&1 ); r=$?; } 4>&1; \
+[ \"$r\" = 0 ] || exit \"$r\"; [ \"$s\" = 0 ] || { echo \"tar failed with status $s\" >&2; exit 1; } )"
+        );
+        assert_eq!(list, "./a/b.txt\n./-c.txt\n");
+    }
+
+    #[test]
+    fn test_direct_command_reports_failure_of_tar() {
+        let dir = std::env::temp_dir().join(format!("crust_direct_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("tree")).unwrap();
+        std::fs::write(dir.join("tree/a.txt"), "a").unwrap();
+        let entries = [entry("a.txt"), entry("missing.txt")];
+        // Receiving side (ssh) is replaced by extraction into local dir
+        let run = |entries: &[TreeEntry]| {
+            let (command, list) = direct_command(
+                "user@host",
+                22,
+                &dir.join("tree"),
+                &dir.join("copy"),
+                entries,
+            );
+            let command = command.replace("ssh -o BatchMode=yes -p 22 'user@host' ", "sh -c ");
+            LocalMachine::default()
+                .exec_with_input(&command, &list)
+                .unwrap()
+        };
+
+        let copied = run(&entries[..1]);
+        let content = std::fs::read_to_string(dir.join("copy/a.txt"));
+        let failed = run(&entries);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(copied.is_success());
+        assert_eq!(content.unwrap(), "a");
+        assert!(!failed.is_success());
+        assert!(failed.stderr().contains("tar failed with status"));
+    }
+}
@@ -0,0 +1,359 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::connection::request::RemoteTarget;
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::parser::Validation;
+use crate::scp::parser::ScpArgs;
+use crate::scp::verify::VerifyMode;
+use crate::scp::TransferOptions;
+use crate::utils::shell_manager::ShellManager;
+
+/// One side of transfer: path with optional remote machine
+/// (None means local machine).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferEndpoint {
+    pub path: PathBuf,
+    pub remote: Option<RemoteTarget>,
+}
+
+/// Validated request to copy data between two machines. Created only by
+/// `TransferRequestBuilder`, so it can be passed to `crust::run_transfer`
+/// without any further checks.
+/// # Example
+/// ```
+/// use crust::connection::request::RemoteTarget;
+/// use crust::scp::request::TransferRequest;
+///
+/// let request = TransferRequest::builder("/tmp/file", "/home/user/file")
+///     .dst_remote(RemoteTarget::new("user@10.10.10.10").password("1234"))
+///     .progress(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferRequest {
+    src: TransferEndpoint,
+    dst: TransferEndpoint,
+    options: TransferOptions,
+}
+
+impl TransferRequest {
+    /// Starts building a request to copy `src` path into `dst` path.
+    pub fn builder(src: impl Into<PathBuf>, dst: impl Into<PathBuf>) -> TransferRequestBuilder {
+        TransferRequestBuilder {
+            src: TransferEndpoint {
+                path: src.into(),
+                remote: None,
+            },
+            dst: TransferEndpoint {
+                path: dst.into(),
+                remote: None,
+            },
+            options: TransferOptions::default(),
+        }
+    }
+
+    /// Getter for source side of transfer.
+    pub fn src(&self) -> &TransferEndpoint {
+        &self.src
+    }
+
+    /// Getter for destination side of transfer.
+    pub fn dst(&self) -> &TransferEndpoint {
+        &self.dst
+    }
+
+    /// Getter for options of transfer.
+    pub fn options(&self) -> &TransferOptions {
+        &self.options
+    }
+}
+
+/// Builder of `TransferRequest`.
+pub struct TransferRequestBuilder {
+    src: TransferEndpoint,
+    dst: TransferEndpoint,
+    options: TransferOptions,
+}
+
+impl TransferRequestBuilder {
+    /// Sets remote machine as a source of data.
+    pub fn src_remote(mut self, target: RemoteTarget) -> Self {
+        self.src.remote = Some(target);
+        self
+    }
+
+    /// Sets remote machine as a destination of data.
+    pub fn dst_remote(mut self, target: RemoteTarget) -> Self {
+        self.dst.remote = Some(target);
+        self
+    }
+
+    /// Shows progress bar during transfer.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.options.progress = progress;
+        self
+    }
+
+    /// Serves unchanged remote files from local download cache.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.options.cache = cache;
+        self
+    }
+
+    /// Downloads a single file with `chunks` parallel channels.
+    pub fn chunks(mut self, chunks: usize) -> Self {
+        self.options.chunks = chunks;
+        self
+    }
+
+    /// Reports progress of transfer periodically with a passed interval.
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.options.heartbeat = Some(interval);
+        self
+    }
+
+    /// Compares copied files with source ones (see `VerifyMode`).
+    pub fn verify(mut self, mode: VerifyMode) -> Self {
+        self.options.verify.mode = mode;
+        self
+    }
+
+    /// Seed of random sample of verified files (makes sample reproducible).
+    pub fn verify_seed(mut self, seed: u64) -> Self {
+        self.options.verify.seed = Some(seed);
+        self
+    }
+
+    /// Files bigger than `threshold` bytes are always verified in sample mode.
+    pub fn verify_threshold(mut self, threshold: u64) -> Self {
+        self.options.verify.threshold = threshold;
+        self
+    }
+
+    /// Runs command on destination machine before every copied file
+    /// (`{path}` is replaced with path of file).
+    pub fn before_file(mut self, hook: &str) -> Self {
+        self.options.hooks.before = Some(hook.to_string());
+        self
+    }
+
+    /// Runs command on destination machine after every copied file
+    /// (`{path}` is replaced with path of file).
+    pub fn after_file(mut self, hook: &str) -> Self {
+        self.options.hooks.after = Some(hook.to_string());
+        self
+    }
+
+    /// Copies only files which are missing or differ on destination.
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.options.sync = sync;
+        self
+    }
+
+    /// Lets source machine send data to destination itself, instead of
+    /// relaying it through local machine (both machines must be remote).
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.options.direct = direct;
+        self
+    }
+
+    /// Validates collected values and creates a request.
+    pub fn build(mut self) -> Result<TransferRequest, CrustError> {
+        if self.options.chunks == 0 {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Number of chunks must be greater than 0".to_string(),
+            });
+        }
+
+        if self.options.heartbeat == Some(Duration::ZERO) {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Heartbeat interval must be greater than 0".to_string(),
+            });
+        }
+
+        self.options.hooks.validate()?;
+
+        if self.options.direct && (self.src.remote.is_none() || self.dst.remote.is_none()) {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: "Direct transfer is available only between remote machines".to_string(),
+            });
+        }
+
+        for endpoint in [&self.src, &self.dst] {
+            if endpoint.path == Path::new("") {
+                return Err(CrustError {
+                    code: ExitCode::Parser,
+                    message: "Transfer path can not be empty".to_string(),
+                });
+            }
+        }
+
+        if let Some(remote) = self.src.remote.as_mut() {
+            remote.validate()?;
+        }
+        if let Some(remote) = self.dst.remote.as_mut() {
+            remote.validate()?;
+        }
+
+        Ok(TransferRequest {
+            src: self.src,
+            dst: self.dst,
+            options: self.options,
+        })
+    }
+}
+
+impl TryFrom<&ScpArgs> for TransferRequest {
+    type Error = CrustError;
+
+    fn try_from(args: &ScpArgs) -> Result<Self, Self::Error> {
+        let mut builder = TransferRequest::builder(&args.src.path_from, &args.dst.path_to)
+            .progress(args.progress)
+            .chunks(args.chunks)
+            .cache((args.cache || ShellManager::is_cache_enabled()) && !args.no_cache)
+            .verify(args.verify.parse()?)
+            .verify_threshold(args.verify_threshold)
+            .sync(args.sync)
+            .direct(args.direct);
+        if let Some(seed) = args.verify_seed {
+            builder = builder.verify_seed(seed);
+        }
+        if let Some(secs) = args.heartbeat {
+            builder = builder.heartbeat(Duration::from_secs(secs));
+        }
+        if let Some(hook) = &args.before_file {
+            builder = builder.before_file(hook);
+        }
+        if let Some(hook) = &args.after_file {
+            builder = builder.after_file(hook);
+        }
+        if let Some(remote) = &args.src.remote_params {
+            builder = builder.src_remote(RemoteTarget::from(remote));
+        }
+        if let Some(remote) = &args.dst.remote_params {
+            builder = builder.dst_remote(RemoteTarget::from(remote));
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_upload_request() {
+        let target = RemoteTarget::new("user@host").password("1234");
+        let request = TransferRequest::builder("a.txt", "/tmp/b.txt")
+            .dst_remote(target.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(request.src().path, PathBuf::from("a.txt"));
+        assert_eq!(request.src().remote, None);
+        assert_eq!(request.dst().path, PathBuf::from("/tmp/b.txt"));
+        assert_eq!(request.dst().remote, Some(target));
+        assert_eq!(request.options(), &TransferOptions::default());
+    }
+
+    #[test]
+    fn test_build_request_with_empty_path() {
+        let result = TransferRequest::builder("", "/tmp/b.txt").build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(err.message, "Transfer path can not be empty");
+    }
+
+    #[test]
+    fn test_build_request_with_zero_chunks() {
+        let result = TransferRequest::builder("a", "b").chunks(0).build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.message, "Number of chunks must be greater than 0");
+    }
+
+    #[test]
+    fn test_build_request_with_zero_heartbeat() {
+        let result = TransferRequest::builder("a", "b")
+            .heartbeat(Duration::ZERO)
+            .build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.message, "Heartbeat interval must be greater than 0");
+    }
+
+    #[test]
+    fn test_build_direct_request() {
+        let target = RemoteTarget::new("user@host").password("1234");
+        let err = TransferRequest::builder("a", "b")
+            .dst_remote(target.clone())
+            .direct(true)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message,
+            "Direct transfer is available only between remote machines"
+        );
+
+        let request = TransferRequest::builder("a", "b")
+            .src_remote(target.clone())
+            .dst_remote(target)
+            .sync(true)
+            .direct(true)
+            .build()
+            .unwrap();
+        assert!(request.options().sync && request.options().direct);
+    }
+
+    #[test]
+    fn test_build_request_with_hooks() {
+        let request = TransferRequest::builder("a", "b")
+            .after_file("chmod +x {path}")
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.options().hooks.after.as_deref(),
+            Some("chmod +x {path}")
+        );
+
+        let err = TransferRequest::builder("a", "b")
+            .before_file("")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.message, "Hook command can not be empty");
+    }
+
+    #[test]
+    fn test_build_request_with_sample_verification() {
+        let request = TransferRequest::builder("a", "b")
+            .verify("sample:5%".parse().unwrap())
+            .verify_seed(42)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            request.options().verify.mode,
+            VerifyMode::Sample { ratio: 0.05 }
+        );
+        assert_eq!(request.options().verify.seed, Some(42));
+    }
+
+    #[test]
+    fn test_build_request_validates_both_targets() {
+        let result = TransferRequest::builder("a", "b")
+            .src_remote(RemoteTarget::with_alias("src"))
+            .dst_remote(RemoteTarget::new("user@").password("1234"))
+            .build();
+
+        let err = result.err().unwrap();
+        assert_eq!(err.message, "Invalid address pattern. Use <user>@<host>");
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use crate::error::CrustError;
+use crate::machine::Machine;
+use crate::scp::tree::TreeEntry;
+use crate::scp::verify;
+
+/// Selects files of source tree which are missing or differ on destination
+/// (so only they have to be copied). Files of the same size are compared
+/// by sha256 computed on both machines - a single command per machine.
+pub fn changed(
+    src: &dyn Machine,
+    src_root: &Path,
+    dst: &dyn Machine,
+    dst_root: &Path,
+    entries: &[TreeEntry],
+) -> Result<Vec<TreeEntry>, CrustError> {
+    let existing = match dst.list_tree(dst_root) {
+        Ok(existing) => existing,
+        Err(e) => {
+            log::debug!("Destination is treated as empty: {e}");
+            return Ok(entries.to_vec());
+        }
+    };
+
+    let mut changed = Vec::new();
+    let mut same_size = Vec::new();
+    for entry in entries {
+        match existing.iter().find(|e| e.path == entry.path) {
+            Some(e) if e.size == entry.size => same_size.push(entry),
+            _ => changed.push(entry.clone()),
+        }
+    }
+    if same_size.is_empty() {
+        return Ok(changed);
+    }
+
+    let src_hashes = verify::hashes(
+        src,
+        &same_size
+            .iter()
+            .map(|e| e.join_to(src_root))
+            .collect::<Vec<_>>(),
+    )?;
+    let dst_hashes = verify::hashes(
+        dst,
+        &same_size
+            .iter()
+            .map(|e| e.join_to(dst_root))
+            .collect::<Vec<_>>(),
+    )?;
+    for (idx, entry) in same_size.into_iter().enumerate() {
+        if src_hashes.get(idx) != dst_hashes.get(idx) {
+            changed.push(entry.clone());
+        }
+    }
+    changed.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::local::LocalMachine;
+    use crate::scp::tree;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_select_changed_files() {
+        let root = PathBuf::from(format!("/tmp/tmp.{}", Uuid::new_v4().as_u128()));
+        let (src, dst) = (root.join("src"), root.join("dst"));
+        std::fs::create_dir_all(src.join("a")).unwrap();
+        std::fs::create_dir_all(dst.join("a")).unwrap();
+        for (path, src_content, dst_content) in [
+            ("same.txt", "123", Some("123")),
+            ("a/edited.txt", "123", Some("321")),
+            ("resized.txt", "1234", Some("1")),
+            ("new.txt", "1", None),
+        ] {
+            std::fs::write(src.join(path), src_content).unwrap();
+            if let Some(content) = dst_content {
+                std::fs::write(dst.join(path), content).unwrap();
+            }
+        }
+        let machine = LocalMachine::default();
+        let entries = tree::list_local(&src).unwrap();
+
+        let changed = changed(&machine, &src, &machine, &dst, &entries).unwrap();
+        assert_eq!(
+            changed.iter().map(|e| e.path.clone()).collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("a/edited.txt"),
+                PathBuf::from("new.txt"),
+                PathBuf::from("resized.txt")
+            ]
+        );
+
+        let missing = root.join("missing");
+        assert_eq!(
+            super::changed(&machine, &src, &machine, &missing, &entries).unwrap(),
+            entries
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
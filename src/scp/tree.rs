@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use indicatif::HumanBytes;
+use ssh2::{Session, Sftp};
+
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::response::CrustResult;
+use crate::scp::failure::FailureClass;
+use crate::scp::verify::VerificationReport;
+
+/// Single file of transferred tree.
+/// - path: path relative to root of tree (empty when root is a file)
+/// - size: size of file in bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl TreeEntry {
+    /// Gets a full path of entry inside passed root.
+    pub fn join_to(&self, root: &Path) -> PathBuf {
+        match self.path.as_os_str().is_empty() {
+            true => root.to_path_buf(),
+            false => root.join(&self.path),
+        }
+    }
+}
+
+/// Lists all files from local tree (sorted by path). If root is a file,
+/// it is the only entry.
+pub fn list_local(root: &Path) -> Result<Vec<TreeEntry>, CrustError> {
+    let metadata = std::fs::metadata(root).map_err(|e| CrustError {
+        code: ExitCode::Local,
+        message: format!("Can not read '{}': {e}", root.display()),
+    })?;
+    if !metadata.is_dir() {
+        return Ok(vec![TreeEntry {
+            path: PathBuf::new(),
+            size: metadata.len(),
+        }]);
+    }
+
+    let mut entries = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            match metadata.is_dir() {
+                true => dirs.push(entry.path()),
+                false => entries.push(TreeEntry {
+                    path: entry.path().strip_prefix(root).unwrap().to_path_buf(),
+                    size: metadata.len(),
+                }),
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Lists all files from remote tree (sorted by path) using sftp.
+pub fn list_remote(session: &Session, root: &Path) -> Result<Vec<TreeEntry>, CrustError> {
+    let sftp = session.sftp()?;
+    let stat = sftp.stat(root).map_err(|e| CrustError {
+        code: ExitCode::Remote,
+        message: format!("Can not read '{}': {e}", root.display()),
+    })?;
+    if !stat.is_dir() {
+        return Ok(vec![TreeEntry {
+            path: PathBuf::new(),
+            size: stat.size.unwrap_or(0),
+        }]);
+    }
+
+    let mut entries = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for (path, stat) in sftp.readdir(&dir)? {
+            match stat.is_dir() {
+                true => dirs.push(path),
+                false => entries.push(TreeEntry {
+                    path: path.strip_prefix(root).unwrap().to_path_buf(),
+                    size: stat.size.unwrap_or(0),
+                }),
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Creates (with parents) remote directory if it does not exist yet.
+pub fn create_remote_dir(sftp: &Sftp, dir: &Path) -> Result<(), CrustError> {
+    let mut current = PathBuf::new();
+    for component in dir.components() {
+        current.push(component);
+        if sftp.stat(&current).is_err() {
+            sftp.mkdir(&current, 0o755)?;
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of transfer of a single file.
+#[derive(Debug, Clone)]
+pub struct FileOutcome {
+    pub path: PathBuf,
+    pub size: u64,
+    pub error: Option<CrustError>,
+}
+
+/// Summary of transfer of many files (with optional verification).
+#[derive(Debug, Clone, Default)]
+pub struct TransferReport {
+    pub files: Vec<FileOutcome>,
+    pub unchanged: usize,
+    pub verification: Option<VerificationReport>,
+}
+
+impl TransferReport {
+    /// Checks whether every file was copied (and verified, if requested).
+    pub fn is_success(&self) -> bool {
+        self.files.iter().all(|f| f.error.is_none())
+            && self.verification.as_ref().is_none_or(|v| v.is_success())
+    }
+
+    /// Human readable summary of transfer.
+    pub fn summary(&self) -> String {
+        let copied = self.files.iter().filter(|f| f.error.is_none());
+        let mut lines = vec![format!(
+            "Transferred {}/{} files ({})",
+            copied.clone().count(),
+            self.files.len(),
+            HumanBytes(copied.map(|f| f.size).sum())
+        )];
+        if self.unchanged > 0 {
+            lines[0].push_str(&format!(", {} unchanged", self.unchanged));
+        }
+
+        for file in &self.files {
+            if let Some(error) = &file.error {
+                lines.push(format!("  {}: {}", file.path.display(), error.message));
+            }
+        }
+        if let Some(verification) = &self.verification {
+            lines.push(verification.summary());
+        }
+
+        let failures = self.failures();
+        if !failures.is_empty() {
+            lines.push(String::from("Failures by class:"));
+            for (class, count) in failures {
+                let retry = match class.is_retryable() {
+                    true => " (retryable)",
+                    false => "",
+                };
+                lines.push(format!(
+                    "  {class}: {count}{retry} - {}",
+                    class.remediation()
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Number of failed files per class of failure (files which do not
+    /// match after verification are integrity failures).
+    pub fn failures(&self) -> Vec<(FailureClass, usize)> {
+        let mut counts = BTreeMap::new();
+        for error in self.files.iter().filter_map(|f| f.error.as_ref()) {
+            *counts.entry(FailureClass::of(error)).or_insert(0) += 1;
+        }
+        if let Some(verification) = &self.verification {
+            if !verification.mismatches.is_empty() {
+                *counts.entry(FailureClass::Integrity).or_insert(0) +=
+                    verification.mismatches.len();
+            }
+        }
+        counts.into_iter().collect()
+    }
+}
+
+impl From<TransferReport> for CrustResult {
+    fn from(report: TransferReport) -> Self {
+        let summary = report.summary();
+        match report.is_success() {
+            true => CrustResult::new(&summary, "", 0),
+            false => CrustResult::new(&summary, &summary, ExitCode::Local.to_int()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scp::verify::VerifyMode;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_list_local_tree() {
+        let root = PathBuf::from(format!("/tmp/tmp.{}", Uuid::new_v4().as_u128()));
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::write(root.join("z.txt"), "12345").unwrap();
+        std::fs::write(root.join("a/b/c.txt"), "1").unwrap();
+
+        let entries = list_local(&root).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                TreeEntry {
+                    path: PathBuf::from("a/b/c.txt"),
+                    size: 1
+                },
+                TreeEntry {
+                    path: PathBuf::from("z.txt"),
+                    size: 5
+                },
+            ]
+        );
+        assert_eq!(
+            entries[0].join_to(Path::new("/dst")),
+            Path::new("/dst/a/b/c.txt")
+        );
+
+        let file = list_local(&root.join("z.txt")).unwrap();
+        assert_eq!(
+            file[0].join_to(Path::new("/dst/file")),
+            Path::new("/dst/file")
+        );
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_list_not_existing_local_tree() {
+        let err = list_local(Path::new("/not/existing")).err().unwrap();
+
+        assert_eq!(err.code, ExitCode::Local);
+        assert!(err.message.starts_with("Can not read '/not/existing'"));
+    }
+
+    #[test]
+    fn test_report_summary() {
+        let report = TransferReport {
+            files: vec![
+                FileOutcome {
+                    path: PathBuf::from("a"),
+                    size: 1024,
+                    error: None,
+                },
+                FileOutcome {
+                    path: PathBuf::from("b"),
+                    size: 10,
+                    error: Some(CrustError {
+                        code: ExitCode::Ssh,
+                        message: String::from("Permission denied"),
+                    }),
+                },
+            ],
+            unchanged: 0,
+            verification: None,
+        };
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.summary(),
+            "Transferred 1/2 files (1.00 KiB)\n  b: Permission denied\nFailures by class:\n  permission: 1 - check owner and mode of destination files and directories"
+        );
+    }
+
+    #[test]
+    fn test_report_with_failed_verification() {
+        let report = TransferReport {
+            files: vec![],
+            unchanged: 0,
+            verification: Some(VerificationReport {
+                mode: VerifyMode::Full,
+                seed: 0,
+                total_files: 1,
+                total_bytes: 1,
+                checked_files: 1,
+                checked_bytes: 1,
+                mismatches: vec![PathBuf::from("a")],
+            }),
+        };
+
+        assert!(!report.is_success());
+        assert_eq!(report.failures(), vec![(FailureClass::Integrity, 1)]);
+        assert_eq!(CrustResult::from(report).retcode(), 2);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use indicatif::HumanBytes;
+
+use crate::error::{CrustError, ExitCode};
+use crate::machine::utilities;
+use crate::machine::Machine;
+use crate::scp::tree::TreeEntry;
+
+/// Files bigger than this are always verified in sample mode (by default).
+pub const DEFAULT_THRESHOLD: u64 = 1024 * 1024 * 1024;
+
+/// How copied files are compared with source ones.
+/// - None: no verification
+/// - Full: every file is hashed on both sides
+/// - Sample: random part (`ratio` of files) is hashed, together with all
+///   files bigger than threshold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyMode {
+    None,
+    Full,
+    Sample { ratio: f64 },
+}
+
+impl FromStr for VerifyMode {
+    type Err = CrustError;
+
+    /// Parses `none`, `full` or `sample:<percent>%` (e.g. `sample:5%`).
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let err = || CrustError {
+            code: ExitCode::Parser,
+            message: format!("Invalid verify mode '{value}'. Use none, full or sample:<N>%"),
+        };
+
+        match value {
+            "none" => Ok(VerifyMode::None),
+            "full" => Ok(VerifyMode::Full),
+            _ => {
+                let percent = value
+                    .strip_prefix("sample:")
+                    .and_then(|v| v.strip_suffix('%'))
+                    .and_then(|v| v.parse::<f64>().ok())
+                    .ok_or_else(err)?;
+                match percent > 0.0 && percent <= 100.0 {
+                    true => Ok(VerifyMode::Sample {
+                        ratio: percent / 100.0,
+                    }),
+                    false => Err(err()),
+                }
+            }
+        }
+    }
+}
+
+/// Verification settings of transfer.
+/// - seed: seed of random sample (random one is used when not passed,
+///   it is always reported, so the same sample can be checked again)
+/// - threshold: size above which files are always verified in sample mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyOptions {
+    pub mode: VerifyMode,
+    pub seed: Option<u64>,
+    pub threshold: u64,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            mode: VerifyMode::None,
+            seed: None,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+}
+
+/// Small, seedable pseudo-random generator (splitmix64).
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Chooses indexes of entries to verify.
+pub fn select(entries: &[TreeEntry], mode: VerifyMode, threshold: u64, seed: u64) -> Vec<usize> {
+    match mode {
+        VerifyMode::None => vec![],
+        VerifyMode::Full => (0..entries.len()).collect(),
+        VerifyMode::Sample { ratio } => {
+            let mut selected: BTreeSet<usize> = entries
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.size > threshold)
+                .map(|(idx, _)| idx)
+                .collect();
+
+            let mut rest = (0..entries.len())
+                .filter(|idx| !selected.contains(idx))
+                .collect::<Vec<_>>();
+            let count = ((rest.len() as f64 * ratio).ceil() as usize).min(rest.len());
+
+            // Partial Fisher-Yates shuffle - first `count` items are the sample
+            let mut rng = SplitMix64(seed);
+            for idx in 0..count {
+                let swap = idx + (rng.next() % (rest.len() - idx) as u64) as usize;
+                rest.swap(idx, swap);
+            }
+            selected.extend(&rest[..count]);
+            selected.into_iter().collect()
+        }
+    }
+}
+
+/// Computes sha256 of passed files on machine (in the same order) with
+/// hashing tool available there.
+pub fn hashes(machine: &dyn Machine, files: &[PathBuf]) -> Result<Vec<String>, CrustError> {
+    utilities::sha256(machine, files)
+}
+
+/// Result of comparing copied files with source ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationReport {
+    pub mode: VerifyMode,
+    pub seed: u64,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub checked_files: usize,
+    pub checked_bytes: u64,
+    pub mismatches: Vec<PathBuf>,
+}
+
+impl VerificationReport {
+    pub fn is_success(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Human readable summary. For sample without mismatches, confidence
+    /// is estimated with "rule of three" (95% upper bound of corrupted
+    /// files ratio is 3/n for n checked files).
+    pub fn summary(&self) -> String {
+        let mut lines = vec![format!(
+            "Verified {}/{} files ({}/{})",
+            self.checked_files,
+            self.total_files,
+            HumanBytes(self.checked_bytes),
+            HumanBytes(self.total_bytes)
+        )];
+
+        if let VerifyMode::Sample { ratio } = self.mode {
+            lines[0].push_str(&format!(" - sample {}%, seed {}", ratio * 100.0, self.seed));
+            if self.is_success() && self.checked_files < self.total_files {
+                let bound = (3.0 / self.checked_files.max(1) as f64).min(1.0) * 100.0;
+                lines.push(format!(
+                    "95% confidence that less than {bound:.2}% of not verified files are corrupted"
+                ));
+            }
+        }
+
+        match self.is_success() {
+            true => lines.push(String::from("All verified files match")),
+            false => {
+                lines.push(format!("{} files do not match:", self.mismatches.len()));
+                for path in &self.mismatches {
+                    lines.push(format!("  {}", path.display()));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// Compares hashes of selected entries on both machines.
+pub fn verify(
+    src_machine: &dyn Machine,
+    src_root: &Path,
+    dst_machine: &dyn Machine,
+    dst_root: &Path,
+    entries: &[TreeEntry],
+    options: &VerifyOptions,
+) -> Result<VerificationReport, CrustError> {
+    let seed = options.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    });
+    let selected = select(entries, options.mode, options.threshold, seed);
+    log::debug!("Verifying {}/{} files", selected.len(), entries.len());
+
+    let src_files = selected
+        .iter()
+        .map(|idx| entries[*idx].join_to(src_root))
+        .collect::<Vec<_>>();
+    let dst_files = selected
+        .iter()
+        .map(|idx| entries[*idx].join_to(dst_root))
+        .collect::<Vec<_>>();
+    let src_hashes = hashes(src_machine, &src_files)?;
+    let dst_hashes = hashes(dst_machine, &dst_files)?;
+
+    let mismatches = (0..selected.len())
+        .filter(|pos| src_hashes.get(*pos) != dst_hashes.get(*pos))
+        .map(|pos| dst_files[pos].clone())
+        .collect();
+
+    Ok(VerificationReport {
+        mode: options.mode,
+        seed,
+        total_files: entries.len(),
+        total_bytes: entries.iter().map(|e| e.size).sum(),
+        checked_files: selected.len(),
+        checked_bytes: selected.iter().map(|idx| entries[*idx].size).sum(),
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(sizes: &[u64]) -> Vec<TreeEntry> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(idx, size)| TreeEntry {
+                path: PathBuf::from(format!("f{idx}")),
+                size: *size,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_verify_mode() {
+        assert_eq!("none".parse::<VerifyMode>().unwrap(), VerifyMode::None);
+        assert_eq!("full".parse::<VerifyMode>().unwrap(), VerifyMode::Full);
+        assert_eq!(
+            "sample:5%".parse::<VerifyMode>().unwrap(),
+            VerifyMode::Sample { ratio: 0.05 }
+        );
+        assert_eq!(
+            "sample:0.5%".parse::<VerifyMode>().unwrap(),
+            VerifyMode::Sample { ratio: 0.005 }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_verify_mode() {
+        for mode in ["sample", "sample:5", "sample:0%", "sample:101%", "partial"] {
+            let err = mode.parse::<VerifyMode>().err().unwrap();
+            assert_eq!(err.code, ExitCode::Parser);
+            assert_eq!(
+                err.message,
+                format!("Invalid verify mode '{mode}'. Use none, full or sample:<N>%")
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_full_and_none() {
+        let entries = entries(&[1, 2, 3]);
+
+        assert_eq!(select(&entries, VerifyMode::Full, 0, 1), vec![0, 1, 2]);
+        assert!(select(&entries, VerifyMode::None, 0, 1).is_empty());
+    }
+
+    #[test]
+    fn test_select_sample_is_seedable() {
+        let entries = entries(&[1; 100]);
+        let mode = VerifyMode::Sample { ratio: 0.1 };
+
+        let sample = select(&entries, mode, u64::MAX, 42);
+        assert_eq!(sample.len(), 10);
+        assert_eq!(sample, select(&entries, mode, u64::MAX, 42));
+        assert_ne!(sample, select(&entries, mode, u64::MAX, 43));
+    }
+
+    #[test]
+    fn test_select_sample_includes_big_files() {
+        let entries = entries(&[1, 1, 500, 1, 1, 1, 1, 1, 1, 900]);
+
+        let sample = select(&entries, VerifyMode::Sample { ratio: 0.01 }, 100, 7);
+        assert!(sample.contains(&2));
+        assert!(sample.contains(&9));
+        assert_eq!(sample.len(), 3);
+    }
+
+    #[test]
+    fn test_sample_summary() {
+        let report = VerificationReport {
+            mode: VerifyMode::Sample { ratio: 0.05 },
+            seed: 42,
+            total_files: 1000,
+            total_bytes: 1024 * 1024,
+            checked_files: 60,
+            checked_bytes: 1024,
+            mismatches: vec![],
+        };
+
+        assert_eq!(
+            report.summary(),
+            "Verified 60/1000 files (1.00 KiB/1.00 MiB) - sample 5%, seed 42\n\
+             95% confidence that less than 5.00% of not verified files are corrupted\n\
+             All verified files match"
+        );
+    }
+
+    #[test]
+    fn test_summary_with_mismatches() {
+        let report = VerificationReport {
+            mode: VerifyMode::Full,
+            seed: 1,
+            total_files: 2,
+            total_bytes: 2,
+            checked_files: 2,
+            checked_bytes: 2,
+            mismatches: vec![PathBuf::from("/dst/a")],
+        };
+
+        assert!(!report.is_success());
+        assert!(report
+            .summary()
+            .ends_with("1 files do not match:\n  /dst/a"));
+    }
+}
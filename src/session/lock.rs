@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::error::{CrustError, ExitCode};
+use crate::inventory::Inventory;
+
+/// Tag of inventory hosts which are locked after idle time.
+pub const SENSITIVE_TAG: &str = "sensitive";
+
+/// Machines of background process which need re-authentication. They are
+/// keyed by hostname, so a machine can not be reached by another alias
+/// or by its address while it is locked.
+/// - timeout: idle time after which sensitive machine is locked (None
+///   disables idle lock)
+/// - used: inventory name and last use of every sensitive host which is
+///   not locked
+/// - locked: inventory names of locked hosts
+struct IdleLock {
+    timeout: Option<Duration>,
+    used: BTreeMap<String, (String, Instant)>,
+    locked: BTreeMap<String, String>,
+}
+
+static LOCK: Mutex<IdleLock> = Mutex::new(IdleLock {
+    timeout: None,
+    used: BTreeMap::new(),
+    locked: BTreeMap::new(),
+});
+
+/// Sensitive hosts of inventory (hostname -> inventory name), read again
+/// only when inventory file changes - they are checked by every command.
+struct SensitiveHosts {
+    path: PathBuf,
+    modified: SystemTime,
+    hosts: BTreeMap<String, String>,
+}
+
+static SENSITIVE: Mutex<Option<SensitiveHosts>> = Mutex::new(None);
+
+/// Sets idle time after which sensitive machines are locked.
+pub fn set_timeout(timeout: Option<Duration>) {
+    LOCK.lock().unwrap().timeout = timeout;
+}
+
+/// Checks whether host can be used (it is not locked) and registers
+/// its usage when it is a sensitive one.
+pub fn guard(host: &str) -> Result<(), CrustError> {
+    check(host)?;
+    let enabled = LOCK.lock().unwrap().timeout.is_some();
+    if enabled {
+        if let Some(name) = sensitive_name(host) {
+            touch(host, &name, Instant::now());
+        }
+    }
+    Ok(())
+}
+
+/// Fails when host is locked.
+pub fn check(host: &str) -> Result<(), CrustError> {
+    match LOCK.lock().unwrap().locked.get(host) {
+        Some(name) => Err(CrustError {
+            code: ExitCode::Ssh,
+            message: format!(
+                "Machine '{name}' is locked. Unlock it with `crust machine unlock {name}`"
+            ),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Registers usage of sensitive host (known in inventory as `name`).
+pub fn touch(host: &str, name: &str, now: Instant) {
+    LOCK.lock()
+        .unwrap()
+        .used
+        .insert(host.to_string(), (name.to_string(), now));
+}
+
+/// Checks whether host is locked.
+pub fn is_locked(host: &str) -> bool {
+    LOCK.lock().unwrap().locked.contains_key(host)
+}
+
+/// Locks host - it can not be used until it is unlocked.
+pub fn lock(host: &str, name: &str) {
+    let mut state = LOCK.lock().unwrap();
+    state.used.remove(host);
+    state.locked.insert(host.to_string(), name.to_string());
+}
+
+/// Unlocks host (its idle time starts from now).
+pub fn unlock(host: &str) -> Result<(), CrustError> {
+    let mut state = LOCK.lock().unwrap();
+    let Some(name) = state.locked.remove(host) else {
+        return Err(CrustError {
+            code: ExitCode::Parser,
+            message: format!("Machine '{host}' is not locked"),
+        });
+    };
+    if state.timeout.is_some() {
+        state.used.insert(host.to_string(), (name, Instant::now()));
+    }
+    Ok(())
+}
+
+/// Locks sensitive hosts which have not been used for idle time and
+/// returns their inventory names.
+pub fn lock_idle(now: Instant) -> Vec<String> {
+    let mut state = LOCK.lock().unwrap();
+    let Some(timeout) = state.timeout else {
+        return Vec::new();
+    };
+
+    let idle = state
+        .used
+        .iter()
+        .filter(|(_, (_, used))| now.saturating_duration_since(*used) >= timeout)
+        .map(|(host, (name, _))| (host.clone(), name.clone()))
+        .collect::<Vec<_>>();
+    for (host, name) in &idle {
+        state.used.remove(host);
+        state.locked.insert(host.clone(), name.clone());
+    }
+    idle.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Inventory name of host when it is tagged as sensitive.
+fn sensitive_name(host: &str) -> Option<String> {
+    sensitive_name_in(&Inventory::default_path(), host)
+}
+
+fn sensitive_name_in(path: &Path, host: &str) -> Option<String> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let mut cache = SENSITIVE.lock().unwrap();
+    let fresh = cache
+        .as_ref()
+        .is_some_and(|c| c.path == path && c.modified == modified);
+    if !fresh {
+        *cache = Some(SensitiveHosts {
+            path: path.to_path_buf(),
+            modified,
+            hosts: sensitive_hosts(path),
+        });
+    }
+    cache.as_ref().and_then(|c| c.hosts.get(host).cloned())
+}
+
+/// Reads sensitive hosts of inventory (the first name wins when more of
+/// them point to the same hostname).
+fn sensitive_hosts(path: &Path) -> BTreeMap<String, String> {
+    let inventory = match Inventory::load(path) {
+        Ok(inventory) => inventory,
+        Err(e) => {
+            log::warn!("Can not read inventory to check sensitive hosts: {e}");
+            return BTreeMap::new();
+        }
+    };
+    let mut hosts = BTreeMap::new();
+    for name in inventory.tagged(SENSITIVE_TAG) {
+        if let Ok(host) = inventory.hostname(&name) {
+            hosts.entry(host).or_insert(name);
+        }
+    }
+    hosts
+}
+
+/// Starts thread which locks idle sensitive machines and passes them
+/// to main loop (as `machine lock` commands written to its fifo), so
+/// their connections are dropped.
+pub fn start_watcher(fifo: PathBuf) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(1));
+        for name in lock_idle(Instant::now()) {
+            log::info!("Machine {name} has been idle for too long - locking it");
+            let written = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&fifo)
+                .and_then(|mut f| writeln!(f, "machine lock {name}"));
+            if let Err(e) = written {
+                log::error!("Can not pass lock of machine {name} to main loop: {e}");
+            }
+        }
+    });
+}
+
+/// Restores initial state (no timeout, nothing used nor locked).
+pub fn reset() {
+    let mut state = LOCK.lock().unwrap();
+    state.timeout = None;
+    state.used.clear();
+    state.locked.clear();
+    *SENSITIVE.lock().unwrap() = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_lock_idle_machines() {
+        reset();
+        set_timeout(Some(Duration::from_secs(60)));
+        let start = Instant::now();
+        touch("10.0.0.1", "web-prod-1", start);
+        touch("10.0.0.2", "db-prod-1", start + Duration::from_secs(30));
+
+        assert!(lock_idle(start + Duration::from_secs(59)).is_empty());
+        assert_eq!(
+            lock_idle(start + Duration::from_secs(60)),
+            vec!["web-prod-1"]
+        );
+        assert!(lock_idle(start + Duration::from_secs(61)).is_empty());
+        assert!(is_locked("10.0.0.1"));
+        assert!(check("10.0.0.2").is_ok());
+
+        let err = check("10.0.0.1").err().unwrap();
+        assert_eq!(err.code, ExitCode::Ssh);
+        assert_eq!(
+            err.message,
+            "Machine 'web-prod-1' is locked. Unlock it with `crust machine unlock web-prod-1`"
+        );
+        reset();
+    }
+
+    #[test]
+    #[serial]
+    fn test_unlock_machine() {
+        reset();
+        assert!(unlock("10.0.0.1").is_err());
+
+        lock("10.0.0.1", "web-prod-1");
+        assert!(guard("10.0.0.1").is_err());
+        unlock("10.0.0.1").unwrap();
+        assert!(guard("10.0.0.1").is_ok());
+        assert!(lock_idle(Instant::now()).is_empty());
+        reset();
+    }
+
+    #[test]
+    #[serial]
+    fn test_sensitive_hosts_are_read_again_when_inventory_changes() {
+        reset();
+        let path = std::env::temp_dir().join(format!("crust_lock_{}.yaml", std::process::id()));
+        let write = |content: &str, modified: SystemTime| {
+            std::fs::write(&path, content).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(modified).unwrap();
+        };
+        let first = SystemTime::now() - Duration::from_secs(60);
+        let sensitive = "hosts:\n  db-prod-1:\n    addr: root@10.0.0.2\n    tags: [sensitive]\n";
+
+        write(sensitive, first);
+        let read = sensitive_name_in(&path, "10.0.0.2");
+        write("hosts: {}\n", first);
+        let cached = sensitive_name_in(&path, "10.0.0.2");
+        write("hosts: {}\n", SystemTime::now());
+        let changed = sensitive_name_in(&path, "10.0.0.2");
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(read.as_deref(), Some("db-prod-1"));
+        assert_eq!(cached.as_deref(), Some("db-prod-1"));
+        assert_eq!(changed, None);
+        reset();
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use indicatif::HumanBytes;
+
+pub mod lock;
+pub mod parser;
+pub mod report;
+pub mod secret;
+
+/// Number of the latest events kept in journal.
+pub const MAX_EVENTS: usize = 1000;
+
+/// Number of bytes of stdout and stderr kept per event.
+pub const MAX_OUTPUT: usize = 64 * 1024;
+
+/// Journal of operations invoked in the current process. In background
+/// mode it collects the session, so it can be exported later as a report.
+/// Only the latest `MAX_EVENTS` events are kept (the number of dropped
+/// ones is counted), so long running process does not grow without limit.
+struct Journal {
+    events: VecDeque<SessionEvent>,
+    dropped: usize,
+}
+
+static JOURNAL: Mutex<Journal> = Mutex::new(Journal {
+    events: VecDeque::new(),
+    dropped: 0,
+});
+
+/// Type of recorded event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    Exec,
+    Scp,
+    HostKey,
+    Lock,
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EventKind::Exec => write!(f, "exec"),
+            EventKind::Scp => write!(f, "scp"),
+            EventKind::HostKey => write!(f, "hostkey"),
+            EventKind::Lock => write!(f, "lock"),
+        }
+    }
+}
+
+/// Single entry of session journal - describes what was invoked, where,
+/// when and with which result.
+#[derive(Debug, Clone)]
+pub struct SessionEvent {
+    pub kind: EventKind,
+    pub started: DateTime<Utc>,
+    pub duration: Duration,
+    pub target: String,
+    pub description: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub retcode: i32,
+}
+
+impl SessionEvent {
+    /// Checks whether recorded operation has been completed successfuly.
+    pub fn is_success(&self) -> bool {
+        self.retcode == 0
+    }
+}
+
+/// Cuts output to `MAX_OUTPUT` bytes, noting that in the kept text.
+fn cut(output: &mut String) {
+    if output.len() <= MAX_OUTPUT {
+        return;
+    }
+    let mut end = MAX_OUTPUT;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    let total = output.len() as u64;
+    output.truncate(end);
+    output.push_str(&format!(
+        "\n[output cut to {} of {}]",
+        HumanBytes(MAX_OUTPUT as u64),
+        HumanBytes(total)
+    ));
+}
+
+/// Adds event to the journal (the oldest event is dropped when journal
+/// is full).
+pub fn record(mut event: SessionEvent) {
+    log::trace!("Recording session event: {:?}", event);
+    cut(&mut event.stdout);
+    cut(&mut event.stderr);
+    let mut journal = JOURNAL.lock().unwrap();
+    if journal.events.len() == MAX_EVENTS {
+        journal.events.pop_front();
+        journal.dropped += 1;
+    }
+    journal.events.push_back(event);
+}
+
+/// Gets a copy of events kept so far (in invoke order).
+pub fn events() -> Vec<SessionEvent> {
+    JOURNAL.lock().unwrap().events.iter().cloned().collect()
+}
+
+/// Number of the oldest events dropped from full journal.
+pub fn dropped() -> usize {
+    JOURNAL.lock().unwrap().dropped
+}
+
+/// Removes all recorded events.
+pub fn clear() {
+    let mut journal = JOURNAL.lock().unwrap();
+    journal.events.clear();
+    journal.dropped = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    fn event(stdout: &str) -> SessionEvent {
+        SessionEvent {
+            kind: EventKind::Exec,
+            started: Utc::now(),
+            duration: Duration::ZERO,
+            target: String::from("LocalMachine"),
+            description: String::from("cat log"),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            retcode: 0,
+        }
+    }
+
+    #[serial]
+    #[test]
+    fn test_journal_keeps_latest_events() {
+        clear();
+        (0..MAX_EVENTS + 2).for_each(|i| record(event(&i.to_string())));
+
+        let events = events();
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events[0].stdout, "2");
+        assert_eq!(dropped(), 2);
+        clear();
+    }
+
+    #[serial]
+    #[test]
+    fn test_journal_cuts_long_output() {
+        clear();
+        record(event(&"a".repeat(MAX_OUTPUT + 10)));
+
+        let stdout = &events()[0].stdout;
+        assert!(stdout.starts_with(&"a".repeat(MAX_OUTPUT)));
+        assert!(stdout.ends_with("\n[output cut to 64.00 KiB of 64.01 KiB]"));
+        clear();
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand, ValueEnum};
+
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+
+#[derive(Debug, Clone, Args)]
+pub struct SessionArgs {
+    #[clap(subcommand)]
+    pub action: SessionAction,
+}
+
+impl Validation for SessionArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SessionAction {
+    /// Renders all operations invoked in background session into a report
+    Export(ExportArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportArgs {
+    /// Format of generated report
+    #[clap(long, value_enum, default_value = "html")]
+    pub format: ReportFormat,
+
+    /// Path to save report (if not passed, report is printed)
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Supported formats of session report.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    Html,
+    Markdown,
+}
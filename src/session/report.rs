@@ -0,0 +1,209 @@
+use crate::session::parser::ReportFormat;
+use crate::session::SessionEvent;
+
+/// Renders session events into a static, shareable document. `dropped`
+/// is the number of older events which are not kept anymore.
+pub fn render(events: &[SessionEvent], dropped: usize, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Html => render_html(events, dropped),
+        ReportFormat::Markdown => render_markdown(events, dropped),
+    }
+}
+
+/// Generates a self-contained HTML page (without any external resources).
+fn render_html(events: &[SessionEvent], dropped: usize) -> String {
+    let mut rows = String::new();
+    for (idx, event) in events.iter().enumerate() {
+        let status = match event.is_success() {
+            true => "ok",
+            false => "failed",
+        };
+        rows.push_str(&format!(
+            "<tr class=\"{status}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><code>{}</code></td><td>{} ms</td><td>{}</td></tr>\n",
+            idx + 1,
+            event.started.format("%Y-%m-%d %H:%M:%S"),
+            event.kind,
+            escape_html(&event.target),
+            escape_html(&event.description),
+            event.duration.as_millis(),
+            event.retcode,
+        ));
+        rows.push_str(&format!(
+            "<tr class=\"output\"><td colspan=\"7\"><pre>{}</pre><pre class=\"stderr\">{}</pre></td></tr>\n",
+            escape_html(&event.stdout),
+            escape_html(&event.stderr),
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>crust session report</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; width: 100%; }}
+td, th {{ border: 1px solid #ccc; padding: 4px; text-align: left; }}
+tr.failed td {{ background: #fdd; }}
+pre.stderr {{ color: #b00; }}
+</style>
+</head>
+<body>
+<h1>crust session report</h1>
+<p>{}</p>
+<table>
+<tr><th>#</th><th>Started (UTC)</th><th>Operation</th><th>Target</th><th>Command</th><th>Duration</th><th>Retcode</th></tr>
+{}</table>
+</body>
+</html>
+"#,
+        summary(events, dropped),
+        rows
+    )
+}
+
+/// Generates a markdown document (table with overview and sections with outputs).
+fn render_markdown(events: &[SessionEvent], dropped: usize) -> String {
+    let mut report = format!("# crust session report\n\n{}\n\n", summary(events, dropped));
+    report.push_str("| # | Started (UTC) | Operation | Target | Command | Duration | Retcode |\n");
+    report.push_str("|---|---|---|---|---|---|---|\n");
+
+    for (idx, event) in events.iter().enumerate() {
+        report.push_str(&format!(
+            "| {} | {} | {} | {} | `{}` | {} ms | {} |\n",
+            idx + 1,
+            event.started.format("%Y-%m-%d %H:%M:%S"),
+            event.kind,
+            escape_markdown(&event.target),
+            escape_markdown(&event.description),
+            event.duration.as_millis(),
+            event.retcode,
+        ));
+    }
+
+    for (idx, event) in events.iter().enumerate() {
+        report.push_str(&format!(
+            "\n## {}. {}\n",
+            idx + 1,
+            escape_heading(&event.description)
+        ));
+        for (name, output) in [("stdout", &event.stdout), ("stderr", &event.stderr)] {
+            if !output.is_empty() {
+                let output = output.trim_end();
+                let fence = fence(output);
+                report.push_str(&format!("\n{name}:\n{fence}\n{output}\n{fence}\n"));
+            }
+        }
+    }
+    report
+}
+
+/// Short info about amount of operations and failures.
+fn summary(events: &[SessionEvent], dropped: usize) -> String {
+    let failed = events.iter().filter(|e| !e.is_success()).count();
+    let summary = format!("Operations: {}, failed: {}", events.len(), failed);
+    match dropped {
+        0 => summary,
+        _ => format!("{summary} (the oldest {dropped} operations are not kept)"),
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(value: &str) -> String {
+    value.replace('|', "\\|").replace('`', "'")
+}
+
+/// Escapes every punctuation character, so text is not interpreted as
+/// markdown (new lines would end the heading, so they are replaced).
+fn escape_heading(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '\n' | '\r' => String::from(" "),
+            c if c.is_ascii_punctuation() => format!("\\{c}"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Code fence longer than any run of backticks in content.
+fn fence(content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::EventKind;
+    use chrono::Utc;
+    use std::time::Duration;
+
+    fn event(description: &str, retcode: i32) -> SessionEvent {
+        SessionEvent {
+            kind: EventKind::Exec,
+            started: Utc::now(),
+            duration: Duration::from_millis(15),
+            target: String::from("RemoteMachine<user@host>"),
+            description: String::from(description),
+            stdout: String::from("out"),
+            stderr: String::from("err"),
+            retcode,
+        }
+    }
+
+    #[test]
+    fn test_render_html_escapes_values() {
+        let report = render(&[event("ls <dir> && pwd", 0)], 0, ReportFormat::Html);
+
+        assert!(report.contains("ls &lt;dir&gt; &amp;&amp; pwd"));
+        assert!(report.contains("RemoteMachine&lt;user@host&gt;"));
+        assert!(report.contains("15 ms"));
+        assert!(!report.contains("<dir>"));
+    }
+
+    #[test]
+    fn test_render_html_marks_failures() {
+        let report = render(&[event("ls", 0), event("abc", 1)], 0, ReportFormat::Html);
+
+        assert!(report.contains("Operations: 2, failed: 1"));
+        assert!(report.contains("<tr class=\"failed\">"));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let report = render(&[event("cat a | grep b", 2)], 0, ReportFormat::Markdown);
+
+        assert!(report.contains("| 1 |"));
+        assert!(report.contains("`cat a \\| grep b`"));
+        assert!(report.contains("stdout:\n```\nout\n```"));
+        assert!(report.contains("stderr:\n```\nerr\n```"));
+        assert!(report.contains("Operations: 1, failed: 1"));
+    }
+
+    #[test]
+    fn test_render_empty_session() {
+        let report = render(&[], 0, ReportFormat::Markdown);
+
+        assert!(report.contains("Operations: 0, failed: 0"));
+    }
+
+    #[test]
+    fn test_render_markdown_keeps_structure() {
+        let mut event = event("# cat *notes*", 0);
+        event.stdout = String::from("```\ncode\n```");
+        let report = render(&[event], 3, ReportFormat::Markdown);
+
+        assert!(report.contains("## 1. \\# cat \\*notes\\*\n"));
+        assert!(report.contains("stdout:\n````\n```\ncode\n```\n````"));
+        assert!(report.contains("(the oldest 3 operations are not kept)"));
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use zeroize::Zeroizing;
+
+use crate::error::{CrustError, ExitCode};
+
+/// Label of password typed for `machine unlock`.
+pub const PASSWORD: &str = "password";
+
+/// Time for which background process waits for secrets of command.
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Secrets passed by client process (runner.sh) with commands of
+/// background process, by their labels: `password` or source of private
+/// key (e.g. `env:CI_KEY`). Background process has neither terminal nor
+/// environment of client, so they are read by client and passed via
+/// private pipe of background process.
+static SECRETS: Mutex<BTreeMap<String, Zeroizing<String>>> = Mutex::new(BTreeMap::new());
+
+/// Checks whether client passes secrets with command - password of
+/// `machine unlock` or private keys of `env:`/`fd:` sources.
+pub fn expected(command: &[String]) -> bool {
+    let inline_key = |flag: &str, value: &str| {
+        flag.starts_with("--pkey-inline") && (value.starts_with("env:") || value.starts_with("fd:"))
+    };
+
+    command.starts_with(&[String::from("machine"), String::from("unlock")])
+        || command
+            .iter()
+            .enumerate()
+            .any(|(idx, arg)| match arg.split_once('=') {
+                Some((flag, value)) => inline_key(flag, value),
+                None => command
+                    .get(idx + 1)
+                    .is_some_and(|value| inline_key(arg, value)),
+            })
+}
+
+/// Reads secrets passed with command from private pipe. Client writes
+/// them at once as lines `<label> <line>` (lines of multi-line secret
+/// share their label). Fails when nothing is passed during `TIMEOUT`.
+pub fn receive(pipe: &Path) -> Result<(), CrustError> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let reader_pipe = pipe.to_path_buf();
+    std::thread::spawn(move || {
+        let mut content = Zeroizing::new(String::new());
+        let read =
+            std::fs::File::open(reader_pipe).and_then(|mut f| f.read_to_string(&mut content));
+        let _ = sender.send(read.map(|_| content));
+    });
+
+    match receiver.recv_timeout(TIMEOUT) {
+        Ok(read) => {
+            SECRETS.lock().unwrap().append(&mut parse(&read?));
+            Ok(())
+        }
+        Err(_) => {
+            release(pipe.to_path_buf());
+            Err(CrustError {
+                code: ExitCode::Local,
+                message: format!("No secrets were passed during {}s", TIMEOUT.as_secs()),
+            })
+        }
+    }
+}
+
+/// Takes secret passed with command (it is not kept any longer).
+pub fn take(label: &str) -> Result<Zeroizing<String>, CrustError> {
+    SECRETS
+        .lock()
+        .unwrap()
+        .remove(label)
+        .ok_or_else(|| CrustError {
+            code: ExitCode::Local,
+            message: format!("Secret '{label}' was not passed by client"),
+        })
+}
+
+/// Groups labelled lines into secrets.
+fn parse(content: &str) -> BTreeMap<String, Zeroizing<String>> {
+    let mut secrets: BTreeMap<String, Zeroizing<String>> = BTreeMap::new();
+    for line in content.lines() {
+        let (label, data) = line.split_once(' ').unwrap_or((line, ""));
+        match secrets.get_mut(label) {
+            Some(secret) => {
+                secret.push('\n');
+                secret.push_str(data);
+            }
+            None => {
+                secrets.insert(label.to_string(), Zeroizing::new(data.to_string()));
+            }
+        }
+    }
+    secrets
+}
+
+/// Reader of pipe waits for a writer - open pipe to release it.
+fn release(pipe: PathBuf) {
+    std::thread::spawn(move || std::fs::OpenOptions::new().write(true).open(pipe));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_expected_secrets() {
+        assert!(expected(&command("machine unlock web-1")));
+        assert!(expected(&command(
+            "exec --addr u@h --pkey-inline env:CI_KEY --cmd ls"
+        )));
+        assert!(expected(&command("scp --pkey-inline-to=fd:3 a b")));
+
+        assert!(!expected(&command("machine lock web-1")));
+        assert!(!expected(&command("exec --alias web --cmd 'echo env:X'")));
+    }
+
+    #[test]
+    fn test_parse_labelled_secrets() {
+        let secrets =
+            parse("password p4ss word\nenv:KEY -----BEGIN\nenv:KEY b3Bl\nenv:KEY -----END\n");
+
+        assert_eq!(secrets.len(), 2);
+        assert_eq!(secrets[PASSWORD].as_str(), "p4ss word");
+        assert_eq!(secrets["env:KEY"].as_str(), "-----BEGIN\nb3Bl\n-----END");
+    }
+}
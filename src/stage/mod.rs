@@ -0,0 +1,271 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::connection::endpoint::Endpoint;
+use crate::connection::request::RemoteTarget;
+use crate::error::{CrustError, ExitCode};
+use crate::interfaces::parser::Validation;
+use crate::inventory::Inventory;
+use crate::utils::shell::quote_path;
+
+pub mod parser;
+
+use parser::FileAuthArgs;
+
+/// Remote file referenced in `crust with` command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteFile {
+    pub target: RemoteTarget,
+    pub path: PathBuf,
+}
+
+impl RemoteFile {
+    /// Parses `<user>@<host>[:<port>]:<path>` (authorized with passed flags)
+    /// or `<name>:<path>`, where name is a host from inventory or alias of
+    /// machine registered in background session.
+    pub fn parse(spec: &str, auth: &FileAuthArgs) -> Result<Self, CrustError> {
+        let endpoint = Endpoint::parse(spec)?;
+        let Some(path) = endpoint.path.clone() else {
+            return Err(CrustError {
+                code: ExitCode::Parser,
+                message: format!(
+                    "Invalid remote file '{spec}'. Use <user>@<host>:<path> or <alias>:<path>"
+                ),
+            });
+        };
+
+        let mut target = match (endpoint.address(), &endpoint.alias) {
+            (Some(addr), _) => {
+                let mut target = RemoteTarget::new(&addr).port(endpoint.port.unwrap_or(auth.port));
+                if let Some(password) = &auth.password {
+                    target = target.password(password);
+                }
+                if let Some(pkey) = &auth.pkey {
+                    target = target.pkey(pkey);
+                }
+                target
+            }
+            (None, alias) => {
+                let alias = alias.as_deref().unwrap_or_default();
+                Inventory::load(&Inventory::default_path())
+                    .and_then(|inventory| inventory.target(alias))
+                    .unwrap_or_else(|_| RemoteTarget::with_alias(alias))
+            }
+        };
+        target.validate()?;
+
+        Ok(Self { target, path })
+    }
+}
+
+/// Remote file downloaded into local temporary directory.
+/// - fingerprint: hash of content right after download (used to find
+///   files modified by local command)
+#[derive(Debug, Clone)]
+pub struct StagedFile {
+    pub remote: RemoteFile,
+    pub local: PathBuf,
+    pub fingerprint: u64,
+}
+
+impl StagedFile {
+    /// Checks whether local copy was changed since download.
+    pub fn is_modified(&self) -> Result<bool, CrustError> {
+        Ok(fingerprint(&self.local)? != self.fingerprint)
+    }
+}
+
+/// Hash of file content.
+pub fn fingerprint(path: &Path) -> Result<u64, CrustError> {
+    let mut hasher = DefaultHasher::new();
+    std::fs::read(path)?.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Builds local command by replacing `{}` with all staged paths and `{N}`
+/// with the N-th one. Without any placeholder, paths are appended at
+/// the end of command.
+pub fn substitute(cmd: &[String], paths: &[PathBuf]) -> Result<String, CrustError> {
+    let all = paths
+        .iter()
+        .map(|p| quote_path(p))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut used = false;
+
+    let mut parts = Vec::new();
+    for arg in cmd {
+        let mut part = String::new();
+        let mut rest = arg.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let placeholder = &rest[start + 1..start + len];
+            let replacement = match placeholder {
+                "" => all.clone(),
+                idx => match idx.parse::<usize>() {
+                    Ok(idx) => paths
+                        .get(idx)
+                        .map(|p| quote_path(p))
+                        .ok_or_else(|| CrustError {
+                            code: ExitCode::Parser,
+                            message: format!(
+                                "Placeholder {{{idx}}} refers to not existing file (staged: {})",
+                                paths.len()
+                            ),
+                        })?,
+                    Err(_) => rest[start..start + len + 1].to_string(),
+                },
+            };
+            used |= replacement != rest[start..start + len + 1];
+
+            part.push_str(&rest[..start]);
+            part.push_str(&replacement);
+            rest = &rest[start + len + 1..];
+        }
+        part.push_str(rest);
+        parts.push(part);
+    }
+
+    if !used {
+        parts.push(all);
+    }
+    Ok(parts.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::parser::BaseConnArgs;
+    use crate::stage::parser::WithArgs;
+
+    fn args() -> WithArgs {
+        WithArgs {
+            files: vec![],
+            cmd: vec![],
+            auth: FileAuthArgs {
+                port: 2222,
+                password: Some(String::from("1234")),
+                pkey: None,
+            },
+            upload_back: false,
+        }
+    }
+
+    fn cmd(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn paths() -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("/tmp/0/app.log"),
+            PathBuf::from("/tmp/1/it's.log"),
+        ]
+    }
+
+    #[test]
+    fn test_parse_remote_file_with_address() {
+        let file = RemoteFile::parse("user@host:/var/log/app.log", &args().auth).unwrap();
+
+        assert_eq!(file.path, PathBuf::from("/var/log/app.log"));
+        assert_eq!(
+            file.target,
+            RemoteTarget::new("user@host").port(2222).password("1234")
+        );
+    }
+
+    #[test]
+    fn test_parse_remote_file_with_ipv6_and_port() {
+        let file = RemoteFile::parse("user@[fe80::1]:2200:/srv/app.log", &args().auth).unwrap();
+
+        assert_eq!(file.path, PathBuf::from("/srv/app.log"));
+        assert_eq!(
+            file.target,
+            RemoteTarget::new("user@[fe80::1]")
+                .port(2200)
+                .password("1234")
+        );
+        assert_eq!(
+            file.target.split_addr(),
+            (String::from("user"), String::from("fe80::1"))
+        );
+    }
+
+    #[test]
+    fn test_parse_remote_file_with_alias() {
+        let file = RemoteFile::parse("not-in-inventory-host:/etc/hosts", &args().auth).unwrap();
+
+        assert_eq!(
+            file.target,
+            RemoteTarget::with_alias("not-in-inventory-host")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_remote_file() {
+        let err = RemoteFile::parse("user@host", &args().auth).err().unwrap();
+
+        assert_eq!(err.code, ExitCode::Parser);
+        assert_eq!(
+            err.message,
+            "Invalid remote file 'user@host'. Use <user>@<host>:<path> or <alias>:<path>"
+        );
+    }
+
+    #[test]
+    fn test_substitute_all_paths() {
+        assert_eq!(
+            substitute(&cmd(&["lnav", "{}"]), &paths()).unwrap(),
+            "lnav '/tmp/0/app.log' '/tmp/1/it'\\''s.log'"
+        );
+    }
+
+    #[test]
+    fn test_substitute_indexed_paths() {
+        assert_eq!(
+            substitute(&cmd(&["diff", "{1}", "{0}"]), &paths()).unwrap(),
+            "diff '/tmp/1/it'\\''s.log' '/tmp/0/app.log'"
+        );
+        assert_eq!(
+            substitute(&cmd(&["--file={0}"]), &paths()).unwrap(),
+            "--file='/tmp/0/app.log'"
+        );
+    }
+
+    #[test]
+    fn test_substitute_without_placeholder() {
+        assert_eq!(
+            substitute(&cmd(&["awk", "{print $1}"]), &paths()[..1]).unwrap(),
+            "awk {print $1} '/tmp/0/app.log'"
+        );
+    }
+
+    #[test]
+    fn test_substitute_not_existing_index() {
+        let err = substitute(&cmd(&["cat", "{2}"]), &paths()).err().unwrap();
+
+        assert_eq!(
+            err.message,
+            "Placeholder {2} refers to not existing file (staged: 2)"
+        );
+    }
+
+    #[test]
+    fn test_detect_modified_file() {
+        let path = PathBuf::from(format!("/tmp/tmp.{}", uuid::Uuid::new_v4().as_u128()));
+        std::fs::write(&path, "a").unwrap();
+        let staged = StagedFile {
+            remote: RemoteFile::parse("user@host:/a", &args().auth).unwrap(),
+            local: path.clone(),
+            fingerprint: fingerprint(&path).unwrap(),
+        };
+
+        assert!(!staged.is_modified().unwrap());
+        std::fs::write(&path, "b").unwrap();
+        assert!(staged.is_modified().unwrap());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
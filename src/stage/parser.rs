@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::error::CrustError;
+use crate::interfaces::parser::Validation;
+use crate::stage::RemoteFile;
+
+#[derive(Debug, Clone, Args)]
+pub struct WithArgs {
+    /// Remote files to stage (<user>@<host>:<path> or <alias>:<path>)
+    #[clap(required = true)]
+    pub files: Vec<String>,
+
+    /// Local command (after `--`). `{}` is replaced with all staged
+    /// paths, `{N}` with the N-th one
+    #[clap(last = true, required = true)]
+    pub cmd: Vec<String>,
+
+    #[clap(flatten)]
+    pub auth: FileAuthArgs,
+
+    /// Uploads files modified by command back to remote machine
+    #[clap(long, default_value = "false")]
+    pub upload_back: bool,
+}
+
+/// Authorization of remote files passed as `<user>@<host>:<path>`.
+#[derive(Debug, Clone, Default, Args)]
+pub struct FileAuthArgs {
+    /// Remote machine's port
+    #[clap(long, default_value = "22")]
+    pub port: u16,
+
+    /// Password to remote server
+    #[clap(long)]
+    pub password: Option<String>,
+
+    /// Path to private ssh-key to remote server
+    #[clap(long)]
+    pub pkey: Option<PathBuf>,
+}
+
+impl Validation for WithArgs {
+    fn validate(&mut self) -> Result<(), CrustError> {
+        for spec in &self.files {
+            RemoteFile::parse(spec, &self.auth)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+/// Quotes value, so it is passed to POSIX shell as a single word without
+/// any expansion (spaces, quotes, `$`, backticks, newlines and non-ASCII
+/// characters are kept as they are).
+/// # Example
+/// ```
+/// use crust::utils::shell::quote;
+///
+/// assert_eq!(quote("it's"), "'it'\\''s'");
+/// ```
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Quotes path like `quote`, but leading `~` (alone or followed by `/`)
+/// is left unquoted, so it is still expanded to home directory of the
+/// user executing command.
+/// # Example
+/// ```
+/// use std::path::Path;
+/// use crust::utils::shell::quote_path;
+///
+/// assert_eq!(quote_path(Path::new("~/my files")), "~/'my files'");
+/// assert_eq!(quote_path(Path::new("/tmp/a b")), "'/tmp/a b'");
+/// ```
+pub fn quote_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    match path.as_ref() {
+        "~" => String::from("~"),
+        _ => match path.strip_prefix("~/") {
+            Some("") => String::from("~/"),
+            Some(rest) => format!("~/{}", quote(rest)),
+            None => quote(&path),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::process::Command;
+
+    /// Words received by shell for passed (already quoted) arguments.
+    fn shell_words(args: &str) -> Vec<String> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "for arg in {args}; do printf '%s\\0' \"$arg\"; done"
+            ))
+            .env("HOME", "/home/crust")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .split_terminator('\0')
+            .map(String::from)
+            .collect()
+    }
+
+    const NASTY_PATHS: &[&str] = &[
+        "/srv/my files/report.txt",
+        "/srv/it's/\"quoted\"",
+        "/srv/$HOME/`id`/$(id)",
+        "/srv/zażółć gęślą jaźń/日本語.txt",
+        "/srv/new\nline",
+        "/srv/-rf",
+        "/srv/*.log",
+        "/srv/a;b&&c|d",
+        "/srv/'",
+        "/srv/~user",
+        "\t leading",
+    ];
+
+    #[test]
+    fn test_quote_nasty_paths() {
+        for path in NASTY_PATHS {
+            assert_eq!(shell_words(&quote_path(Path::new(path))), vec![*path]);
+        }
+    }
+
+    #[test]
+    fn test_quote_path_expands_tilde() {
+        assert_eq!(
+            shell_words(&quote_path(Path::new("~"))),
+            vec!["/home/crust"]
+        );
+        assert_eq!(
+            shell_words(&quote_path(Path::new("~/my dir/it's"))),
+            vec!["/home/crust/my dir/it's"]
+        );
+        assert_eq!(shell_words(&quote_path(Path::new("~user"))), vec!["~user"]);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn test_quote_keeps_any_value(value in "[^\0]*") {
+            prop_assert_eq!(shell_words(&quote(&value)), vec![value]);
+        }
+
+        #[test]
+        fn test_quote_path_keeps_home_relative_paths(rest in "[^\0]+") {
+            let expected = format!("/home/crust/{rest}");
+            prop_assert_eq!(
+                shell_words(&quote_path(Path::new(&format!("~/{rest}")))),
+                vec![expected]
+            );
+        }
+    }
+}
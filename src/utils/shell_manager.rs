@@ -9,7 +9,11 @@ impl ShellManager {
         ShellManager::is_bool_flag_set("CRUST_SHELL_INVOKE")
     }
 
+    pub fn is_cache_enabled() -> bool {
+        ShellManager::is_bool_flag_set("CRUST_CACHE")
+    }
+
     fn is_bool_flag_set(flag: &str) -> bool {
-        std::env::var(flag).map_or(false, |v| v.to_lowercase() == "true")
+        std::env::var(flag).is_ok_and(|v| v.to_lowercase() == "true")
     }
 }
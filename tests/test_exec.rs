@@ -1,4 +1,3 @@
-use assert_cmd;
 use serial_test::serial;
 use test_utils::{exec_on_local, exec_on_remote};
 
@@ -8,7 +7,7 @@ extern crate test_utils;
 #[test]
 fn test_exec_on_remote() {
     let mut cmd = assert_cmd::Command::cargo_bin("crust").unwrap();
-    cmd.args(&[
+    cmd.args([
         "exec",
         "whoami",
         "--addr-to",
@@ -25,7 +24,7 @@ fn test_exec_on_remote() {
 #[test]
 fn test_exec_on_local() {
     let mut cmd = assert_cmd::Command::cargo_bin("crust").unwrap();
-    cmd.args(&["exec", "echo 'test'"]);
+    cmd.args(["exec", "echo 'test'"]);
 
     cmd.assert().success();
     cmd.assert().stdout("test\n\n");
@@ -36,7 +35,7 @@ fn test_exec_on_local() {
 #[test]
 fn test_exec_rt_not_merged_streams_on_local() {
     let mut cmd = assert_cmd::Command::cargo_bin("crust").unwrap();
-    cmd.args(&["exec", "./test_utils/test_run_script.sh", "--rt"]);
+    cmd.args(["exec", "./test_utils/test_run_script.sh", "--rt"]);
 
     cmd.assert().success();
     cmd.assert()
@@ -47,7 +46,7 @@ fn test_exec_rt_not_merged_streams_on_local() {
 #[test]
 fn test_exec_rt_merged_streams_on_local() {
     let mut cmd = assert_cmd::Command::cargo_bin("crust").unwrap();
-    cmd.args(&["exec", "./test_utils/test_run_script.sh", "--rt", "--merge"]);
+    cmd.args(["exec", "./test_utils/test_run_script.sh", "--rt", "--merge"]);
 
     cmd.assert().success();
     cmd.assert()
@@ -70,7 +69,7 @@ fn test_exec_rt_not_merged_streams_on_remote() {
     exec_on_remote("chmod +x test_run_script.sh");
 
     let mut cmd = assert_cmd::Command::cargo_bin("crust").unwrap();
-    cmd.args(&[
+    cmd.args([
         "exec",
         "./test_run_script.sh",
         "--rt",
@@ -101,7 +100,7 @@ fn test_exec_rt_merged_streams_on_remote() {
     exec_on_remote("chmod +x test_run_script.sh");
 
     let mut cmd = assert_cmd::Command::cargo_bin("crust").unwrap();
-    cmd.args(&[
+    cmd.args([
         "exec",
         "./test_run_script.sh",
         "--rt",